@@ -0,0 +1,153 @@
+//! The variable bag a template renders against.
+
+use crate::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Template render-time state: variables, which of them are pre-escaped
+/// ("safe"), and bookkeeping for `{% for %}` loop variables.
+#[derive(Debug, Clone)]
+pub struct Context {
+    values: HashMap<String, Value>,
+    safe_vars: HashSet<String>,
+    /// Maps a loop variable name to `(iterable_name, index)` so later
+    /// lookups can trace a loop-bound value back to its source collection.
+    loop_mappings: HashMap<String, (String, usize)>,
+    /// Short locale code (e.g. `"ru"`) used by `djust_templates::locale`
+    /// to pick month/weekday names for `django_date_format`. `None` keeps
+    /// the default English output.
+    locale: Option<String>,
+    /// Whether `{{ }}` output in this scope is HTML-escaped. Flipped to
+    /// `false` inside `{% autoescape off %}...{% endautoescape %}`, which
+    /// renders its body against a cloned `Context` so the flag reverts once
+    /// that call returns.
+    autoescape: bool,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            safe_vars: HashSet::new(),
+            loop_mappings: HashMap::new(),
+            locale: None,
+            autoescape: true,
+        }
+    }
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_dict(values: HashMap<String, Value>) -> Self {
+        Self {
+            values,
+            ..Self::default()
+        }
+    }
+
+    /// Set the locale code templates rendered against this context should
+    /// use for date formatting (e.g. `"ru"`).
+    pub fn set_locale(&mut self, code: impl Into<String>) {
+        self.locale = Some(code.into());
+    }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    /// Resolve a (possibly dotted, e.g. `"user.name"`) variable path.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut segments = path.split('.');
+        let mut current = self.values.get(segments.next()?)?;
+        for segment in segments {
+            current = match current {
+                Value::Object(map) => map.get(segment)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    pub fn set(&mut self, key: String, value: Value) {
+        self.values.insert(key, value);
+    }
+
+    /// Whether `name` should bypass auto-escaping (e.g. marked via
+    /// `{{ x|safe }}` upstream, or known-safe HTML from the embedder).
+    pub fn is_safe(&self, name: &str) -> bool {
+        self.safe_vars.contains(name)
+    }
+
+    pub fn mark_safe(&mut self, name: &str) {
+        self.safe_vars.insert(name.to_string());
+    }
+
+    /// Record that `var` was bound from `iterable[index]` in the current
+    /// `{% for %}` iteration.
+    pub fn set_loop_mapping(&mut self, var: String, iterable: String, index: usize) {
+        self.loop_mappings.insert(var, (iterable, index));
+    }
+
+    pub fn clear_loop_mapping(&mut self, var: &str) {
+        self.loop_mappings.remove(var);
+    }
+
+    pub fn loop_mapping(&self, var: &str) -> Option<&(String, usize)> {
+        self.loop_mappings.get(var)
+    }
+
+    pub fn to_hashmap(&self) -> HashMap<String, Value> {
+        self.values.clone()
+    }
+
+    /// Whether `{{ }}` output should be HTML-escaped in this scope. `true`
+    /// unless inside `{% autoescape off %}`.
+    pub fn autoescape(&self) -> bool {
+        self.autoescape
+    }
+
+    pub fn set_autoescape(&mut self, enabled: bool) {
+        self.autoescape = enabled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_dotted_path() {
+        let mut obj = HashMap::new();
+        obj.insert("name".to_string(), Value::String("Ada".to_string()));
+        let mut ctx = Context::new();
+        ctx.set("user".to_string(), Value::Object(obj));
+        assert_eq!(ctx.get("user.name"), Some(&Value::String("Ada".to_string())));
+    }
+
+    #[test]
+    fn test_is_safe_after_mark_safe() {
+        let mut ctx = Context::new();
+        assert!(!ctx.is_safe("body"));
+        ctx.mark_safe("body");
+        assert!(ctx.is_safe("body"));
+    }
+
+    #[test]
+    fn test_autoescape_defaults_to_true() {
+        let mut ctx = Context::new();
+        assert!(ctx.autoescape());
+        ctx.set_autoescape(false);
+        assert!(!ctx.autoescape());
+    }
+
+    #[test]
+    fn test_loop_mapping_roundtrip() {
+        let mut ctx = Context::new();
+        ctx.set_loop_mapping("item".to_string(), "items".to_string(), 2);
+        assert_eq!(ctx.loop_mapping("item"), Some(&("items".to_string(), 2)));
+        ctx.clear_loop_mapping("item");
+        assert_eq!(ctx.loop_mapping("item"), None);
+    }
+}
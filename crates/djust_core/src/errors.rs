@@ -0,0 +1,56 @@
+//! Error types shared across the djust crates.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::PyErr;
+use std::fmt;
+
+/// The crate-wide result alias: every fallible djust operation returns this.
+pub type Result<T> = std::result::Result<T, DjangoRustError>;
+
+/// Errors raised while tokenizing, parsing, or rendering a template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DjangoRustError {
+    /// A tokenizer, parser, or renderer failure, with a human-readable
+    /// description of what went wrong.
+    TemplateError(String),
+}
+
+impl fmt::Display for DjangoRustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DjangoRustError::TemplateError(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for DjangoRustError {}
+
+impl From<std::io::Error> for DjangoRustError {
+    fn from(err: std::io::Error) -> Self {
+        DjangoRustError::TemplateError(format!("I/O error while rendering: {err}"))
+    }
+}
+
+impl From<DjangoRustError> for PyErr {
+    fn from(err: DjangoRustError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_includes_message() {
+        let err = DjangoRustError::TemplateError("boom".to_string());
+        assert_eq!(err.to_string(), "boom");
+    }
+
+    #[test]
+    fn test_from_io_error_wraps_message() {
+        let io_err = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "pipe closed");
+        let err: DjangoRustError = io_err.into();
+        assert!(err.to_string().contains("pipe closed"));
+    }
+}
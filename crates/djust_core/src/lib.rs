@@ -3,6 +3,7 @@
 //! This crate provides foundational data structures and utilities used across
 //! the djust ecosystem.
 
+use chrono::{DateTime, FixedOffset};
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -16,7 +17,7 @@ pub use context::Context;
 pub use errors::{DjangoRustError, Result};
 
 /// A value that can be used in Django templates
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Value {
     Null,
@@ -26,6 +27,25 @@ pub enum Value {
     String(String),
     List(Vec<Value>),
     Object(HashMap<String, Value>),
+    /// A timezone-aware datetime, e.g. ingested from an ISO-8601 literal or
+    /// produced by `djust_templates::date_parse`/`parsedate`. Keeps its
+    /// original offset so `django_date_format` can round-trip it.
+    DateTime(DateTime<FixedOffset>),
+    /// A string already known to be safe for insertion into HTML text or a
+    /// quoted attribute value - e.g. `djust_templates::filters::html_escape`
+    /// output an embedder pre-escaped before handing it to the context.
+    /// Unlike the blanket `Context::mark_safe`, this is typed: the renderer
+    /// only skips escaping when the destination `EscapeContext` is also an
+    /// HTML one, so a `SafeHtml` value landing inside a `<script>` still
+    /// gets JS-escaped rather than passed through verbatim.
+    SafeHtml(String),
+    /// A string already safe for a JS context (e.g. `|escapejs` output).
+    SafeJs(String),
+    /// A string already safe for a URL-bearing attribute (e.g. `|urlencode`
+    /// output, or a scheme-checked URL).
+    SafeUrl(String),
+    /// A string already safe for a CSS context (e.g. `|escapecss` output).
+    SafeCss(String),
 }
 
 impl Value {
@@ -38,6 +58,10 @@ impl Value {
             Value::String(s) => !s.is_empty(),
             Value::List(l) => !l.is_empty(),
             Value::Object(o) => !o.is_empty(),
+            Value::DateTime(_) => true,
+            Value::SafeHtml(s) | Value::SafeJs(s) | Value::SafeUrl(s) | Value::SafeCss(s) => {
+                !s.is_empty()
+            }
         }
     }
 }
@@ -53,6 +77,10 @@ impl fmt::Display for Value {
             Value::String(s) => write!(f, "{s}"),
             Value::List(_) => write!(f, "[List]"),
             Value::Object(_) => write!(f, "[Object]"),
+            Value::DateTime(dt) => write!(f, "{}", dt.to_rfc3339()),
+            Value::SafeHtml(s) | Value::SafeJs(s) | Value::SafeUrl(s) | Value::SafeCss(s) => {
+                write!(f, "{s}")
+            }
         }
     }
 }
@@ -89,6 +117,10 @@ impl ToPyObject for Value {
             Value::String(s) => s.to_object(py),
             Value::List(l) => l.to_object(py),
             Value::Object(o) => o.to_object(py),
+            Value::DateTime(dt) => dt.to_rfc3339().to_object(py),
+            Value::SafeHtml(s) | Value::SafeJs(s) | Value::SafeUrl(s) | Value::SafeCss(s) => {
+                s.to_object(py)
+            }
         }
     }
 }
@@ -107,4 +139,12 @@ mod tests {
         assert!(Value::String("hello".to_string()).is_truthy());
         assert!(!Value::String("".to_string()).is_truthy());
     }
+
+    #[test]
+    fn test_safe_variants_display_and_truthy_like_plain_string() {
+        let v = Value::SafeJs("\\u003C".to_string());
+        assert!(v.is_truthy());
+        assert_eq!(v.to_string(), "\\u003C");
+        assert!(!Value::SafeHtml(String::new()).is_truthy());
+    }
 }
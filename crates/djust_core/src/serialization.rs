@@ -0,0 +1,71 @@
+//! Conversions between `Value` and JSON, for embedders that need to hand a
+//! rendered context to JavaScript (e.g. `json_script`, VDOM hydration data).
+
+use crate::Value;
+use std::collections::HashMap;
+
+/// Serialize a `Value` to a JSON string.
+pub fn to_json(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::String(s) => json_string(s),
+        Value::List(items) => {
+            let parts: Vec<String> = items.iter().map(to_json).collect();
+            format!("[{}]", parts.join(","))
+        }
+        Value::Object(map) => {
+            let parts: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}:{}", json_string(k), to_json(v)))
+                .collect();
+            format!("{{{}}}", parts.join(","))
+        }
+        Value::DateTime(dt) => json_string(&dt.to_rfc3339()),
+        Value::SafeHtml(s) | Value::SafeJs(s) | Value::SafeUrl(s) | Value::SafeCss(s) => {
+            json_string(s)
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Build an Object `Value` from a `HashMap`, for round-tripping a rendered
+/// `Context` back out to Python/JSON.
+pub fn object_from_map(map: HashMap<String, Value>) -> Value {
+    Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_escapes_string() {
+        assert_eq!(to_json(&Value::String("a\"b".to_string())), "\"a\\\"b\"");
+    }
+
+    #[test]
+    fn test_to_json_list() {
+        let list = Value::List(vec![Value::Integer(1), Value::Integer(2)]);
+        assert_eq!(to_json(&list), "[1,2]");
+    }
+}
@@ -0,0 +1,182 @@
+//! The `{% if %}`-in-attribute quick fix: rewrites a rejected
+//! `{% if cond %}true{% else %}false{% endif %}` found inside an HTML
+//! attribute value into the inline-conditional form
+//! `lexer::validate_no_block_tags_in_attrs`'s own error message already
+//! suggests (`{{ 'true' if cond else 'false' }}`).
+
+use lsp_types::{
+    CodeAction, CodeActionKind, Diagnostic, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+use std::collections::HashMap;
+
+/// Build the quick fix for `diagnostic`, if it's one of ours and the
+/// `{% if %}...{% endif %}` block it points at can still be found in
+/// `source`. Returns `None` for any other diagnostic (e.g. the unknown-tag
+/// warning, which has no rewrite to offer).
+pub fn rewrite_block_tag_in_attr(
+    uri: &Url,
+    source: &str,
+    diagnostic: &Diagnostic,
+) -> Option<CodeAction> {
+    if !diagnostic.message.contains("block tag found inside an HTML attribute value") {
+        return None;
+    }
+
+    let start_offset = position_to_byte_offset(source, diagnostic.range.start)?;
+    let block = find_if_block(source, start_offset)?;
+    let replacement = block.as_inline_conditional();
+
+    let edit = TextEdit {
+        range: byte_range(source, block.start, block.end),
+        new_text: replacement,
+    };
+
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), vec![edit]);
+
+    Some(CodeAction {
+        title: "Rewrite as an inline conditional".to_string(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+struct IfBlock {
+    start: usize,
+    end: usize,
+    condition: String,
+    true_branch: String,
+    false_branch: String,
+}
+
+impl IfBlock {
+    fn as_inline_conditional(&self) -> String {
+        format!(
+            "{{{{ '{}' if {} else '{}' }}}}",
+            self.true_branch, self.condition, self.false_branch
+        )
+    }
+}
+
+/// If `source` has a `{% if cond %}true{% endif %}` or
+/// `{% if cond %}true{% else %}false{% endif %}` block starting exactly at
+/// `from`, pull out its pieces so the caller can rewrite it verbatim.
+/// `from` is the byte offset `validate_no_block_tags_in_attrs` reported -
+/// this only offers the rewrite when that offset is itself an `{% if %}`;
+/// a flagged `{% elif %}`/`{% for %}`/bare `{% endif %}` has no single-tag
+/// rewrite to suggest, so it's left for the user to fix by hand.
+fn find_if_block(source: &str, from: usize) -> Option<IfBlock> {
+    if !source[from..].starts_with("{% if") {
+        return None;
+    }
+    let start = from;
+    let cond_start = start + "{% if".len();
+    let cond_end = cond_start + source[cond_start..].find("%}")?;
+    let condition = source[cond_start..cond_end].trim().to_string();
+
+    let body_start = cond_end + "%}".len();
+    let else_pos = source[body_start..].find("{% else %}");
+    let endif_rel = source[body_start..].find("{% endif %}")?;
+    let endif_pos = body_start + endif_rel;
+
+    let (true_branch, false_branch) = match else_pos {
+        Some(rel) if body_start + rel < endif_pos => {
+            let else_pos = body_start + rel;
+            let false_start = else_pos + "{% else %}".len();
+            (
+                source[body_start..else_pos].to_string(),
+                source[false_start..endif_pos].to_string(),
+            )
+        }
+        _ => (source[body_start..endif_pos].to_string(), String::new()),
+    };
+
+    let end = endif_pos + "{% endif %}".len();
+
+    Some(IfBlock {
+        start,
+        end,
+        condition,
+        true_branch,
+        false_branch,
+    })
+}
+
+fn position_to_byte_offset(source: &str, position: Position) -> Option<usize> {
+    let mut offset = 0;
+    for (i, line) in source.split_inclusive('\n').enumerate() {
+        if i as u32 == position.line {
+            return Some(offset + position.character as usize);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn byte_range(source: &str, start: usize, end: usize) -> Range {
+    Range {
+        start: byte_offset_to_position(source, start),
+        end: byte_offset_to_position(source, end),
+    }
+}
+
+fn byte_offset_to_position(source: &str, offset: usize) -> Position {
+    let prefix = &source[..offset];
+    let line = prefix.matches('\n').count() as u32;
+    let character = match prefix.rfind('\n') {
+        Some(idx) => (offset - idx - 1) as u32,
+        None => offset as u32,
+    };
+    Position { line, character }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::compute_diagnostics;
+
+    #[test]
+    fn test_rewrite_if_else_in_attribute() {
+        let src = r#"<a class="{% if active %}on{% else %}off{% endif %}">x</a>"#;
+        let diagnostics = compute_diagnostics(src);
+        let diag = diagnostics
+            .iter()
+            .find(|d| d.message.contains("block tag found inside"))
+            .expect("expected block-tag-in-attribute diagnostic");
+
+        let uri = Url::parse("file:///template.html").unwrap();
+        let action = rewrite_block_tag_in_attr(&uri, src, diag)
+            .expect("expected a quick fix for the if/else block");
+
+        let edit = &action.edit.unwrap().changes.unwrap()[&uri][0];
+        assert_eq!(edit.new_text, "{{ 'on' if active else 'off' }}");
+    }
+
+    #[test]
+    fn test_rewrite_if_without_else_in_attribute() {
+        let src = r#"<a class="{% if active %}on{% endif %}">x</a>"#;
+        let diagnostics = compute_diagnostics(src);
+        let diag = diagnostics
+            .iter()
+            .find(|d| d.message.contains("block tag found inside"))
+            .unwrap();
+
+        let uri = Url::parse("file:///template.html").unwrap();
+        let action = rewrite_block_tag_in_attr(&uri, src, diag).unwrap();
+        let edit = &action.edit.unwrap().changes.unwrap()[&uri][0];
+        assert_eq!(edit.new_text, "{{ 'on' if active else '' }}");
+    }
+
+    #[test]
+    fn test_no_quick_fix_for_unrelated_diagnostic() {
+        let diagnostics = compute_diagnostics("{% frobnicate x %}");
+        let diag = diagnostics.first().unwrap();
+        let uri = Url::parse("file:///template.html").unwrap();
+        assert!(rewrite_block_tag_in_attr(&uri, "{% frobnicate x %}", diag).is_none());
+    }
+}
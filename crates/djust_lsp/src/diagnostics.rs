@@ -0,0 +1,210 @@
+//! Turns a djust template source string into `lsp_types::Diagnostic`s.
+//!
+//! Reuses the same checks `djust_templates::Template::new` already runs at
+//! compile time (`lexer::validate_no_block_tags_in_attrs`, `lexer::tokenize`,
+//! `parser::parse`) rather than re-implementing any template-syntax logic
+//! here - this module's only job is turning their `Result`s into `Range`s an
+//! editor can underline.
+
+use djust_templates::lexer;
+use djust_templates::parser;
+use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+/// Every tag name the built-in parser understands, including the
+/// "middle"/"end" keywords (`else`, `endif`, ...) that only ever appear
+/// inside one of those blocks. Anything else found as a `{% name ... %}`
+/// tag is flagged with `unknown_tag_diagnostics` below.
+///
+/// This is necessarily best-effort: tags registered at runtime via
+/// `djust_templates::environment::register_tag` aren't visible from a bare
+/// template file, so a project using custom tags will see (and should
+/// ignore) a warning for each one.
+const KNOWN_TAGS: &[&str] = &[
+    "if", "else", "elif", "endif", "for", "empty", "endfor", "block", "endblock", "with",
+    "endwith", "spaceless", "endspaceless", "autoescape", "endautoescape", "extends", "include",
+    "csrf_token", "static", "widthratio", "firstof", "templatetag", "cycle", "now", "macro",
+    "endmacro", "import", "from", "markdown", "endmarkdown",
+];
+
+/// Compute every diagnostic this server knows how to produce for `source`.
+pub fn compute_diagnostics(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if let Err(err) = lexer::validate_no_block_tags_in_attrs(source) {
+        diagnostics.push(block_tag_in_attr_diagnostic(source, &err.to_string()));
+    }
+
+    match lexer::tokenize_with_spans(source) {
+        Ok(spanned) => {
+            diagnostics.extend(unknown_tag_diagnostics(&spanned));
+
+            let tokens: Vec<_> = spanned.iter().map(|(t, _)| t.clone()).collect();
+            if let Err(err) = parser::parse(&tokens) {
+                diagnostics.push(whole_document_error(source, err.to_string()));
+            }
+        }
+        // Covers an unterminated JSX component: `tokenize_with_spans` (via
+        // `parse_jsx_component`) errors instead of accepting it. An
+        // unterminated `{{`/`{%`/`{#` is accepted by the tokenizer and not
+        // reported here.
+        Err(err) => diagnostics.push(whole_document_error(source, err.to_string())),
+    }
+
+    diagnostics
+}
+
+/// `validate_no_block_tags_in_attrs` already prefixes its error with
+/// `line L, col C: ...` (see `lexer::line_col_at`) - parse that back out so
+/// the diagnostic can underline the exact tag instead of the whole file.
+fn block_tag_in_attr_diagnostic(source: &str, message: &str) -> Diagnostic {
+    let range = message
+        .split_once(':')
+        .and_then(|(prefix, _)| parse_line_col_prefix(prefix))
+        .map(|(line, col)| point_range(line, col))
+        .unwrap_or_else(|| whole_document_range(source));
+
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("djust".to_string()),
+        message: message.to_string(),
+        ..Default::default()
+    }
+}
+
+fn parse_line_col_prefix(prefix: &str) -> Option<(usize, usize)> {
+    let rest = prefix.strip_prefix("line ")?;
+    let (line, rest) = rest.split_once(", col ")?;
+    Some((line.trim().parse().ok()?, rest.trim().parse().ok()?))
+}
+
+/// A zero-width `Range` at 1-based `(line, col)`, converted to LSP's
+/// 0-based `Position`.
+fn point_range(line: usize, col: usize) -> Range {
+    let position = Position {
+        line: line.saturating_sub(1) as u32,
+        character: col.saturating_sub(1) as u32,
+    };
+    Range {
+        start: position,
+        end: position,
+    }
+}
+
+/// Flag any `{% name ... %}` tag whose name isn't in `KNOWN_TAGS`, at the
+/// tag's own span.
+fn unknown_tag_diagnostics(spanned: &[(lexer::Token, lexer::Span)]) -> Vec<Diagnostic> {
+    spanned
+        .iter()
+        .filter_map(|(token, span)| match token {
+            lexer::Token::Tag(name, _) if !KNOWN_TAGS.contains(&name.as_str()) => {
+                Some(Diagnostic {
+                    range: span_to_range(span),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    source: Some("djust".to_string()),
+                    message: format!(
+                        "Unknown tag '{name}'. If this is a custom tag registered via \
+                         `environment::register_tag`, this warning can be ignored."
+                    ),
+                    ..Default::default()
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn span_to_range(span: &lexer::Span) -> Range {
+    let (start_line, start_col) = span.start;
+    let (end_line, end_col) = span.end;
+    Range {
+        start: Position {
+            line: start_line.saturating_sub(1) as u32,
+            character: start_col.saturating_sub(1) as u32,
+        },
+        end: Position {
+            line: end_line.saturating_sub(1) as u32,
+            character: end_col.saturating_sub(1) as u32,
+        },
+    }
+}
+
+fn whole_document_error(source: &str, message: String) -> Diagnostic {
+    Diagnostic {
+        range: whole_document_range(source),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("djust".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Span the whole document, for errors (the tokenizer's and
+/// `parser::parse`'s, or a block-tag message without a `line L, col C`
+/// prefix) that don't carry a precise location yet.
+fn whole_document_range(source: &str) -> Range {
+    let last_line = source.lines().count().saturating_sub(1) as u32;
+    let last_col = source.lines().last().map(|l| l.len()).unwrap_or(0) as u32;
+    Range {
+        start: Position { line: 0, character: 0 },
+        end: Position {
+            line: last_line,
+            character: last_col,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_tag_in_attr_produces_precise_range() {
+        let src = "<div>\n  <a class=\"{% if x %}active{% endif %}\">link</a>\n</div>";
+        let diagnostics = compute_diagnostics(src);
+        let diag = diagnostics
+            .iter()
+            .find(|d| d.message.contains("block tag"))
+            .expect("expected a block-tag-in-attribute diagnostic");
+        assert_eq!(diag.range.start.line, 1);
+        assert_eq!(diag.severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn test_unknown_tag_produces_warning() {
+        let diagnostics = compute_diagnostics("{% frobnicate x %}");
+        let diag = diagnostics
+            .iter()
+            .find(|d| d.message.contains("Unknown tag"))
+            .expect("expected an unknown-tag diagnostic");
+        assert_eq!(diag.severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_known_tag_produces_no_unknown_tag_warning() {
+        let diagnostics = compute_diagnostics("{% if x %}y{% endif %}");
+        assert!(!diagnostics.iter().any(|d| d.message.contains("Unknown tag")));
+    }
+
+    #[test]
+    fn test_unterminated_jsx_produces_diagnostic() {
+        let diagnostics = compute_diagnostics("<Button>Click me");
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.message.contains("Unterminated JSX component")));
+    }
+
+    #[test]
+    fn test_mismatched_block_produces_diagnostic() {
+        let diagnostics = compute_diagnostics("{% if x %}{% endfor %}{% endif %}");
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.message.contains("expected {% endif %}")));
+    }
+
+    #[test]
+    fn test_well_formed_template_has_no_diagnostics() {
+        let diagnostics = compute_diagnostics("<p>Hello {{ name }}</p>");
+        assert!(diagnostics.is_empty());
+    }
+}
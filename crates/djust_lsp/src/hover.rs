@@ -0,0 +1,64 @@
+//! `textDocument/hover` support for built-in `{% tag %}` names.
+
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind};
+
+/// One-line doc text per built-in tag, keyed by the tag name as it appears
+/// right after `{%`. Kept in the same order `parser::parse_tag` matches
+/// them in, so the two lists are easy to keep in sync by eye.
+const TAG_DOCS: &[(&str, &str)] = &[
+    ("if", "`{% if condition %}...{% elif other %}...{% else %}...{% endif %}` - conditional block."),
+    ("for", "`{% for x in items %}...{% empty %}...{% endfor %}` - loop over an iterable, with an optional `{% empty %}` branch for when it's empty."),
+    ("block", "`{% block name %}...{% endblock %}` - a named, overridable region for `{% extends %}` children."),
+    ("with", "`{% with a=1 b=2 %}...{% endwith %}` - bind local variables for the duration of the block."),
+    ("spaceless", "`{% spaceless %}...{% endspaceless %}` - strip whitespace between HTML tags in the rendered output."),
+    ("markdown", "`{% markdown \"prefix\" %}...{% endmarkdown %}` - render the enclosed CommonMark prose to HTML at parse time; embedded `{{ }}` variables stay live."),
+    ("autoescape", "`{% autoescape off %}...{% endautoescape %}` (or `on`) - toggle auto-escaping of `{{ }}` output inside the block."),
+    ("extends", "`{% extends \"parent.html\" %}` - inherit from a parent template; must be the first tag in the file."),
+    ("include", "`{% include \"partial.html\" with x=1 only %}` - render another template inline, optionally passing/restricting context."),
+    ("csrf_token", "`{% csrf_token %}` - render a hidden CSRF-protection input."),
+    ("static", "`{% static \"path.css\" %}` - resolve a static asset path."),
+    ("widthratio", "`{% widthratio value max_value max_width %}` - compute `value / max_value * max_width`."),
+    ("firstof", "`{% firstof a b c %}` - render the first argument that is truthy."),
+    ("templatetag", "`{% templatetag openblock %}` - emit a literal template-syntax character sequence."),
+    ("cycle", "`{% cycle a b c as name %}` - cycle through values across loop iterations."),
+    ("now", "`{% now \"Y-m-d\" %}` - render the current date/time in the given format."),
+    ("macro", "`{% macro name(params) %}...{% endmacro %}` - define a reusable, parameterized fragment callable as `{{ name(args) }}`."),
+    ("import", "`{% import \"helpers.html\" as h %}` - bind every top-level macro in another template under a namespace."),
+    ("from", "`{% from \"helpers.html\" import card, button as b %}` - bind specific macros from another template."),
+];
+
+/// Hover text for the tag name under the cursor, if `word` is a recognized
+/// built-in tag.
+pub fn hover_for_tag(word: &str) -> Option<Hover> {
+    let doc = TAG_DOCS
+        .iter()
+        .find(|(name, _)| *name == word)
+        .map(|(_, doc)| *doc)?;
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: doc.to_string(),
+        }),
+        range: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hover_for_known_tag() {
+        let hover = hover_for_tag("for").expect("expected hover text for 'for'");
+        match hover.contents {
+            HoverContents::Markup(content) => assert!(content.value.contains("loop over")),
+            other => panic!("expected Markup contents, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_hover_for_unknown_word_is_none() {
+        assert!(hover_for_tag("not_a_real_tag").is_none());
+    }
+}
@@ -0,0 +1,196 @@
+//! `djust-lsp`: a language server over djust template files (`.html`/
+//! `.djust`), modeled on the way the nml language server wires
+//! `lsp-server` to a parser and pushes diagnostics.
+//!
+//! The server speaks LSP over stdio. `main` owns the connection and the
+//! request/notification loop; [`diagnostics`] turns a template source into
+//! `publishDiagnostics`-ready `Diagnostic`s using the same `lexer`/`parser`
+//! validation `djust_templates::Template::new` already runs at compile
+//! time, [`hover`] answers `textDocument/hover` for built-in tag names, and
+//! [`code_action`] offers the inline-conditional rewrite suggested by
+//! `validate_no_block_tags_in_attrs`'s own error message.
+
+mod code_action;
+mod diagnostics;
+mod hover;
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{CodeActionRequest, HoverRequest, Request as _};
+use lsp_types::{
+    CodeActionParams, CodeActionProviderCapability, HoverParams, HoverProviderCapability,
+    InitializeParams, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+
+fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    let server_capabilities = serde_json::to_value(capabilities)?;
+    let initialize_params = connection.initialize(server_capabilities)?;
+    run(&connection, initialize_params)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+/// A template file's text, tracked so hover/code-action requests (which
+/// only carry a `Url`, not the content) can look it back up.
+type Documents = HashMap<Url, String>;
+
+fn run(connection: &Connection, params: serde_json::Value) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let _params: InitializeParams = serde_json::from_value(params)?;
+    let mut documents: Documents = HashMap::new();
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                let response = dispatch_request(&documents, req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(not) => {
+                if not.method == DidOpenTextDocument::METHOD {
+                    let params: lsp_types::DidOpenTextDocumentParams =
+                        serde_json::from_value(not.params)?;
+                    let uri = params.text_document.uri;
+                    documents.insert(uri.clone(), params.text_document.text);
+                    publish_diagnostics(connection, &uri, &documents)?;
+                } else if not.method == DidChangeTextDocument::METHOD {
+                    let params: lsp_types::DidChangeTextDocumentParams =
+                        serde_json::from_value(not.params)?;
+                    let uri = params.text_document.uri;
+                    // Server advertises `TextDocumentSyncKind::FULL`, so the
+                    // last change event always carries the whole new text.
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        documents.insert(uri.clone(), change.text);
+                    }
+                    publish_diagnostics(connection, &uri, &documents)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn dispatch_request(documents: &Documents, req: lsp_server::Request) -> Response {
+    let id = req.id.clone();
+    match req.method.as_str() {
+        m if m == HoverRequest::METHOD => handle_hover(documents, id, req),
+        m if m == CodeActionRequest::METHOD => handle_code_action(documents, id, req),
+        _ => Response::new_err(id, ErrorCode::MethodNotFound as i32, "unhandled method".to_string()),
+    }
+}
+
+fn handle_hover(documents: &Documents, id: RequestId, req: lsp_server::Request) -> Response {
+    let params: HoverParams = match serde_json::from_value(req.params) {
+        Ok(p) => p,
+        Err(e) => return Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    };
+    let uri = params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let hover = documents
+        .get(&uri)
+        .and_then(|text| word_at(text, position))
+        .and_then(|word| hover::hover_for_tag(&word));
+
+    Response::new_ok(id, serde_json::to_value(hover).unwrap_or(serde_json::Value::Null))
+}
+
+fn handle_code_action(documents: &Documents, id: RequestId, req: lsp_server::Request) -> Response {
+    let params: CodeActionParams = match serde_json::from_value(req.params) {
+        Ok(p) => p,
+        Err(e) => return Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    };
+    let uri = params.text_document.uri.clone();
+
+    let actions: Vec<_> = match documents.get(&uri) {
+        Some(source) => params
+            .context
+            .diagnostics
+            .iter()
+            .filter_map(|diag| code_action::rewrite_block_tag_in_attr(&uri, source, diag))
+            .map(lsp_types::CodeActionOrCommand::CodeAction)
+            .collect(),
+        None => vec![],
+    };
+
+    Response::new_ok(id, serde_json::to_value(actions).unwrap_or(serde_json::Value::Null))
+}
+
+fn publish_diagnostics(
+    connection: &Connection,
+    uri: &Url,
+    documents: &Documents,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let Some(source) = documents.get(uri) else {
+        return Ok(());
+    };
+    let params = PublishDiagnosticsParams {
+        uri: uri.clone(),
+        diagnostics: diagnostics::compute_diagnostics(source),
+        version: None,
+    };
+    let notification = lsp_server::Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        serde_json::to_value(params)?,
+    );
+    connection.sender.send(Message::Notification(notification))?;
+    Ok(())
+}
+
+/// The identifier-ish word touching `position` in `text`, for hover lookups.
+/// Only meant to find a `{% tag %}` name, so this is deliberately simple:
+/// alphanumeric/underscore characters on either side of the cursor.
+fn word_at(text: &str, position: lsp_types::Position) -> Option<String> {
+    let line = text.lines().nth(position.line as usize)?;
+    let chars: Vec<char> = line.chars().collect();
+    let col = (position.character as usize).min(chars.len());
+
+    let is_word_char = |c: &char| c.is_alphanumeric() || *c == '_';
+    let mut start = col;
+    while start > 0 && is_word_char(&chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = col;
+    while end < chars.len() && is_word_char(&chars[end]) {
+        end += 1;
+    }
+    if start == end {
+        return None;
+    }
+    Some(chars[start..end].iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_at_finds_tag_name() {
+        let text = "{% if x %}y{% endif %}";
+        let word = word_at(text, lsp_types::Position { line: 0, character: 4 });
+        assert_eq!(word.as_deref(), Some("if"));
+    }
+
+    #[test]
+    fn test_word_at_outside_word_is_none() {
+        let text = "{% if x %}";
+        let word = word_at(text, lsp_types::Position { line: 0, character: 2 });
+        assert!(word.is_none());
+    }
+}
@@ -0,0 +1,439 @@
+//! Compiles a parsed `Node` tree into a flat [`Program`](crate::instruction::Program)
+//! of [`Instruction`]s, then replays it with an explicit instruction
+//! pointer and loop-state stack instead of recursing back into the tree on
+//! every render.
+//!
+//! Constructs with simple, purely local semantics - literal text, `{{ }}`
+//! variables, `if`, and `for` - are lowered directly into the instruction
+//! vector (see `compile_node`). Everything else (inheritance, includes,
+//! macros, components, ...) is left as an [`Instruction::RenderNode`],
+//! which falls back to the ordinary recursive renderer for just that one
+//! node, so compiling a template never changes what it renders - it only
+//! changes how much work repeated renders redo.
+
+use crate::inheritance::TemplateLoader;
+use crate::instruction::{Instruction, Program};
+use crate::parser::Node;
+use crate::renderer;
+use djust_core::{Context, Result, Value};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Flattens `nodes` into a `Program`. Call once per template and reuse the
+/// result across renders via `render_program`.
+pub fn compile(nodes: &[Node]) -> Program {
+    let mut instructions = Vec::new();
+    compile_nodes(nodes, &mut instructions);
+    Program { instructions }
+}
+
+fn compile_nodes(nodes: &[Node], out: &mut Vec<Instruction>) {
+    for node in nodes {
+        compile_node(node, out);
+    }
+}
+
+fn compile_node(node: &Node, out: &mut Vec<Instruction>) {
+    match node {
+        Node::Text(text) => out.push(Instruction::PushLiteral(text.clone())),
+        // `block.super` resolves against the per-block ancestor stack
+        // built by the inheritance chain, which the flat VM doesn't carry
+        // - fall back to the tree-walker for it, same as every other
+        // inheritance-dependent construct.
+        Node::Variable(path, filters, escape_context) if path != "block.super" => {
+            out.push(Instruction::PushVar {
+                path: path.clone(),
+                filters: filters.clone(),
+                escape_context: *escape_context,
+            });
+        }
+        Node::If {
+            condition,
+            true_nodes,
+            false_nodes,
+        } => compile_if(condition, true_nodes, false_nodes, out),
+        Node::For {
+            var_names,
+            iterable,
+            reversed,
+            key,
+            nodes,
+            empty_nodes,
+        } => compile_for(var_names, iterable, *reversed, key, nodes, empty_nodes, out),
+        other => out.push(Instruction::RenderNode(other.clone())),
+    }
+}
+
+fn compile_if(condition: &str, true_nodes: &[Node], false_nodes: &[Node], out: &mut Vec<Instruction>) {
+    let jump_idx = out.len();
+    out.push(Instruction::JumpIfFalse {
+        condition: condition.to_string(),
+        target: 0,
+    });
+
+    compile_nodes(true_nodes, out);
+
+    let goto_idx = out.len();
+    out.push(Instruction::Goto(0));
+
+    let false_start = out.len();
+    if false_nodes.is_empty() {
+        // Mirrors `write_node`'s placeholder comment, so VDOM diffing
+        // still has a stable node to target when the condition later
+        // flips true.
+        out.push(Instruction::PushLiteral("<!--dj-if-->".to_string()));
+    } else {
+        compile_nodes(false_nodes, out);
+    }
+
+    out[goto_idx] = Instruction::Goto(out.len());
+    out[jump_idx] = Instruction::JumpIfFalse {
+        condition: condition.to_string(),
+        target: false_start,
+    };
+}
+
+fn compile_for(
+    var_names: &[String],
+    iterable: &str,
+    reversed: bool,
+    key: &Option<String>,
+    body: &[Node],
+    empty_nodes: &[Node],
+    out: &mut Vec<Instruction>,
+) {
+    let begin_idx = out.len();
+    out.push(Instruction::ForBegin {
+        var_names: var_names.to_vec(),
+        iterable: iterable.to_string(),
+        reversed,
+        key: key.clone(),
+        body_start: 0,
+        empty_start: None,
+        end: 0,
+    });
+
+    compile_nodes(body, out);
+    out.push(Instruction::ForEnd { begin: begin_idx });
+
+    let empty_start = if empty_nodes.is_empty() {
+        None
+    } else {
+        // When the loop actually runs, `ForEnd` exhaustion falls straight
+        // through to here - skip over the `{% empty %}` block in that case.
+        let skip_idx = out.len();
+        out.push(Instruction::Goto(0));
+        let start = out.len();
+        compile_nodes(empty_nodes, out);
+        out[skip_idx] = Instruction::Goto(out.len());
+        Some(start)
+    };
+
+    out[begin_idx] = Instruction::ForBegin {
+        var_names: var_names.to_vec(),
+        iterable: iterable.to_string(),
+        reversed,
+        key: key.clone(),
+        body_start: begin_idx + 1,
+        empty_start,
+        end: out.len(),
+    };
+}
+
+/// One active `{% for %}` loop on the VM's loop-state stack, tracking just
+/// enough to resume at the next iteration (or unwind) when `ForEnd` is hit.
+struct LoopState {
+    var_names: Vec<String>,
+    iterable: String,
+    key: Option<String>,
+    items: Vec<(usize, Value)>,
+    next_idx: usize,
+    total: usize,
+    counter: usize,
+    body_start: usize,
+    saved_cycle_counter: Option<Value>,
+    saved_forloop: Option<Value>,
+}
+
+/// Executes a compiled `Program` against `context`, writing output to
+/// `out`. Behaves identically to walking the original `Node` tree with
+/// `renderer::render_nodes_to_writer`, just without re-parsing it.
+pub fn render_program<W: Write, L: TemplateLoader>(
+    program: &Program,
+    context: &Context,
+    loader: Option<&L>,
+    out: &mut W,
+) -> Result<()> {
+    // Collected once per render, same as the tree-walker does today - the
+    // flat instructions carry original `Node`s for anything unflattened
+    // (including `{% macro %}`/`{% import %}`), so macro resolution sees
+    // exactly what it would see walking the tree directly.
+    let pseudo_nodes: Vec<Node> = program
+        .instructions
+        .iter()
+        .filter_map(|instr| match instr {
+            Instruction::RenderNode(node) => Some(node.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut macros = HashMap::new();
+    renderer::collect_macro_scope(&pseudo_nodes, loader, &mut macros)?;
+
+    let mut ctx = context.clone();
+    let mut loop_stack: Vec<LoopState> = Vec::new();
+    let mut ip = 0usize;
+
+    while ip < program.instructions.len() {
+        match &program.instructions[ip] {
+            Instruction::PushLiteral(text) => {
+                out.write_all(text.as_bytes())?;
+                ip += 1;
+            }
+            Instruction::PushVar {
+                path,
+                filters,
+                escape_context,
+            } => {
+                renderer::render_variable(path, filters, *escape_context, &ctx, out)?;
+                ip += 1;
+            }
+            Instruction::JumpIfFalse { condition, target } => {
+                if renderer::evaluate_condition(condition, &ctx)? {
+                    ip += 1;
+                } else {
+                    ip = *target;
+                }
+            }
+            Instruction::Goto(target) => ip = *target,
+            Instruction::ForBegin {
+                var_names,
+                iterable,
+                reversed,
+                key,
+                body_start,
+                empty_start,
+                end,
+            } => {
+                match renderer::resolve_for_iteration(&ctx, var_names, iterable, *reversed) {
+                    Some(items) if !items.is_empty() => {
+                        let total = items.len();
+                        let saved_cycle_counter = ctx.get("__djust_cycle_counter").cloned();
+                        let saved_forloop = ctx.get("forloop").cloned();
+                        let (index, item) = items[0].clone();
+                        renderer::bind_for_iteration(
+                            &mut ctx,
+                            var_names,
+                            iterable,
+                            0,
+                            total,
+                            index,
+                            item,
+                            &saved_forloop,
+                        );
+                        renderer::write_for_key_marker(key.as_deref(), &ctx, out)?;
+                        loop_stack.push(LoopState {
+                            var_names: var_names.clone(),
+                            iterable: iterable.clone(),
+                            key: key.clone(),
+                            items,
+                            next_idx: 1,
+                            total,
+                            counter: 0,
+                            body_start: *body_start,
+                            saved_cycle_counter,
+                            saved_forloop,
+                        });
+                        ip = *body_start;
+                    }
+                    _ if empty_start.is_none() => {
+                        // Mirrors `write_node`'s DJE-053-style placeholder:
+                        // no items and no {% empty %} block, so emit a
+                        // stable anchor instead of nothing.
+                        out.write_all(b"<!--dj-for-->")?;
+                        ip = *end;
+                    }
+                    _ => ip = empty_start.unwrap_or(*end),
+                }
+            }
+            Instruction::ForEnd { .. } => {
+                let has_next = {
+                    let state = loop_stack.last().expect("ForEnd without matching ForBegin");
+                    state.next_idx < state.items.len()
+                };
+
+                if has_next {
+                    let state = loop_stack.last_mut().unwrap();
+                    let (index, item) = state.items[state.next_idx].clone();
+                    state.next_idx += 1;
+                    state.counter += 1;
+                    let (counter, total, body_start) = (state.counter, state.total, state.body_start);
+                    let var_names = state.var_names.clone();
+                    let iterable = state.iterable.clone();
+                    let key = state.key.clone();
+                    let saved_forloop = state.saved_forloop.clone();
+                    renderer::bind_for_iteration(
+                        &mut ctx,
+                        &var_names,
+                        &iterable,
+                        counter,
+                        total,
+                        index,
+                        item,
+                        &saved_forloop,
+                    );
+                    renderer::write_for_key_marker(key.as_deref(), &ctx, out)?;
+                    ip = body_start;
+                } else {
+                    let state = loop_stack.pop().unwrap();
+                    if let Some(saved) = state.saved_cycle_counter {
+                        ctx.set("__djust_cycle_counter".to_string(), saved);
+                    }
+                    if let Some(saved) = state.saved_forloop {
+                        ctx.set("forloop".to_string(), saved);
+                    }
+                    for var_name in &state.var_names {
+                        ctx.clear_loop_mapping(var_name);
+                    }
+                    ip += 1;
+                }
+            }
+            Instruction::RenderNode(node) => {
+                renderer::render_node_standalone(node, &ctx, loader, &macros, out)?;
+                ip += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use djust_core::Value;
+
+    struct NoLoader;
+    impl TemplateLoader for NoLoader {
+        fn load_template(&self, _name: &str) -> Result<Vec<Node>> {
+            Err(djust_core::DjangoRustError::TemplateError(
+                "no loader configured".to_string(),
+            ))
+        }
+    }
+
+    fn run(source: &str, context: &Context) -> String {
+        let tokens = tokenize(source).unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let program = compile(&nodes);
+        let mut buf = Vec::new();
+        render_program(&program, context, None::<&NoLoader>, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_compiles_text_and_variable_to_flat_instructions() {
+        let program = compile(&parse(&tokenize("Hi {{ name }}!").unwrap()).unwrap());
+        assert_eq!(
+            program.instructions,
+            vec![
+                Instruction::PushLiteral("Hi ".to_string()),
+                Instruction::PushVar {
+                    path: "name".to_string(),
+                    filters: vec![],
+                    escape_context: crate::filters::EscapeContext::Text,
+                },
+                Instruction::PushLiteral("!".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_program_matches_tree_walker_for_if() {
+        let mut context = Context::new();
+        context.set("flag".to_string(), Value::Bool(true));
+        let result = run("{% if flag %}yes{% else %}no{% endif %}", &context);
+        assert_eq!(result, "yes");
+
+        context.set("flag".to_string(), Value::Bool(false));
+        let result = run("{% if flag %}yes{% else %}no{% endif %}", &context);
+        assert_eq!(result, "no");
+    }
+
+    #[test]
+    fn test_render_program_if_without_else_emits_placeholder() {
+        let mut context = Context::new();
+        context.set("flag".to_string(), Value::Bool(false));
+        let result = run("{% if flag %}yes{% endif %}", &context);
+        assert_eq!(result, "<!--dj-if-->");
+    }
+
+    #[test]
+    fn test_render_program_for_loop_with_forloop_counters() {
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+            ]),
+        );
+        let result = run(
+            "{% for item in items %}{{ forloop.counter }}:{{ item }},{% endfor %}",
+            &context,
+        );
+        assert_eq!(result, "1:a,2:b,");
+    }
+
+    #[test]
+    fn test_render_program_for_loop_empty_block() {
+        let mut context = Context::new();
+        context.set("items".to_string(), Value::List(vec![]));
+        let result = run(
+            "{% for item in items %}{{ item }}{% empty %}nothing{% endfor %}",
+            &context,
+        );
+        assert_eq!(result, "nothing");
+    }
+
+    #[test]
+    fn test_render_program_nested_for_loops() {
+        let mut context = Context::new();
+        context.set(
+            "outer".to_string(),
+            Value::List(vec![
+                Value::String("x".to_string()),
+                Value::String("y".to_string()),
+            ]),
+        );
+        context.set(
+            "inner".to_string(),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+        );
+        let result = run(
+            "{% for o in outer %}{% for i in inner %}{{ o }}{{ i }}{% endfor %}{% endfor %}",
+            &context,
+        );
+        assert_eq!(result, "x1x2y1y2");
+    }
+
+    #[test]
+    fn test_render_program_for_loop_emits_key_anchors() {
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+        );
+        let result = run("{% for item in items key item %}{{ item }}{% endfor %}", &context);
+        assert_eq!(result, "<!--dj-for:1-->1<!--dj-for:2-->2");
+    }
+
+    #[test]
+    fn test_render_program_empty_for_without_empty_block_emits_placeholder() {
+        let mut context = Context::new();
+        context.set("items".to_string(), Value::List(vec![]));
+        let result = run("{% for item in items %}{{ item }}{% endfor %}", &context);
+        assert_eq!(result, "<!--dj-for-->");
+    }
+}
@@ -0,0 +1,771 @@
+//! Lexer + precedence-climbing parser for `{% if %}` conditions and for
+//! arithmetic expressions shared with `{{ }}` output (see
+//! [`evaluate_value`]).
+//!
+//! Replaces the old string-splitting evaluator (`condition.contains("==")`,
+//! `condition.find(" and ")`, ...), which misfired whenever an operator's
+//! characters showed up inside a quoted string literal (`{% if name == "a >
+//! b" %}`) and couldn't express parenthesized grouping or nesting.
+//!
+//! Binding power, loosest to tightest: `or` < `and` < `not` < comparisons/
+//! `in` < `+ -` < `* / %` < unary `-`. A parenthesized group overrides this
+//! and is parsed as a fresh sub-expression - whether that's a boolean group
+//! (`(b or c)`, used standalone) or an arithmetic one (`(total + 1) > 5`) is
+//! decided by whether an operator follows the matching close paren, see
+//! `Parser::paren_group_feeds_arithmetic`. Value operands - dotted paths,
+//! int/float/string literals, and `|filter:arg` pipelines, including a
+//! parenthesized filter-argument subexpression like `default:(other|upper)`
+//! - are left as raw source spans and handed to `renderer::get_value`
+//! unchanged, so existing literal and filter-pipe resolution isn't
+//! reimplemented here; only the arithmetic combination of their resolved
+//! values is new.
+
+use crate::renderer::{compare_values, get_value, values_equal, ToF64};
+use djust_core::{Context, DjangoRustError, Result, Value};
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident,
+    Number,
+    Str,
+    And,
+    Or,
+    Not,
+    In,
+    True,
+    False,
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Pipe,
+    Colon,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+    #[allow(dead_code)]
+    text: &'a str,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token<'_>>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        match c {
+            b'"' | b'\'' => {
+                let quote = c;
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+                if i >= bytes.len() {
+                    return Err(DjangoRustError::TemplateError(format!(
+                        "Unterminated string literal in condition: {input}"
+                    )));
+                }
+                i += 1; // closing quote
+                tokens.push(Token {
+                    kind: TokenKind::Str,
+                    text: &input[start..i],
+                    start,
+                    end: i,
+                });
+            }
+            b'(' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::LParen, text: "(", start, end: i });
+            }
+            b')' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::RParen, text: ")", start, end: i });
+            }
+            b'|' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Pipe, text: "|", start, end: i });
+            }
+            b':' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Colon, text: ":", start, end: i });
+            }
+            b'=' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                tokens.push(Token { kind: TokenKind::Eq, text: "==", start, end: i });
+            }
+            b'!' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                tokens.push(Token { kind: TokenKind::Ne, text: "!=", start, end: i });
+            }
+            b'>' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                tokens.push(Token { kind: TokenKind::Ge, text: ">=", start, end: i });
+            }
+            b'<' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                tokens.push(Token { kind: TokenKind::Le, text: "<=", start, end: i });
+            }
+            b'>' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Gt, text: ">", start, end: i });
+            }
+            b'<' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Lt, text: "<", start, end: i });
+            }
+            b'+' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Plus, text: "+", start, end: i });
+            }
+            // Note: unlike `>=`/`<=`, a bare `-` is never folded into a
+            // negative number literal here - `a-5` and `a - 5` both lex as
+            // `Ident(a) Minus Number(5)`, and the parser's unary-minus tier
+            // (see `parse_unary`) is what turns `Minus Number` into a
+            // negated operand. This lets `-` double as subtraction without
+            // a lexer-level ambiguity between "a minus 5" and "a, -5".
+            b'-' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Minus, text: "-", start, end: i });
+            }
+            b'*' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Star, text: "*", start, end: i });
+            }
+            b'/' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Slash, text: "/", start, end: i });
+            }
+            b'%' => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Percent, text: "%", start, end: i });
+            }
+            _ if c.is_ascii_digit() => {
+                i += 1;
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number,
+                    text: &input[start..i],
+                    start,
+                    end: i,
+                });
+            }
+            _ if c.is_ascii_alphabetic() || c == b'_' => {
+                i += 1;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.')
+                {
+                    i += 1;
+                }
+                let text = &input[start..i];
+                let kind = match text {
+                    "and" => TokenKind::And,
+                    "or" => TokenKind::Or,
+                    "not" => TokenKind::Not,
+                    "in" => TokenKind::In,
+                    "true" | "True" => TokenKind::True,
+                    "false" | "False" => TokenKind::False,
+                    _ => TokenKind::Ident,
+                };
+                tokens.push(Token { kind, text, start, end: i });
+            }
+            _ => {
+                return Err(DjangoRustError::TemplateError(format!(
+                    "Unexpected character {:?} in condition: {input}",
+                    c as char
+                )));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A parsed `{% if %}` expression. `Operand`/`Compare`/`In` hold a
+/// [`ValueExpr`] rather than a bare span, since either side may itself be an
+/// arithmetic expression (`total * 2 > limit`).
+#[derive(Debug)]
+enum Expr<'a> {
+    Bool(bool),
+    Operand(ValueExpr<'a>),
+    Not(Box<Expr<'a>>),
+    And(Box<Expr<'a>>, Box<Expr<'a>>),
+    Or(Box<Expr<'a>>, Box<Expr<'a>>),
+    Compare(CompareOp, ValueExpr<'a>, ValueExpr<'a>),
+    In(ValueExpr<'a>, ValueExpr<'a>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+/// A value-producing expression - the `+ - * / %` subsystem shared by
+/// `{% if %}` comparisons and `{{ }}` output (see [`evaluate_value`]).
+/// `Operand` leaves are raw source spans (not pre-resolved), same as
+/// `Expr`'s leaves used to be, since `get_value` already knows how to
+/// resolve a dotted path/literal/filter-pipeline from its original text.
+#[derive(Debug)]
+enum ValueExpr<'a> {
+    Operand(&'a str),
+    Neg(Box<ValueExpr<'a>>),
+    Arith(ArithOp, Box<ValueExpr<'a>>, Box<ValueExpr<'a>>),
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek_kind(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn advance(&mut self) -> Option<Token<'a>> {
+        let tok = self.tokens.get(self.pos).cloned();
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token<'a>> {
+        match self.advance() {
+            Some(tok) if tok.kind == kind => Ok(tok),
+            _ => Err(DjangoRustError::TemplateError(format!(
+                "Expected {kind:?} in condition: {}",
+                self.input
+            ))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr<'a>> {
+        let mut left = self.parse_and()?;
+        while matches!(self.peek_kind(), Some(TokenKind::Or)) {
+            self.advance();
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr<'a>> {
+        let mut left = self.parse_not()?;
+        while matches!(self.peek_kind(), Some(TokenKind::And)) {
+            self.advance();
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr<'a>> {
+        if matches!(self.peek_kind(), Some(TokenKind::Not)) {
+            self.advance();
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr<'a>> {
+        // A leading `(` is a standalone boolean group (`(b or c)`) unless an
+        // operator follows its matching close paren, in which case it's an
+        // arithmetic grouping that feeds the value-expression parsed below
+        // (`(total + 1) > 5`).
+        if matches!(self.peek_kind(), Some(TokenKind::LParen)) && !self.paren_group_feeds_arithmetic()
+        {
+            self.advance();
+            let inner = self.parse_or()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(inner);
+        }
+        if matches!(self.peek_kind(), Some(TokenKind::True)) {
+            self.advance();
+            return Ok(Expr::Bool(true));
+        }
+        if matches!(self.peek_kind(), Some(TokenKind::False)) {
+            self.advance();
+            return Ok(Expr::Bool(false));
+        }
+
+        let left = self.parse_add_sub()?;
+
+        if matches!(self.peek_kind(), Some(TokenKind::In)) {
+            self.advance();
+            let right = self.parse_add_sub()?;
+            return Ok(Expr::In(left, right));
+        }
+
+        let op = match self.peek_kind() {
+            Some(TokenKind::Eq) => CompareOp::Eq,
+            Some(TokenKind::Ne) => CompareOp::Ne,
+            Some(TokenKind::Ge) => CompareOp::Ge,
+            Some(TokenKind::Le) => CompareOp::Le,
+            Some(TokenKind::Gt) => CompareOp::Gt,
+            Some(TokenKind::Lt) => CompareOp::Lt,
+            _ => return Ok(Expr::Operand(left)),
+        };
+        self.advance();
+        let right = self.parse_add_sub()?;
+        Ok(Expr::Compare(op, left, right))
+    }
+
+    /// Scans ahead from the current `(` to its matching `)` without
+    /// consuming tokens, and reports whether an arithmetic/comparison
+    /// operator immediately follows it.
+    fn paren_group_feeds_arithmetic(&self) -> bool {
+        let mut depth = 0i32;
+        let mut idx = self.pos;
+        loop {
+            match self.tokens.get(idx).map(|t| &t.kind) {
+                Some(TokenKind::LParen) => depth += 1,
+                Some(TokenKind::RParen) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                Some(_) => {}
+                None => return false,
+            }
+            idx += 1;
+        }
+        matches!(
+            self.tokens.get(idx + 1).map(|t| &t.kind),
+            Some(
+                TokenKind::Eq
+                    | TokenKind::Ne
+                    | TokenKind::Ge
+                    | TokenKind::Le
+                    | TokenKind::Gt
+                    | TokenKind::Lt
+                    | TokenKind::In
+                    | TokenKind::Plus
+                    | TokenKind::Minus
+                    | TokenKind::Star
+                    | TokenKind::Slash
+                    | TokenKind::Percent
+            )
+        )
+    }
+
+    /// `+ -`, precedence 4.
+    fn parse_add_sub(&mut self) -> Result<ValueExpr<'a>> {
+        let mut left = self.parse_mul_div_mod()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Plus) => ArithOp::Add,
+                Some(TokenKind::Minus) => ArithOp::Sub,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_mul_div_mod()?;
+            left = ValueExpr::Arith(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// `* / %`, precedence 5.
+    fn parse_mul_div_mod(&mut self) -> Result<ValueExpr<'a>> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Star) => ArithOp::Mul,
+                Some(TokenKind::Slash) => ArithOp::Div,
+                Some(TokenKind::Percent) => ArithOp::Mod,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_unary()?;
+            left = ValueExpr::Arith(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// Unary `-`, precedence 6 (tightest).
+    fn parse_unary(&mut self) -> Result<ValueExpr<'a>> {
+        if matches!(self.peek_kind(), Some(TokenKind::Minus)) {
+            self.advance();
+            return Ok(ValueExpr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_value_primary()
+    }
+
+    /// A parenthesized arithmetic sub-expression, or a leaf operand.
+    fn parse_value_primary(&mut self) -> Result<ValueExpr<'a>> {
+        if matches!(self.peek_kind(), Some(TokenKind::LParen)) {
+            self.advance();
+            let inner = self.parse_add_sub()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(inner);
+        }
+        let (start, end) = self.parse_leaf()?;
+        Ok(ValueExpr::Operand(self.input[start..end].trim()))
+    }
+
+    /// Parse a value operand - a dotted path/number/string literal,
+    /// optionally followed by a `|filter[:arg]` pipeline - and return its
+    /// source span. A parenthesized filter argument (e.g.
+    /// `default:(other|upper)`) is matched for balanced parens so the whole
+    /// subexpression stays in the span for `get_value` to resolve.
+    fn parse_leaf(&mut self) -> Result<(usize, usize)> {
+        let start_tok = self.advance().ok_or_else(|| {
+            DjangoRustError::TemplateError(format!("Expected a value in condition: {}", self.input))
+        })?;
+        if !matches!(
+            start_tok.kind,
+            TokenKind::Ident | TokenKind::Number | TokenKind::Str
+        ) {
+            return Err(DjangoRustError::TemplateError(format!(
+                "Expected a value in condition: {}",
+                self.input
+            )));
+        }
+        let start = start_tok.start;
+        let mut end = start_tok.end;
+
+        while matches!(self.peek_kind(), Some(TokenKind::Pipe)) {
+            self.advance();
+            let name_tok = self.expect(TokenKind::Ident)?;
+            end = name_tok.end;
+            if matches!(self.peek_kind(), Some(TokenKind::Colon)) {
+                self.advance();
+                if matches!(self.peek_kind(), Some(TokenKind::LParen)) {
+                    let mut depth = 0i32;
+                    loop {
+                        let tok = self.advance().ok_or_else(|| {
+                            DjangoRustError::TemplateError(format!(
+                                "Unbalanced parentheses in condition: {}",
+                                self.input
+                            ))
+                        })?;
+                        match tok.kind {
+                            TokenKind::LParen => depth += 1,
+                            TokenKind::RParen => depth -= 1,
+                            _ => {}
+                        }
+                        end = tok.end;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                } else {
+                    let arg_tok = self.advance().ok_or_else(|| {
+                        DjangoRustError::TemplateError(format!(
+                            "Expected a filter argument in condition: {}",
+                            self.input
+                        ))
+                    })?;
+                    end = arg_tok.end;
+                }
+            }
+        }
+
+        Ok((start, end))
+    }
+}
+
+fn eval(expr: &Expr, context: &Context) -> Result<bool> {
+    match expr {
+        Expr::Bool(b) => Ok(*b),
+        Expr::Operand(value_expr) => Ok(eval_value(value_expr, context)?.is_truthy()),
+        Expr::Not(inner) => Ok(!eval(inner, context)?),
+        Expr::And(left, right) => Ok(eval(left, context)? && eval(right, context)?),
+        Expr::Or(left, right) => Ok(eval(left, context)? || eval(right, context)?),
+        Expr::Compare(op, left, right) => {
+            let left = eval_value(left, context)?;
+            let right = eval_value(right, context)?;
+            Ok(match op {
+                CompareOp::Eq => values_equal(&left, &right),
+                CompareOp::Ne => !values_equal(&left, &right),
+                CompareOp::Gt => compare_values(&left, &right) > 0,
+                CompareOp::Ge => compare_values(&left, &right) >= 0,
+                CompareOp::Lt => compare_values(&left, &right) < 0,
+                CompareOp::Le => compare_values(&left, &right) <= 0,
+            })
+        }
+        Expr::In(needle, haystack) => {
+            let needle = eval_value(needle, context)?;
+            let haystack = eval_value(haystack, context)?;
+            Ok(match haystack {
+                Value::List(items) => items.iter().any(|item| values_equal(&needle, item)),
+                Value::String(s) => match &needle {
+                    Value::String(n) => s.contains(n.as_str()),
+                    _ => false,
+                },
+                // Django: "x in dict" checks dict keys.
+                Value::Object(map) => map.contains_key(&needle.to_string()),
+                _ => false,
+            })
+        }
+    }
+}
+
+fn eval_value(expr: &ValueExpr, context: &Context) -> Result<Value> {
+    match expr {
+        ValueExpr::Operand(span) => get_value(span, context),
+        ValueExpr::Neg(inner) => Ok(negate(eval_value(inner, context)?)),
+        ValueExpr::Arith(op, left, right) => {
+            let left = eval_value(left, context)?;
+            let right = eval_value(right, context)?;
+            Ok(arith(*op, &left, &right))
+        }
+    }
+}
+
+fn negate(value: Value) -> Value {
+    match value {
+        Value::Integer(i) => Value::Integer(-i),
+        Value::Float(f) => Value::Float(-f),
+        other => match other.to_f64() {
+            Some(f) => Value::Float(-f),
+            None => Value::Null,
+        },
+    }
+}
+
+/// Applies `op` to two already-resolved values, coercing through
+/// [`ToF64`] the same way the renderer's `{% widthratio %}` support does.
+/// Two integers stay integral under `+ - * %` (so `{{ total }}` keeps
+/// rendering without a trailing `.0`); `/` always yields a float, and any
+/// other numeric pairing promotes to float. Division/modulo by zero yields `Value::Null` (renders as an
+/// empty string) rather than panicking, matching Django's template
+/// philosophy of suppressing arithmetic errors instead of aborting the
+/// render.
+fn arith(op: ArithOp, left: &Value, right: &Value) -> Value {
+    if let (Value::Integer(a), Value::Integer(b)) = (left, right) {
+        return match op {
+            ArithOp::Add => Value::Integer(a + b),
+            ArithOp::Sub => Value::Integer(a - b),
+            ArithOp::Mul => Value::Integer(a * b),
+            ArithOp::Div => {
+                if *b == 0 {
+                    Value::Null
+                } else {
+                    Value::Float(*a as f64 / *b as f64)
+                }
+            }
+            ArithOp::Mod => {
+                if *b == 0 {
+                    Value::Null
+                } else {
+                    Value::Integer(a.rem_euclid(*b))
+                }
+            }
+        };
+    }
+
+    match (left.to_f64(), right.to_f64()) {
+        (Some(a), Some(b)) => match op {
+            ArithOp::Add => Value::Float(a + b),
+            ArithOp::Sub => Value::Float(a - b),
+            ArithOp::Mul => Value::Float(a * b),
+            ArithOp::Div => {
+                if b == 0.0 {
+                    Value::Null
+                } else {
+                    Value::Float(a / b)
+                }
+            }
+            ArithOp::Mod => {
+                if b == 0.0 {
+                    Value::Null
+                } else {
+                    Value::Float(a % b)
+                }
+            }
+        },
+        _ => Value::Null,
+    }
+}
+
+/// Parse and evaluate a `{% if %}` condition. Falls back to `false` on any
+/// lex/parse error - including trailing tokens the parser didn't consume -
+/// so a malformed condition doesn't abort rendering, matching the previous
+/// evaluator's catch-all for conditions it didn't recognize.
+pub(crate) fn evaluate(condition: &str, context: &Context) -> Result<bool> {
+    let tokens = match tokenize(condition) {
+        Ok(tokens) => tokens,
+        Err(_) => return Ok(false),
+    };
+    let mut parser = Parser { input: condition, tokens, pos: 0 };
+    let expr = match parser.parse_or() {
+        Ok(expr) if parser.pos >= parser.tokens.len() => expr,
+        _ => return Ok(false),
+    };
+    eval(&expr, context)
+}
+
+/// Parse and evaluate a `{{ }}` variable expression that may use the
+/// `+ - * / %` arithmetic subsystem (e.g. `price * qty`), returning its
+/// resolved `Value`. Falls back to a plain `get_value` lookup - covering
+/// the overwhelmingly common case of a bare dotted path/literal - on any
+/// lex/parse error or trailing tokens, so this is a safe drop-in for
+/// anywhere a `{{ }}` expression was previously resolved with `get_value`
+/// alone.
+pub(crate) fn evaluate_value(expr: &str, context: &Context) -> Result<Value> {
+    let tokens = match tokenize(expr) {
+        Ok(tokens) => tokens,
+        Err(_) => return get_value(expr, context),
+    };
+    let mut parser = Parser { input: expr, tokens, pos: 0 };
+    match parser.parse_add_sub() {
+        Ok(value_expr) if parser.pos >= parser.tokens.len() => eval_value(&value_expr, context),
+        _ => get_value(expr, context),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use djust_core::Context;
+
+    fn ctx() -> Context {
+        Context::new()
+    }
+
+    #[test]
+    fn test_operator_inside_string_literal_does_not_misfire() {
+        let mut context = ctx();
+        context.set("name".to_string(), Value::String("a > b".to_string()));
+        assert!(evaluate("name == \"a > b\"", &context).unwrap());
+        assert!(!evaluate("name == \"a < b\"", &context).unwrap());
+    }
+
+    #[test]
+    fn test_and_or_not_precedence() {
+        let mut context = ctx();
+        context.set("a".to_string(), Value::Bool(false));
+        context.set("b".to_string(), Value::Bool(true));
+        context.set("c".to_string(), Value::Bool(false));
+        // not a and b or c == ((not a) and b) or c == (true and true) or false == true
+        assert!(evaluate("not a and b or c", &context).unwrap());
+    }
+
+    #[test]
+    fn test_parenthesized_group_overrides_precedence() {
+        let mut context = ctx();
+        context.set("a".to_string(), Value::Bool(false));
+        context.set("b".to_string(), Value::Bool(false));
+        context.set("c".to_string(), Value::Bool(true));
+        // a and (b or c) == false and (false or true) == false
+        assert!(!evaluate("a and (b or c)", &context).unwrap());
+    }
+
+    #[test]
+    fn test_in_operator_over_list() {
+        let mut context = ctx();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+        );
+        assert!(evaluate("1 in items", &context).unwrap());
+        assert!(!evaluate("3 in items", &context).unwrap());
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let mut context = ctx();
+        context.set("n".to_string(), Value::Integer(5));
+        assert!(evaluate("n >= 5", &context).unwrap());
+        assert!(evaluate("n <= 5", &context).unwrap());
+        assert!(evaluate("n > 4", &context).unwrap());
+        assert!(evaluate("n < 6", &context).unwrap());
+        assert!(evaluate("n != 6", &context).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_condition_is_false_not_an_error() {
+        let context = ctx();
+        assert!(!evaluate("== ==", &context).unwrap());
+    }
+
+    #[test]
+    fn test_arithmetic_precedence_in_condition() {
+        let mut context = ctx();
+        context.set("total".to_string(), Value::Integer(10));
+        context.set("limit".to_string(), Value::Integer(20));
+        // total * 2 > limit == 20 > 20 == false
+        assert!(!evaluate("total * 2 > limit", &context).unwrap());
+        // 2 + total * 2 > limit == 2 + 20 > 20 == true
+        assert!(evaluate("2 + total * 2 > limit", &context).unwrap());
+    }
+
+    #[test]
+    fn test_parenthesized_arithmetic_group_feeds_comparison() {
+        let mut context = ctx();
+        context.set("total".to_string(), Value::Integer(4));
+        // (total + 1) * 2 == 10
+        assert!(evaluate("(total + 1) * 2 == 10", &context).unwrap());
+    }
+
+    #[test]
+    fn test_unary_minus_negates_operand() {
+        let mut context = ctx();
+        context.set("n".to_string(), Value::Integer(5));
+        assert!(evaluate("n + -3 == 2", &context).unwrap());
+        assert!(evaluate("-n == -5", &context).unwrap());
+    }
+
+    #[test]
+    fn test_evaluate_value_computes_arithmetic_expression() {
+        let mut context = ctx();
+        context.set("price".to_string(), Value::Integer(3));
+        context.set("qty".to_string(), Value::Integer(4));
+        assert_eq!(evaluate_value("price * qty", &context).unwrap(), Value::Integer(12));
+    }
+
+    #[test]
+    fn test_evaluate_value_division_promotes_to_float() {
+        let context = ctx();
+        assert_eq!(evaluate_value("7 / 2", &context).unwrap(), Value::Float(3.5));
+    }
+
+    #[test]
+    fn test_evaluate_value_division_by_zero_is_null_not_a_panic() {
+        let context = ctx();
+        assert_eq!(evaluate_value("1 / 0", &context).unwrap(), Value::Null);
+        assert_eq!(evaluate_value("1 % 0", &context).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_evaluate_value_falls_back_to_plain_lookup() {
+        let mut context = ctx();
+        context.set("name".to_string(), Value::String("ada".to_string()));
+        assert_eq!(evaluate_value("name", &context).unwrap(), Value::String("ada".to_string()));
+    }
+}
@@ -0,0 +1,248 @@
+//! Free-text date parsing for the `parsedate` filter (see
+//! `filters::apply_filter`'s `"parsedate"` arm).
+//!
+//! Tokenizes the input into alpha/numeric/separator runs, classifies
+//! numeric tokens by heuristic (a 4-digit number or any value over 31 is a
+//! year, 13-31 is the day, 1-12 is ambiguous and resolved month-first once
+//! every token has been seen, and a number followed by `:` starts a
+//! `HH:MM[:SS]` time), matches alpha tokens against the month-name table
+//! below and `am`/`pm` markers, and recognizes a trailing `+HH:MM`/`-HH:MM`
+//! offset as a timezone. In `fuzzy` mode, alpha tokens that match none of
+//! the above are skipped rather than failing the whole parse, so prose
+//! like "Today is 25 of September of 2003, exactly at 10:49:41 with
+//! timezone -03:00" still parses.
+
+use chrono::{DateTime, FixedOffset, TimeZone};
+
+/// Full month name and its 3-letter abbreviation, 1-indexed by position
+/// (`MONTHS[0]` is January, matched as `"january"`/`"jan"`). Kept as a
+/// plain table (rather than hard-coded into the tokenizer) so locale work
+/// can swap in a non-English table later.
+pub(crate) const MONTHS: [(&str, &str); 12] = [
+    ("january", "jan"),
+    ("february", "feb"),
+    ("march", "mar"),
+    ("april", "apr"),
+    ("may", "may"),
+    ("june", "jun"),
+    ("july", "jul"),
+    ("august", "aug"),
+    ("september", "sep"),
+    ("october", "oct"),
+    ("november", "nov"),
+    ("december", "dec"),
+];
+
+/// Ordinal day suffixes (as in "25th"), tolerated regardless of mode since
+/// they're part of date notation rather than surrounding prose.
+const ORDINAL_SUFFIXES: [&str; 4] = ["st", "nd", "rd", "th"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Alpha(&'a str),
+    Number(i64),
+    Colon,
+    Plus,
+    Minus,
+    Other,
+}
+
+fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_alphabetic() {
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            tokens.push(Token::Alpha(&input[start..i]));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            tokens.push(Token::Number(input[start..i].parse().unwrap_or(0)));
+        } else if c == b':' {
+            tokens.push(Token::Colon);
+            i += 1;
+        } else if c == b'+' {
+            tokens.push(Token::Plus);
+            i += 1;
+        } else if c == b'-' {
+            tokens.push(Token::Minus);
+            i += 1;
+        } else {
+            tokens.push(Token::Other);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+fn month_from_word(word: &str) -> Option<u32> {
+    let lower = word.to_ascii_lowercase();
+    MONTHS
+        .iter()
+        .position(|(full, abbr)| lower == *full || lower == *abbr)
+        .map(|pos| pos as u32 + 1)
+}
+
+#[derive(Default)]
+struct Parsed {
+    year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    hour: Option<u32>,
+    minute: Option<u32>,
+    second: Option<u32>,
+    pm: Option<bool>,
+    tz_offset_minutes: Option<i32>,
+}
+
+/// Parse a free-text date/time string into a `DateTime<FixedOffset>`.
+/// Returns `None` if no year could be determined, if strict (non-`fuzzy`)
+/// mode hits a word it doesn't recognize, or if the resolved fields don't
+/// form a valid calendar date.
+pub(crate) fn parse_natural_date(input: &str, fuzzy: bool) -> Option<DateTime<FixedOffset>> {
+    let tokens = tokenize(input);
+    let mut parsed = Parsed::default();
+    let mut ambiguous_numbers: Vec<u32> = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i] {
+            Token::Alpha(word) => {
+                let lower = word.to_ascii_lowercase();
+                if let Some(month) = month_from_word(&lower) {
+                    parsed.month = Some(month);
+                } else if lower == "am" || lower == "a" {
+                    parsed.pm = Some(false);
+                } else if lower == "pm" || lower == "p" {
+                    parsed.pm = Some(true);
+                } else if ORDINAL_SUFFIXES.contains(&lower.as_str()) {
+                    // Part of a day ordinal like "25th" - not prose.
+                } else if !fuzzy {
+                    return None;
+                }
+                i += 1;
+            }
+            Token::Number(n) => {
+                if tokens.get(i + 1) == Some(&Token::Colon) {
+                    parsed.hour = Some(n as u32);
+                    i += 2;
+                    if let Some(Token::Number(m)) = tokens.get(i) {
+                        parsed.minute = Some(*m as u32);
+                        i += 1;
+                        if tokens.get(i) == Some(&Token::Colon) {
+                            if let Some(Token::Number(s)) = tokens.get(i + 1) {
+                                parsed.second = Some(*s as u32);
+                                i += 2;
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                if n >= 1000 || n > 31 {
+                    parsed.year = Some(n as i32);
+                } else if n > 12 {
+                    parsed.day = Some(n as u32);
+                } else {
+                    ambiguous_numbers.push(n as u32);
+                }
+                i += 1;
+            }
+            Token::Plus | Token::Minus => {
+                let sign = if tokens[i] == Token::Plus { 1 } else { -1 };
+                if let (Some(Token::Number(oh)), Some(Token::Colon), Some(Token::Number(om))) =
+                    (tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3))
+                {
+                    parsed.tz_offset_minutes = Some(sign * (*oh as i32 * 60 + *om as i32));
+                    i += 4;
+                } else {
+                    i += 1;
+                }
+            }
+            Token::Colon | Token::Other => i += 1,
+        }
+    }
+
+    // Resolve leftover 1-12 numbers month-first, then day, then year.
+    for n in ambiguous_numbers {
+        if parsed.month.is_none() {
+            parsed.month = Some(n);
+        } else if parsed.day.is_none() {
+            parsed.day = Some(n);
+        } else if parsed.year.is_none() {
+            parsed.year = Some(n as i32);
+        }
+    }
+
+    let year = parsed.year?;
+    let month = parsed.month.unwrap_or(1);
+    let day = parsed.day.unwrap_or(1);
+
+    let mut hour = parsed.hour.unwrap_or(0);
+    match parsed.pm {
+        Some(true) if hour < 12 => hour += 12,
+        Some(false) if hour == 12 => hour = 0,
+        _ => {}
+    }
+    let minute = parsed.minute.unwrap_or(0);
+    let second = parsed.second.unwrap_or(0);
+    let offset_minutes = parsed.tz_offset_minutes.unwrap_or(0);
+
+    let offset = FixedOffset::east_opt(offset_minutes * 60)?;
+    offset
+        .with_ymd_and_hms(year, month, day, hour, minute, second)
+        .single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_day_month_name_year_time() {
+        let dt = parse_natural_date("10 September 2015 10:20", false).unwrap();
+        assert_eq!(dt.format("%Y-%m-%d %H:%M").to_string(), "2015-09-10 10:20");
+    }
+
+    #[test]
+    fn test_fuzzy_mode_skips_surrounding_prose() {
+        let dt = parse_natural_date(
+            "Today is 25 of September of 2003, exactly at 10:49:41 with timezone -03:00",
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            dt.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+            "2003-09-25 10:49:41 -0300"
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_fails_on_unrecognized_word() {
+        assert!(parse_natural_date("Today is 25 September 2003", false).is_none());
+    }
+
+    #[test]
+    fn test_numeric_date_defaults_month_first() {
+        let dt = parse_natural_date("09/10/2015", false).unwrap();
+        assert_eq!(dt.format("%Y-%m-%d").to_string(), "2015-09-10");
+    }
+
+    #[test]
+    fn test_pm_marker_converts_to_24_hour() {
+        let dt = parse_natural_date("1 January 2020 3:15 pm", false).unwrap();
+        assert_eq!(dt.format("%H:%M").to_string(), "15:15");
+    }
+
+    #[test]
+    fn test_no_year_fails_to_parse() {
+        assert!(parse_natural_date("10 September", false).is_none());
+    }
+}
@@ -0,0 +1,185 @@
+//! Pluggable tag/filter registration, in the spirit of Handlebars'
+//! `registerHelper` - this is what turns the crate from a fixed
+//! Django-subset into an extensible engine.
+//!
+//! Mirrors the process-global `Lazy<RwLock<..>>` shape already used by
+//! [`crate::rust_tags`] (native tag handlers) and [`crate::fallback`]
+//! (the missing-tag/filter hook) rather than threading an explicit
+//! `Environment` object through `parse`/`render_nodes`, since neither of
+//! those takes one today and every other pluggable seam in this crate
+//! works the same way: register before parsing/rendering, consult by name
+//! when the built-in pipeline doesn't recognize it.
+//!
+//! - [`register_filter`] adds a `{{ value|myfilter:arg }}` implementation,
+//!   tried by `filters::apply_filter`'s unknown-filter arm.
+//! - [`register_tag`] adds a `{% mytag ... %}...{% endmytag %}` block tag.
+//!   Unlike a plain [`crate::rust_tags`] handler (always a self-closing,
+//!   bodyless call), this declares the matching end-tag name up front so
+//!   `parser::parse_tag` can capture the inner node list and hand it to
+//!   the closure at render time (see `Node::CustomBlockTag`).
+
+use crate::parser::Node;
+use djust_core::{Context, Result, Value};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A custom `{{ value|myfilter:arg }}` filter. `args` holds the already
+/// resolved filter arguments - empty for a bare `|myfilter`, one element
+/// for `|myfilter:arg`, since this crate's filter syntax only ever takes a
+/// single argument.
+pub trait CustomFilter: Send + Sync {
+    fn call(&self, value: &Value, args: &[Value]) -> Result<Value>;
+}
+
+impl<F> CustomFilter for F
+where
+    F: Fn(&Value, &[Value]) -> Result<Value> + Send + Sync,
+{
+    fn call(&self, value: &Value, args: &[Value]) -> Result<Value> {
+        self(value, args)
+    }
+}
+
+/// A custom block tag's body, e.g. `{% mytag arg %}...{% endmytag %}`.
+/// Receives a mutable copy of the render context (so it can bind
+/// loop-local variables the way `{% for %}`/`{% with %}` do), the already
+/// variable-resolved tag arguments, and the parsed inner node list.
+pub trait CustomTag: Send + Sync {
+    fn call(&self, ctx: &mut Context, args: &[String], body: &[Node]) -> Result<String>;
+}
+
+impl<F> CustomTag for F
+where
+    F: Fn(&mut Context, &[String], &[Node]) -> Result<String> + Send + Sync,
+{
+    fn call(&self, ctx: &mut Context, args: &[String], body: &[Node]) -> Result<String> {
+        self(ctx, args, body)
+    }
+}
+
+struct RegisteredTag {
+    end_name: String,
+    handler: Box<dyn CustomTag>,
+}
+
+static FILTERS: Lazy<RwLock<HashMap<String, Box<dyn CustomFilter>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+static TAGS: Lazy<RwLock<HashMap<String, RegisteredTag>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Registers `handler` as the implementation of the `name` filter,
+/// replacing any previously registered filter under that name.
+pub fn register_filter<F: CustomFilter + 'static>(name: &str, handler: F) {
+    FILTERS.write().unwrap().insert(name.to_string(), Box::new(handler));
+}
+
+/// Removes the filter registered under `name`, if any.
+pub fn unregister_filter(name: &str) {
+    FILTERS.write().unwrap().remove(name);
+}
+
+/// Registers `handler` as the implementation of `{% name ... %}...{%
+/// end_name %}`. Must be called before a template using `name` is parsed,
+/// since `parser::parse_tag` consults [`end_tag_for`] to know where the
+/// block ends.
+pub fn register_tag<H: CustomTag + 'static>(name: &str, end_name: &str, handler: H) {
+    TAGS.write().unwrap().insert(
+        name.to_string(),
+        RegisteredTag {
+            end_name: end_name.to_string(),
+            handler: Box::new(handler),
+        },
+    );
+}
+
+/// Removes the block tag registered under `name`, if any.
+pub fn unregister_tag(name: &str) {
+    TAGS.write().unwrap().remove(name);
+}
+
+/// The end-tag name a registered block tag expects, if any is registered
+/// for `name`.
+pub fn end_tag_for(name: &str) -> Option<String> {
+    TAGS.read().unwrap().get(name).map(|tag| tag.end_name.clone())
+}
+
+/// Runs `name`'s registered filter with `value` and `args`, if one is
+/// registered.
+pub fn call_filter(name: &str, value: &Value, args: &[Value]) -> Option<Result<Value>> {
+    FILTERS.read().unwrap().get(name).map(|f| f.call(value, args))
+}
+
+/// Runs `name`'s registered block tag body, if one is registered.
+pub fn call_tag(name: &str, ctx: &mut Context, args: &[String], body: &[Node]) -> Option<Result<String>> {
+    TAGS.read().unwrap().get(name).map(|tag| tag.handler.call(ctx, args, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use crate::renderer::render_nodes;
+
+    #[test]
+    fn test_register_filter_is_consulted_for_unknown_filter() {
+        register_filter("shout", |value: &Value, _args: &[Value]| {
+            Ok(Value::String(format!("{}!!!", value.to_string().to_uppercase())))
+        });
+
+        let tokens = tokenize("{{ name|shout }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("name".to_string(), Value::String("hi".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "HI!!!");
+
+        unregister_filter("shout");
+    }
+
+    #[test]
+    fn test_register_filter_receives_its_argument() {
+        register_filter("repeat", |value: &Value, args: &[Value]| {
+            let count: usize = args.first().map(|v| v.to_string()).unwrap_or_default().parse().unwrap_or(1);
+            Ok(Value::String(value.to_string().repeat(count)))
+        });
+
+        let tokens = tokenize("{{ name|repeat:3 }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("name".to_string(), Value::String("ab".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "ababab");
+
+        unregister_filter("repeat");
+    }
+
+    #[test]
+    fn test_register_tag_captures_its_body() {
+        register_tag("shout", "endshout", |_ctx: &mut Context, _args: &[String], body: &[Node]| {
+            let context = Context::new();
+            Ok(render_nodes(body, &context)?.to_uppercase())
+        });
+
+        let tokens = tokenize("{% shout %}hello{% endshout %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let context = Context::new();
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "HELLO");
+
+        unregister_tag("shout");
+    }
+
+    #[test]
+    fn test_unregistered_tag_name_falls_back_to_custom_tag() {
+        // Without a registration, `end_tag_for` returns `None`, so the
+        // parser produces a bodyless `Node::CustomTag` as before - proven
+        // here by the fact that "endmystery" is left as its own sibling
+        // tag rather than being consumed as a terminator.
+        assert_eq!(end_tag_for("mystery"), None);
+
+        let nodes = parse(&tokenize("{% mystery %}x{% endmystery %}").unwrap()).unwrap();
+        assert!(matches!(&nodes[0], Node::CustomTag { name, .. } if name == "mystery"));
+        assert!(matches!(nodes.last(), Some(Node::CustomTag { name, .. }) if name == "endmystery"));
+    }
+}
@@ -0,0 +1,100 @@
+//! Configurable fallback hook for unknown tags and filters.
+//!
+//! An unresolved tag used to hit a dead end: `warn_unsupported_tag` plus a
+//! static `<!-- ... -->` comment, with no way for an embedder to plug in
+//! their own behavior. This mirrors Handlebars' `helperMissing` /
+//! `blockHelperMissing`: install a [`MissingHook`] that both
+//! `Node::UnsupportedTag` and `filters::apply_filter`'s unknown-filter arm
+//! consult before giving up. Returning `Ok(Some(output))` supplies the
+//! output directly; `Ok(None)` defers to the default behavior (or, in
+//! strict mode, to an error).
+
+use djust_core::{Context, DjangoRustError, Result};
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// A fallback for an unrecognized tag or filter name.
+pub trait MissingHook: Send + Sync {
+    fn handle(&self, name: &str, args: &[String], ctx: &Context) -> Result<Option<String>>;
+}
+
+impl<F> MissingHook for F
+where
+    F: Fn(&str, &[String], &Context) -> Result<Option<String>> + Send + Sync,
+{
+    fn handle(&self, name: &str, args: &[String], ctx: &Context) -> Result<Option<String>> {
+        self(name, args, ctx)
+    }
+}
+
+static HOOK: Lazy<RwLock<Option<Box<dyn MissingHook>>>> = Lazy::new(|| RwLock::new(None));
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Installs `hook` as the fallback for unknown tags/filters, replacing any
+/// previously installed hook.
+pub fn set_missing_hook<H: MissingHook + 'static>(hook: H) {
+    *HOOK.write().unwrap() = Some(Box::new(hook));
+}
+
+/// Removes the installed fallback hook, if any, restoring the default
+/// warn-and-comment (tags) / error (filters) behavior.
+pub fn clear_missing_hook() {
+    *HOOK.write().unwrap() = None;
+}
+
+/// In strict mode, a tag/filter left unresolved after the hook runs (no
+/// hook installed, or the hook returned `None`) is a hard error
+/// ([`strict_error`]) instead of the default warn-and-comment (tags) /
+/// `Unknown filter` error (filters).
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::SeqCst);
+}
+
+pub fn is_strict() -> bool {
+    STRICT.load(Ordering::SeqCst)
+}
+
+/// Runs the installed hook for `name`, if any is installed.
+pub fn run(name: &str, args: &[String], ctx: &Context) -> Result<Option<String>> {
+    match HOOK.read().unwrap().as_ref() {
+        Some(hook) => hook.handle(name, args, ctx),
+        None => Ok(None),
+    }
+}
+
+/// The error raised in strict mode once the hook has had a chance to
+/// resolve `name` and declined.
+pub fn strict_error(kind: &str, name: &str) -> DjangoRustError {
+    DjangoRustError::TemplateError(format!("Unknown {kind} '{name}' (strict mode)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_returns_none_without_hook() {
+        clear_missing_hook();
+        let ctx = Context::new();
+        assert!(run("mystery", &[], &ctx).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_hook_can_resolve_a_name() {
+        set_missing_hook(|name: &str, _args: &[String], _ctx: &Context| {
+            Ok(Some(format!("handled:{name}")))
+        });
+        let ctx = Context::new();
+        assert_eq!(run("mystery", &[], &ctx).unwrap(), Some("handled:mystery".to_string()));
+        clear_missing_hook();
+    }
+
+    #[test]
+    fn test_strict_mode_flag_round_trips() {
+        assert!(!is_strict());
+        set_strict(true);
+        assert!(is_strict());
+        set_strict(false);
+    }
+}
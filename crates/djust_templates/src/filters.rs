@@ -0,0 +1,999 @@
+//! Template filters: the `|name:arg` pipeline applied to `{{ }}` output.
+
+use djust_core::{Context, DjangoRustError, Result, Value};
+
+/// Where in a URL-bearing attribute's value a `{{ }}` expression landed:
+/// the scheme/host portion must stay structurally intact (a literal `/` or
+/// `:` there is part of the URL, not data to encode), while the query
+/// portion is free-form data that should be fully percent-encoded. The
+/// lexer decides this by scanning the attribute value's literal text for
+/// an unescaped `?` before reaching the `{{ }}` (see `AttrState::InAttrValue`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlPart {
+    /// Before any `?` - the scheme/host/path portion of the URL.
+    BeforeQuery,
+    /// After a `?` - the query string.
+    Query,
+}
+
+/// Where a `{{ }}` expression's output lands in the surrounding markup.
+/// The lexer tags every `Variable`/`InlineIf` node with one of these at
+/// tokenize time (see `lexer::tokenize`'s attribute-value tracking), since
+/// each position needs a different escaping rule to stay safe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeContext {
+    /// Plain HTML text content, e.g. `<p>{{ x }}</p>`.
+    Text,
+    /// Inside a `"..."`-quoted attribute value.
+    DoubleQuotedAttr,
+    /// Inside a `'...'`-quoted attribute value.
+    SingleQuotedAttr,
+    /// Inside an unquoted attribute value, e.g. `class={{ x }}`.
+    UnquotedAttr,
+    /// Inside a URL-bearing attribute (`href`, `src`, `action`, `formaction`).
+    Url(UrlPart),
+    /// Inside an `on*` event-handler attribute (`onclick="..."`), or a
+    /// `<script>` element's body outside any quoted JS string literal.
+    Js,
+    /// Inside a `"..."` string literal within a `<script>` body.
+    JsDqStr,
+    /// Inside a `'...'` string literal within a `<script>` body.
+    JsSqStr,
+    /// Inside a `style="..."` attribute, or a `<style>` element's body
+    /// outside any quoted CSS string literal.
+    Css,
+    /// Inside a `"..."` string literal within a `<style>` body.
+    CssDqStr,
+    /// Inside a `'...'` string literal within a `<style>` body.
+    CssSqStr,
+    /// `{{{ var }}}` (Handlebars-style triple-brace) - always unescaped,
+    /// regardless of where it appears. A terser alternative to `|safe`.
+    Raw,
+}
+
+/// Escape the five HTML-significant characters for safe insertion into text
+/// content. This is Django's default auto-escaping behavior.
+pub fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape for a `"..."`-quoted attribute value: `"` and `&` must not
+/// terminate or corrupt the attribute, and backtick/newline/tab are
+/// included because some browsers treat them specially inside attributes.
+pub fn escape_double_quoted_attr(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            '`' => out.push_str("&#x60;"),
+            '\n' => out.push_str("&#10;"),
+            '\t' => out.push_str("&#9;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape for a `'...'`-quoted attribute value — same rule set as a
+/// double-quoted one, since either quote character must be neutralized.
+pub fn escape_single_quoted_attr(s: &str) -> String {
+    escape_double_quoted_attr(s)
+}
+
+/// Escape for an unquoted attribute value. Any whitespace, `=`, `` ` ``, or
+/// `<`/`>` would terminate the value early or reopen markup, so all of them
+/// are escaped in addition to the double-quoted set.
+pub fn escape_unquoted_attr(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            '`' => out.push_str("&#x60;"),
+            '=' => out.push_str("&#x3D;"),
+            c if c.is_whitespace() => out.push_str("&#32;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Schemes allowed through `url_scheme_is_safe` - everything else absolute
+/// (`javascript:`, `data:`, `vbscript:`, any custom scheme) is rejected.
+const ALLOWED_URL_SCHEMES: [&str; 5] = ["http", "https", "ftp", "mailto", "tel"];
+
+/// Extract the URI scheme `s` would resolve to, ignoring embedded
+/// whitespace/control characters (so `"java\tscript:"` and `"java\0script:"`
+/// are still read as `javascript`) - `None` means `s` has no scheme at all
+/// (a relative, anchor, path, or protocol-relative reference), per RFC 3986
+/// `scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )`.
+fn detect_url_scheme(s: &str) -> Option<String> {
+    let mut scheme = String::new();
+    for c in s.chars() {
+        if c.is_whitespace() || c.is_control() {
+            continue;
+        }
+        if c == ':' {
+            return if scheme.is_empty() { None } else { Some(scheme.to_lowercase()) };
+        }
+        if scheme.is_empty() {
+            if !c.is_ascii_alphabetic() {
+                return None;
+            }
+        } else if !(c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+            return None;
+        }
+        scheme.push(c);
+    }
+    None
+}
+
+/// Is `s` safe to use as the value of a URL-bearing attribute? Relative,
+/// anchor, query-only, and protocol-relative references (no scheme) are
+/// always safe; an absolute reference is safe only if its scheme is on
+/// [`ALLOWED_URL_SCHEMES`].
+pub fn url_scheme_is_safe(s: &str) -> bool {
+    match detect_url_scheme(s) {
+        Some(scheme) => ALLOWED_URL_SCHEMES.contains(&scheme.as_str()),
+        None => true,
+    }
+}
+
+/// Escape for a URL-bearing attribute (`href`, `src`, `action`,
+/// `formaction`): percent-encode unsafe characters and refuse any scheme
+/// not on [`ALLOWED_URL_SCHEMES`] (e.g. `javascript:`, `data:`,
+/// `vbscript:`), replacing the whole value with a harmless `#` so a click
+/// can't execute script.
+pub fn escape_url(s: &str) -> String {
+    if !url_scheme_is_safe(s) {
+        return "#".to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    for b in s.as_bytes() {
+        match *b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~'
+            | b'/'
+            | b':'
+            | b'?'
+            | b'#'
+            | b'&'
+            | b'='
+            | b'%' => out.push(*b as char),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// Escape a `{{ }}` landing in the query portion of a URL-bearing
+/// attribute: unlike the scheme/host portion, this is free-form data
+/// following a literal `?` already in the template text, so it gets the
+/// same full percent-encoding as the `urlencode` filter rather than the
+/// structural allowlist `escape_url` uses for a whole-URL value.
+fn escape_url_query(s: &str) -> String {
+    percent_encode(s)
+}
+
+/// If `s` is (ignoring surrounding whitespace) a single `url(...)` CSS
+/// function call, return its argument with one layer of matching quotes
+/// stripped - e.g. `url("javascript:alert(1)")` -> `javascript:alert(1)`.
+/// `None` means `s` isn't shaped like a bare `url()` call.
+fn css_url_target(s: &str) -> Option<&str> {
+    let trimmed = s.trim();
+    if trimmed.len() < 5 || !trimmed.to_ascii_lowercase().starts_with("url(") {
+        return None;
+    }
+    let inner = trimmed[4..].strip_suffix(')')?.trim();
+    Some(inner.trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Escape for a CSS context (`style="..."` attribute or `<style>` body):
+/// anything outside a conservative safe set becomes a CSS numeric escape
+/// (backslash + hex code point + trailing space), so a value can't close
+/// out a declaration, open a new one, or smuggle `</style>`, `{`/`}`,
+/// `@`/`/*`/`*/` comments, or a new `url(...)` call. As defense in depth,
+/// a value that is itself a bare `url(...)` call also gets its target
+/// scheme-checked the same way [`escape_url`] checks `href`/`src` - an
+/// unsafe scheme (e.g. `url(javascript:alert(1))`) is replaced outright
+/// rather than relying solely on the char-by-char escape to neutralize it.
+pub fn escape_css(s: &str) -> String {
+    if let Some(target) = css_url_target(s) {
+        if !url_scheme_is_safe(target) {
+            return "url(#)".to_string();
+        }
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | ' ' => out.push(c),
+            _ => out.push_str(&format!("\\{:x} ", c as u32)),
+        }
+    }
+    out
+}
+
+/// Route `text` through the escaper matching `ctx`.
+pub fn escape_for_context(text: &str, ctx: EscapeContext) -> String {
+    match ctx {
+        EscapeContext::Text => html_escape(text),
+        EscapeContext::DoubleQuotedAttr => escape_double_quoted_attr(text),
+        EscapeContext::SingleQuotedAttr => escape_single_quoted_attr(text),
+        EscapeContext::UnquotedAttr => escape_unquoted_attr(text),
+        EscapeContext::Url(UrlPart::BeforeQuery) => escape_url(text),
+        EscapeContext::Url(UrlPart::Query) => escape_url_query(text),
+        EscapeContext::Js | EscapeContext::JsDqStr | EscapeContext::JsSqStr => escape_js(text),
+        EscapeContext::Css | EscapeContext::CssDqStr | EscapeContext::CssSqStr => escape_css(text),
+        EscapeContext::Raw => text.to_string(),
+    }
+}
+
+/// Does `value`'s typed safe-content variant (if any) already match `ctx`?
+/// A `SafeHtml` string is only safe to emit verbatim into an HTML text or
+/// attribute position - the same value landing inside a `<script>` still
+/// needs `escape_for_context` run on it, since `SafeHtml` says nothing
+/// about JS-string-literal safety. Plain `Value::String` (and every other
+/// variant) is untyped and always falls through to the normal escaper.
+pub fn value_matches_context(value: &Value, ctx: EscapeContext) -> bool {
+    matches!(
+        (value, ctx),
+        (
+            Value::SafeHtml(_),
+            EscapeContext::Text
+                | EscapeContext::DoubleQuotedAttr
+                | EscapeContext::SingleQuotedAttr
+                | EscapeContext::UnquotedAttr
+        ) | (
+            Value::SafeJs(_),
+            EscapeContext::Js | EscapeContext::JsDqStr | EscapeContext::JsSqStr
+        ) | (Value::SafeUrl(_), EscapeContext::Url(_))
+            | (
+                Value::SafeCss(_),
+                EscapeContext::Css | EscapeContext::CssDqStr | EscapeContext::CssSqStr
+            )
+    )
+}
+
+/// Wrap `text` in the `Value::Safe*` variant matching `ctx`, for `|noescape`
+/// - an explicit per-action opt-out that (unlike the blanket `|safe`) still
+/// lets a later mismatched context re-escape the value, since it only
+/// asserts "trusted for *this* position", not "trusted everywhere". `Raw`
+/// has no escaper to skip in the first place, so it's treated as HTML.
+pub fn wrap_safe_for_context(text: String, ctx: EscapeContext) -> Value {
+    match ctx {
+        EscapeContext::Text
+        | EscapeContext::DoubleQuotedAttr
+        | EscapeContext::SingleQuotedAttr
+        | EscapeContext::UnquotedAttr
+        | EscapeContext::Raw => Value::SafeHtml(text),
+        EscapeContext::Url(_) => Value::SafeUrl(text),
+        EscapeContext::Js | EscapeContext::JsDqStr | EscapeContext::JsSqStr => Value::SafeJs(text),
+        EscapeContext::Css | EscapeContext::CssDqStr | EscapeContext::CssSqStr => {
+            Value::SafeCss(text)
+        }
+    }
+}
+
+/// Apply a single named filter to `value`, returning the transformed value.
+pub fn apply_filter(name: &str, value: &Value, arg: Option<&str>, ctx: &Context) -> Result<Value> {
+    match name {
+        "safe" | "safeseq" | "force_escape" => Ok(value.clone()),
+        "upper" => Ok(Value::String(value.to_string().to_uppercase())),
+        "lower" => Ok(Value::String(value.to_string().to_lowercase())),
+        "length" => Ok(Value::Integer(value_length(value) as i64)),
+        "default" => {
+            if value.is_truthy() {
+                Ok(value.clone())
+            } else {
+                Ok(Value::String(unquote_arg(arg.unwrap_or(""))))
+            }
+        }
+        "trim" => Ok(Value::String(value.to_string().trim().to_string())),
+        "trim_start" => Ok(Value::String(value.to_string().trim_start().to_string())),
+        "trim_end" => Ok(Value::String(value.to_string().trim_end().to_string())),
+        "trim_start_matches" => {
+            let pattern = unquote_arg(arg.unwrap_or(""));
+            Ok(Value::String(trim_start_matches(&value.to_string(), &pattern)))
+        }
+        "trim_end_matches" => {
+            let pattern = unquote_arg(arg.unwrap_or(""));
+            Ok(Value::String(trim_end_matches(&value.to_string(), &pattern)))
+        }
+        "truncatewords" => {
+            let count: usize = arg.and_then(|a| a.trim().parse().ok()).unwrap_or(0);
+            let text = value.to_string();
+            let words: Vec<&str> = text.split_whitespace().collect();
+            if words.len() <= count {
+                Ok(Value::String(text))
+            } else {
+                Ok(Value::String(format!("{} ...", words[..count].join(" "))))
+            }
+        }
+        "striptags" => {
+            let text = value.to_string();
+            let mut out = String::with_capacity(text.len());
+            let mut in_tag = false;
+            for c in text.chars() {
+                match c {
+                    '<' => in_tag = true,
+                    '>' => in_tag = false,
+                    _ if !in_tag => out.push(c),
+                    _ => {}
+                }
+            }
+            Ok(Value::String(out))
+        }
+        "date" => {
+            let django_fmt = arg.map(unquote_arg).unwrap_or_else(|| "N j, Y".to_string());
+            let locale = ctx.locale().and_then(crate::locale::Locale::from_code);
+            // A `Value::DateTime` already carries its real offset - format it
+            // directly rather than round-tripping it through `parse_date_value`,
+            // which would collapse it onto the server's `Local` zone.
+            if let Value::DateTime(dt) = value {
+                return Ok(Value::String(crate::renderer::django_date_format(
+                    dt,
+                    &django_fmt,
+                    locale.as_ref(),
+                )));
+            }
+            match parse_date_value(&value.to_string()) {
+                Some(dt) => Ok(Value::String(crate::renderer::django_date_format(
+                    &dt,
+                    &django_fmt,
+                    locale.as_ref(),
+                ))),
+                None => Ok(value.clone()),
+            }
+        }
+        "parsedate" => {
+            let fuzzy = matches!(arg.map(unquote_arg).as_deref(), Some("fuzzy"));
+            match crate::date_parse::parse_natural_date(&value.to_string(), fuzzy) {
+                Some(dt) => Ok(Value::String(dt.to_rfc3339())),
+                None => Ok(Value::Null),
+            }
+        }
+        // Expands an RFC 5545 RRULE into a `Value::List` of datetimes, e.g.
+        // `{% for occ in start|recurrence:"FREQ=WEEKLY;COUNT=5" %}`. There's
+        // no dedicated `{% recurrence %}` tag - `{% for %}` already resolves
+        // a filtered iterable (see `renderer::resolve_for_iteration`).
+        "recurrence" => {
+            let start = match value {
+                Value::DateTime(dt) => Some(*dt),
+                _ => crate::renderer::parse_iso8601_literal(&value.to_string()),
+            };
+            let rrule = arg.map(unquote_arg).unwrap_or_default();
+            match start {
+                Some(dt) => Ok(Value::List(
+                    crate::recurrence::expand_rrule(dt, &rrule)
+                        .into_iter()
+                        .map(Value::DateTime)
+                        .collect(),
+                )),
+                None => Ok(Value::List(Vec::new())),
+            }
+        }
+        "capfirst" => {
+            let text = value.to_string();
+            let mut chars = text.chars();
+            Ok(Value::String(match chars.next() {
+                Some(first) => format!("{}{}", first.to_uppercase(), chars.as_str()),
+                None => text,
+            }))
+        }
+        "truncatechars" => {
+            let count: usize = arg.and_then(|a| a.trim().parse().ok()).unwrap_or(0);
+            let text = value.to_string();
+            if text.chars().count() <= count {
+                Ok(Value::String(text))
+            } else {
+                let truncated: String = text.chars().take(count).collect();
+                Ok(Value::String(format!("{truncated} ...")))
+            }
+        }
+        "yesno" => {
+            let choices = arg
+                .map(unquote_arg)
+                .unwrap_or_else(|| "yes,no,maybe".to_string());
+            let parts: Vec<&str> = choices.split(',').collect();
+            let yes = parts.first().copied().unwrap_or("");
+            let no = parts.get(1).copied().unwrap_or("");
+            let maybe = parts.get(2).copied().unwrap_or(no);
+            Ok(Value::String(
+                match value {
+                    Value::Null => maybe,
+                    _ if value.is_truthy() => yes,
+                    _ => no,
+                }
+                .to_string(),
+            ))
+        }
+        // `{{ mydict|lookup:keyvar }}` / `{{ mylist|lookup:index }}` — a
+        // dynamic counterpart to dotted-path access, for when the key/index
+        // itself lives in a variable. No separate `{% lookup %}` tag is
+        // needed: the filter already composes with others in any `{{ }}`
+        // expression, e.g. `{{ data|lookup:current.id|default:"—" }}`.
+        "lookup" => {
+            let key = match arg {
+                Some(a) => crate::renderer::get_value(a, ctx).unwrap_or(Value::Null),
+                None => Value::Null,
+            };
+            Ok(match value {
+                // Same string-coercion rule as `x in mydict` (see
+                // `condition::eval`'s `Expr::In` arm): integer keys are
+                // stringified before the lookup.
+                Value::Object(map) => map.get(&key.to_string()).cloned().unwrap_or(Value::Null),
+                Value::List(items) => match key {
+                    Value::Integer(i) if i >= 0 => {
+                        items.get(i as usize).cloned().unwrap_or(Value::Null)
+                    }
+                    _ => Value::Null,
+                },
+                _ => Value::Null,
+            })
+        }
+        "urlencode" => Ok(Value::SafeUrl(percent_encode(&value.to_string()))),
+        // `{{ url|safeurl }}` — standalone counterpart to the automatic
+        // `href`/`src`/... scheme check `escape_url` already runs: lets a
+        // template author run the same allowlist sanitization explicitly
+        // (e.g. on a URL embedded somewhere other than a recognized URL
+        // attribute) and get back a `SafeUrl` the renderer won't re-escape
+        // once it lands in a URL context.
+        "safeurl" => Ok(Value::SafeUrl(escape_url(&value.to_string()))),
+        // `{{ color|escapecss }}` — standalone counterpart to the automatic
+        // `style="..."`/`<style>` escaping, for a CSS value assembled
+        // somewhere other than one of those two recognized positions.
+        "escapecss" => Ok(Value::SafeCss(escape_css(&value.to_string()))),
+        "escapejs" => Ok(Value::SafeJs(escape_js(&value.to_string()))),
+        // `{{ data|json_script:"id" }}` — Django's pattern for handing a
+        // value to client-side JS without a raw `<script>{{ data }}</script>`
+        // injection point: serializes to JSON, escapes the handful of bytes
+        // that could prematurely close a `<script>` tag, and wraps the
+        // result in its own `<script type="application/json">` element so
+        // the template author never writes the tag by hand. The whole
+        // element is literal HTML, so it's `SafeHtml`, not `SafeJs`.
+        "json_script" => {
+            let id = html_escape(&arg.map(unquote_arg).unwrap_or_default());
+            let json = escape_json_script(&djust_core::serialization::to_json(value));
+            Ok(Value::SafeHtml(format!(
+                "<script id=\"{id}\" type=\"application/json\">{json}</script>"
+            )))
+        }
+        "markdown" => Ok(Value::String(crate::markdown::render_markdown(
+            &value.to_string(),
+            arg.map(unquote_arg).as_deref(),
+        ))),
+        _ => {
+            // A closure registered via `environment::register_filter` is
+            // tried before the generic missing-filter hook, since it's an
+            // explicit registration for this exact name rather than a
+            // catch-all.
+            let filter_args: Vec<Value> = match arg {
+                Some(a) => vec![Value::String(unquote_arg(a))],
+                None => Vec::new(),
+            };
+            if let Some(result) = crate::environment::call_filter(name, value, &filter_args) {
+                return result;
+            }
+
+            let hook_args = match arg {
+                Some(a) => vec![a.to_string()],
+                None => Vec::new(),
+            };
+            if let Some(output) = crate::fallback::run(name, &hook_args, ctx)? {
+                return Ok(Value::String(output));
+            }
+            if crate::fallback::is_strict() {
+                return Err(crate::fallback::strict_error("filter", name));
+            }
+            Err(DjangoRustError::TemplateError(format!(
+                "Unknown filter: '{name}'"
+            )))
+        }
+    }
+}
+
+/// Repeatedly strip a leading `pattern` occurrence, mirroring
+/// `str::trim_start_matches` — only the start is touched, so
+/// `trim_end_matches` needs its own symmetric pass rather than sharing this.
+fn trim_start_matches(s: &str, pattern: &str) -> String {
+    if pattern.is_empty() {
+        return s.to_string();
+    }
+    let mut rest = s;
+    while let Some(stripped) = rest.strip_prefix(pattern) {
+        rest = stripped;
+    }
+    rest.to_string()
+}
+
+/// Repeatedly strip a trailing `pattern` occurrence, mirroring
+/// `str::trim_end_matches`.
+fn trim_end_matches(s: &str, pattern: &str) -> String {
+    if pattern.is_empty() {
+        return s.to_string();
+    }
+    let mut rest = s;
+    while let Some(stripped) = rest.strip_suffix(pattern) {
+        rest = stripped;
+    }
+    rest.to_string()
+}
+
+/// Parse a context value's string form as a date/datetime for the `date`
+/// filter, trying RFC3339 (`2024-01-15T10:30:00Z`) then the plain
+/// `YYYY-MM-DD[ HH:MM:SS]` shapes a view is likely to have stringified a
+/// date/datetime into. Returns `None` for anything else, which leaves the
+/// original value untouched rather than erroring on an unparseable date.
+fn parse_date_value(s: &str) -> Option<chrono::DateTime<chrono::Local>> {
+    use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Local));
+    }
+    if let Ok(ndt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Local.from_local_datetime(&ndt).single();
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .single();
+    }
+    None
+}
+
+fn value_length(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.chars().count(),
+        Value::List(items) => items.len(),
+        Value::Object(map) => map.len(),
+        _ => 0,
+    }
+}
+
+fn unquote_arg(arg: &str) -> String {
+    arg.trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.as_bytes() {
+        match *b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(*b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// Render a single character as a `\uXXXX` escape. The one place that
+/// decides the escape's shape (four uppercase hex digits), so `escape_js`
+/// and `escape_json_script` can't drift into emitting different forms for
+/// the same character.
+fn unicode_escape(c: char) -> String {
+    format!("\\u{:04X}", c as u32)
+}
+
+/// Escape the handful of JSON-legal characters that are dangerous sitting
+/// inside a `<script type="application/json">` body: `<`, `>`, and `&` can
+/// prematurely close the tag or open a new one, and U+2028/U+2029 (valid in
+/// JSON strings but not in JS string literals per the ECMAScript grammar)
+/// would break a consumer that `eval`s the content as JS. Used by the
+/// `json_script` filter on top of `to_json`'s own quote/control-char
+/// escaping.
+fn escape_json_script(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => out.push_str(&unicode_escape(c)),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape a raw (not yet JSON-encoded) value for use inside a JS string
+/// literal the template author quotes by hand, e.g.
+/// `var x = "{{ value|escapejs }}";`. Always emits `\uXXXX` - never the
+/// shorthand `\n`/`\"`/`\\` forms - so the output is simultaneously valid
+/// JS and valid JSON, and shares `unicode_escape` with `escape_json_script`
+/// so the two can't disagree on what a given character's escape looks like.
+///
+/// Escapes `<`, `>`, `&`, `'`, `"`, `=`, U+2028, U+2029, and every C0
+/// control character, matching the set a value could use to break out of
+/// an HTML `<script>` body or a surrounding JS/HTML attribute string.
+/// Backslash is escaped too, even though it can't itself break out of
+/// anything: an unescaped trailing `\` would otherwise swallow the quote
+/// the template author wrote to close the string, e.g. `"...\" + x + "..."`
+/// silently extending the literal past where the author intended.
+fn escape_js(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        let needs_escape =
+            matches!(c, '<' | '>' | '&' | '\'' | '"' | '=' | '\\' | '\u{2028}' | '\u{2029}')
+                || (c as u32) < 0x20;
+        if needs_escape {
+            out.push_str(&unicode_escape(c));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_escape_all_chars() {
+        assert_eq!(
+            html_escape("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&#x27;&amp;&#x27;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_apply_filter_upper_lower() {
+        let v = Value::String("Hi".to_string());
+        let ctx = Context::new();
+        assert_eq!(apply_filter("upper", &v, None, &ctx).unwrap().to_string(), "HI");
+        assert_eq!(apply_filter("lower", &v, None, &ctx).unwrap().to_string(), "hi");
+    }
+
+    #[test]
+    fn test_apply_filter_default_on_falsy() {
+        let v = Value::Null;
+        let ctx = Context::new();
+        let result = apply_filter("default", &v, Some("\"fallback\""), &ctx).unwrap();
+        assert_eq!(result.to_string(), "fallback");
+    }
+
+    #[test]
+    fn test_apply_filter_truncatewords() {
+        let v = Value::String("one two three four".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("truncatewords", &v, Some("2"), &ctx).unwrap();
+        assert_eq!(result.to_string(), "one two ...");
+    }
+
+    #[test]
+    fn test_apply_filter_lookup_resolves_dict_key_from_context_variable() {
+        let mut map = std::collections::HashMap::new();
+        map.insert("2".to_string(), Value::String("b".to_string()));
+        let v = Value::Object(map);
+        let mut ctx = Context::new();
+        ctx.set("keyvar".to_string(), Value::Integer(2));
+        let result = apply_filter("lookup", &v, Some("keyvar"), &ctx).unwrap();
+        assert_eq!(result, Value::String("b".to_string()));
+    }
+
+    #[test]
+    fn test_apply_filter_lookup_indexes_list_by_integer() {
+        let v = Value::List(vec![Value::Integer(10), Value::Integer(20)]);
+        let ctx = Context::new();
+        let result = apply_filter("lookup", &v, Some("1"), &ctx).unwrap();
+        assert_eq!(result, Value::Integer(20));
+    }
+
+    #[test]
+    fn test_apply_filter_lookup_returns_null_on_miss() {
+        let v = Value::List(vec![Value::Integer(10)]);
+        let ctx = Context::new();
+        let result = apply_filter("lookup", &v, Some("5"), &ctx).unwrap();
+        assert_eq!(result, Value::Null);
+    }
+
+    #[test]
+    fn test_apply_filter_unknown_errors() {
+        let v = Value::String("x".to_string());
+        let ctx = Context::new();
+        assert!(apply_filter("not_a_filter", &v, None, &ctx).is_err());
+    }
+
+    #[test]
+    fn test_apply_filter_unknown_uses_missing_hook() {
+        let v = Value::String("x".to_string());
+        let ctx = Context::new();
+        crate::fallback::set_missing_hook(|name: &str, _args: &[String], _ctx: &Context| {
+            Ok(Some(format!("handled:{name}")))
+        });
+        let result = apply_filter("mystery", &v, None, &ctx).unwrap();
+        assert_eq!(result.to_string(), "handled:mystery");
+        crate::fallback::clear_missing_hook();
+    }
+
+    #[test]
+    fn test_escape_double_quoted_attr_covers_quote_and_amp() {
+        assert_eq!(escape_double_quoted_attr("a\"b&c"), "a&quot;b&amp;c");
+    }
+
+    #[test]
+    fn test_escape_unquoted_attr_escapes_whitespace_and_equals() {
+        assert_eq!(escape_unquoted_attr("a b=c"), "a&#32;b&#x3D;c");
+    }
+
+    #[test]
+    fn test_escape_url_rejects_javascript_scheme() {
+        assert_eq!(escape_url("javascript:alert(1)"), "#");
+    }
+
+    #[test]
+    fn test_escape_url_allows_plain_path() {
+        assert_eq!(escape_url("/a/b?x=1&y=2"), "/a/b?x=1&y=2");
+    }
+
+    #[test]
+    fn test_escape_url_allows_protocol_relative_and_fragment() {
+        assert_eq!(escape_url("//evil.example/x"), "//evil.example/x");
+        assert_eq!(escape_url("#frag"), "#frag");
+        assert_eq!(escape_url("?q=1"), "?q=1");
+    }
+
+    #[test]
+    fn test_escape_url_allows_allowlisted_schemes() {
+        assert_eq!(
+            escape_url("https://example.com/a"),
+            "https://example.com/a"
+        );
+        assert_eq!(escape_url("mailto:a@b.com"), "mailto:a%40b.com");
+        assert_eq!(escape_url("tel:+15551234"), "tel:%2B15551234");
+    }
+
+    #[test]
+    fn test_escape_url_rejects_scheme_with_embedded_tab() {
+        // A tab tucked inside the scheme word must not let it slip past
+        // as something other than "javascript".
+        assert_eq!(escape_url("java\tscript:alert(1)"), "#");
+    }
+
+    #[test]
+    fn test_escape_url_rejects_scheme_with_embedded_nul() {
+        assert_eq!(escape_url("java\0script:alert(1)"), "#");
+    }
+
+    #[test]
+    fn test_escape_url_rejects_scheme_after_leading_whitespace() {
+        assert_eq!(escape_url("  \n javascript:alert(1)"), "#");
+    }
+
+    #[test]
+    fn test_escape_url_rejects_unknown_absolute_scheme() {
+        assert_eq!(escape_url("custom-scheme:payload"), "#");
+    }
+
+    #[test]
+    fn test_apply_filter_safeurl_returns_typed_safe_url() {
+        let v = Value::String("javascript:alert(1)".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("safeurl", &v, None, &ctx).unwrap();
+        assert!(matches!(result, Value::SafeUrl(_)));
+        assert_eq!(result.to_string(), "#");
+
+        let v = Value::String("/dashboard".to_string());
+        let result = apply_filter("safeurl", &v, None, &ctx).unwrap();
+        assert_eq!(result.to_string(), "/dashboard");
+    }
+
+    #[test]
+    fn test_escape_css_escapes_braces_and_comment_delimiters() {
+        assert_eq!(escape_css("}{"), "\\7d \\7b ");
+        assert_eq!(escape_css("*/"), "\\2a \\2f ");
+        assert_eq!(escape_css("/*"), "\\2f \\2a ");
+        assert_eq!(escape_css("@import"), "\\40 import");
+    }
+
+    #[test]
+    fn test_escape_css_preserves_safe_identifier_chars() {
+        assert_eq!(escape_css("red-orange_1 2"), "red-orange_1 2");
+    }
+
+    #[test]
+    fn test_escape_css_neutralizes_javascript_url() {
+        assert_eq!(escape_css("url(javascript:alert(1))"), "url(#)");
+        assert_eq!(escape_css("url(\"javascript:alert(1)\")"), "url(#)");
+    }
+
+    #[test]
+    fn test_escape_css_allows_safe_url() {
+        // The scheme check passes (no scheme at all - a relative path), so
+        // the value falls through to the normal char-by-char CSS escape,
+        // which still numerically escapes `(`, `/`, `.`, and `)`.
+        assert_eq!(
+            escape_css("url(/images/x.png)"),
+            "url\\28 \\2f images\\2f x\\2e png\\29 "
+        );
+    }
+
+    #[test]
+    fn test_apply_filter_escapecss_returns_typed_safe_css() {
+        let v = Value::String("url(javascript:alert(1))".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("escapecss", &v, None, &ctx).unwrap();
+        assert!(matches!(result, Value::SafeCss(_)));
+        assert_eq!(result.to_string(), "url(#)");
+    }
+
+    #[test]
+    fn test_trim_start_matches_strips_only_leading_occurrences() {
+        let v = Value::String("11foo1".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("trim_start_matches", &v, Some("\"1\""), &ctx).unwrap();
+        assert_eq!(result.to_string(), "foo1");
+    }
+
+    #[test]
+    fn test_trim_end_matches_strips_only_trailing_occurrences() {
+        let v = Value::String("index.html".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("trim_end_matches", &v, Some("\".html\""), &ctx).unwrap();
+        assert_eq!(result.to_string(), "index");
+    }
+
+    #[test]
+    fn test_apply_filter_markdown_renders_heading() {
+        let v = Value::String("# Title".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("markdown", &v, None, &ctx).unwrap();
+        assert_eq!(result.to_string(), "<h1 id=\"title\">Title</h1>\n");
+    }
+
+    #[test]
+    fn test_escape_for_context_dispatches() {
+        assert_eq!(escape_for_context("<x>", EscapeContext::Text), "&lt;x&gt;");
+        assert_eq!(
+            escape_for_context("javascript:x", EscapeContext::Url(UrlPart::BeforeQuery)),
+            "#"
+        );
+    }
+
+    #[test]
+    fn test_escape_for_context_url_query_fully_percent_encodes() {
+        assert_eq!(
+            escape_for_context("a b&c", EscapeContext::Url(UrlPart::Query)),
+            "a%20b%26c"
+        );
+    }
+
+    #[test]
+    fn test_escape_for_context_js_reuses_escapejs_escaping() {
+        assert_eq!(
+            escape_for_context("</script>", EscapeContext::Js),
+            "\\u003C/script\\u003E"
+        );
+    }
+
+    #[test]
+    fn test_escape_for_context_css_escapes_braces_and_parens() {
+        assert_eq!(
+            escape_for_context("}{", EscapeContext::Css),
+            "\\7d \\7b "
+        );
+    }
+
+    #[test]
+    fn test_value_matches_context_checks_both_type_and_destination() {
+        let safe_js = Value::SafeJs("x".to_string());
+        assert!(value_matches_context(&safe_js, EscapeContext::Js));
+        assert!(value_matches_context(&safe_js, EscapeContext::JsDqStr));
+        assert!(!value_matches_context(&safe_js, EscapeContext::Text));
+        assert!(!value_matches_context(
+            &Value::String("x".to_string()),
+            EscapeContext::Text
+        ));
+    }
+
+    #[test]
+    fn test_wrap_safe_for_context_picks_matching_variant() {
+        assert!(matches!(
+            wrap_safe_for_context("x".to_string(), EscapeContext::UnquotedAttr),
+            Value::SafeHtml(_)
+        ));
+        assert!(matches!(
+            wrap_safe_for_context("x".to_string(), EscapeContext::Url(UrlPart::Query)),
+            Value::SafeUrl(_)
+        ));
+    }
+
+    #[test]
+    fn test_apply_filter_escapejs_returns_typed_safe_js() {
+        let v = Value::String("<b>".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("escapejs", &v, None, &ctx).unwrap();
+        assert!(matches!(result, Value::SafeJs(_)));
+        assert_eq!(result.to_string(), "\\u003Cb\\u003E");
+    }
+
+    #[test]
+    fn test_apply_filter_escapejs_escapes_quotes_backslash_and_controls() {
+        let v = Value::String("a'b\"c\\d\ne=f\u{2028}".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("escapejs", &v, None, &ctx).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "a\\u0027b\\u0022c\\u005Cd\\u000Ae\\u003Df\\u2028"
+        );
+    }
+
+    #[test]
+    fn test_escapejs_output_never_contains_a_raw_breakout_char() {
+        let dangerous = "<>&'\"=\\\n\r\t\0\u{2028}\u{2029}";
+        let escaped = escape_js(dangerous);
+        for c in ['<', '>', '&', '\'', '"', '=', '\u{2028}', '\u{2029}'] {
+            assert!(
+                !escaped.contains(c),
+                "escape_js left {c:?} unescaped in {escaped:?}"
+            );
+        }
+        // Every `\uXXXX` escape starts with a backslash, so a raw `\` can't
+        // be checked with `contains`: instead, exactly one backslash per
+        // input character means the input's own `\` was escaped too.
+        assert_eq!(
+            escaped.matches('\\').count(),
+            dangerous.chars().count(),
+            "escape_js left a raw backslash in {escaped:?}"
+        );
+        // Still a well-formed sequence of `\uXXXX` units, so it parses
+        // back out to the exact same characters one escape at a time.
+        assert!(escaped.chars().all(|c| c.is_ascii()));
+    }
+
+    #[test]
+    fn test_escape_js_and_escape_json_script_agree_on_shared_chars() {
+        // Both functions escape `<`/`>`/`&` - they must render the same
+        // four hex digits for those, or `escapejs` and `json_script`
+        // output could disagree about what a byte-identical input means.
+        for c in ['<', '>', '&'] {
+            assert_eq!(
+                escape_js(&c.to_string()),
+                escape_json_script(&c.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_filter_urlencode_returns_typed_safe_url() {
+        let v = Value::String("a b".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("urlencode", &v, None, &ctx).unwrap();
+        assert!(matches!(result, Value::SafeUrl(_)));
+        assert_eq!(result.to_string(), "a%20b");
+    }
+
+    #[test]
+    fn test_apply_filter_json_script_wraps_escaped_json_in_script_tag() {
+        let v = Value::String("</script>".to_string());
+        let ctx = Context::new();
+        let result = apply_filter("json_script", &v, Some("\"my-id\""), &ctx).unwrap();
+        assert!(matches!(result, Value::SafeHtml(_)));
+        assert_eq!(
+            result.to_string(),
+            "<script id=\"my-id\" type=\"application/json\">\"\\u003C/script\\u003E\"</script>"
+        );
+    }
+}
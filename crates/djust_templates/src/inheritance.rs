@@ -11,11 +11,22 @@ use crate::parser::Node;
 use djust_core::{DjangoRustError, Result};
 use std::collections::HashMap;
 
+/// A `{% macro %}` definition, as seen by the string-reconstruction pipeline
+/// in this module (a separate, simpler copy of `renderer::MacroDef` - this
+/// one only ever needs to splice literal argument text into `nodes`, never
+/// evaluate anything against a `Context`).
+#[derive(Debug, Clone)]
+pub struct MacroDefinition {
+    pub params: Vec<(String, Option<String>)>,
+    pub body: Vec<Node>,
+}
+
 /// Represents a template in the inheritance chain
 #[derive(Debug, Clone)]
 pub struct TemplateLayer {
     pub nodes: Vec<Node>,
     pub blocks: HashMap<String, Vec<Node>>,
+    pub macros: HashMap<String, MacroDefinition>,
 }
 
 /// Represents a complete inheritance chain from child to root
@@ -23,22 +34,37 @@ pub struct TemplateLayer {
 pub struct InheritanceChain {
     pub layers: Vec<TemplateLayer>, // Index 0 = child, last = root parent
     pub merged_blocks: HashMap<String, Vec<Node>>,
-    pub parent_blocks: HashMap<String, Vec<Node>>, // Parent block content for {{ block.super }}
+    /// Every body ever declared for a block name, ordered root-most-ancestor
+    /// first to most-derived last. `{{ block.super }}` walks this stack one
+    /// level at a time from wherever rendering currently is, so it resolves
+    /// correctly across more than two inheritance levels (not just the
+    /// immediate parent).
+    pub block_stacks: HashMap<String, Vec<Vec<Node>>>,
+    /// Every macro callable from the child template: its own `{% macro %}`
+    /// defs, every ancestor's (child overrides same-named ancestor macros,
+    /// mirroring `merged_blocks`), and anything pulled in via
+    /// `{% import %}`/`{% from ... import ... %}` - `Import` bindings are
+    /// keyed `alias.name`, `FromImport` ones by their (possibly aliased)
+    /// bare name, matching how `{{ name(args) }}` call sites reference them.
+    pub merged_macros: HashMap<String, MacroDefinition>,
 }
 
 impl InheritanceChain {
-    /// Create a new inheritance chain from parsed nodes
-    pub fn new(nodes: Vec<Node>) -> Self {
+    /// Create a new inheritance chain from parsed nodes. Errors if `nodes`
+    /// declares the same block name more than once (see `extract_blocks`).
+    pub fn new(nodes: Vec<Node>) -> Result<Self> {
         let layer = TemplateLayer {
             nodes: nodes.clone(),
-            blocks: extract_blocks(&nodes),
+            blocks: extract_blocks(&nodes)?,
+            macros: extract_macros(&nodes),
         };
 
-        InheritanceChain {
+        Ok(InheritanceChain {
             layers: vec![layer],
             merged_blocks: HashMap::new(),
-            parent_blocks: HashMap::new(),
-        }
+            block_stacks: HashMap::new(),
+            merged_macros: HashMap::new(),
+        })
     }
 
     /// Check if this template uses extends
@@ -55,35 +81,60 @@ impl InheritanceChain {
         None
     }
 
-    /// Add a parent layer to the chain
-    pub fn add_parent(&mut self, parent_nodes: Vec<Node>) {
+    /// Add a parent layer to the chain. Errors if `parent_nodes` declares
+    /// the same block name more than once (see `extract_blocks`).
+    pub fn add_parent(&mut self, parent_nodes: Vec<Node>) -> Result<()> {
         let parent_layer = TemplateLayer {
             nodes: parent_nodes.clone(),
-            blocks: extract_blocks(&parent_nodes),
+            blocks: extract_blocks(&parent_nodes)?,
+            macros: extract_macros(&parent_nodes),
         };
         self.layers.push(parent_layer);
+        Ok(())
+    }
+
+    /// Merge every layer's own macros (child overrides same-named ancestor),
+    /// then resolve every `{% import %}`/`{% from ... import ... %}` found
+    /// anywhere in the chain through `loader`, so a child can call a macro
+    /// declared in any ancestor template or explicitly imported file.
+    pub fn merge_macros<L: TemplateLoader>(&mut self, loader: &L) -> Result<()> {
+        let mut merged: HashMap<String, MacroDefinition> = HashMap::new();
+
+        // Root to child, same ordering as `merge_blocks`, so a child's own
+        // `{% macro %}` of the same name wins.
+        for layer in self.layers.iter().rev() {
+            for (name, def) in &layer.macros {
+                merged.insert(name.clone(), def.clone());
+            }
+        }
+
+        for layer in self.layers.iter().rev() {
+            for node in &layer.nodes {
+                collect_imported_macros(node, loader, &mut merged)?;
+            }
+        }
+
+        self.merged_macros = merged;
+        Ok(())
     }
 
     /// Merge blocks from all layers (child overrides parent)
     pub fn merge_blocks(&mut self) {
         let mut merged: HashMap<String, Vec<Node>> = HashMap::new();
-        let mut parents: HashMap<String, Vec<Node>> = HashMap::new();
+        let mut stacks: HashMap<String, Vec<Vec<Node>>> = HashMap::new();
 
-        // Start from root (parent) and work toward child (first layer)
-        // Track parent content before it gets overridden by child
+        // Walk root (parent) to child so each name's stack ends up ordered
+        // root-first, most-derived last — `{{ block.super }}` pops from the
+        // end of whatever's left once the most-derived body is rendering.
         for layer in self.layers.iter().rev() {
             for (name, nodes) in &layer.blocks {
-                // If this block already exists, save current content as parent
-                if let Some(existing) = merged.get(name) {
-                    parents.insert(name.clone(), existing.clone());
-                }
-                // Insert new content (child overrides parent)
+                stacks.entry(name.clone()).or_default().push(nodes.clone());
                 merged.insert(name.clone(), nodes.clone());
             }
         }
 
         self.merged_blocks = merged;
-        self.parent_blocks = parents;
+        self.block_stacks = stacks;
     }
 
     /// Get the root template nodes (furthest ancestor)
@@ -101,12 +152,15 @@ impl InheritanceChain {
 
     fn apply_override_to_node(&self, node: &Node) -> Node {
         match node {
-            Node::Block { name, nodes: _ } => {
-                // Replace block with merged content
+            Node::Block { name, nodes: _, scoped } => {
+                // Replace block with merged content. Recurse into the
+                // override body too, so a nested `{% block %}` inside it
+                // still picks up its own override further down the chain.
                 if let Some(merged_nodes) = self.merged_blocks.get(name) {
                     Node::Block {
                         name: name.clone(),
-                        nodes: merged_nodes.clone(),
+                        nodes: self.apply_block_overrides(merged_nodes),
+                        scoped: *scoped,
                     }
                 } else {
                     // Keep original if no override
@@ -127,12 +181,14 @@ impl InheritanceChain {
                 var_names,
                 iterable,
                 reversed,
+                key,
                 nodes,
                 empty_nodes,
             } => Node::For {
                 var_names: var_names.clone(),
                 iterable: iterable.clone(),
                 reversed: *reversed,
+                key: key.clone(),
                 nodes: self.apply_block_overrides(nodes),
                 empty_nodes: self.apply_block_overrides(empty_nodes),
             },
@@ -148,24 +204,100 @@ impl InheritanceChain {
     }
 }
 
-/// Extract all {% block %} tags from nodes and map them by name
-fn extract_blocks(nodes: &[Node]) -> HashMap<String, Vec<Node>> {
+/// Extract all {% block %} tags from nodes and map them by name.
+///
+/// Block names form one flat namespace per template regardless of how
+/// deeply they're nested (exactly like Django: `{% block outer %}{% block
+/// inner %}...{% endblock %}{% endblock %}` lets a child override `inner`
+/// directly, without repeating `outer`), so this also tracks the chain of
+/// enclosing block names while it walks the tree and rejects a second
+/// `{% block %}` with a name already seen in this same template - Django
+/// raises `TemplateSyntaxError: 'block' tag with name '...' appears more
+/// than once` for exactly this, and our flat map would otherwise silently
+/// let the second definition clobber the first.
+pub(crate) fn extract_blocks(nodes: &[Node]) -> Result<HashMap<String, Vec<Node>>> {
     let mut blocks = HashMap::new();
-
+    let mut path = Vec::new();
     for node in nodes {
-        extract_blocks_recursive(node, &mut blocks);
+        extract_blocks_recursive(node, &mut blocks, &mut path)?;
     }
-
-    blocks
+    Ok(blocks)
 }
 
-fn extract_blocks_recursive(node: &Node, blocks: &mut HashMap<String, Vec<Node>>) {
+fn extract_blocks_recursive(
+    node: &Node,
+    blocks: &mut HashMap<String, Vec<Node>>,
+    path: &mut Vec<String>,
+) -> Result<()> {
     match node {
-        Node::Block { name, nodes } => {
+        Node::Block { name, nodes, .. } => {
+            if blocks.contains_key(name) {
+                let location = if path.is_empty() {
+                    "at the top level".to_string()
+                } else {
+                    format!("nested inside {}", path.join(" -> "))
+                };
+                return Err(DjangoRustError::TemplateError(format!(
+                    "'block' tag with name '{name}' appears more than once in the same template ({location})"
+                )));
+            }
             blocks.insert(name.clone(), nodes.clone());
             // Also extract nested blocks
+            path.push(name.clone());
+            for child in nodes {
+                extract_blocks_recursive(child, blocks, path)?;
+            }
+            path.pop();
+        }
+        Node::If {
+            true_nodes,
+            false_nodes,
+            ..
+        } => {
+            for child in true_nodes {
+                extract_blocks_recursive(child, blocks, path)?;
+            }
+            for child in false_nodes {
+                extract_blocks_recursive(child, blocks, path)?;
+            }
+        }
+        Node::For { nodes, .. } | Node::With { nodes, .. } => {
             for child in nodes {
-                extract_blocks_recursive(child, blocks);
+                extract_blocks_recursive(child, blocks, path)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Extract all {% macro %} definitions from nodes, by name.
+pub(crate) fn extract_macros(nodes: &[Node]) -> HashMap<String, MacroDefinition> {
+    let mut macros = HashMap::new();
+    for node in nodes {
+        extract_macros_recursive(node, &mut macros);
+    }
+    macros
+}
+
+fn extract_macros_recursive(node: &Node, macros: &mut HashMap<String, MacroDefinition>) {
+    match node {
+        Node::Macro { name, params, body } => {
+            macros.insert(
+                name.clone(),
+                MacroDefinition {
+                    params: params.clone(),
+                    body: body.clone(),
+                },
+            );
+            // Also extract nested macros
+            for child in body {
+                extract_macros_recursive(child, macros);
+            }
+        }
+        Node::Block { nodes, .. } => {
+            for child in nodes {
+                extract_macros_recursive(child, macros);
             }
         }
         Node::If {
@@ -174,25 +306,102 @@ fn extract_blocks_recursive(node: &Node, blocks: &mut HashMap<String, Vec<Node>>
             ..
         } => {
             for child in true_nodes {
-                extract_blocks_recursive(child, blocks);
+                extract_macros_recursive(child, macros);
             }
             for child in false_nodes {
-                extract_blocks_recursive(child, blocks);
+                extract_macros_recursive(child, macros);
             }
         }
         Node::For { nodes, .. } | Node::With { nodes, .. } => {
             for child in nodes {
-                extract_blocks_recursive(child, blocks);
+                extract_macros_recursive(child, macros);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Walk `node` (recursing into the same container kinds
+/// `extract_macros_recursive` does) looking for `{% import %}` /
+/// `{% from ... import ... %}` directives, resolving each one through
+/// `loader` and folding the imported macros into `merged` - `Import`
+/// bindings land under `alias.name`, `FromImport` ones under their
+/// (possibly aliased) bare name, matching `{{ name(args) }}` call-site
+/// syntax.
+fn collect_imported_macros<L: TemplateLoader>(
+    node: &Node,
+    loader: &L,
+    merged: &mut HashMap<String, MacroDefinition>,
+) -> Result<()> {
+    match node {
+        Node::Import { template, alias } => {
+            let imported = load_macros_transitively(template, loader)?;
+            for (name, def) in imported {
+                merged.insert(format!("{alias}.{name}"), def);
+            }
+        }
+        Node::FromImport { template, names, .. } => {
+            let imported = load_macros_transitively(template, loader)?;
+            for (orig, alias) in names {
+                if let Some(def) = imported.get(orig) {
+                    merged.insert(alias.clone(), def.clone());
+                }
+            }
+        }
+        Node::Block { nodes, .. } | Node::For { nodes, .. } | Node::With { nodes, .. } => {
+            for child in nodes {
+                collect_imported_macros(child, loader, merged)?;
+            }
+        }
+        Node::If {
+            true_nodes,
+            false_nodes,
+            ..
+        } => {
+            for child in true_nodes {
+                collect_imported_macros(child, loader, merged)?;
+            }
+            for child in false_nodes {
+                collect_imported_macros(child, loader, merged)?;
             }
         }
         _ => {}
     }
+    Ok(())
+}
+
+/// Load `template` (an `{% import %}`/`{% from %}` target, still
+/// quote-wrapped as the parser left it) and extract every macro reachable
+/// from it - its own `{% macro %}` defs plus anything *it* imports in turn,
+/// so a chain of imports resolves transitively just like macro lookup does
+/// in the main render pipeline (`renderer::collect_macro_scope`).
+fn load_macros_transitively<L: TemplateLoader>(
+    template: &str,
+    loader: &L,
+) -> Result<HashMap<String, MacroDefinition>> {
+    let name = template.trim_matches(|c| c == '"' || c == '\'');
+    let nodes = loader.load_template(name)?;
+    let mut macros = extract_macros(&nodes);
+    for node in &nodes {
+        collect_imported_macros(node, loader, &mut macros)?;
+    }
+    Ok(macros)
 }
 
 /// Trait for loading parent templates
 /// This will be implemented by the Python integration layer
 pub trait TemplateLoader {
     fn load_template(&self, name: &str) -> Result<Vec<Node>>;
+
+    /// Last-modified time of the source backing `name`, if the loader has
+    /// one. `CachingLoader` uses this to invalidate a cached parse when the
+    /// underlying file changes; loaders with no notion of a modification
+    /// time (in-memory sources, composed loaders) return `None`, which
+    /// `CachingLoader` treats as "never goes stale on its own" - fine for
+    /// production, where `clear()` is the explicit way to drop entries.
+    fn mtime(&self, _name: &str) -> Option<std::time::SystemTime> {
+        None
+    }
 }
 
 /// Build complete inheritance chain by recursively loading parents
@@ -201,15 +410,30 @@ pub fn build_inheritance_chain<L: TemplateLoader>(
     loader: &L,
     max_depth: usize,
 ) -> Result<InheritanceChain> {
-    let mut chain = InheritanceChain::new(nodes);
+    let mut chain = InheritanceChain::new(nodes)?;
     let mut depth = 0;
+    // Every parent name visited on this path so far, in order, so a cycle
+    // (rather than just a deep chain) can be reported with the exact loop
+    // that closes it instead of a vague depth-limit error.
+    let mut visited: Vec<String> = Vec::new();
 
     // Follow extends chain up to max_depth
     while depth < max_depth {
         if let Some(parent_name) = chain.uses_extends() {
             let parent_name = parent_name.to_string(); // Clone to avoid borrow issues
+
+            if visited.contains(&parent_name) {
+                let mut cycle = visited.clone();
+                cycle.push(parent_name);
+                return Err(DjangoRustError::TemplateError(format!(
+                    "Circular template inheritance detected: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+
             let parent_nodes = loader.load_template(&parent_name)?;
-            chain.add_parent(parent_nodes);
+            visited.push(parent_name);
+            chain.add_parent(parent_nodes)?;
             depth += 1;
         } else {
             // No more parents
@@ -225,6 +449,9 @@ pub fn build_inheritance_chain<L: TemplateLoader>(
 
     // Merge all blocks
     chain.merge_blocks();
+    // Merge macros declared anywhere in the chain, plus anything pulled in
+    // via {% import %}/{% from ... import ... %}
+    chain.merge_macros(loader)?;
 
     Ok(chain)
 }
@@ -285,6 +512,11 @@ impl TemplateLoader for FilesystemTemplateLoader {
         let tokens = lexer::tokenize(&source)?;
         parser::parse(&tokens)
     }
+
+    fn mtime(&self, name: &str) -> Option<std::time::SystemTime> {
+        let path = self.find_template(name).ok()?;
+        std::fs::metadata(path).ok()?.modified().ok()
+    }
 }
 
 /// Convert AST nodes back to template string format (preserves Django syntax)
@@ -300,11 +532,16 @@ fn nodes_to_template_string(nodes: &[Node]) -> String {
 fn node_to_template_string(node: &Node) -> String {
     match node {
         Node::Text(text) => text.clone(),
-        Node::Variable(var_name, filters) => {
+        Node::Variable(var_name, filters, _) => {
             let mut result = format!("{{{{ {var_name} ");
             for (filter_name, arg) in filters {
                 if let Some(arg) = arg {
-                    result.push_str(&format!("|{filter_name}:\"{arg}\" "));
+                    // `arg` is stored exactly as the source wrote it - quotes
+                    // included when it was a quoted literal, bare when it
+                    // was a number or variable reference - so it's emitted
+                    // verbatim rather than re-quoted, which would otherwise
+                    // turn `add:tax` into `add:"tax"` and corrupt it.
+                    result.push_str(&format!("|{filter_name}:{arg} "));
                 } else {
                     result.push_str(&format!("|{filter_name} "));
                 }
@@ -312,8 +549,13 @@ fn node_to_template_string(node: &Node) -> String {
             result.push_str("}}");
             result
         }
-        Node::Block { name, nodes } => {
-            let mut result = format!("{{% block {name} %}}");
+        Node::Block { name, nodes, scoped } => {
+            let tag = if *scoped {
+                format!("{{% block {name} scoped %}}")
+            } else {
+                format!("{{% block {name} %}}")
+            };
+            let mut result = tag;
             result.push_str(&nodes_to_template_string(nodes));
             result.push_str("{% endblock %}");
             result
@@ -336,11 +578,15 @@ fn node_to_template_string(node: &Node) -> String {
             var_names,
             iterable,
             reversed,
+            key,
             nodes,
             empty_nodes,
         } => {
             let var_names_str = var_names.join(", ");
             let mut result = format!("{{% for {var_names_str} in {iterable}");
+            if let Some(key_expr) = key {
+                result.push_str(&format!(" key {key_expr}"));
+            }
             if *reversed {
                 result.push_str(" reversed");
             }
@@ -388,15 +634,27 @@ fn node_to_template_string(node: &Node) -> String {
         }
         Node::CsrfToken => "{% csrf_token %}".to_string(),
         Node::Static(path) => format!("{{% static \"{path}\" %}}"),
-        Node::ReactComponent { .. } => {
-            // React components should be preserved as-is if possible
-            // For now, skip them as they're handled separately
-            String::new()
+        Node::ReactComponent {
+            name,
+            props,
+            children,
+        } => {
+            let mut result = format!("<{name}");
+            for (key, value) in props {
+                result.push_str(&format!(" {key}=\"{value}\""));
+            }
+            result.push('>');
+            result.push_str(&nodes_to_template_string(children));
+            result.push_str(&format!("</{name}>"));
+            result
         }
-        Node::RustComponent { .. } => {
-            // Rust components should be preserved as-is if possible
-            // For now, skip them as they're handled separately
-            String::new()
+        Node::RustComponent { name, props } => {
+            let mut result = format!("<{name}");
+            for (key, value) in props {
+                result.push_str(&format!(" {key}=\"{value}\""));
+            }
+            result.push_str(" />");
+            result
         }
         Node::CustomTag { name, args } => {
             // Reconstruct custom tag: {% tagname arg1 arg2 %}
@@ -408,6 +666,26 @@ fn node_to_template_string(node: &Node) -> String {
             result.push_str(" %}");
             result
         }
+        Node::CustomBlockTag { name, args, nodes } => {
+            // Reconstruct: {% tagname arg1 %}...body...{% endtagname %}
+            let mut result = format!("{{% {name}");
+            for arg in args {
+                result.push(' ');
+                result.push_str(arg);
+            }
+            result.push_str(" %}");
+            result.push_str(&nodes_to_template_string(nodes));
+            result.push_str(&format!("{{% end{name} %}}"));
+            result
+        }
+        Node::Autoescape { enabled, nodes } => {
+            let tag = if *enabled { "on" } else { "off" };
+            let mut result = format!("{{% autoescape {tag} %}}");
+            result.push_str(&nodes_to_template_string(nodes));
+            result.push_str("{% endautoescape %}");
+            result
+        }
+        _ => String::new(),
     }
 }
 
@@ -449,13 +727,117 @@ pub fn resolve_template_inheritance(
     let root_nodes = chain.get_root_nodes();
     let final_nodes = chain.apply_block_overrides(root_nodes);
 
+    // Inline every `{{ name(args) }}` macro call site (own macros, ancestor
+    // macros, and anything pulled in via `{% import %}`) before stringifying
+    // - `node_to_template_string` has no way to call a macro itself.
+    let expanded_nodes = expand_macro_calls(&final_nodes, &chain.merged_macros);
+
     // Convert AST back to template string (preserves {{ var }} syntax)
-    Ok(nodes_to_template_string(&final_nodes))
+    Ok(nodes_to_template_string(&expanded_nodes))
+}
+
+/// Inline every `{% macro %}`/`MacroCall` in `nodes`, binding each call's
+/// arguments (positional, then matching kwarg, then the parameter's own
+/// default - same precedence as `renderer::write_node`'s `Node::MacroCall`
+/// arm) by substituting the bound expression text directly into any
+/// `{{ param }}` reference inside the macro body. `{% macro %}` defs and
+/// `{% import %}`/`{% from ... import ... %}` directives themselves produce
+/// no output, same as they don't render anything in the main pipeline.
+fn expand_macro_calls(nodes: &[Node], macros: &HashMap<String, MacroDefinition>) -> Vec<Node> {
+    expand_nodes(nodes, macros, &HashMap::new())
+}
+
+fn expand_nodes(
+    nodes: &[Node],
+    macros: &HashMap<String, MacroDefinition>,
+    bindings: &HashMap<String, String>,
+) -> Vec<Node> {
+    nodes
+        .iter()
+        .flat_map(|node| expand_node(node, macros, bindings))
+        .collect()
+}
+
+fn expand_node(
+    node: &Node,
+    macros: &HashMap<String, MacroDefinition>,
+    bindings: &HashMap<String, String>,
+) -> Vec<Node> {
+    match node {
+        Node::Variable(path, filters, escape_context) => match bindings.get(path) {
+            Some(replacement) => vec![Node::Variable(
+                replacement.clone(),
+                filters.clone(),
+                *escape_context,
+            )],
+            None => vec![node.clone()],
+        },
+        Node::Macro { .. } | Node::Import { .. } | Node::FromImport { .. } => vec![],
+        Node::MacroCall { name, args, kwargs } => {
+            let Some(def) = macros.get(name) else {
+                // Unresolvable call (typo, or an import this loader couldn't
+                // find) - drop it rather than fail the whole resolve, since
+                // this pipeline never reported per-call-site errors before.
+                return vec![];
+            };
+            let mut call_bindings = HashMap::new();
+            for (i, (param_name, default)) in def.params.iter().enumerate() {
+                let value_expr = args
+                    .get(i)
+                    .cloned()
+                    .or_else(|| {
+                        kwargs
+                            .iter()
+                            .find(|(k, _)| k == param_name)
+                            .map(|(_, v)| v.clone())
+                    })
+                    .or_else(|| default.clone())
+                    .unwrap_or_else(|| "\"\"".to_string());
+                call_bindings.insert(param_name.clone(), value_expr);
+            }
+            expand_nodes(&def.body, macros, &call_bindings)
+        }
+        Node::Block { name, nodes, scoped } => vec![Node::Block {
+            name: name.clone(),
+            nodes: expand_nodes(nodes, macros, bindings),
+            scoped: *scoped,
+        }],
+        Node::If {
+            condition,
+            true_nodes,
+            false_nodes,
+        } => vec![Node::If {
+            condition: condition.clone(),
+            true_nodes: expand_nodes(true_nodes, macros, bindings),
+            false_nodes: expand_nodes(false_nodes, macros, bindings),
+        }],
+        Node::For {
+            var_names,
+            iterable,
+            reversed,
+            key,
+            nodes,
+            empty_nodes,
+        } => vec![Node::For {
+            var_names: var_names.clone(),
+            iterable: iterable.clone(),
+            reversed: *reversed,
+            key: key.clone(),
+            nodes: expand_nodes(nodes, macros, bindings),
+            empty_nodes: expand_nodes(empty_nodes, macros, bindings),
+        }],
+        Node::With { assignments, nodes } => vec![Node::With {
+            assignments: assignments.clone(),
+            nodes: expand_nodes(nodes, macros, bindings),
+        }],
+        other => vec![other.clone()],
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::filters::EscapeContext;
 
     #[test]
     fn test_extract_blocks() {
@@ -464,15 +846,62 @@ mod tests {
             Node::Block {
                 name: "content".to_string(),
                 nodes: vec![Node::Text("Hello".to_string())],
+                scoped: false,
             },
             Node::Text("After".to_string()),
         ];
 
-        let blocks = extract_blocks(&nodes);
+        let blocks = extract_blocks(&nodes).unwrap();
         assert_eq!(blocks.len(), 1);
         assert!(blocks.contains_key("content"));
     }
 
+    #[test]
+    fn test_extract_blocks_errors_on_duplicate_top_level_name() {
+        let nodes = vec![
+            Node::Block {
+                name: "content".to_string(),
+                nodes: vec![],
+                scoped: false,
+            },
+            Node::Block {
+                name: "content".to_string(),
+                nodes: vec![],
+                scoped: false,
+            },
+        ];
+
+        let err = extract_blocks(&nodes).unwrap_err();
+        assert!(err.to_string().contains("content"));
+    }
+
+    #[test]
+    fn test_extract_blocks_errors_on_duplicate_name_nested_in_different_blocks() {
+        let nodes = vec![
+            Node::Block {
+                name: "left".to_string(),
+                nodes: vec![Node::Block {
+                    name: "inner".to_string(),
+                    nodes: vec![],
+                    scoped: false,
+                }],
+                scoped: false,
+            },
+            Node::Block {
+                name: "right".to_string(),
+                nodes: vec![Node::Block {
+                    name: "inner".to_string(),
+                    nodes: vec![],
+                    scoped: false,
+                }],
+                scoped: false,
+            },
+        ];
+
+        let err = extract_blocks(&nodes).unwrap_err();
+        assert!(err.to_string().contains("inner"));
+    }
+
     #[test]
     fn test_uses_extends() {
         let nodes = vec![
@@ -480,17 +909,18 @@ mod tests {
             Node::Block {
                 name: "content".to_string(),
                 nodes: vec![],
+                scoped: false,
             },
         ];
 
-        let chain = InheritanceChain::new(nodes);
+        let chain = InheritanceChain::new(nodes).unwrap();
         assert_eq!(chain.uses_extends(), Some("base.html"));
     }
 
     #[test]
     fn test_no_extends() {
         let nodes = vec![Node::Text("Hello".to_string())];
-        let chain = InheritanceChain::new(nodes);
+        let chain = InheritanceChain::new(nodes).unwrap();
         assert_eq!(chain.uses_extends(), None);
     }
 
@@ -498,7 +928,7 @@ mod tests {
     fn test_nodes_to_template_string_preserves_variables() {
         // Test that variables are preserved as {{ var }} not rendered
         let nodes = vec![
-            Node::Variable("name".to_string(), vec![]),
+            Node::Variable("name".to_string(), vec![], EscapeContext::Text),
             Node::Text(" is here".to_string()),
         ];
 
@@ -511,31 +941,71 @@ mod tests {
 
     #[test]
     fn test_nodes_to_template_string_preserves_filters() {
+        // Filter args are stored exactly as the source wrote them - quotes
+        // included when the arg was a quoted literal, bare when it was a
+        // number or a variable reference - so serialization must echo them
+        // back verbatim rather than re-quoting everything (that previously
+        // turned `floatformat:2` into `floatformat:"2"`, corrupting a
+        // numeric argument).
         let nodes = vec![Node::Variable(
             "price".to_string(),
             vec![
                 ("floatformat".to_string(), Some("2".to_string())),
-                ("default".to_string(), Some("0.00".to_string())),
+                ("default".to_string(), Some("\"0.00\"".to_string())),
             ],
+            EscapeContext::Text,
         )];
 
         let result = nodes_to_template_string(&nodes);
 
         assert!(result.contains("{{ price"));
-        assert!(result.contains("|floatformat:\"2\""));
+        assert!(result.contains("|floatformat:2"));
         assert!(result.contains("|default:\"0.00\""));
         assert!(result.contains("}}"));
     }
 
+    /// Parses `source`, serializes the result back to template text, and
+    /// reparses that text, returning both ASTs so callers can assert they
+    /// match - i.e. that `nodes_to_template_string` lost nothing.
+    fn round_trip(source: &str) -> (Vec<Node>, Vec<Node>) {
+        let nodes =
+            crate::parser::parse(&crate::lexer::tokenize(source).unwrap()).unwrap();
+        let serialized = nodes_to_template_string(&nodes);
+        let reparsed =
+            crate::parser::parse(&crate::lexer::tokenize(&serialized).unwrap()).unwrap();
+        (nodes, reparsed)
+    }
+
+    #[test]
+    fn test_round_trip_react_component_preserves_ast() {
+        let (nodes, reparsed) =
+            round_trip("<Greeting name=\"World\">Hello <b>{{ name }}</b></Greeting>");
+        assert_eq!(nodes, reparsed);
+    }
+
+    #[test]
+    fn test_round_trip_self_closing_rust_component_preserves_ast() {
+        let (nodes, reparsed) = round_trip("<RustButton label=\"Click me\" />");
+        assert_eq!(nodes, reparsed);
+    }
+
+    #[test]
+    fn test_round_trip_mixed_quoted_and_unquoted_filter_args_preserves_ast() {
+        let (nodes, reparsed) =
+            round_trip("{{ price|floatformat:2|default:\"N/A\"|add:tax }}");
+        assert_eq!(nodes, reparsed);
+    }
+
     #[test]
     fn test_nodes_to_template_string_block_syntax() {
         let nodes = vec![Node::Block {
             name: "content".to_string(),
             nodes: vec![
                 Node::Text("<p>".to_string()),
-                Node::Variable("message".to_string(), vec![]),
+                Node::Variable("message".to_string(), vec![], EscapeContext::Text),
                 Node::Text("</p>".to_string()),
             ],
+            scoped: false,
         }];
 
         let result = nodes_to_template_string(&nodes);
@@ -568,7 +1038,8 @@ mod tests {
             var_names: vec!["item".to_string()],
             iterable: "items".to_string(),
             reversed: false,
-            nodes: vec![Node::Variable("item.name".to_string(), vec![])],
+            key: None,
+            nodes: vec![Node::Variable("item.name".to_string(), vec![], EscapeContext::Text)],
             empty_nodes: vec![],
         }];
 
@@ -585,6 +1056,7 @@ mod tests {
             var_names: vec!["item".to_string()],
             iterable: "items".to_string(),
             reversed: true,
+            key: None,
             nodes: vec![Node::Text("Item".to_string())],
             empty_nodes: vec![],
         }];
@@ -601,7 +1073,7 @@ mod tests {
                 ("total".to_string(), "price|add:tax".to_string()),
                 ("discount".to_string(), "0.1".to_string()),
             ],
-            nodes: vec![Node::Variable("total".to_string(), vec![])],
+            nodes: vec![Node::Variable("total".to_string(), vec![], EscapeContext::Text)],
         }];
 
         let result = nodes_to_template_string(&nodes);
@@ -647,15 +1119,17 @@ mod tests {
                     var_names: vec!["item".to_string()],
                     iterable: "items".to_string(),
                     reversed: false,
+                    key: None,
                     nodes: vec![
                         Node::Text("<li>".to_string()),
-                        Node::Variable("item.name".to_string(), vec![("upper".to_string(), None)]),
+                        Node::Variable("item.name".to_string(), vec![("upper".to_string(), None)], EscapeContext::Text),
                         Node::Text("</li>".to_string()),
                     ],
                     empty_nodes: vec![],
                 }],
                 false_nodes: vec![Node::Text("<p>No items</p>".to_string())],
             }],
+            scoped: false,
         }];
 
         let result = nodes_to_template_string(&nodes);
@@ -716,4 +1190,258 @@ mod tests {
         // Should have proper formatting with bullet points
         assert!(error_message.contains("  - "));
     }
+
+    /// An in-memory `TemplateLoader` for exercising `{% extends %}`/
+    /// `{% import %}` resolution without touching the filesystem.
+    struct TestLoader {
+        templates: HashMap<String, String>,
+    }
+
+    impl TestLoader {
+        fn new() -> Self {
+            Self {
+                templates: HashMap::new(),
+            }
+        }
+
+        fn add(&mut self, name: &str, source: &str) {
+            self.templates.insert(name.to_string(), source.to_string());
+        }
+    }
+
+    impl TemplateLoader for TestLoader {
+        fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+            let source = self.templates.get(name).ok_or_else(|| {
+                DjangoRustError::TemplateError(format!("Template not found: {name}"))
+            })?;
+            let tokens = crate::lexer::tokenize(source)?;
+            crate::parser::parse(&tokens)
+        }
+    }
+
+    #[test]
+    fn test_extract_macros() {
+        let nodes = vec![
+            Node::Text("Before".to_string()),
+            Node::Macro {
+                name: "greet".to_string(),
+                params: vec![("name".to_string(), None)],
+                body: vec![Node::Text("Hi".to_string())],
+            },
+        ];
+        let macros = extract_macros(&nodes);
+        assert!(macros.contains_key("greet"));
+        assert_eq!(macros["greet"].params, vec![("name".to_string(), None)]);
+    }
+
+    #[test]
+    fn test_merge_macros_child_overrides_ancestor_macro_of_same_name() {
+        let loader = TestLoader::new();
+        let parent_nodes =
+            crate::parser::parse(&crate::lexer::tokenize(
+                "{% macro greet() %}Hello from parent{% endmacro %}",
+            )
+            .unwrap())
+            .unwrap();
+
+        let mut chain = InheritanceChain::new(
+            crate::parser::parse(
+                &crate::lexer::tokenize("{% macro greet() %}Hello from child{% endmacro %}")
+                    .unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        chain.add_parent(parent_nodes).unwrap();
+        chain.merge_macros(&loader).unwrap();
+
+        let def = &chain.merged_macros["greet"];
+        assert_eq!(def.body, vec![Node::Text("Hello from child".to_string())]);
+    }
+
+    #[test]
+    fn test_merge_macros_resolves_namespaced_import() {
+        let mut loader = TestLoader::new();
+        loader.add(
+            "helpers.html",
+            "{% macro card(title) %}<h2>{{ title }}</h2>{% endmacro %}",
+        );
+
+        let child_nodes = crate::parser::parse(
+            &crate::lexer::tokenize("{% import \"helpers.html\" as h %}").unwrap(),
+        )
+        .unwrap();
+        let mut chain = InheritanceChain::new(child_nodes).unwrap();
+        chain.merge_macros(&loader).unwrap();
+
+        assert!(chain.merged_macros.contains_key("h.card"));
+    }
+
+    #[test]
+    fn test_merge_macros_resolves_from_import_with_alias() {
+        let mut loader = TestLoader::new();
+        loader.add(
+            "helpers.html",
+            "{% macro card(title) %}<h2>{{ title }}</h2>{% endmacro %}",
+        );
+
+        let child_nodes = crate::parser::parse(
+            &crate::lexer::tokenize("{% from \"helpers.html\" import card as c %}").unwrap(),
+        )
+        .unwrap();
+        let mut chain = InheritanceChain::new(child_nodes).unwrap();
+        chain.merge_macros(&loader).unwrap();
+
+        assert!(chain.merged_macros.contains_key("c"));
+        assert!(!chain.merged_macros.contains_key("card"));
+    }
+
+    #[test]
+    fn test_expand_macro_calls_inlines_body_with_bound_positional_arg() {
+        let mut macros = HashMap::new();
+        macros.insert(
+            "greet".to_string(),
+            MacroDefinition {
+                params: vec![("name".to_string(), None)],
+                body: vec![
+                    Node::Text("Hi ".to_string()),
+                    Node::Variable("name".to_string(), vec![], EscapeContext::Text),
+                ],
+            },
+        );
+        let nodes = vec![Node::MacroCall {
+            name: "greet".to_string(),
+            args: vec!["\"Ada\"".to_string()],
+            kwargs: vec![],
+        }];
+
+        let expanded = expand_macro_calls(&nodes, &macros);
+
+        assert_eq!(
+            expanded,
+            vec![
+                Node::Text("Hi ".to_string()),
+                Node::Variable("\"Ada\"".to_string(), vec![], EscapeContext::Text),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_macro_calls_falls_back_to_default_then_drops_unknown_call() {
+        let mut macros = HashMap::new();
+        macros.insert(
+            "greet".to_string(),
+            MacroDefinition {
+                params: vec![("name".to_string(), Some("\"World\"".to_string()))],
+                body: vec![Node::Variable("name".to_string(), vec![], EscapeContext::Text)],
+            },
+        );
+
+        // No args at all - falls back to the parameter's own default.
+        let with_default = expand_macro_calls(
+            &[Node::MacroCall {
+                name: "greet".to_string(),
+                args: vec![],
+                kwargs: vec![],
+            }],
+            &macros,
+        );
+        assert_eq!(
+            with_default,
+            vec![Node::Variable(
+                "\"World\"".to_string(),
+                vec![],
+                EscapeContext::Text
+            )]
+        );
+
+        // Unresolvable macro name - dropped rather than panicking.
+        let unknown = expand_macro_calls(
+            &[
+                Node::Text("before".to_string()),
+                Node::MacroCall {
+                    name: "nope".to_string(),
+                    args: vec![],
+                    kwargs: vec![],
+                },
+                Node::Text("after".to_string()),
+            ],
+            &macros,
+        );
+        assert_eq!(
+            unknown,
+            vec![Node::Text("before".to_string()), Node::Text("after".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_build_inheritance_chain_reports_direct_two_template_cycle() {
+        // a.html extends b.html, which extends a.html right back.
+        let mut loader = TestLoader::new();
+        loader.add("a.html", "{% extends \"b.html\" %}{% block x %}A{% endblock %}");
+        loader.add("b.html", "{% extends \"a.html\" %}{% block x %}B{% endblock %}");
+
+        let tokens = crate::lexer::tokenize(
+            "{% extends \"a.html\" %}{% block x %}child{% endblock %}",
+        )
+        .unwrap();
+        let nodes = crate::parser::parse(&tokens).unwrap();
+
+        let err = build_inheritance_chain(nodes, &loader, 10).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("a.html -> b.html -> a.html"),
+            "Error message should contain the full cycle: {message}"
+        );
+    }
+
+    #[test]
+    fn test_build_inheritance_chain_reports_longer_cycle_path() {
+        // a.html -> b.html -> c.html -> b.html (cycle doesn't include a.html).
+        let mut loader = TestLoader::new();
+        loader.add("a.html", "{% extends \"b.html\" %}{% block x %}A{% endblock %}");
+        loader.add("b.html", "{% extends \"c.html\" %}{% block x %}B{% endblock %}");
+        loader.add("c.html", "{% extends \"b.html\" %}{% block x %}C{% endblock %}");
+
+        let tokens = crate::lexer::tokenize(
+            "{% extends \"a.html\" %}{% block x %}child{% endblock %}",
+        )
+        .unwrap();
+        let nodes = crate::parser::parse(&tokens).unwrap();
+
+        let err = build_inheritance_chain(nodes, &loader, 10).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("a.html -> b.html -> c.html -> b.html"),
+            "Error message should contain the full cycle: {message}"
+        );
+    }
+
+    #[test]
+    fn test_build_inheritance_chain_allows_deep_non_cyclic_extends() {
+        // A legitimately deep (but acyclic) chain should resolve fine and
+        // shouldn't trip the cycle check.
+        let mut loader = TestLoader::new();
+        loader.add("base.html", "{% block x %}Base{% endblock %}");
+        for i in 1..=3 {
+            let parent = if i == 1 {
+                "base.html".to_string()
+            } else {
+                format!("layer{}.html", i - 1)
+            };
+            loader.add(
+                &format!("layer{i}.html"),
+                &format!("{{% extends \"{parent}\" %}}{{% block x %}}L{i}{{% endblock %}}"),
+            );
+        }
+
+        let tokens =
+            crate::lexer::tokenize("{% extends \"layer3.html\" %}{% block x %}child{% endblock %}")
+                .unwrap();
+        let nodes = crate::parser::parse(&tokens).unwrap();
+
+        let chain = build_inheritance_chain(nodes, &loader, 10).unwrap();
+        // child + layer1 + layer2 + layer3 + base
+        assert_eq!(chain.layers.len(), 5);
+    }
 }
@@ -0,0 +1,68 @@
+//! Flat instruction representation produced by [`crate::compiler::compile`]
+//! and replayed by [`crate::compiler::render_program`].
+//!
+//! Lowering a template once into a `Vec<Instruction>` means a template
+//! rendered thousands of times with different contexts pays the `Node`
+//! tree-walk cost only once; `render_program` then executes the vector
+//! with an explicit instruction pointer and loop-state stack instead of
+//! recursing back into the tree for every render.
+
+use crate::filters::EscapeContext;
+use crate::parser::Node;
+
+/// One step of a compiled template. Indices inside `JumpIfFalse`, `Goto`,
+/// `ForBegin`, and `ForEnd` are absolute offsets into the owning
+/// `Program.instructions` vector.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// Emit a literal string verbatim.
+    PushLiteral(String),
+    /// Resolve a `{{ var|filter:arg }}` expression and emit its (escaped,
+    /// unless filtered/marked safe) output.
+    PushVar {
+        path: String,
+        filters: Vec<(String, Option<String>)>,
+        escape_context: EscapeContext,
+    },
+    /// Evaluate `condition`; if falsy, jump to `target` instead of falling
+    /// through into the `if` body that follows this instruction.
+    JumpIfFalse { condition: String, target: usize },
+    /// Unconditional jump - used after an `if` body to skip past its
+    /// `else` branch.
+    Goto(usize),
+    /// Begin a `{% for %}` loop. `body_start` is the index of the first
+    /// body instruction (immediately after this one); `empty_start` is the
+    /// `{% empty %}` block's first instruction, if any; `end` is the first
+    /// instruction after the whole construct.
+    ForBegin {
+        var_names: Vec<String>,
+        iterable: String,
+        reversed: bool,
+        /// Mirrors `Node::For`'s `key` - evaluated against each iteration's
+        /// bound variables to emit a `<!--dj-for:KEY-->` anchor before that
+        /// iteration's body.
+        key: Option<String>,
+        body_start: usize,
+        empty_start: Option<usize>,
+        end: usize,
+    },
+    /// Marks the end of a `{% for %}` body. On reaching it, the VM either
+    /// jumps back to `body_start` (more items remain) or falls through to
+    /// whatever follows (loop exhausted).
+    ForEnd { begin: usize },
+    /// A node the flat compiler doesn't lower - inheritance, includes,
+    /// macros, components, and the like - rendered through the ordinary
+    /// recursive tree-walker so behavior is identical to the uncompiled
+    /// path. Flattening only pays off for the hot literal/variable/
+    /// branch/loop instructions above; everything else keeps working
+    /// exactly as it did before compilation existed.
+    RenderNode(Node),
+}
+
+/// A compiled, flattened template, ready to be replayed against any number
+/// of contexts via `compiler::render_program`. Cheap to clone/store so a
+/// server can compile a template once and cache the `Program` per route.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+}
@@ -1,13 +1,137 @@
 //! Template lexer for tokenizing Django template syntax
 
+use crate::filters::{EscapeContext, UrlPart};
 use djust_core::{DjangoRustError, Result};
 
+/// Attribute names whose value is a URL, so `{{ }}` inside them gets
+/// scheme-checking/percent-encoding instead of plain attribute escaping.
+const URL_ATTRS: &[&str] = &["href", "src", "action", "formaction"];
+
+/// Source location of an emitted token, in the spirit of Handlebars'
+/// parallel `TemplateMapping(line, col)` vector: `start`/`end` are 1-based
+/// `(line, col)` pairs the way editors display them, and `byte_offset` is
+/// the 0-based byte offset of `start` for direct slicing of the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub byte_offset: usize,
+}
+
+/// A `Peekable<Chars>` that tracks line/column/byte-offset as it advances,
+/// so `tokenize_with_spans` can stamp each emitted token with a [`Span`].
+#[derive(Clone)]
+struct PosChars<'a> {
+    inner: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    col: usize,
+    byte_offset: usize,
+}
+
+impl<'a> PosChars<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            inner: source.chars().peekable(),
+            line: 1,
+            col: 1,
+            byte_offset: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.inner.peek()
+    }
+
+    /// Current 1-based `(line, col)`, i.e. the position of the next
+    /// character `next()` would return.
+    fn pos(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+}
+
+impl Iterator for PosChars<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.inner.next()?;
+        self.byte_offset += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(ch)
+    }
+}
+
+/// Tag names whose body `tokenize_with_spans` must buffer verbatim rather
+/// than scan for `{%`/`{#`/`<Jsx` (`<script>`/`<style>` bodies still
+/// tokenize `{{ }}`, see `raw_escape_ctx`) — see `RAWTEXT_TAGS`, which
+/// `validate_no_block_tags_in_attrs` uses to track the same thing over a
+/// plain `&[char]` instead of a `PosChars`.
+const TOKENIZE_RAWTEXT_TAGS: &[&str] = &["script", "style", "textarea"];
+
+/// True when `chars` is positioned just past a `<` that opens an HTML
+/// comment (`<!--`), without consuming anything.
+fn is_comment_start(chars: &PosChars) -> bool {
+    let mut lookahead = chars.clone();
+    lookahead.next() == Some('!') && lookahead.next() == Some('-') && lookahead.next() == Some('-')
+}
+
+/// If `chars` is positioned just past a `<` that opens a `<script>`/`<style>`/
+/// `<textarea>` tag (case-insensitive, not followed by more alphabetic
+/// characters), returns that tag name lowercased.
+fn rawtext_tag_name_at(chars: &PosChars) -> Option<&'static str> {
+    let mut lookahead = chars.clone();
+    let mut word = String::new();
+    while let Some(&c) = lookahead.peek() {
+        if c.is_ascii_alphabetic() {
+            word.push(c);
+            lookahead.next();
+        } else {
+            break;
+        }
+    }
+    let lower = word.to_ascii_lowercase();
+    TOKENIZE_RAWTEXT_TAGS.iter().find(|&&t| lower == t).copied()
+}
+
+/// True when `chars` is positioned just past a `<` that opens the matching
+/// case-insensitive `</tag>` close for `tag` (allowing whitespace before `>`).
+fn closes_rawtext(chars: &PosChars, tag: &str) -> bool {
+    let mut lookahead = chars.clone();
+    if lookahead.next() != Some('/') {
+        return false;
+    }
+    let mut name = String::new();
+    while let Some(&c) = lookahead.peek() {
+        if c.is_ascii_alphabetic() {
+            name.push(c);
+            lookahead.next();
+        } else {
+            break;
+        }
+    }
+    if name.to_ascii_lowercase() != tag {
+        return false;
+    }
+    while let Some(&c) = lookahead.peek() {
+        if c.is_whitespace() {
+            lookahead.next();
+        } else {
+            break;
+        }
+    }
+    lookahead.peek() == Some(&'>')
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Text(String),
-    Variable(String),         // {{ var }}
-    Tag(String, Vec<String>), // {% tag args %}
-    Comment,                  // {# comment #}
+    Variable(String, EscapeContext), // {{ var }}
+    Tag(String, Vec<String>),        // {% tag args %}
+    Comment,                         // {# comment #}
     JsxComponent {
         // <Button prop="value">children</Button>
         name: String,
@@ -17,7 +141,117 @@ pub enum Token {
     },
 }
 
-fn parse_jsx_component(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Token> {
+/// Parses a `{{ ... }}`/`{{{ ... }}}` variable body, given that the caller
+/// already consumed the opening `{{`. `default_ctx` is the `EscapeContext`
+/// used when the variable isn't raw (`{{{ }}}`) — callers outside an HTML
+/// attribute value (e.g. JSX children) pass `EscapeContext::Text`.
+///
+/// Returns the right-trim flag alongside the token: `true` when the body
+/// ended in `-}}` (a whitespace-control marker), which the caller strips
+/// from the neighboring `Text` token that follows.
+fn parse_variable(chars: &mut PosChars, default_ctx: EscapeContext) -> (Token, bool) {
+    let raw = chars.peek() == Some(&'{');
+    if raw {
+        chars.next(); // consume third {
+    }
+
+    let mut var_content = String::new();
+
+    while let Some(ch) = chars.next() {
+        if ch == '}' && chars.peek() == Some(&'}') {
+            if raw {
+                // A raw variable needs a third `}` to close; a plain `}}`
+                // here (e.g. inside a JSON literal argument) is just content.
+                let mut lookahead = chars.clone();
+                lookahead.next();
+                if lookahead.peek() != Some(&'}') {
+                    var_content.push(ch);
+                    continue;
+                }
+                chars.next(); // consume second }
+                chars.next(); // consume third }
+            } else {
+                chars.next(); // consume second }
+            }
+            let escape_ctx = if raw { EscapeContext::Raw } else { default_ctx };
+            let right_trim = var_content.ends_with('-');
+            if right_trim {
+                var_content.pop();
+            }
+            return (Token::Variable(var_content.trim().to_string(), escape_ctx), right_trim);
+        } else {
+            var_content.push(ch);
+        }
+    }
+
+    (
+        Token::Variable(
+            var_content.trim().to_string(),
+            if raw { EscapeContext::Raw } else { default_ctx },
+        ),
+        false,
+    )
+}
+
+/// Parses a `{% ... %}` tag body, given that the caller already consumed
+/// the opening `{%`. Returns `None` for an empty tag (`{%  %}`), alongside
+/// the right-trim flag (whether the body ended in `-%}`).
+fn parse_tag(chars: &mut PosChars) -> (Option<Token>, bool) {
+    let mut tag_content = String::new();
+
+    while let Some(ch) = chars.next() {
+        if ch == '%' && chars.peek() == Some(&'}') {
+            chars.next(); // consume }
+            let right_trim = tag_content.ends_with('-');
+            if right_trim {
+                tag_content.pop();
+            }
+            let parts: Vec<String> = tag_content
+                .split_whitespace()
+                .map(|s| s.to_string())
+                .collect();
+            let token = parts
+                .first()
+                .map(|tag_name| Token::Tag(tag_name.clone(), parts[1..].to_vec()));
+            return (token, right_trim);
+        } else {
+            tag_content.push(ch);
+        }
+    }
+
+    (None, false)
+}
+
+/// Parses a `{# ... #}` comment body, given that the caller already
+/// consumed the opening `{#`, alongside the right-trim flag (whether the
+/// body ended in `-#}`).
+fn parse_comment(chars: &mut PosChars) -> (Token, bool) {
+    let mut prev = None;
+    while let Some(ch) = chars.next() {
+        if ch == '#' && chars.peek() == Some(&'}') {
+            chars.next(); // consume }
+            return (Token::Comment, prev == Some('-'));
+        }
+        prev = Some(ch);
+    }
+    (Token::Comment, false)
+}
+
+/// Consumes a leading `-` right after an opening `{{`/`{%`/`{#` delimiter
+/// (the caller has already consumed both opener characters), the
+/// whitespace-control marker that trims trailing whitespace off the `Text`
+/// token immediately before it — e.g. `{%- if x %}`. Returns whether one
+/// was found.
+fn consume_left_trim_marker(chars: &mut PosChars) -> bool {
+    if chars.peek() == Some(&'-') {
+        chars.next();
+        true
+    } else {
+        false
+    }
+}
+
+fn parse_jsx_component(chars: &mut PosChars) -> Result<Token> {
     let mut name = String::new();
     let mut props = Vec::new();
 
@@ -117,9 +351,30 @@ fn parse_jsx_component(chars: &mut std::iter::Peekable<std::str::Chars>) -> Resu
     // Consume >
     chars.next();
 
-    // Parse children (simplified - just text for now)
+    // Parse children by recursively running the same dispatch the top-level
+    // tokenizer uses: `<Upper` recurses into a nested component (whose own
+    // closing tag it consumes itself, which is what makes a `<Button>`
+    // nested inside a `<Button>` pair to the right close), `{{`/`{%`/`{#`
+    // become real Variable/Tag/Comment tokens, and a lowercase `<tag>` is
+    // left as literal markup text — only a closing tag matching `name`
+    // ends this loop.
     let mut children = vec![];
     let mut child_text = String::new();
+    let mut closed = false;
+    // Parallel to `children`: whether the token at that index ended in a
+    // `-}}`/`-%}`/`-#}` whitespace-control marker, so leading whitespace on
+    // the *next* child text run can be trimmed once the loop below is done.
+    let mut child_right_trims: Vec<bool> = vec![];
+
+    macro_rules! flush_child_text {
+        () => {
+            if !child_text.is_empty() {
+                children.push(Token::Text(child_text.clone()));
+                child_right_trims.push(false);
+                child_text.clear();
+            }
+        };
+    }
 
     while let Some(ch) = chars.next() {
         if ch == '<' && chars.peek() == Some(&'/') {
@@ -140,24 +395,68 @@ fn parse_jsx_component(chars: &mut std::iter::Peekable<std::str::Chars>) -> Resu
             if tag_name == name {
                 // This is our closing tag
                 chars.next(); // consume /
-                if !child_text.is_empty() {
-                    children.push(Token::Text(child_text.trim().to_string()));
-                }
+                flush_child_text!();
                 // Skip to >
                 while chars.peek() != Some(&'>') {
                     chars.next();
                 }
                 chars.next(); // consume >
+                closed = true;
                 break;
             } else {
                 // This is a closing tag for nested HTML, add it as-is
                 child_text.push(ch); // add the '<'
             }
+        } else if ch == '<' && matches!(chars.peek(), Some(c) if c.is_uppercase()) {
+            // Nested component.
+            flush_child_text!();
+            children.push(parse_jsx_component(chars)?);
+            child_right_trims.push(false);
+        } else if ch == '{' && chars.peek() == Some(&'{') {
+            chars.next(); // consume second {
+            let left_trim = consume_left_trim_marker(chars);
+            if left_trim {
+                child_text = child_text.trim_end().to_string();
+            }
+            flush_child_text!();
+            let (token, right_trim) = parse_variable(chars, EscapeContext::Text);
+            children.push(token);
+            child_right_trims.push(right_trim);
+        } else if ch == '{' && chars.peek() == Some(&'%') {
+            chars.next(); // consume %
+            let left_trim = consume_left_trim_marker(chars);
+            if left_trim {
+                child_text = child_text.trim_end().to_string();
+            }
+            flush_child_text!();
+            let (tag, right_trim) = parse_tag(chars);
+            if let Some(tag) = tag {
+                children.push(tag);
+                child_right_trims.push(right_trim);
+            }
+        } else if ch == '{' && chars.peek() == Some(&'#') {
+            chars.next(); // consume #
+            let left_trim = consume_left_trim_marker(chars);
+            if left_trim {
+                child_text = child_text.trim_end().to_string();
+            }
+            flush_child_text!();
+            let (token, right_trim) = parse_comment(chars);
+            children.push(token);
+            child_right_trims.push(right_trim);
         } else {
             child_text.push(ch);
         }
     }
 
+    if !closed {
+        return Err(DjangoRustError::TemplateError(format!(
+            "Unterminated JSX component <{name}>: expected a matching </{name}>"
+        )));
+    }
+
+    apply_right_trims(&mut children, &child_right_trims);
+
     Ok(Token::JsxComponent {
         name,
         props,
@@ -166,6 +465,39 @@ fn parse_jsx_component(chars: &mut std::iter::Peekable<std::str::Chars>) -> Resu
     })
 }
 
+/// Strip leading whitespace off each `Token::Text` in `tokens` that
+/// immediately follows a token whose `right_trims` flag is set — the
+/// `-}}`/`-%}`/`-#}` whitespace-control marker's effect on the token stream,
+/// applied once the full pass is done (the left-trim half is applied
+/// in-place to the preceding text buffer as it's collected, since that text
+/// is already known at the time the marker is seen).
+fn apply_right_trims(tokens: &mut [Token], right_trims: &[bool]) {
+    for i in 0..tokens.len() {
+        if right_trims[i] {
+            if let Some(Token::Text(s)) = tokens.get_mut(i + 1) {
+                *s = s.trim_start().to_string();
+            }
+        }
+    }
+}
+
+/// 1-based `(line, col)` of `chars[index]`, computed by scanning from the
+/// start of the source. Only called once per rejected template, so an
+/// O(n) scan here is fine.
+fn line_col_at(chars: &[char], index: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut col = 1;
+    for &ch in &chars[..index.min(chars.len())] {
+        if ch == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (line, col)
+}
+
 /// Validates that `{% if %}`, `{% elif %}`, `{% else %}`, `{% endif %}`,
 /// `{% for %}`, and `{% endfor %}` block tags do not appear inside HTML
 /// attribute values (between quote characters).
@@ -177,12 +509,35 @@ fn parse_jsx_component(chars: &mut std::iter::Peekable<std::str::Chars>) -> Resu
 ///
 /// Use inline conditionals instead:
 ///   `class="{{ 'active' if condition else '' }}"`
+
+/// Tag names whose body html5ever's tokenizer treats as opaque
+/// `ScriptData`/`Rawtext` rather than markup — their content can contain
+/// `<`, `{{`, and `{%` without any of it being template syntax.
+const RAWTEXT_TAGS: &[&str] = &["script", "style", "textarea"];
+
+/// If `chars[start..]` begins with one of [`RAWTEXT_TAGS`] (case-insensitive,
+/// not followed by more alphabetic characters), returns that tag name.
+fn rawtext_tag_at(chars: &[char], start: usize) -> Option<&'static str> {
+    let mut end = start;
+    while end < chars.len() && chars[end].is_ascii_alphabetic() {
+        end += 1;
+    }
+    let word: String = chars[start..end].iter().collect::<String>().to_ascii_lowercase();
+    RAWTEXT_TAGS.iter().find(|&&tag| word == tag).copied()
+}
+
 pub fn validate_no_block_tags_in_attrs(source: &str) -> Result<()> {
-    #[derive(Clone, Copy, PartialEq)]
+    #[derive(Clone, PartialEq)]
     enum State {
         OutsideTag,
         InsideTag,
         InsideAttrValue(char),
+        /// Inside a `<script>`/`<style>`/`<textarea>` element's raw body,
+        /// named after html5ever's `Rawtext`/`ScriptData` tokenizer states.
+        /// Carries the lowercased tag name so we know which `</tag>` ends it.
+        Rawtext(String),
+        /// Inside an HTML comment (`<!-- ... -->`); ends at the matching `-->`.
+        Comment,
     }
 
     let chars: Vec<char> = source.chars().collect();
@@ -193,16 +548,38 @@ pub fn validate_no_block_tags_in_attrs(source: &str) -> Result<()> {
     while i < len {
         let ch = chars[i];
 
-        match state {
+        match state.clone() {
             State::OutsideTag => {
                 if ch == '<' && i + 1 < len {
                     let next = chars[i + 1];
-                    // Only enter tag state for actual element tags (not <! or whitespace)
-                    if next.is_alphabetic() || next == '/' {
+                    if next == '!'
+                        && chars.get(i + 2) == Some(&'-')
+                        && chars.get(i + 3) == Some(&'-')
+                    {
+                        state = State::Comment;
+                    } else if let Some(tag) = rawtext_tag_at(&chars, i + 1) {
+                        state = State::Rawtext(tag.to_string());
+                    } else if next.is_alphabetic() || next == '/' {
+                        // Only enter tag state for actual element tags (not <! or whitespace)
                         state = State::InsideTag;
                     }
                 }
             }
+            State::Rawtext(tag) => {
+                // Look for the matching case-insensitive `</tag>` close.
+                if ch == '<' && chars.get(i + 1) == Some(&'/') {
+                    if let Some(found) = rawtext_tag_at(&chars, i + 2) {
+                        if found == tag {
+                            state = State::OutsideTag;
+                        }
+                    }
+                }
+            }
+            State::Comment => {
+                if ch == '-' && chars.get(i + 1) == Some(&'-') && chars.get(i + 2) == Some(&'>') {
+                    state = State::OutsideTag;
+                }
+            }
             State::InsideTag => {
                 if ch == '>' {
                     state = State::OutsideTag;
@@ -228,14 +605,23 @@ pub fn validate_no_block_tags_in_attrs(source: &str) -> Result<()> {
                     while j + 1 < len && !(chars[j] == '%' && chars[j + 1] == '}') {
                         j += 1;
                     }
-                    let tag_content: String = chars[tag_start..j].iter().collect();
+                    // Strip whitespace-control `-` markers (`{%- if -%}`) so
+                    // they don't get read as part of the tag name below.
+                    let mut tag_content: String = chars[tag_start..j].iter().collect();
+                    if let Some(stripped) = tag_content.strip_prefix('-') {
+                        tag_content = stripped.to_string();
+                    }
+                    if let Some(stripped) = tag_content.strip_suffix('-') {
+                        tag_content = stripped.to_string();
+                    }
                     let tag_name = tag_content.split_whitespace().next().unwrap_or("");
 
                     match tag_name {
                         "if" | "elif" | "else" | "endif" | "for" | "endfor" => {
+                            let (line, col) = line_col_at(&chars, i);
                             return Err(DjangoRustError::TemplateError(format!(
-                                "Template error: '{{% {tag_name} %}}' block tag found inside \
-                                 an HTML attribute value. Block tags cannot be used inside \
+                                "line {line}, col {col}: '{{% {tag_name} %}}' block tag found \
+                                 inside an HTML attribute value. Block tags cannot be used inside \
                                  attribute values because they insert DOM comment anchors that \
                                  browsers discard in attribute context, causing VDOM path index \
                                  mismatches.\n\
@@ -258,98 +644,300 @@ pub fn validate_no_block_tags_in_attrs(source: &str) -> Result<()> {
     Ok(())
 }
 
+/// Tracks whether the cursor is inside a tag's attribute value (and which
+/// attribute), so a `{{ }}` encountered there can be tagged with the right
+/// `EscapeContext`. Mirrors the state `validate_no_block_tags_in_attrs`
+/// tracks for the same reason (issue #388). `InAttrValue`'s `bool` is
+/// whether an unescaped `?` has been seen yet in this value, so a URL
+/// attribute can tell its `EscapeContext::Url` apart into `BeforeQuery`/
+/// `Query` (see `escape_context_for`).
+#[derive(Clone, Copy, PartialEq)]
+enum AttrState {
+    OutsideTag,
+    InsideTag,
+    InAttrValue(Option<char>, bool),
+}
+
+/// Tokenize `source`, discarding the [`Span`] of each token. Prefer
+/// [`tokenize_with_spans`] when the caller can report errors against a
+/// source location (e.g. editor tooling); this wrapper exists so the many
+/// existing call sites that only care about the `Token` stream don't need
+/// to thread spans through.
 pub fn tokenize(source: &str) -> Result<Vec<Token>> {
+    Ok(tokenize_with_spans(source)?
+        .into_iter()
+        .map(|(token, _span)| token)
+        .collect())
+}
+
+/// Tokenize `source`, pairing each emitted [`Token`] with the [`Span`] it
+/// came from. Mirrors the approach Handlebars uses internally: a parallel
+/// mapping kept alongside the parsed elements, rather than a `span` field
+/// threaded through every `Token` variant.
+pub fn tokenize_with_spans(source: &str) -> Result<Vec<(Token, Span)>> {
     let mut tokens = Vec::new();
-    let mut chars = source.chars().peekable();
+    // Parallel to `tokens`: whether that token ended in a `-}}`/`-%}`/`-#}`
+    // whitespace-control marker, resolved into an actual `Text` trim once
+    // the full pass is done (see `apply_right_trims`).
+    let mut right_trims: Vec<bool> = Vec::new();
+    let mut chars = PosChars::new(source);
     let mut current = String::new();
+    let mut current_start: (usize, usize) = (1, 1);
+    let mut current_start_byte: usize = 0;
+
+    let mut attr_state = AttrState::OutsideTag;
+    let mut current_attr_name = String::new();
+    let mut last_attr_name = String::new();
+    let mut prev_char: Option<char> = None;
+
+    // Mirrors html5ever's `Rawtext`/`ScriptData`/`Comment` tokenizer states:
+    // while set, `<Jsx`/`{%`/`{#` below are not interpreted at all, since
+    // `<script>`/`<style>`/`<textarea>` bodies and HTML comments are opaque
+    // to template syntax. `{{ }}` is the one exception for `<script>`/
+    // `<style>` (not `<textarea>`, which stays fully opaque): it's still
+    // tokenized so the contextual escaper can apply JS/CSS escaping to it
+    // (see `raw_escape_ctx`).
+    let mut raw_until: Option<String> = None;
+    let mut raw_str_state = RawStrState::None;
+    let mut in_comment = false;
+
+    loop {
+        let ch_start = chars.pos();
+        let ch_start_byte = chars.byte_offset;
+        let Some(ch) = chars.next() else { break };
+
+        if current.is_empty() {
+            current_start = ch_start;
+            current_start_byte = ch_start_byte;
+        }
+
+        macro_rules! flush_text {
+            () => {
+                if !current.is_empty() {
+                    tokens.push((
+                        Token::Text(current.clone()),
+                        Span {
+                            start: current_start,
+                            end: ch_start,
+                            byte_offset: current_start_byte,
+                        },
+                    ));
+                    right_trims.push(false);
+                    current.clear();
+                }
+            };
+        }
+
+        if in_comment {
+            current.push(ch);
+            if current.ends_with("-->") {
+                in_comment = false;
+                flush_text!();
+            }
+            continue;
+        }
+
+        if let Some(tag) = raw_until.clone() {
+            if ch == '<' && closes_rawtext(&chars, &tag) {
+                flush_text!();
+                raw_until = None;
+                raw_str_state = RawStrState::None;
+                current_start = ch_start;
+                current_start_byte = ch_start_byte;
+            } else if tag == "textarea" {
+                current.push(ch);
+                continue;
+            } else {
+                // <script>/<style>: track whether we're inside a quoted JS/CSS
+                // string literal (a `{{ }}` there still just gets the bare
+                // Js/Css context - the Dq/Sq split is about HTML attribute
+                // quoting, not JS/CSS string quoting) and tokenize `{{ }}`
+                // while leaving `{%`/`{#`/`<` otherwise untouched, same as
+                // before this chunk.
+                let escaped = prev_char == Some('\\');
+                raw_str_state = match raw_str_state {
+                    RawStrState::None if ch == '"' => RawStrState::InDqStr,
+                    RawStrState::None if ch == '\'' => RawStrState::InSqStr,
+                    RawStrState::InDqStr if ch == '"' && !escaped => RawStrState::None,
+                    RawStrState::InSqStr if ch == '\'' && !escaped => RawStrState::None,
+                    other => other,
+                };
+
+                if ch == '{' && chars.peek() == Some(&'{') {
+                    chars.next(); // consume second {
+                    let left_trim = consume_left_trim_marker(&mut chars);
+                    if left_trim {
+                        current = current.trim_end().to_string();
+                    }
+                    flush_text!();
+                    let default_ctx = raw_escape_ctx(&tag, raw_str_state);
+                    let (token, right_trim) = parse_variable(&mut chars, default_ctx);
+                    tokens.push((
+                        token,
+                        Span {
+                            start: ch_start,
+                            end: chars.pos(),
+                            byte_offset: ch_start_byte,
+                        },
+                    ));
+                    right_trims.push(right_trim);
+                } else {
+                    current.push(ch);
+                }
+                prev_char = Some(ch);
+                continue;
+            }
+        }
+
+        // Advance the attribute-tracking state machine on every raw
+        // character outside `{{ }}`/`{% %}`/`{# #}` bodies — those are
+        // consumed whole by `parse_variable`/`parse_tag`/`parse_comment`
+        // below, so quotes or `>` inside them never reach it.
+        match attr_state {
+            AttrState::OutsideTag => {
+                if ch == '<' {
+                    if let Some(&next) = chars.peek() {
+                        if next.is_alphabetic() || next == '/' {
+                            attr_state = AttrState::InsideTag;
+                            current_attr_name.clear();
+                            last_attr_name.clear();
+                        }
+                    }
+                }
+            }
+            AttrState::InsideTag => {
+                if ch == '>' {
+                    attr_state = AttrState::OutsideTag;
+                } else if ch == '=' {
+                    last_attr_name = current_attr_name.trim().to_string();
+                    current_attr_name.clear();
+                    // Unquoted attribute value: `class=foo` / `class={{ x }}`.
+                    if let Some(&next) = chars.peek() {
+                        if next != '"' && next != '\'' && !next.is_whitespace() {
+                            attr_state = AttrState::InAttrValue(None, false);
+                        }
+                    }
+                } else if (ch == '"' || ch == '\'') && prev_char == Some('=') {
+                    attr_state = AttrState::InAttrValue(Some(ch), false);
+                } else if ch.is_whitespace() {
+                    current_attr_name.clear();
+                } else if ch.is_alphanumeric() || ch == '-' || ch == '_' {
+                    current_attr_name.push(ch);
+                }
+            }
+            AttrState::InAttrValue(quote, seen_query) => match quote {
+                Some(q) if ch == q => attr_state = AttrState::InsideTag,
+                None if ch.is_whitespace() || ch == '>' => attr_state = AttrState::InsideTag,
+                _ if ch == '?' && !seen_query => {
+                    attr_state = AttrState::InAttrValue(quote, true);
+                }
+                _ => {}
+            },
+        }
+        prev_char = Some(ch);
 
-    while let Some(ch) = chars.next() {
         if ch == '<' {
+            if is_comment_start(&chars) {
+                chars.next(); // consume !
+                chars.next(); // consume -
+                chars.next(); // consume -
+                flush_text!();
+                in_comment = true;
+                current.push_str("<!--");
+                current_start = ch_start;
+                current_start_byte = ch_start_byte;
+                continue;
+            }
+
             // Check if this is a JSX component (starts with uppercase)
             if let Some(&next_ch) = chars.peek() {
                 if next_ch.is_uppercase() {
                     // JSX component detected
-                    if !current.is_empty() {
-                        tokens.push(Token::Text(current.clone()));
-                        current.clear();
-                    }
-                    match parse_jsx_component(&mut chars) {
-                        Ok(token) => tokens.push(token),
-                        Err(_) => current.push(ch), // Fallback to text if parsing fails
-                    }
+                    flush_text!();
+                    let token = parse_jsx_component(&mut chars)?;
+                    tokens.push((
+                        token,
+                        Span {
+                            start: ch_start,
+                            end: chars.pos(),
+                            byte_offset: ch_start_byte,
+                        },
+                    ));
+                    right_trims.push(false);
                     continue;
                 }
             }
+
+            if let Some(tag) = rawtext_tag_name_at(&chars) {
+                raw_until = Some(tag.to_string());
+            }
+
             current.push(ch);
         } else if ch == '{' {
             if let Some(&next) = chars.peek() {
                 match next {
                     '{' => {
-                        // Variable start {{
+                        // Variable start `{{`, or a raw/unescaped `{{{ ... }}}`
+                        // (Handlebars-style triple-brace, a terser `|safe`).
                         chars.next(); // consume second {
-                        if !current.is_empty() {
-                            tokens.push(Token::Text(current.clone()));
-                            current.clear();
+                        let left_trim = consume_left_trim_marker(&mut chars);
+                        if left_trim {
+                            current = current.trim_end().to_string();
                         }
+                        flush_text!();
 
-                        let mut var_content = String::new();
-                        let _depth = 0;
-
-                        while let Some(ch) = chars.next() {
-                            if ch == '}' && chars.peek() == Some(&'}') {
-                                chars.next(); // consume second }
-                                tokens.push(Token::Variable(var_content.trim().to_string()));
-                                var_content.clear();
-                                break;
-                            } else {
-                                var_content.push(ch);
-                            }
-                        }
+                        let default_ctx = escape_context_for(attr_state, &last_attr_name);
+                        let (token, right_trim) = parse_variable(&mut chars, default_ctx);
+                        tokens.push((
+                            token,
+                            Span {
+                                start: ch_start,
+                                end: chars.pos(),
+                                byte_offset: ch_start_byte,
+                            },
+                        ));
+                        right_trims.push(right_trim);
                     }
                     '%' => {
                         // Tag start {%
                         chars.next(); // consume %
-                        if !current.is_empty() {
-                            tokens.push(Token::Text(current.clone()));
-                            current.clear();
+                        let left_trim = consume_left_trim_marker(&mut chars);
+                        if left_trim {
+                            current = current.trim_end().to_string();
                         }
+                        flush_text!();
 
-                        let mut tag_content = String::new();
-
-                        while let Some(ch) = chars.next() {
-                            if ch == '%' && chars.peek() == Some(&'}') {
-                                chars.next(); // consume }
-                                let parts: Vec<String> = tag_content
-                                    .split_whitespace()
-                                    .map(|s| s.to_string())
-                                    .collect();
-
-                                if let Some(tag_name) = parts.first() {
-                                    tokens.push(Token::Tag(tag_name.clone(), parts[1..].to_vec()));
-                                }
-                                tag_content.clear();
-                                break;
-                            } else {
-                                tag_content.push(ch);
-                            }
+                        let (tag, right_trim) = parse_tag(&mut chars);
+                        if let Some(token) = tag {
+                            tokens.push((
+                                token,
+                                Span {
+                                    start: ch_start,
+                                    end: chars.pos(),
+                                    byte_offset: ch_start_byte,
+                                },
+                            ));
+                            right_trims.push(right_trim);
                         }
                     }
                     '#' => {
                         // Comment start {#
                         chars.next(); // consume #
-                        if !current.is_empty() {
-                            tokens.push(Token::Text(current.clone()));
-                            current.clear();
-                        }
-
-                        // Skip until #}
-                        while let Some(ch) = chars.next() {
-                            if ch == '#' && chars.peek() == Some(&'}') {
-                                chars.next(); // consume }
-                                tokens.push(Token::Comment);
-                                break;
-                            }
+                        let left_trim = consume_left_trim_marker(&mut chars);
+                        if left_trim {
+                            current = current.trim_end().to_string();
                         }
+                        flush_text!();
+                        let (token, right_trim) = parse_comment(&mut chars);
+                        tokens.push((
+                            token,
+                            Span {
+                                start: ch_start,
+                                end: chars.pos(),
+                                byte_offset: ch_start_byte,
+                            },
+                        ));
+                        right_trims.push(right_trim);
                     }
                     _ => {
                         current.push(ch);
@@ -364,12 +952,85 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>> {
     }
 
     if !current.is_empty() {
-        tokens.push(Token::Text(current));
+        tokens.push((
+            Token::Text(current),
+            Span {
+                start: current_start,
+                end: chars.pos(),
+                byte_offset: current_start_byte,
+            },
+        ));
+        right_trims.push(false);
+    }
+
+    for i in 0..tokens.len() {
+        if right_trims[i] {
+            if let Some((Token::Text(s), _)) = tokens.get_mut(i + 1) {
+                *s = s.trim_start().to_string();
+            }
+        }
     }
 
     Ok(tokens)
 }
 
+/// Whether a `{{ }}` found inside a `<script>`/`<style>` body sits within a
+/// quoted JS/CSS string literal, so `raw_escape_ctx` can pick the `*DqStr`/
+/// `*SqStr` sub-context over the bare `Js`/`Css` one.
+#[derive(Clone, Copy, PartialEq)]
+enum RawStrState {
+    None,
+    InDqStr,
+    InSqStr,
+}
+
+/// Decide the `EscapeContext` for a `{{ }}` found inside a `<script>`/
+/// `<style>` element body, given the lowercased tag name and the current
+/// [`RawStrState`].
+fn raw_escape_ctx(tag: &str, str_state: RawStrState) -> EscapeContext {
+    match tag {
+        "script" => match str_state {
+            RawStrState::None => EscapeContext::Js,
+            RawStrState::InDqStr => EscapeContext::JsDqStr,
+            RawStrState::InSqStr => EscapeContext::JsSqStr,
+        },
+        "style" => match str_state {
+            RawStrState::None => EscapeContext::Css,
+            RawStrState::InDqStr => EscapeContext::CssDqStr,
+            RawStrState::InSqStr => EscapeContext::CssSqStr,
+        },
+        _ => EscapeContext::Text,
+    }
+}
+
+/// Decide the `EscapeContext` for a `{{ }}` found while in `state`, given
+/// the name of the attribute it's inside (if any).
+fn escape_context_for(state: AttrState, attr_name: &str) -> EscapeContext {
+    match state {
+        AttrState::InAttrValue(quote, seen_query) => {
+            let name = attr_name.to_lowercase();
+            if URL_ATTRS.contains(&name.as_str()) {
+                EscapeContext::Url(if seen_query {
+                    UrlPart::Query
+                } else {
+                    UrlPart::BeforeQuery
+                })
+            } else if name.starts_with("on") {
+                EscapeContext::Js
+            } else if name == "style" {
+                EscapeContext::Css
+            } else {
+                match quote {
+                    Some('"') => EscapeContext::DoubleQuotedAttr,
+                    Some('\'') => EscapeContext::SingleQuotedAttr,
+                    _ => EscapeContext::UnquotedAttr,
+                }
+            }
+        }
+        AttrState::OutsideTag | AttrState::InsideTag => EscapeContext::Text,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -460,9 +1121,159 @@ mod tests {
             tokens,
             vec![
                 Token::Text("Hello ".to_string()),
-                Token::Variable("name".to_string()),
+                Token::Variable("name".to_string(), EscapeContext::Text),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_triple_brace_is_raw_regardless_of_attr_context() {
+        let tokens = tokenize("Hello {{{ name }}}").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Text("Hello ".to_string()),
+                Token::Variable("name".to_string(), EscapeContext::Raw),
             ]
         );
+
+        let tokens = tokenize(r#"<div class="{{{ cls }}}">"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "cls" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::Raw);
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_double_quoted_attr() {
+        let tokens = tokenize(r#"<div class="{{ cls }}">"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "cls" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::DoubleQuotedAttr);
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_href_is_url_context() {
+        let tokens = tokenize(r#"<a href="{{ target }}">link</a>"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "target" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::Url(UrlPart::BeforeQuery));
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_href_query_is_url_query_context() {
+        let tokens = tokenize(r#"<a href="/search?q={{ term }}">link</a>"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "term" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::Url(UrlPart::Query));
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_onclick_is_js_context() {
+        let tokens = tokenize(r#"<a onclick="go({{ id }})">link</a>"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "id" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::Js);
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_style_attr_is_css_context() {
+        let tokens = tokenize(r#"<div style="color: {{ c }}">x</div>"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "c" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::Css);
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_script_body_is_js_context() {
+        let tokens = tokenize("<script>var x = {{ name }};</script>").unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "name" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token inside <script> must now be tokenized");
+        assert_eq!(var, EscapeContext::Js);
+    }
+
+    #[test]
+    fn test_tokenize_variable_inside_script_js_string_is_js_str_context() {
+        let tokens = tokenize("<script>var x = '{{ name }}';</script>").unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "name" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::JsSqStr);
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_style_body_is_css_context() {
+        let tokens = tokenize("<style>.a { color: {{ c }}; }</style>").unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "c" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::Css);
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_textarea_body_stays_opaque() {
+        // Unlike <script>/<style>, <textarea> stays fully opaque - its
+        // content is user-visible text, not a JS/CSS context to escape for.
+        let tokens = tokenize("<textarea>{{ name }}</textarea>").unwrap();
+        assert!(
+            tokens.iter().all(|t| !matches!(t, Token::Variable(..))),
+            "template syntax inside <textarea> must stay literal text: {tokens:?}"
+        );
+    }
+
+    #[test]
+    fn test_tokenize_variable_in_unquoted_attr() {
+        let tokens = tokenize(r#"<div class={{ cls }}>"#).unwrap();
+        let var = tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Variable(name, ctx) if name == "cls" => Some(*ctx),
+                _ => None,
+            })
+            .expect("variable token");
+        assert_eq!(var, EscapeContext::UnquotedAttr);
     }
 
     #[test]
@@ -509,6 +1320,54 @@ mod tests {
         }
     }
 
+    // ---------------------------------------------------------------------------
+    // tokenize_with_spans tests
+    // ---------------------------------------------------------------------------
+
+    #[test]
+    fn test_span_tracks_line_and_col_across_newlines() {
+        let tokens = tokenize_with_spans("line one\n{{ name }}").unwrap();
+        let (_, span) = tokens
+            .iter()
+            .find(|(t, _)| matches!(t, Token::Variable(n, _) if n == "name"))
+            .expect("variable token");
+
+        assert_eq!(span.start, (2, 1));
+    }
+
+    #[test]
+    fn test_span_byte_offset_matches_source_position() {
+        let src = "Hello {{ name }}";
+        let tokens = tokenize_with_spans(src).unwrap();
+        let (_, span) = tokens
+            .iter()
+            .find(|(t, _)| matches!(t, Token::Variable(n, _) if n == "name"))
+            .expect("variable token");
+
+        assert_eq!(&src[span.byte_offset..span.byte_offset + 2], "{{");
+    }
+
+    #[test]
+    fn test_tokenize_and_tokenize_with_spans_agree_on_tokens() {
+        let src = "Hello {{ name }}{% if x %}yes{% endif %}";
+        let plain = tokenize(src).unwrap();
+        let spanned: Vec<Token> = tokenize_with_spans(src)
+            .unwrap()
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect();
+
+        assert_eq!(plain, spanned);
+    }
+
+    #[test]
+    fn test_block_tag_in_attr_error_reports_line_and_col() {
+        let src = "<div>\n  <a class=\"{% if x %}active{% endif %}\">link</a>\n</div>";
+        let result = validate_no_block_tags_in_attrs(src);
+        let msg = result.unwrap_err().to_string();
+        assert!(msg.starts_with("line 2, col"), "unexpected message: {msg}");
+    }
+
     #[test]
     fn test_tokenize_jsx_with_children() {
         let tokens = tokenize("<Button>Click me</Button>").unwrap();
@@ -527,4 +1386,267 @@ mod tests {
             panic!("Expected JsxComponent token");
         }
     }
+
+    // ---------------------------------------------------------------------------
+    // RAWTEXT/comment handling tests
+    // ---------------------------------------------------------------------------
+
+    #[test]
+    fn test_script_body_with_angle_bracket_is_not_jsx() {
+        let tokens = tokenize("<script>if (a < B) { go(); }</script>").unwrap();
+        assert!(
+            tokens
+                .iter()
+                .all(|t| !matches!(t, Token::JsxComponent { .. })),
+            "script body must not be parsed as JSX: {tokens:?}"
+        );
+    }
+
+    #[test]
+    fn test_script_body_double_brace_is_tokenized_as_js_variable() {
+        // As of chunk13-1, `{{ }}` inside <script> is tokenized (with a
+        // Js escape context) rather than kept opaque, so the contextual
+        // escaper can apply JS-unicode-escaping to real template variables
+        // used there. A non-variable-shaped `{{ ... }}` (like this one,
+        // which happens to look like a JS object literal) still becomes a
+        // `Variable` node - same as any other template typo elsewhere.
+        let tokens = tokenize("<script>var x = {{ not: 'a var' }};</script>").unwrap();
+        assert!(
+            tokens
+                .iter()
+                .any(|t| matches!(t, Token::Variable(n, EscapeContext::Js) if n == "not: 'a var'")),
+            "template syntax inside <script> must now be tokenized: {tokens:?}"
+        );
+    }
+
+    #[test]
+    fn test_style_body_is_buffered_verbatim() {
+        let tokens = tokenize("<style>.a { color: red; }</style>").unwrap();
+        assert!(tokens
+            .iter()
+            .any(|t| matches!(t, Token::Text(s) if s.contains(".a { color: red; }"))));
+    }
+
+    #[test]
+    fn test_script_tokenize_matches_spanned_tokenize() {
+        let src = "<script>if (a < B) {{ x }}</script>{{ after }}";
+        let plain = tokenize(src).unwrap();
+        let spanned: Vec<Token> = tokenize_with_spans(src)
+            .unwrap()
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect();
+        assert_eq!(plain, spanned);
+        // The real variable after </script> must still be tokenized.
+        assert!(plain
+            .iter()
+            .any(|t| matches!(t, Token::Variable(n, _) if n == "after")));
+    }
+
+    #[test]
+    fn test_html_comment_is_not_tokenized_as_tag() {
+        let tokens = tokenize("<!-- {% if x %}ignored{% endif %} -->Real").unwrap();
+        assert!(tokens.iter().all(|t| !matches!(t, Token::Tag(..))));
+        assert!(tokens
+            .iter()
+            .any(|t| matches!(t, Token::Text(s) if s.contains("Real"))));
+    }
+
+    #[test]
+    fn test_script_in_attr_validator_does_not_reject_block_tag_look_alikes() {
+        // `validate_no_block_tags_in_attrs` must not be fooled by block-tag
+        // syntax appearing inside <script>/<style>/comments.
+        let src = r#"<script>var s = "{% if %}";</script><div class="ok">x</div>"#;
+        assert!(validate_no_block_tags_in_attrs(src).is_ok());
+    }
+
+    #[test]
+    fn test_comment_in_validator_does_not_reject_block_tag_look_alikes() {
+        let src = r#"<!-- <div class="{% if x %}bad{% endif %}"> --><div class="ok">x</div>"#;
+        assert!(validate_no_block_tags_in_attrs(src).is_ok());
+    }
+
+    // ---------------------------------------------------------------------------
+    // Recursive JSX children tests
+    // ---------------------------------------------------------------------------
+
+    #[test]
+    fn test_jsx_children_include_variable_token() {
+        let tokens = tokenize("<Greeting>Hello {{ name }}!</Greeting>").unwrap();
+        assert_eq!(tokens.len(), 1);
+        if let Token::JsxComponent { children, .. } = &tokens[0] {
+            assert_eq!(
+                children,
+                &vec![
+                    Token::Text("Hello ".to_string()),
+                    Token::Variable("name".to_string(), EscapeContext::Text),
+                    Token::Text("!".to_string()),
+                ]
+            );
+        } else {
+            panic!("Expected JsxComponent token");
+        }
+    }
+
+    #[test]
+    fn test_jsx_children_include_tag_and_comment_tokens() {
+        let tokens =
+            tokenize("<List>{% for x in items %}{# item #}{{ x }}{% endfor %}</List>").unwrap();
+        if let Token::JsxComponent { children, .. } = &tokens[0] {
+            assert_eq!(
+                children[0],
+                Token::Tag("for".to_string(), vec!["x".into(), "in".into(), "items".into()])
+            );
+            assert_eq!(children[1], Token::Comment);
+            assert_eq!(
+                children[2],
+                Token::Variable("x".to_string(), EscapeContext::Text)
+            );
+            assert_eq!(children[3], Token::Tag("endfor".to_string(), vec![]));
+        } else {
+            panic!("Expected JsxComponent token");
+        }
+    }
+
+    #[test]
+    fn test_jsx_children_nested_component_is_recursively_tokenized() {
+        let tokens = tokenize("<Outer><Inner>{{ x }}</Inner></Outer>").unwrap();
+        if let Token::JsxComponent {
+            name, children, ..
+        } = &tokens[0]
+        {
+            assert_eq!(name, "Outer");
+            assert_eq!(children.len(), 1);
+            if let Token::JsxComponent {
+                name: inner_name,
+                children: inner_children,
+                ..
+            } = &children[0]
+            {
+                assert_eq!(inner_name, "Inner");
+                assert_eq!(
+                    inner_children,
+                    &vec![Token::Variable("x".to_string(), EscapeContext::Text)]
+                );
+            } else {
+                panic!("Expected nested JsxComponent token");
+            }
+        } else {
+            panic!("Expected JsxComponent token");
+        }
+    }
+
+    #[test]
+    fn test_jsx_children_same_named_nested_component_pairs_with_own_close() {
+        let tokens = tokenize("<Box><Box>inner</Box>outer-tail</Box>").unwrap();
+        if let Token::JsxComponent { children, .. } = &tokens[0] {
+            assert_eq!(children.len(), 2);
+            if let Token::JsxComponent {
+                name: inner_name,
+                children: inner_children,
+                ..
+            } = &children[0]
+            {
+                assert_eq!(inner_name, "Box");
+                assert_eq!(
+                    inner_children,
+                    &vec![Token::Text("inner".to_string())]
+                );
+            } else {
+                panic!("Expected nested JsxComponent token");
+            }
+            assert_eq!(children[1], Token::Text("outer-tail".to_string()));
+        } else {
+            panic!("Expected JsxComponent token");
+        }
+    }
+
+    #[test]
+    fn test_unterminated_jsx_component_errors() {
+        let err = tokenize("<Button>Click me").unwrap_err();
+        assert!(err.to_string().contains("Unterminated JSX component <Button>"));
+    }
+
+    // ---------------------------------------------------------------------------
+    // Whitespace-control markers (`{%- -%}`, `{{- -}}`, `{#- -#}`)
+    // ---------------------------------------------------------------------------
+
+    #[test]
+    fn test_tag_left_trim_strips_preceding_whitespace() {
+        let tokens = tokenize("hello   \n  {%- if true %}x{% endif %}").unwrap();
+        assert_eq!(tokens[0], Token::Text("hello".to_string()));
+        assert_eq!(
+            tokens[1],
+            Token::Tag("if".to_string(), vec!["true".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_tag_right_trim_strips_following_whitespace() {
+        let tokens = tokenize("{% if true -%}   \n  x{% endif %}").unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::Tag("if".to_string(), vec!["true".to_string()])
+        );
+        assert_eq!(tokens[1], Token::Text("x".to_string()));
+    }
+
+    #[test]
+    fn test_variable_whitespace_control_both_sides() {
+        let tokens = tokenize("a   {{- name -}}   b").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Text("a".to_string()),
+                Token::Variable("name".to_string(), EscapeContext::Text),
+                Token::Text("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comment_whitespace_control_both_sides() {
+        let tokens = tokenize("a   {#- note -#}   b").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Text("a".to_string()),
+                Token::Comment,
+                Token::Text("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_whitespace_control_does_not_touch_non_adjacent_text() {
+        // No `-` markers: ordinary whitespace around a tag is untouched.
+        let tokens = tokenize("a   {% if true %}x{% endif %}   b").unwrap();
+        assert_eq!(tokens[0], Token::Text("a   ".to_string()));
+        assert_eq!(*tokens.last().unwrap(), Token::Text("   b".to_string()));
+    }
+
+    #[test]
+    fn test_jsx_children_whitespace_control() {
+        let tokens = tokenize("<Button>  \n  {%- if true -%}  \n  x  \n  {% endif %}</Button>").unwrap();
+        if let Token::JsxComponent { children, .. } = &tokens[0] {
+            assert_eq!(children[0], Token::Tag("if".to_string(), vec!["true".to_string()]));
+            assert_eq!(children[1], Token::Text("x  \n  ".to_string()));
+        } else {
+            panic!("Expected JsxComponent token");
+        }
+    }
+
+    #[test]
+    fn test_validator_ignores_whitespace_control_markers_on_allowed_tags() {
+        // Non-block tags with markers still pass.
+        assert!(validate_no_block_tags_in_attrs(r#"<a class="{%- widthratio a b 100 -%}"></a>"#).is_ok());
+    }
+
+    #[test]
+    fn test_validator_still_rejects_block_tag_with_whitespace_control_markers() {
+        let src = r#"<a class="nav {%- if active -%}active{%- endif -%}">link</a>"#;
+        let result = validate_no_block_tags_in_attrs(src);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("{% if %}"));
+    }
 }
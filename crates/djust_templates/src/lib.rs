@@ -12,17 +12,33 @@ use once_cell::sync::Lazy;
 use pyo3::prelude::*;
 use regex::Regex;
 use std::collections::HashMap;
+use std::io::Write;
 
+pub mod compiler;
+pub mod condition;
+pub mod date_parse;
+pub mod environment;
+pub mod fallback;
 pub mod filters;
 pub mod inheritance;
+pub mod instruction;
 pub mod lexer;
+pub mod loaders;
+pub mod locale;
+pub mod markdown;
 pub mod parser;
+pub mod recurrence;
 pub mod renderer;
+pub mod rust_tags;
 pub mod tags;
+pub mod theme;
+pub mod truncate;
 
 use inheritance::{build_inheritance_chain, TemplateLoader};
 use parser::Node;
-use renderer::render_nodes_with_loader;
+use renderer::{
+    render_nodes_to_fmt_writer, render_nodes_to_writer, render_template_with_loader,
+};
 
 // Re-export for JIT auto-serialization
 pub use parser::extract_template_variables;
@@ -45,6 +61,7 @@ impl Template {
     pub fn new(source: &str) -> Result<Self> {
         let tokens = lexer::tokenize(source)?;
         let nodes = parser::parse(&tokens)?;
+        parser::validate_template(&nodes)?;
 
         Ok(Self {
             nodes,
@@ -62,25 +79,91 @@ impl Template {
         context: &Context,
         loader: &L,
     ) -> Result<String> {
-        // Check if template uses inheritance
+        render_template_with_loader(&self.nodes, context, loader)
+    }
+
+    /// Render the template, then truncate the result to at most `max_chars`
+    /// visible characters while keeping the output tag-balanced.
+    ///
+    /// Useful for safe previews/excerpts (search results, notification
+    /// feeds) where naively slicing the rendered HTML could split a tag in
+    /// half or leave elements unclosed.
+    pub fn render_truncated(&self, context: &Context, max_chars: usize) -> Result<String> {
+        let full = self.render(context)?;
+        Ok(truncate::truncate_html(&full, max_chars))
+    }
+
+    /// Render directly into `out` instead of building a `String`, bounding
+    /// memory use for large inheritance/include trees.
+    pub fn render_to<W: Write>(&self, context: &Context, out: &mut W) -> Result<()> {
+        self.render_to_with_loader(context, &NoOpTemplateLoader, out)
+    }
+
+    /// Like [`Template::render_with_loader`], but streams into `out` rather
+    /// than returning a `String`.
+    pub fn render_to_with_loader<W: Write, L: TemplateLoader>(
+        &self,
+        context: &Context,
+        loader: &L,
+        out: &mut W,
+    ) -> Result<()> {
         let uses_extends = self
             .nodes
             .iter()
             .any(|node| matches!(node, Node::Extends(_)));
 
         if uses_extends {
-            // Build inheritance chain
             let chain = build_inheritance_chain(self.nodes.clone(), loader, 10)?;
+            let root_nodes = chain.get_root_nodes();
+            let final_nodes = chain.apply_block_overrides(root_nodes);
+
+            renderer::render_nodes_with_inheritance_to(
+                &final_nodes,
+                context,
+                Some(loader),
+                &chain.block_stacks,
+                out,
+            )
+        } else {
+            render_nodes_to_writer(&self.nodes, context, Some(loader), out)
+        }
+    }
+
+    /// Like [`Template::render`], but streams into a `std::fmt::Write` sink
+    /// (e.g. a `String`) instead of allocating the whole output up front.
+    /// Templates using `{% extends %}` still build their merged node list
+    /// first; only the final write pass is unbuffered.
+    pub fn render_to_fmt<W: std::fmt::Write>(&self, context: &Context, out: &mut W) -> Result<()> {
+        self.render_to_fmt_with_loader(context, &NoOpTemplateLoader, out)
+    }
+
+    /// Like [`Template::render_with_loader`], but streams into a
+    /// `std::fmt::Write` sink rather than returning a `String`.
+    pub fn render_to_fmt_with_loader<W: std::fmt::Write, L: TemplateLoader>(
+        &self,
+        context: &Context,
+        loader: &L,
+        out: &mut W,
+    ) -> Result<()> {
+        let uses_extends = self
+            .nodes
+            .iter()
+            .any(|node| matches!(node, Node::Extends(_)));
 
-            // Get root template nodes with block overrides applied
+        if uses_extends {
+            let chain = build_inheritance_chain(self.nodes.clone(), loader, 10)?;
             let root_nodes = chain.get_root_nodes();
             let final_nodes = chain.apply_block_overrides(root_nodes);
 
-            // Render the merged template with loader for includes
-            render_nodes_with_loader(&final_nodes, context, Some(loader))
+            renderer::render_nodes_with_inheritance_to_fmt(
+                &final_nodes,
+                context,
+                Some(loader),
+                &chain.block_stacks,
+                out,
+            )
         } else {
-            // No inheritance, render normally with loader for includes
-            render_nodes_with_loader(&self.nodes, context, Some(loader))
+            render_nodes_to_fmt_writer(&self.nodes, context, Some(loader), out)
         }
     }
 }
@@ -96,6 +179,31 @@ impl TemplateLoader for NoOpTemplateLoader {
     }
 }
 
+/// Adapts a Python file-like object (anything with a `.write(bytes)` method)
+/// into `std::io::Write`, so [`Template::render_to`] can stream straight
+/// into it without buffering the whole output on the Rust side first.
+struct PyWriter<'py> {
+    writer: Bound<'py, PyAny>,
+}
+
+impl<'py> Write for PyWriter<'py> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .call_method1("write", (buf,))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.writer.hasattr("flush").unwrap_or(false) {
+            self.writer
+                .call_method0("flush")
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
 #[pymethods]
 impl Template {
     #[new]
@@ -108,6 +216,27 @@ impl Template {
         Ok(self.render(&context)?)
     }
 
+    fn py_render_truncated(
+        &self,
+        context_dict: HashMap<String, Value>,
+        max_chars: usize,
+    ) -> PyResult<String> {
+        let context = Context::from_dict(context_dict);
+        Ok(self.render_truncated(&context, max_chars)?)
+    }
+
+    /// Render straight into a Python file-like object (anything with
+    /// `.write(bytes)`), instead of building and returning a `String`.
+    fn py_render_to<'py>(
+        &self,
+        context_dict: HashMap<String, Value>,
+        writer: Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        let context = Context::from_dict(context_dict);
+        let mut py_writer = PyWriter { writer };
+        Ok(self.render_to(&context, &mut py_writer)?)
+    }
+
     #[getter]
     fn source(&self) -> String {
         self.source.clone()
@@ -209,6 +338,32 @@ mod tests {
         assert!(result.contains("Hello World"));
     }
 
+    #[test]
+    fn test_render_template_with_loader_matches_render_with_loader() {
+        // `render_template_with_loader` is the free-function counterpart to
+        // `Template::render_with_loader` for callers that already have a
+        // parsed node list instead of a `Template`.
+        let mut loader = TestTemplateLoader::new();
+        loader.add(
+            "base.html",
+            "Header {% block content %}Default content{% endblock %} Footer",
+        );
+
+        let child_source =
+            "{% extends \"base.html\" %}{% block content %}Child content{% endblock %}";
+        let child = Template::new(child_source).unwrap();
+        let tokens = lexer::tokenize(child_source).unwrap();
+        let nodes = parser::parse(&tokens).unwrap();
+
+        let context = Context::new();
+        let via_template = child.render_with_loader(&context, &loader).unwrap();
+        let via_free_fn =
+            renderer::render_template_with_loader(&nodes, &context, &loader).unwrap();
+
+        assert_eq!(via_template, via_free_fn);
+        assert!(via_free_fn.contains("Child content"));
+    }
+
     #[test]
     fn test_inheritance_block_override() {
         let mut loader = TestTemplateLoader::new();
@@ -371,6 +526,153 @@ mod tests {
         assert!(!result.contains("L3"), "Should not have L3 default");
     }
 
+    #[test]
+    fn test_nested_block_super_resolves_against_its_own_ancestor_not_the_outer_blocks() {
+        // The child overrides only the *nested* block and calls
+        // `block.super` there - it should pick up the nested block's own
+        // default ("Default Inner"), not anything from the outer "content"
+        // block it happens to live inside.
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add(
+            "base.html",
+            "<html>{% block content %}<div class=\"wrapper\">{% block inner %}Default Inner{% endblock %}</div>{% endblock %}</html>",
+        );
+
+        let child_source = "{% extends \"base.html\" %}{% block inner %}{{ block.super }} + Child Inner{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let result = child_template
+            .render_with_loader(&context, &loader)
+            .unwrap();
+
+        assert_eq!(
+            result,
+            "<html><div class=\"wrapper\">Default Inner + Child Inner</div></html>"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_block_name_in_same_template_is_a_template_error() {
+        let mut loader = TestTemplateLoader::new();
+        loader.add("base.html", "{% block content %}Base{% endblock %}");
+
+        let child_source = "{% extends \"base.html\" %}{% block content %}A{% endblock %}{% block content %}B{% endblock %}";
+        // Two same-scope `content` blocks are rejected by
+        // `parser::validate_template` inside `Template::new`, before any
+        // render (or loader lookup) happens.
+        let err = match Template::new(child_source) {
+            Ok(_) => panic!("duplicate block name should be rejected"),
+            Err(err) => err,
+        };
+
+        assert!(
+            err.to_string().contains("content"),
+            "Error should name the duplicated block: {err}"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_block_name_nested_inside_different_block_is_a_template_error() {
+        // "inner" is declared twice - once inside "left", once inside
+        // "right" - which Django also rejects, since block names are one
+        // flat namespace per template regardless of nesting.
+        let mut loader = TestTemplateLoader::new();
+        loader.add("base.html", "{% block content %}Base{% endblock %}");
+
+        let child_source = "{% extends \"base.html\" %}{% block left %}{% block inner %}A{% endblock %}{% endblock %}{% block right %}{% block inner %}B{% endblock %}{% endblock %}";
+        let template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let err = template.render_with_loader(&context, &loader).unwrap_err();
+
+        assert!(
+            err.to_string().contains("inner"),
+            "Error should name the duplicated block: {err}"
+        );
+    }
+
+    #[test]
+    fn test_block_super_renders_parent_content() {
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add(
+            "base.html",
+            "{% block scripts %}<script src=\"base.js\"></script>{% endblock %}",
+        );
+
+        let child_source = "{% extends \"base.html\" %}{% block scripts %}{{ block.super }}<script src=\"child.js\"></script>{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let result = child_template
+            .render_with_loader(&context, &loader)
+            .unwrap();
+
+        assert_eq!(
+            result,
+            "<script src=\"base.js\"></script><script src=\"child.js\"></script>"
+        );
+    }
+
+    #[test]
+    fn test_block_super_at_root_renders_nothing() {
+        // No ancestor to fall back to, so block.super is silently empty —
+        // matching Django's behavior for a block with no parent.
+        let child_source = "{% block content %}{{ block.super }}Only Mine{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let result = child_template.render(&context).unwrap();
+
+        assert_eq!(result, "Only Mine");
+    }
+
+    #[test]
+    fn test_block_super_with_no_ancestor_definition_renders_nothing() {
+        // Parent has no `{% block scripts %}` at all (not even an empty
+        // one) — the child introduces the block itself, so its
+        // `block.super` has nothing to fall back to.
+        let mut loader = TestTemplateLoader::new();
+        loader.add("base.html", "<head></head>");
+
+        let child_source = "{% extends \"base.html\" %}{% block scripts %}{{ block.super }}<script src=\"child.js\"></script>{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let result = child_template
+            .render_with_loader(&context, &loader)
+            .unwrap();
+
+        assert_eq!(result, "<script src=\"child.js\"></script>");
+    }
+
+    #[test]
+    fn test_block_super_drills_through_three_levels() {
+        // grandparent -> parent (uses block.super) -> child (uses block.super)
+        // Each block.super should resolve to the *next* ancestor down, not
+        // jump straight to the root.
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add("grandparent.html", "{% block greeting %}Hello{% endblock %}");
+
+        loader.add(
+            "parent.html",
+            "{% extends \"grandparent.html\" %}{% block greeting %}{{ block.super }}, Parent{% endblock %}",
+        );
+
+        let child_source = "{% extends \"parent.html\" %}{% block greeting %}{{ block.super }}, Child{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let result = child_template
+            .render_with_loader(&context, &loader)
+            .unwrap();
+
+        assert_eq!(result, "Hello, Parent, Child");
+    }
+
     #[test]
     fn test_inheritance_with_variables() {
         let mut loader = TestTemplateLoader::new();
@@ -451,6 +753,32 @@ mod tests {
         assert!(result.contains("</ul>"));
     }
 
+    #[test]
+    fn test_scoped_block_renders_once_per_loop_iteration() {
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add("base.html", "{% block useless %}{% endblock %}");
+
+        let child_source = "{% extends \"base.html\" %}{% block useless %}{% for x in items %}{% block inner scoped %}{{ x }}{% endblock %}{% endfor %}{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Integer(3),
+            ]),
+        );
+
+        let result = child_template
+            .render_with_loader(&context, &loader)
+            .unwrap();
+
+        assert_eq!(result, "123");
+    }
+
     // Tests for {% include %} tag with variables (Issue #35)
     #[test]
     fn test_include_basic() {
@@ -642,4 +970,131 @@ mod tests {
         assert!(result.contains("<p>Post excerpt here...</p>"));
         assert!(result.contains("</article>"));
     }
+
+    // Tests for {% macro %} / {% import %} / {% from ... import ... %}
+    #[test]
+    fn test_import_namespaced_macro_call() {
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add(
+            "helpers.html",
+            "{% macro card(title) %}[{{ title }}]{% endmacro %}",
+        );
+
+        let main_source = "{% import \"helpers.html\" as h %}{{ h.card(\"Hi\") }}";
+        let main_template = Template::new(main_source).unwrap();
+
+        let context = Context::new();
+        let result = main_template.render_with_loader(&context, &loader).unwrap();
+
+        assert_eq!(result, "[Hi]");
+    }
+
+    #[test]
+    fn test_from_import_with_local_alias() {
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add(
+            "helpers.html",
+            "{% macro button(label) %}<button>{{ label }}</button>{% endmacro %}",
+        );
+
+        let main_source = "{% from \"helpers.html\" import button as b %}{{ b(\"Go\") }}";
+        let main_template = Template::new(main_source).unwrap();
+
+        let context = Context::new();
+        let result = main_template.render_with_loader(&context, &loader).unwrap();
+
+        assert_eq!(result, "<button>Go</button>");
+    }
+
+    #[test]
+    fn test_macro_usable_across_inheritance() {
+        let mut loader = TestTemplateLoader::new();
+
+        loader.add(
+            "base.html",
+            "{% macro greet(name) %}Hi {{ name }}{% endmacro %}{% block content %}{% endblock %}",
+        );
+
+        let child_source =
+            "{% extends \"base.html\" %}{% block content %}{{ greet(\"Sam\") }}{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let result = child_template
+            .render_with_loader(&context, &loader)
+            .unwrap();
+
+        assert_eq!(result, "Hi Sam");
+    }
+
+    #[test]
+    fn test_render_to_matches_render() {
+        let template = Template::new("Hello {{ name }}!").unwrap();
+        let mut context = Context::new();
+        context.set("name".to_string(), Value::String("World".to_string()));
+
+        let mut buf = Vec::new();
+        template.render_to(&context, &mut buf).unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), "Hello World!");
+    }
+
+    #[test]
+    fn test_render_to_with_loader_resolves_inheritance() {
+        let mut loader = TestTemplateLoader::new();
+        loader.add(
+            "base.html",
+            "<body>{% block content %}default{% endblock %}</body>",
+        );
+
+        let child_source =
+            "{% extends \"base.html\" %}{% block content %}child content{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let mut buf = Vec::new();
+        child_template
+            .render_to_with_loader(&context, &loader, &mut buf)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "<body>child content</body>"
+        );
+    }
+
+    #[test]
+    fn test_render_to_fmt_matches_render() {
+        let template = Template::new("Hello {{ name }}!").unwrap();
+        let mut context = Context::new();
+        context.set("name".to_string(), Value::String("World".to_string()));
+
+        let mut out = String::new();
+        template.render_to_fmt(&context, &mut out).unwrap();
+
+        assert_eq!(out, "Hello World!");
+    }
+
+    #[test]
+    fn test_render_to_fmt_with_loader_resolves_inheritance() {
+        let mut loader = TestTemplateLoader::new();
+        loader.add(
+            "base.html",
+            "<body>{% block content %}default{% endblock %}</body>",
+        );
+
+        let child_source =
+            "{% extends \"base.html\" %}{% block content %}child content{% endblock %}";
+        let child_template = Template::new(child_source).unwrap();
+
+        let context = Context::new();
+        let mut out = String::new();
+        child_template
+            .render_to_fmt_with_loader(&context, &loader, &mut out)
+            .unwrap();
+
+        assert_eq!(out, "<body>child content</body>");
+    }
 }
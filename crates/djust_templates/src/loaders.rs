@@ -0,0 +1,332 @@
+//! Built-in `TemplateLoader` implementations.
+//!
+//! `FilesystemTemplateLoader` (see `inheritance`) is the original disk-backed
+//! loader; this module adds the pieces embedders otherwise have to
+//! reimplement themselves: an in-memory source, loader composition
+//! (fallback and prefix routing), and a caching wrapper so inheritance
+//! chains and `{% include %}` don't re-tokenize/re-parse the same partial
+//! on every render.
+
+use crate::inheritance::{extract_blocks, TemplateLoader};
+use crate::lexer;
+use crate::parser::{self, Node};
+use djust_core::{DjangoRustError, Result};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// Re-exported under the name embedders reach for when wiring up
+/// disk-backed templates; identical to `inheritance::FilesystemTemplateLoader`.
+pub use crate::inheritance::FilesystemTemplateLoader as FilesystemLoader;
+
+/// Serves templates from an in-memory name -> source map. Useful for tests
+/// and for embedding a handful of templates without touching the filesystem.
+pub struct DictLoader {
+    templates: HashMap<String, String>,
+}
+
+impl DictLoader {
+    pub fn new(templates: HashMap<String, String>) -> Self {
+        Self { templates }
+    }
+}
+
+impl TemplateLoader for DictLoader {
+    fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+        let source = self
+            .templates
+            .get(name)
+            .ok_or_else(|| DjangoRustError::TemplateError(format!("Template not found: {name}")))?;
+        let tokens = lexer::tokenize(source)?;
+        parser::parse(&tokens)
+    }
+}
+
+/// Tries each child loader in order, returning the first successful load.
+/// If every child fails, the error reports how many were tried and why each
+/// one failed.
+pub struct ChoiceLoader {
+    loaders: Vec<Box<dyn TemplateLoader>>,
+}
+
+impl ChoiceLoader {
+    pub fn new(loaders: Vec<Box<dyn TemplateLoader>>) -> Self {
+        Self { loaders }
+    }
+}
+
+impl TemplateLoader for ChoiceLoader {
+    fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+        let mut errors = Vec::new();
+        for loader in &self.loaders {
+            match loader.load_template(name) {
+                Ok(nodes) => return Ok(nodes),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(DjangoRustError::TemplateError(format!(
+            "Template not found: {name}\nTried {} loader(s):\n{}",
+            self.loaders.len(),
+            errors.join("\n")
+        )))
+    }
+}
+
+/// Splits a template name on its first `/` and routes to the sub-loader
+/// registered for that prefix, passing the remainder along — e.g.
+/// `"admin/index.html"` with an `"admin"` entry loads `"index.html"` from
+/// that sub-loader.
+pub struct PrefixLoader {
+    loaders: HashMap<String, Box<dyn TemplateLoader>>,
+}
+
+impl PrefixLoader {
+    pub fn new(loaders: HashMap<String, Box<dyn TemplateLoader>>) -> Self {
+        Self { loaders }
+    }
+}
+
+impl TemplateLoader for PrefixLoader {
+    fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+        let (prefix, rest) = name.split_once('/').ok_or_else(|| {
+            DjangoRustError::TemplateError(format!(
+                "Template not found: {name} (PrefixLoader requires a 'prefix/name' path)"
+            ))
+        })?;
+        let loader = self.loaders.get(prefix).ok_or_else(|| {
+            DjangoRustError::TemplateError(format!(
+                "Template not found: {name} (no loader registered for prefix '{prefix}')"
+            ))
+        })?;
+        loader.load_template(rest)
+    }
+}
+
+/// A memoized parse: the block bodies are extracted alongside the nodes so
+/// callers building an inheritance chain from a `CachingLoader` (see
+/// `load_layer`) don't redo that walk on every lookup either, and `mtime` is
+/// the inner loader's modification time at the moment this entry was
+/// populated, so a later lookup can tell whether the source has moved on.
+struct CachedParse {
+    nodes: Vec<Node>,
+    blocks: HashMap<String, Vec<Node>>,
+    mtime: Option<SystemTime>,
+}
+
+/// Wraps another loader and memoizes its parsed output by name, behind a
+/// `RwLock`, so repeated lookups for the same template skip tokenizing and
+/// parsing entirely. When the inner loader reports a modification time (see
+/// `TemplateLoader::mtime`), a changed file is re-parsed automatically -
+/// otherwise entries are cached until `clear()` is called, which is the
+/// right trade-off for sources (in-memory, composed loaders) with no
+/// meaningful notion of "changed on disk".
+pub struct CachingLoader<L: TemplateLoader> {
+    inner: L,
+    cache: RwLock<HashMap<String, CachedParse>>,
+}
+
+impl<L: TemplateLoader> CachingLoader<L> {
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Drop every cached parse, forcing the next lookup of each template to
+    /// go back to the inner loader.
+    pub fn clear(&self) {
+        self.cache.write().unwrap().clear();
+    }
+
+    /// Like `load_template`, but also returns the block bodies already
+    /// extracted from the cached parse, so a caller building an
+    /// `InheritanceChain` doesn't have to call `extract_blocks` itself.
+    pub fn load_layer(&self, name: &str) -> Result<(Vec<Node>, HashMap<String, Vec<Node>>)> {
+        let current_mtime = self.inner.mtime(name);
+
+        if let Some(entry) = self.cache.read().unwrap().get(name) {
+            if entry.mtime.is_none() || entry.mtime == current_mtime {
+                return Ok((entry.nodes.clone(), entry.blocks.clone()));
+            }
+        }
+
+        let nodes = self.inner.load_template(name)?;
+        let blocks = extract_blocks(&nodes)?;
+        self.cache.write().unwrap().insert(
+            name.to_string(),
+            CachedParse {
+                nodes: nodes.clone(),
+                blocks: blocks.clone(),
+                mtime: current_mtime,
+            },
+        );
+        Ok((nodes, blocks))
+    }
+}
+
+impl<L: TemplateLoader> TemplateLoader for CachingLoader<L> {
+    fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+        self.load_layer(name).map(|(nodes, _)| nodes)
+    }
+
+    fn mtime(&self, name: &str) -> Option<SystemTime> {
+        self.inner.mtime(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn dict(entries: &[(&str, &str)]) -> DictLoader {
+        let map = entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        DictLoader::new(map)
+    }
+
+    #[test]
+    fn test_dict_loader_loads_known_template() {
+        let loader = dict(&[("greeting.html", "Hello {{ name }}")]);
+        let nodes = loader.load_template("greeting.html").unwrap();
+        assert!(!nodes.is_empty());
+    }
+
+    #[test]
+    fn test_dict_loader_errors_on_missing_template() {
+        let loader = dict(&[]);
+        assert!(loader.load_template("missing.html").is_err());
+    }
+
+    #[test]
+    fn test_choice_loader_falls_back_to_second_loader() {
+        let first = dict(&[]);
+        let second = dict(&[("page.html", "Hi")]);
+        let loader = ChoiceLoader::new(vec![Box::new(first), Box::new(second)]);
+        assert!(loader.load_template("page.html").is_ok());
+    }
+
+    #[test]
+    fn test_choice_loader_errors_when_all_fail() {
+        let loader = ChoiceLoader::new(vec![Box::new(dict(&[])), Box::new(dict(&[]))]);
+        let err = loader.load_template("nope.html").unwrap_err();
+        assert!(err.to_string().contains("Tried 2 loader(s)"));
+    }
+
+    #[test]
+    fn test_prefix_loader_routes_by_first_segment() {
+        let mut loaders: HashMap<String, Box<dyn TemplateLoader>> = HashMap::new();
+        loaders.insert(
+            "admin".to_string(),
+            Box::new(dict(&[("index.html", "Admin")])),
+        );
+        let loader = PrefixLoader::new(loaders);
+        assert!(loader.load_template("admin/index.html").is_ok());
+        assert!(loader.load_template("shop/index.html").is_err());
+    }
+
+    /// Counts how many times `load_template` is actually invoked, so the
+    /// caching wrapper's hit behavior can be asserted directly.
+    struct CountingLoader {
+        inner: DictLoader,
+        calls: Cell<usize>,
+    }
+
+    impl TemplateLoader for CountingLoader {
+        fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.load_template(name)
+        }
+    }
+
+    #[test]
+    fn test_caching_loader_only_parses_once() {
+        let counting = CountingLoader {
+            inner: dict(&[("page.html", "Hi")]),
+            calls: Cell::new(0),
+        };
+        let loader = CachingLoader::new(counting);
+        loader.load_template("page.html").unwrap();
+        loader.load_template("page.html").unwrap();
+        assert_eq!(loader.inner.calls.get(), 1);
+    }
+
+    #[test]
+    fn test_caching_loader_clear_forces_reparse() {
+        let counting = CountingLoader {
+            inner: dict(&[("page.html", "Hi")]),
+            calls: Cell::new(0),
+        };
+        let loader = CachingLoader::new(counting);
+        loader.load_template("page.html").unwrap();
+        loader.clear();
+        loader.load_template("page.html").unwrap();
+        assert_eq!(loader.inner.calls.get(), 2);
+    }
+
+    #[test]
+    fn test_load_layer_returns_extracted_blocks_from_cache() {
+        let loader = CachingLoader::new(dict(&[(
+            "page.html",
+            "{% block title %}Hi{% endblock %}",
+        )]));
+        let (_, blocks) = loader.load_layer("page.html").unwrap();
+        assert!(blocks.contains_key("title"));
+    }
+
+    /// A loader whose reported `mtime` can be bumped mid-test, so cache
+    /// invalidation can be exercised without touching the filesystem.
+    struct StaleableLoader {
+        inner: DictLoader,
+        calls: Cell<usize>,
+        mtime: Cell<SystemTime>,
+    }
+
+    impl TemplateLoader for StaleableLoader {
+        fn load_template(&self, name: &str) -> Result<Vec<Node>> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.load_template(name)
+        }
+
+        fn mtime(&self, _name: &str) -> Option<SystemTime> {
+            Some(self.mtime.get())
+        }
+    }
+
+    #[test]
+    fn test_caching_loader_reparses_when_inner_mtime_changes() {
+        let staleable = StaleableLoader {
+            inner: dict(&[("page.html", "Hi")]),
+            calls: Cell::new(0),
+            mtime: Cell::new(SystemTime::UNIX_EPOCH),
+        };
+        let loader = CachingLoader::new(staleable);
+        loader.load_template("page.html").unwrap();
+        loader.load_template("page.html").unwrap();
+        assert_eq!(loader.inner.calls.get(), 1);
+
+        // File changed on disk - bump the mtime the inner loader reports.
+        loader
+            .inner
+            .mtime
+            .set(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1));
+        loader.load_template("page.html").unwrap();
+        assert_eq!(loader.inner.calls.get(), 2);
+    }
+
+    #[test]
+    fn test_caching_loader_never_reparses_when_inner_has_no_mtime() {
+        let counting = CountingLoader {
+            inner: dict(&[("page.html", "Hi")]),
+            calls: Cell::new(0),
+        };
+        let loader = CachingLoader::new(counting);
+        loader.load_template("page.html").unwrap();
+        loader.load_template("page.html").unwrap();
+        loader.load_template("page.html").unwrap();
+        assert_eq!(loader.inner.calls.get(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+//! Locale-pluggable names for `renderer::django_date_format`'s `M`/`F`/`D`/
+//! `l`/`S`/`P`/`A` format codes.
+//!
+//! A template's [`Context`](djust_core::Context) can carry a locale code
+//! via `Context::set_locale`; `django_date_format` resolves it through
+//! [`Locale::from_code`] and falls back to [`Locale::english`] when no
+//! locale is set or the code isn't recognized, so existing templates keep
+//! today's English output unchanged.
+
+use chrono::Weekday;
+
+/// A set of names and an ordinal-suffix rule for one language, used in
+/// place of chrono's English-only `%B`/`%b`/`%A`/`%a` formatting.
+pub struct Locale {
+    month_names: [&'static str; 12],
+    month_abbr: [&'static str; 12],
+    weekday_names: [&'static str; 7],
+    weekday_abbr: [&'static str; 7],
+    pub am: &'static str,
+    pub pm: &'static str,
+    pub noon: &'static str,
+    pub midnight: &'static str,
+    ordinal_suffix_fn: fn(u32) -> &'static str,
+}
+
+impl Locale {
+    /// The default locale, matching `django_date_format`'s pre-locale
+    /// English output exactly.
+    pub fn english() -> Self {
+        Locale {
+            month_names: [
+                "January", "February", "March", "April", "May", "June", "July", "August",
+                "September", "October", "November", "December",
+            ],
+            month_abbr: [
+                "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+            ],
+            weekday_names: [
+                "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday",
+            ],
+            weekday_abbr: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+            am: "a.m.",
+            pm: "p.m.",
+            noon: "noon",
+            midnight: "midnight",
+            ordinal_suffix_fn: english_ordinal_suffix,
+        }
+    }
+
+    /// Russian month/weekday names, used by `from_code("ru")`.
+    pub fn russian() -> Self {
+        Locale {
+            month_names: [
+                "Январь",
+                "Февраль",
+                "Март",
+                "Апрель",
+                "Май",
+                "Июнь",
+                "Июль",
+                "Август",
+                "Сентябрь",
+                "Октябрь",
+                "Ноябрь",
+                "Декабрь",
+            ],
+            month_abbr: [
+                "янв", "фев", "мар", "апр", "май", "июн", "июл", "авг", "сен", "окт", "ноя", "дек",
+            ],
+            weekday_names: [
+                "понедельник",
+                "вторник",
+                "среда",
+                "четверг",
+                "пятница",
+                "суббота",
+                "воскресенье",
+            ],
+            weekday_abbr: ["пн", "вт", "ср", "чт", "пт", "сб", "вс"],
+            am: "ДП",
+            pm: "ПП",
+            noon: "полдень",
+            midnight: "полночь",
+            ordinal_suffix_fn: no_ordinal_suffix,
+        }
+    }
+
+    /// Resolve a locale by its short code (e.g. `"en"`, `"ru"`). `None` for
+    /// an unrecognized code, so callers can fall back to `Locale::english`.
+    pub fn from_code(code: &str) -> Option<Locale> {
+        match code {
+            "en" => Some(Locale::english()),
+            "ru" => Some(Locale::russian()),
+            _ => None,
+        }
+    }
+
+    pub fn month_name(&self, month: u32) -> &'static str {
+        self.month_names[(month.clamp(1, 12) - 1) as usize]
+    }
+
+    pub fn month_abbr(&self, month: u32) -> &'static str {
+        self.month_abbr[(month.clamp(1, 12) - 1) as usize]
+    }
+
+    pub fn weekday_name(&self, weekday: Weekday) -> &'static str {
+        self.weekday_names[weekday.num_days_from_monday() as usize]
+    }
+
+    pub fn weekday_abbr(&self, weekday: Weekday) -> &'static str {
+        self.weekday_abbr[weekday.num_days_from_monday() as usize]
+    }
+
+    pub fn ordinal_suffix(&self, day: u32) -> &'static str {
+        (self.ordinal_suffix_fn)(day)
+    }
+}
+
+fn english_ordinal_suffix(day: u32) -> &'static str {
+    match day {
+        1 | 21 | 31 => "st",
+        2 | 22 => "nd",
+        3 | 23 => "rd",
+        _ => "th",
+    }
+}
+
+/// Russian (and most other languages) doesn't suffix cardinal day numbers
+/// the way English does, so `S` renders as nothing for these locales.
+fn no_ordinal_suffix(_day: u32) -> &'static str {
+    ""
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english_is_default_and_matches_prior_output() {
+        let locale = Locale::english();
+        assert_eq!(locale.month_name(9), "September");
+        assert_eq!(locale.ordinal_suffix(21), "st");
+    }
+
+    #[test]
+    fn test_from_code_resolves_russian() {
+        let locale = Locale::from_code("ru").unwrap();
+        assert_eq!(locale.month_name(9), "Сентябрь");
+    }
+
+    #[test]
+    fn test_from_code_rejects_unknown() {
+        assert!(Locale::from_code("xx").is_none());
+    }
+}
@@ -0,0 +1,490 @@
+//! CommonMark-subset renderer backing the `markdown` filter and the
+//! `{% markdown %}...{% endmarkdown %}` block tag (see `parser::parse_tag`).
+//!
+//! Only the constructs content-heavy pages actually use are supported:
+//! headings, emphasis, inline code, fenced code blocks, links, GFM tables,
+//! footnotes, and paragraph/list blocks. Raw HTML and all text content are
+//! passed through `html_escape` so Markdown sourced from user input can
+//! never inject a `<script>` tag into the page — the output is safe to
+//! mark `|safe`.
+
+use crate::filters::html_escape;
+
+/// A GFM table column's alignment, from the `:---`/`:---:`/`---:` markers
+/// in its separator row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    fn style_attr(self) -> &'static str {
+        match self {
+            Alignment::None => "",
+            Alignment::Left => " style=\"text-align:left\"",
+            Alignment::Center => " style=\"text-align:center\"",
+            Alignment::Right => " style=\"text-align:right\"",
+        }
+    }
+}
+
+/// Render `src` (CommonMark subset) to an auto-escaped-safe HTML fragment.
+///
+/// `class_prefix`, if given, is prepended (as `"{prefix}-"`) to the
+/// Bootstrap-style classes applied to generated `<pre>` and `<table>`
+/// elements, so a caller can namespace them per section of a site:
+/// `{{ body|markdown:"docs" }}` emits `class="docs-table table-striped"`.
+/// A fenced block's `<code>` is not prefixed: it carries only a
+/// `language-*` class, and only when the fence names a language.
+pub fn render_markdown(src: &str, class_prefix: Option<&str>) -> String {
+    let (body_lines, footnotes) = extract_footnote_defs(src);
+
+    let mut out = String::new();
+    let mut lines = body_lines.iter().map(String::as_str).peekable();
+    let mut in_list = false;
+
+    while let Some(line) = lines.next() {
+        if let Some(fence) = line.trim_start().strip_prefix("```") {
+            close_list(&mut out, &mut in_list);
+            let lang = fence.trim();
+            let pre_class = prefixed_class(class_prefix, "code-block");
+            if lang.is_empty() {
+                out.push_str(&format!("<pre class=\"{pre_class}\"><code>"));
+            } else {
+                out.push_str(&format!(
+                    "<pre class=\"{pre_class}\"><code class=\"language-{}\">",
+                    html_escape(lang)
+                ));
+            }
+            for code_line in lines.by_ref() {
+                if code_line.trim_start().starts_with("```") {
+                    break;
+                }
+                out.push_str(&html_escape(code_line));
+                out.push('\n');
+            }
+            out.push_str("</code></pre>\n");
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if let Some(heading) = parse_heading(trimmed) {
+            close_list(&mut out, &mut in_list);
+            let (level, text) = heading;
+            let id = slugify(text);
+            out.push_str(&format!(
+                "<h{level} id=\"{id}\">{}</h{level}>\n",
+                render_inline(text)
+            ));
+            continue;
+        }
+
+        if trimmed.contains('|') {
+            if let Some(aligns) = lines.peek().and_then(|next| parse_table_separator(next)) {
+                close_list(&mut out, &mut in_list);
+                let header = split_table_row(trimmed);
+                lines.next(); // consume the separator row
+                let mut rows = Vec::new();
+                while let Some(row_line) = lines.peek() {
+                    let row_trimmed = row_line.trim();
+                    if row_trimmed.is_empty() || !row_trimmed.contains('|') {
+                        break;
+                    }
+                    rows.push(split_table_row(row_trimmed));
+                    lines.next();
+                }
+                out.push_str(&render_table(&header, &aligns, &rows, class_prefix));
+                continue;
+            }
+        }
+
+        if let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            if !in_list {
+                out.push_str("<ul>\n");
+                in_list = true;
+            }
+            out.push_str(&format!("<li>{}</li>\n", render_inline(item)));
+            continue;
+        }
+
+        close_list(&mut out, &mut in_list);
+        if trimmed.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("<p>{}</p>\n", render_inline(trimmed)));
+    }
+    close_list(&mut out, &mut in_list);
+
+    if !footnotes.is_empty() {
+        out.push_str(&render_footnote_defs(&footnotes));
+    }
+
+    out
+}
+
+/// Pulls `[^label]: definition` footnote-definition lines out of `src`,
+/// skipping anything inside a fenced code block so a literal `[^1]:` in a
+/// code sample isn't mistaken for one. Returns the remaining lines (in
+/// order) plus the footnotes found, in first-seen order.
+fn extract_footnote_defs(src: &str) -> (Vec<String>, Vec<(String, String)>) {
+    let mut body = Vec::new();
+    let mut footnotes = Vec::new();
+    let mut in_fence = false;
+
+    for line in src.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            body.push(line.to_string());
+            continue;
+        }
+        if !in_fence {
+            if let Some((label, definition)) = parse_footnote_def(line) {
+                footnotes.push((label, definition));
+                continue;
+            }
+        }
+        body.push(line.to_string());
+    }
+
+    (body, footnotes)
+}
+
+/// Parses a `[^label]: definition text` line, if `line` is one.
+fn parse_footnote_def(line: &str) -> Option<(String, String)> {
+    let rest = line.trim_start().strip_prefix("[^")?;
+    let (label, rest) = rest.split_once("]:")?;
+    if !is_valid_footnote_label(label) {
+        return None;
+    }
+    Some((label.to_string(), rest.trim_start().to_string()))
+}
+
+/// A footnote label is spliced unescaped into `id="fn-{label}"` /
+/// `id="fnref-{label}"` / `href="#fn-{label}"`, so it's restricted to a
+/// safe id-fragment character set at parse time rather than escaped at
+/// render time - that way a malformed/hostile `[^...]` is simply not
+/// recognized as a footnote at all.
+fn is_valid_footnote_label(label: &str) -> bool {
+    !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Renders the `<div class="footnotes">` block collecting every footnote
+/// definition, in the order they were first defined — matching how
+/// pulldown-cmark and GitHub both render footnotes, with a `↩` back-link to
+/// the inline reference.
+fn render_footnote_defs(footnotes: &[(String, String)]) -> String {
+    let mut out = String::from("<div class=\"footnotes\">\n<hr>\n<ol>\n");
+    for (label, definition) in footnotes {
+        out.push_str(&format!(
+            "<li id=\"fn-{label}\">{} <a href=\"#fnref-{label}\">\u{21a9}</a></li>\n",
+            render_inline(definition)
+        ));
+    }
+    out.push_str("</ol>\n</div>\n");
+    out
+}
+
+/// Parses a GFM table separator row (`| --- | :---: | ---: |`) into one
+/// [`Alignment`] per column, or `None` if `line` isn't a separator row.
+fn parse_table_separator(line: &str) -> Option<Vec<Alignment>> {
+    let cells = split_table_row(line);
+    if cells.is_empty() {
+        return None;
+    }
+    cells
+        .iter()
+        .map(|cell| {
+            let cell = cell.trim();
+            let inner = cell.trim_matches(':');
+            if inner.is_empty() || !inner.chars().all(|c| c == '-') {
+                return None;
+            }
+            let left = cell.starts_with(':');
+            let right = cell.ends_with(':');
+            Some(match (left, right) {
+                (true, true) => Alignment::Center,
+                (false, true) => Alignment::Right,
+                (true, false) => Alignment::Left,
+                (false, false) => Alignment::None,
+            })
+        })
+        .collect()
+}
+
+/// Splits a `| a | b |` (or bare `a | b`) table row into trimmed cells.
+fn split_table_row(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix('|').unwrap_or(trimmed);
+    trimmed.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Renders a GFM table to `<table class="{prefix}-table table-striped">`
+/// (the Bootstrap-style class convention `render_markdown`'s doc comment
+/// already documents for `<pre>`).
+fn render_table(
+    header: &[String],
+    aligns: &[Alignment],
+    rows: &[Vec<String>],
+    class_prefix: Option<&str>,
+) -> String {
+    let table_class = prefixed_class(class_prefix, "table");
+    let mut out = format!("<table class=\"{table_class} table-striped\">\n<thead>\n<tr>\n");
+    for (i, cell) in header.iter().enumerate() {
+        let align = aligns.get(i).copied().unwrap_or(Alignment::None);
+        out.push_str(&format!("<th{}>{}</th>\n", align.style_attr(), render_inline(cell)));
+    }
+    out.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        out.push_str("<tr>\n");
+        for (i, cell) in row.iter().enumerate() {
+            let align = aligns.get(i).copied().unwrap_or(Alignment::None);
+            out.push_str(&format!("<td{}>{}</td>\n", align.style_attr(), render_inline(cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+    out
+}
+
+fn close_list(out: &mut String, in_list: &mut bool) {
+    if *in_list {
+        out.push_str("</ul>\n");
+        *in_list = false;
+    }
+}
+
+fn prefixed_class(class_prefix: Option<&str>, base: &str) -> String {
+    match class_prefix {
+        Some(prefix) if !prefix.is_empty() => format!("{prefix}-{base}"),
+        _ => base.to_string(),
+    }
+}
+
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    let text = rest.strip_prefix(' ')?;
+    Some((level, text.trim_end()))
+}
+
+/// Slugify heading text into a URL-safe anchor id: lowercase, alphanumerics
+/// kept, runs of everything else collapsed to a single `-`.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut last_was_dash = true;
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+            last_was_dash = false;
+        } else if !last_was_dash {
+            slug.push('-');
+            last_was_dash = true;
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Render inline spans (escaping raw text first, then re-introducing the
+/// small set of tags `**`/`*`/`` ` ``/`[text](url)` produce) within a single
+/// block-level line.
+fn render_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i..].starts_with(&['*', '*']) {
+            if let Some(end) = find_close(&chars, i + 2, &['*', '*']) {
+                out.push_str("<strong>");
+                out.push_str(&render_inline(&chars[i + 2..end].iter().collect::<String>()));
+                out.push_str("</strong>");
+                i = end + 2;
+                continue;
+            }
+        }
+        if chars[i] == '*' {
+            if let Some(end) = find_close(&chars, i + 1, &['*']) {
+                out.push_str("<em>");
+                out.push_str(&render_inline(&chars[i + 1..end].iter().collect::<String>()));
+                out.push_str("</em>");
+                i = end + 1;
+                continue;
+            }
+        }
+        if chars[i] == '`' {
+            if let Some(end) = find_close(&chars, i + 1, &['`']) {
+                out.push_str("<code>");
+                out.push_str(&html_escape(&chars[i + 1..end].iter().collect::<String>()));
+                out.push_str("</code>");
+                i = end + 1;
+                continue;
+            }
+        }
+        if chars[i..].starts_with(&['[', '^']) {
+            if let Some((label, end)) = parse_footnote_ref(&chars, i) {
+                out.push_str(&format!(
+                    "<sup id=\"fnref-{label}\"><a href=\"#fn-{label}\">{label}</a></sup>"
+                ));
+                i = end;
+                continue;
+            }
+        }
+        if chars[i] == '[' {
+            if let Some((link_text, url, end)) = parse_link(&chars, i) {
+                out.push_str(&format!(
+                    "<a href=\"{}\">{}</a>",
+                    crate::filters::escape_url(&url),
+                    render_inline(&link_text)
+                ));
+                i = end;
+                continue;
+            }
+        }
+        out.push_str(&html_escape(&chars[i].to_string()));
+        i += 1;
+    }
+    out
+}
+
+fn find_close(chars: &[char], from: usize, needle: &[char]) -> Option<usize> {
+    let mut i = from;
+    while i + needle.len() <= chars.len() {
+        if &chars[i..i + needle.len()] == needle {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn parse_link(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let close_bracket = find_close(chars, start + 1, &[']'])?;
+    if chars.get(close_bracket + 1) != Some(&'(') {
+        return None;
+    }
+    let close_paren = find_close(chars, close_bracket + 2, &[')'])?;
+    let link_text = chars[start + 1..close_bracket].iter().collect();
+    let url = chars[close_bracket + 2..close_paren].iter().collect();
+    Some((link_text, url, close_paren + 1))
+}
+
+/// Parses a `[^label]` footnote reference at `chars[start]` (already known
+/// to start with `[^`). Returns the label and the index right after `]`.
+fn parse_footnote_ref(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let close_bracket = find_close(chars, start + 2, &[']'])?;
+    let label: String = chars[start + 2..close_bracket].iter().collect();
+    if !is_valid_footnote_label(&label) {
+        return None;
+    }
+    Some((label, close_bracket + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_heading_with_slugged_id() {
+        let html = render_markdown("# Hello World", None);
+        assert_eq!(html, "<h1 id=\"hello-world\">Hello World</h1>\n");
+    }
+
+    #[test]
+    fn test_escapes_raw_html_in_text() {
+        let html = render_markdown("<script>alert(1)</script>", None);
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn test_renders_emphasis_and_inline_code() {
+        let html = render_markdown("**bold** and *em* and `code`", None);
+        assert_eq!(
+            html,
+            "<p><strong>bold</strong> and <em>em</em> and <code>code</code></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_renders_list_items() {
+        let html = render_markdown("- one\n- two", None);
+        assert_eq!(html, "<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n");
+    }
+
+    #[test]
+    fn test_renders_link_with_safe_url() {
+        let html = render_markdown("[go](javascript:alert(1))", None);
+        assert!(html.contains("href=\"#\""));
+    }
+
+    #[test]
+    fn test_fenced_code_block_uses_prefixed_class() {
+        let html = render_markdown("```rust\nfn x() {}\n```", Some("docs"));
+        assert!(html.contains("class=\"docs-code-block\""));
+        assert!(html.contains("class=\"language-rust\""));
+        assert!(html.contains("fn x() {}"));
+    }
+
+    #[test]
+    fn test_slugify_collapses_punctuation() {
+        assert_eq!(slugify("Hello, World!!"), "hello-world");
+    }
+
+    #[test]
+    fn test_renders_gfm_table_with_alignment() {
+        let html = render_markdown(
+            "| Name | Age |\n| :--- | ---: |\n| Alice | 30 |",
+            Some("docs"),
+        );
+        assert!(html.contains("class=\"docs-table table-striped\""));
+        assert!(html.contains("<th style=\"text-align:left\">Name</th>"));
+        assert!(html.contains("<th style=\"text-align:right\">Age</th>"));
+        assert!(html.contains("<td style=\"text-align:left\">Alice</td>"));
+        assert!(html.contains("<td style=\"text-align:right\">30</td>"));
+    }
+
+    #[test]
+    fn test_non_table_pipe_text_is_left_alone() {
+        let html = render_markdown("a | b", None);
+        assert_eq!(html, "<p>a | b</p>\n");
+    }
+
+    #[test]
+    fn test_footnote_reference_and_definition_are_linked() {
+        let html = render_markdown("See note.[^1]\n\n[^1]: The actual footnote text.", None);
+        assert!(html.contains("<sup id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup>"));
+        assert!(html.contains("<li id=\"fn-1\">The actual footnote text. <a href=\"#fnref-1\">\u{21a9}</a></li>"));
+        // The definition line itself doesn't leak into the main body as a paragraph.
+        assert!(!html.contains("<p>[^1]:"));
+    }
+
+    #[test]
+    fn test_footnote_def_inside_code_fence_is_left_alone() {
+        let html = render_markdown("```\n[^1]: not a real footnote\n```", None);
+        assert!(html.contains("[^1]: not a real footnote"));
+        assert!(!html.contains("class=\"footnotes\""));
+    }
+
+    #[test]
+    fn test_footnote_label_with_quote_and_markup_is_not_recognized() {
+        // A label outside the safe id-fragment character set must not be
+        // spliced unescaped into `id`/`href` attributes - it's simply not
+        // treated as a footnote at all.
+        let html = render_markdown(
+            "See note.[^a\"><script>alert(1)</script>]\n\n[^a\"><script>alert(1)</script>]: text",
+            None,
+        );
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("id=\"fnref-a\""));
+        assert!(!html.contains("id=\"fn-a\""));
+    }
+}
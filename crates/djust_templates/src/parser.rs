@@ -0,0 +1,1275 @@
+//! Parses a token stream into a template AST.
+//!
+//! `parse` turns the flat `Vec<Token>` produced by `lexer::tokenize` into a
+//! tree of `Node`s, resolving block-tag pairs (`{% if %}`/`{% endif %}`,
+//! `{% for %}`/`{% endfor %}`, etc.) and splitting `{{ var|filter:arg }}`
+//! variable syntax into a name plus an ordered filter pipeline.
+
+use crate::filters::EscapeContext;
+use crate::lexer::Token;
+use djust_core::{DjangoRustError, Result};
+
+/// A single parsed template construct.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Text(String),
+    Variable(String, Vec<(String, Option<String>)>, EscapeContext),
+    InlineIf {
+        true_expr: String,
+        condition: String,
+        false_expr: String,
+        filters: Vec<(String, Option<String>)>,
+        escape_context: EscapeContext,
+    },
+    If {
+        condition: String,
+        true_nodes: Vec<Node>,
+        false_nodes: Vec<Node>,
+    },
+    For {
+        var_names: Vec<String>,
+        iterable: String,
+        reversed: bool,
+        /// `{% for item in items key item.id %}` — an expression evaluated
+        /// against each iteration's bound variables to produce a stable key
+        /// for the `<!--dj-for:KEY-->` placeholder (see `renderer::write_node`),
+        /// letting VDOM diffing do keyed moves instead of a full re-render.
+        key: Option<String>,
+        nodes: Vec<Node>,
+        empty_nodes: Vec<Node>,
+    },
+    Block {
+        name: String,
+        nodes: Vec<Node>,
+        /// `{% block name scoped %}`. `inheritance::InheritanceChain`
+        /// carries the flag through merging unchanged, and the renderer's
+        /// `Node::Block` arm renders scoped and unscoped blocks alike.
+        scoped: bool,
+    },
+    Include {
+        template: String,
+        with_vars: Vec<(String, String)>,
+        only: bool,
+    },
+    ReactComponent {
+        name: String,
+        props: Vec<(String, String)>,
+        children: Vec<Node>,
+    },
+    RustComponent {
+        name: String,
+        props: Vec<(String, String)>,
+    },
+    CsrfToken,
+    Static(String),
+    With {
+        assignments: Vec<(String, String)>,
+        nodes: Vec<Node>,
+    },
+    Extends(String),
+    Comment,
+    WidthRatio {
+        value: String,
+        max_value: String,
+        max_width: String,
+    },
+    FirstOf {
+        args: Vec<String>,
+    },
+    TemplateTag(String),
+    Spaceless {
+        nodes: Vec<Node>,
+    },
+    /// `{% autoescape off %}...{% endautoescape %}` (or `on`, to re-enable
+    /// it inside an already-`off` block) — flips `Context::autoescape` for
+    /// everything rendered inside, then restores it on exit.
+    Autoescape {
+        enabled: bool,
+        nodes: Vec<Node>,
+    },
+    Cycle {
+        values: Vec<String>,
+        name: Option<String>,
+    },
+    Now(String),
+    UnsupportedTag {
+        name: String,
+        args: Vec<String>,
+    },
+    CustomTag {
+        name: String,
+        args: Vec<String>,
+    },
+    /// A block tag registered via `environment::register_tag`, e.g.
+    /// `{% mytag arg %}...{% endmytag %}`. Unlike `CustomTag`, this is only
+    /// produced when `environment::end_tag_for` recognizes `name` at parse
+    /// time - otherwise the parser has no way to know where the block ends.
+    CustomBlockTag {
+        name: String,
+        args: Vec<String>,
+        nodes: Vec<Node>,
+    },
+    /// `{% macro name(params) %}...{% endmacro %}` — a reusable parameterized
+    /// fragment. `params` pairs each parameter name with its default-value
+    /// expression, if any (`body=""`). Collected by the renderer before a
+    /// template renders so `{{ name(args) }}` can call it.
+    Macro {
+        name: String,
+        params: Vec<(String, Option<String>)>,
+        body: Vec<Node>,
+    },
+    /// `{{ name(args, kw=val) }}` — call a macro defined by `{% macro %}` or
+    /// brought in via `{% import %}` / `{% from ... import ... %}`. `name`
+    /// may be dotted (`h.card`) when called through an `{% import as %}`
+    /// namespace.
+    MacroCall {
+        name: String,
+        args: Vec<String>,
+        kwargs: Vec<(String, String)>,
+    },
+    /// `{% import "helpers.html" as h %}` — bind every top-level macro in
+    /// the target template under the `h.` namespace.
+    Import { template: String, alias: String },
+    /// `{% from "helpers.html" import card, button as b %}` — bind specific
+    /// macros from the target template directly, optionally under a local
+    /// alias. `with_context` is set by the trailing `with context` keyword.
+    FromImport {
+        template: String,
+        names: Vec<(String, String)>,
+        with_context: bool,
+    },
+    /// `{% markdown %}...{% endmarkdown %}` — prose written in CommonMark
+    /// rather than hand-written HTML. Unlike every other container node,
+    /// `nodes` isn't the block's literal children: `parse_markdown_block`
+    /// already rendered each literal `Text` run to HTML with
+    /// `markdown::render_markdown` and re-tokenized/re-parsed the result at
+    /// parse time, so `nodes` holds ordinary `Text`/`Variable`/... nodes
+    /// ready to render like any other block — this variant exists only so
+    /// the tag has something to return instead of splicing into its parent.
+    Markdown(Vec<Node>),
+}
+
+/// Parse a full token stream into a node tree.
+pub fn parse(tokens: &[Token]) -> Result<Vec<Node>> {
+    let mut cursor = Cursor { tokens, pos: 0 };
+    let (nodes, _) = parse_until(&mut cursor, &[])?;
+    Ok(nodes)
+}
+
+/// Reject malformed templates the parser itself accepts silently: two
+/// `{% block %}` tags with the same name at the same nesting scope, more
+/// than one `{% extends %}`, and an `{% extends %}` that isn't the first
+/// meaningful thing in the template. Called from `Template::new` right
+/// after parsing so these surface as compile-time errors instead of
+/// confusing render output.
+pub fn validate_template(nodes: &[Node]) -> Result<()> {
+    validate_extends_placement(nodes)?;
+    validate_block_names(nodes)
+}
+
+fn validate_extends_placement(nodes: &[Node]) -> Result<()> {
+    let mut seen_extends: Option<&str> = None;
+    let mut seen_content = false;
+
+    for node in nodes {
+        match node {
+            Node::Extends(parent) => {
+                if let Some(first) = seen_extends {
+                    return Err(DjangoRustError::TemplateError(format!(
+                        "Template has more than one {{% extends %}} tag: already extending \
+                         '{first}', found a second one extending '{parent}'"
+                    )));
+                }
+                if seen_content {
+                    return Err(DjangoRustError::TemplateError(format!(
+                        "{{% extends \"{parent}\" %}} must be the first tag in the template"
+                    )));
+                }
+                seen_extends = Some(parent);
+            }
+            Node::Text(text) if text.trim().is_empty() => {}
+            Node::Comment => {}
+            _ => seen_content = true,
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_block_names(nodes: &[Node]) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+
+    for node in nodes {
+        if let Node::Block { name, nodes: body, .. } = node {
+            if !seen.insert(name.as_str()) {
+                return Err(DjangoRustError::TemplateError(format!(
+                    "Block named '{name}' already exists at this nesting scope"
+                )));
+            }
+            validate_block_names(body)?;
+        } else {
+            for child in child_scopes(node) {
+                validate_block_names(child)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The nested node lists a container node renders its children from, so
+/// `validate_block_names` (and the renderer's macro-definition scan) can
+/// recurse into each as its own scope.
+pub(crate) fn child_scopes(node: &Node) -> Vec<&[Node]> {
+    match node {
+        Node::If {
+            true_nodes,
+            false_nodes,
+            ..
+        } => vec![true_nodes.as_slice(), false_nodes.as_slice()],
+        Node::For {
+            nodes, empty_nodes, ..
+        } => vec![nodes.as_slice(), empty_nodes.as_slice()],
+        Node::With { nodes, .. } => vec![nodes.as_slice()],
+        Node::Spaceless { nodes } => vec![nodes.as_slice()],
+        Node::Autoescape { nodes, .. } => vec![nodes.as_slice()],
+        Node::Macro { body, .. } => vec![body.as_slice()],
+        Node::ReactComponent { children, .. } => vec![children.as_slice()],
+        _ => vec![],
+    }
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let tok = self.tokens.get(self.pos);
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+}
+
+/// Maps every "middle" or "end" block-tag keyword to the tag that opens the
+/// block it belongs to, so `parse_until` can reject one appearing somewhere
+/// it doesn't belong (wrong block type, or no open block at all) instead of
+/// silently swallowing it as an unrecognized `CustomTag`.
+const BLOCK_END_KEYWORDS: &[(&str, &str)] = &[
+    ("else", "if"),
+    ("elif", "if"),
+    ("endif", "if"),
+    ("empty", "for"),
+    ("endfor", "for"),
+    ("endblock", "block"),
+    ("endwith", "with"),
+    ("endmacro", "macro"),
+    ("endspaceless", "spaceless"),
+    ("endautoescape", "autoescape"),
+    ("endmarkdown", "markdown"),
+];
+
+/// Parse nodes until a tag whose name is in `terminators` is encountered
+/// (without consuming it), or the stream runs out. Returns the parsed nodes
+/// plus the name of the terminating tag, if any.
+///
+/// Any `{% else %}`/`{% elif %}`/`{% end* %}`-style keyword that turns up
+/// but isn't one of `terminators` is a mismatched or orphaned block tag, not
+/// a normal custom tag, so it's rejected here rather than falling through to
+/// `parse_tag`'s catch-all `CustomTag` handling.
+fn parse_until(cursor: &mut Cursor, terminators: &[&str]) -> Result<(Vec<Node>, Option<String>)> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = cursor.peek() {
+        if let Token::Tag(name, _) = token {
+            if terminators.contains(&name.as_str()) {
+                return Ok((nodes, Some(name.clone())));
+            }
+
+            if let Some((_, opener)) = BLOCK_END_KEYWORDS.iter().find(|(kw, _)| *kw == name) {
+                return Err(DjangoRustError::TemplateError(match terminators.last() {
+                    Some(expected) => {
+                        format!("expected {{% {expected} %}}, found {{% {name} %}}")
+                    }
+                    None => format!("{{% {name} %}} without matching {{% {opener} %}}"),
+                }));
+            }
+        }
+
+        let token = cursor.next().unwrap();
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text.clone())),
+            Token::Comment => nodes.push(Node::Comment),
+            Token::Variable(content, escape_context) => {
+                nodes.push(parse_variable(content, *escape_context)?)
+            }
+            Token::JsxComponent {
+                name,
+                props,
+                children,
+                ..
+            } => nodes.push(parse_jsx(name, props, children)?),
+            Token::Tag(name, args) => nodes.push(parse_tag(cursor, name, args)?),
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+/// Split `{{ expr|filter:arg|filter2 }}` content into a base expression and
+/// an ordered filter pipeline, including the Jinja-style inline-if form
+/// `{{ a if cond else b }}`.
+fn parse_variable(content: &str, escape_context: EscapeContext) -> Result<Node> {
+    let mut parts = content.splitn(2, '|');
+    let expr = parts.next().unwrap_or("").trim();
+    let filter_chain = parts.next().unwrap_or("");
+    let filters = parse_filter_chain(filter_chain);
+
+    if let Some(inline_if) = parse_inline_if(expr) {
+        let (true_expr, condition, false_expr) = inline_if;
+        return Ok(Node::InlineIf {
+            true_expr,
+            condition,
+            false_expr,
+            filters,
+            escape_context,
+        });
+    }
+
+    if let Some(call) = parse_macro_call(expr) {
+        return Ok(call);
+    }
+
+    Ok(Node::Variable(expr.to_string(), filters, escape_context))
+}
+
+/// Recognize `{{ name(args, kw=val) }}` macro-call syntax. `name` may be
+/// dotted (`h.card`) for calls through an `{% import as %}` namespace.
+fn parse_macro_call(expr: &str) -> Option<Node> {
+    if !expr.ends_with(')') {
+        return None;
+    }
+    let open = expr.find('(')?;
+    let name = expr[..open].trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .next()
+            .map(|c| c.is_alphabetic() || c == '_')
+            .unwrap_or(false)
+        || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return None;
+    }
+
+    let inner = &expr[open + 1..expr.len() - 1];
+    let mut args = Vec::new();
+    let mut kwargs = Vec::new();
+    for part in split_top_level_commas(inner) {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        if let Some((key, value)) = part.split_once('=') {
+            let key = key.trim();
+            if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                kwargs.push((key.to_string(), value.trim().to_string()));
+                continue;
+            }
+        }
+        args.push(part.to_string());
+    }
+
+    Some(Node::MacroCall {
+        name: name.to_string(),
+        args,
+        kwargs,
+    })
+}
+
+/// Split on commas that aren't inside a quoted string, so
+/// `card("Hi, there", x)` splits into two arguments, not three.
+fn split_top_level_commas(s: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quote: Option<char> = None;
+
+    for c in s.chars() {
+        match in_quote {
+            Some(q) => {
+                current.push(c);
+                if c == q {
+                    in_quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                in_quote = Some(c);
+                current.push(c);
+            }
+            None if c == ',' => {
+                parts.push(std::mem::take(&mut current));
+            }
+            None => current.push(c),
+        }
+    }
+    if !current.is_empty() || !parts.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Recognize the Jinja2-style inline conditional `a if cond else b`.
+fn parse_inline_if(expr: &str) -> Option<(String, String, String)> {
+    let if_pos = find_keyword(expr, "if")?;
+    let else_pos = find_keyword(&expr[if_pos..], "else")? + if_pos;
+
+    let true_expr = expr[..if_pos].trim().to_string();
+    let condition = expr[if_pos + 2..else_pos].trim().to_string();
+    let false_expr = expr[else_pos + 4..].trim().to_string();
+
+    if true_expr.is_empty() || condition.is_empty() || false_expr.is_empty() {
+        return None;
+    }
+
+    Some((true_expr, condition, false_expr))
+}
+
+/// Find a whole-word occurrence of `keyword` in `s` (not part of a longer
+/// identifier), returning its byte offset.
+fn find_keyword(s: &str, keyword: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let klen = keyword.len();
+    let mut i = 0;
+    while i + klen <= s.len() {
+        if &s[i..i + klen] == keyword {
+            let before_ok = i == 0 || !is_ident_char(bytes[i - 1]);
+            let after_ok = i + klen == s.len() || !is_ident_char(bytes[i + klen]);
+            if before_ok && after_ok {
+                return Some(i);
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+fn is_ident_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Parse a `|`-separated filter chain into `(name, Some(arg))` pairs.
+fn parse_filter_chain(chain: &str) -> Vec<(String, Option<String>)> {
+    if chain.trim().is_empty() {
+        return Vec::new();
+    }
+
+    chain
+        .split('|')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|segment| match segment.split_once(':') {
+            Some((name, arg)) => (name.trim().to_string(), Some(arg.trim().to_string())),
+            None => (segment.to_string(), None),
+        })
+        .collect()
+}
+
+fn parse_jsx(
+    name: &str,
+    props: &[(String, String)],
+    children: &[Token],
+) -> Result<Node> {
+    let props = props.to_vec();
+
+    if name.starts_with("Rust") {
+        Ok(Node::RustComponent { name: name.to_string(), props })
+    } else {
+        let mut child_cursor = Cursor {
+            tokens: children,
+            pos: 0,
+        };
+        let (child_nodes, _) = parse_until(&mut child_cursor, &[])?;
+        Ok(Node::ReactComponent {
+            name: name.to_string(),
+            props,
+            children: child_nodes,
+        })
+    }
+}
+
+fn parse_tag(cursor: &mut Cursor, name: &str, args: &[String]) -> Result<Node> {
+    match name {
+        "if" => parse_if(cursor, args),
+        "for" => parse_for(cursor, args),
+        "block" => parse_block(cursor, args),
+        "with" => parse_with(cursor, args),
+        "spaceless" => {
+            let (nodes, terminator) = parse_until(cursor, &["endspaceless"])?;
+            if terminator.is_none() {
+                return Err(DjangoRustError::TemplateError(
+                    "Unclosed {% spaceless %} tag: expected {% endspaceless %}".to_string(),
+                ));
+            }
+            cursor.next(); // consume endspaceless
+            Ok(Node::Spaceless { nodes })
+        }
+        "markdown" => {
+            let class_prefix = args.first().map(|a| unquote(a));
+            let (nodes, terminator) = parse_until(cursor, &["endmarkdown"])?;
+            if terminator.is_none() {
+                return Err(DjangoRustError::TemplateError(
+                    "Unclosed {% markdown %} tag: expected {% endmarkdown %}".to_string(),
+                ));
+            }
+            cursor.next(); // consume endmarkdown
+            parse_markdown_block(nodes, class_prefix.as_deref())
+        }
+        "autoescape" => {
+            let enabled = args.first().map(|a| a.as_str() != "off").unwrap_or(true);
+            let (nodes, terminator) = parse_until(cursor, &["endautoescape"])?;
+            if terminator.is_none() {
+                return Err(DjangoRustError::TemplateError(
+                    "Unclosed {% autoescape %} tag: expected {% endautoescape %}".to_string(),
+                ));
+            }
+            cursor.next(); // consume endautoescape
+            Ok(Node::Autoescape { enabled, nodes })
+        }
+        "extends" => Ok(Node::Extends(unquote(args.join(" ").trim()))),
+        "include" => parse_include(args),
+        "csrf_token" => Ok(Node::CsrfToken),
+        "static" => Ok(Node::Static(unquote(args.join(" ").trim()))),
+        "widthratio" => {
+            if args.len() < 3 {
+                return Err(DjangoRustError::TemplateError(
+                    "{% widthratio %} requires 3 arguments: value max_value max_width".to_string(),
+                ));
+            }
+            Ok(Node::WidthRatio {
+                value: args[0].clone(),
+                max_value: args[1].clone(),
+                max_width: args[2].clone(),
+            })
+        }
+        "firstof" => Ok(Node::FirstOf { args: args.to_vec() }),
+        "templatetag" => Ok(Node::TemplateTag(
+            args.first().cloned().unwrap_or_default(),
+        )),
+        "cycle" => {
+            let mut values = args.to_vec();
+            let mut cycle_name = None;
+            if values.len() >= 2 && values[values.len() - 2] == "as" {
+                cycle_name = values.pop();
+                values.pop(); // remove "as"
+            }
+            Ok(Node::Cycle {
+                values,
+                name: cycle_name,
+            })
+        }
+        "now" => Ok(Node::Now(unquote(args.join(" ").trim()))),
+        "macro" => parse_macro(cursor, args),
+        "import" => parse_import(args),
+        "from" => parse_from_import(args),
+        _ => {
+            if let Some(end_name) = crate::environment::end_tag_for(name) {
+                let (nodes, _terminator) = parse_until(cursor, &[end_name.as_str()])?;
+                cursor.next(); // consume the end tag
+                return Ok(Node::CustomBlockTag {
+                    name: name.to_string(),
+                    args: args.to_vec(),
+                    nodes,
+                });
+            }
+            Ok(Node::CustomTag {
+                name: name.to_string(),
+                args: args.to_vec(),
+            })
+        }
+    }
+}
+
+fn parse_if(cursor: &mut Cursor, args: &[String]) -> Result<Node> {
+    let condition = args.join(" ");
+    let (true_nodes, terminator) = parse_until(cursor, &["else", "elif", "endif"])?;
+
+    let mut false_nodes = Vec::new();
+    match terminator.as_deref() {
+        Some("else") => {
+            cursor.next(); // consume else
+            let (nodes, _) = parse_until(cursor, &["endif"])?;
+            cursor.next(); // consume endif
+            false_nodes = nodes;
+        }
+        Some("elif") => {
+            // Re-dispatch as a nested if so `elif` chains behave like
+            // `else { if ... }`, without consuming the outer endif twice.
+            if let Some(Token::Tag(_, elif_args)) = cursor.peek().cloned() {
+                cursor.next(); // consume elif
+                false_nodes = vec![parse_if(cursor, &elif_args)?];
+            }
+        }
+        Some("endif") => {
+            cursor.next(); // consume endif
+        }
+        _ => {
+            return Err(DjangoRustError::TemplateError(
+                "Unclosed {% if %} tag: expected {% endif %}".to_string(),
+            ));
+        }
+    }
+
+    Ok(Node::If {
+        condition,
+        true_nodes,
+        false_nodes,
+    })
+}
+
+fn parse_for(cursor: &mut Cursor, args: &[String]) -> Result<Node> {
+    let reversed = args.last().map(|s| s == "reversed").unwrap_or(false);
+    let args = if reversed { &args[..args.len() - 1] } else { args };
+
+    let key_pos = args.iter().position(|a| a == "key");
+    let (args, key) = match key_pos {
+        Some(pos) => (&args[..pos], Some(args[pos + 1..].join(" "))),
+        None => (args, None),
+    };
+
+    let in_pos = args.iter().position(|a| a == "in").ok_or_else(|| {
+        DjangoRustError::TemplateError("{% for %} tag requires 'in': {% for x in items %}".to_string())
+    })?;
+
+    let var_names: Vec<String> = args[..in_pos]
+        .join(" ")
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let iterable = args[in_pos + 1..].join(" ");
+
+    let (nodes, terminator) = parse_until(cursor, &["empty", "endfor"])?;
+    let mut empty_nodes = Vec::new();
+    match terminator.as_deref() {
+        Some("empty") => {
+            cursor.next(); // consume empty
+            let (e_nodes, _) = parse_until(cursor, &["endfor"])?;
+            cursor.next(); // consume endfor
+            empty_nodes = e_nodes;
+        }
+        Some("endfor") => {
+            cursor.next(); // consume endfor
+        }
+        _ => {
+            return Err(DjangoRustError::TemplateError(
+                "Unclosed {% for %} tag: expected {% endfor %}".to_string(),
+            ));
+        }
+    }
+
+    Ok(Node::For {
+        var_names,
+        iterable,
+        reversed,
+        key,
+        nodes,
+        empty_nodes,
+    })
+}
+
+fn parse_block(cursor: &mut Cursor, args: &[String]) -> Result<Node> {
+    let name = args.first().cloned().unwrap_or_default();
+    let scoped = args.get(1).map(|s| s == "scoped").unwrap_or(false);
+    let (nodes, terminator) = parse_until(cursor, &["endblock"])?;
+    if terminator.is_none() {
+        return Err(DjangoRustError::TemplateError(format!(
+            "Unclosed {{% block {name} %}} tag: expected {{% endblock %}}"
+        )));
+    }
+    cursor.next(); // consume endblock
+    Ok(Node::Block { name, nodes, scoped })
+}
+
+fn parse_with(cursor: &mut Cursor, args: &[String]) -> Result<Node> {
+    // {% with a=1 b=2 %} ... {% endwith %}
+    let mut assignments = Vec::new();
+    for arg in args {
+        if let Some((key, value)) = arg.split_once('=') {
+            assignments.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let (nodes, terminator) = parse_until(cursor, &["endwith"])?;
+    if terminator.is_none() {
+        return Err(DjangoRustError::TemplateError(
+            "Unclosed {% with %} tag: expected {% endwith %}".to_string(),
+        ));
+    }
+    cursor.next(); // consume endwith
+    Ok(Node::With { assignments, nodes })
+}
+
+/// Turns a `{% markdown %}...{% endmarkdown %}` block's literal nodes into
+/// real renderable ones, at parse time: each literal `Text` run (the raw
+/// Markdown prose — `lexer::tokenize` already split it from any embedded
+/// `{{ variables }}`, which arrive here as ordinary `Node::Variable`s) is
+/// rendered to HTML via `markdown::render_markdown`, then re-tokenized and
+/// re-parsed so the result is a real `Text`/`Variable`/... node sequence
+/// rather than one opaque HTML blob — the same node kinds the renderer's
+/// VDOM diffing already knows how to compare.
+fn parse_markdown_block(nodes: Vec<Node>, class_prefix: Option<&str>) -> Result<Node> {
+    let mut rendered = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match node {
+            Node::Text(raw) => {
+                let html = crate::markdown::render_markdown(&raw, class_prefix);
+                let tokens = crate::lexer::tokenize(&html)?;
+                rendered.extend(parse(&tokens)?);
+            }
+            other => rendered.push(other),
+        }
+    }
+    Ok(Node::Markdown(rendered))
+}
+
+fn parse_include(args: &[String]) -> Result<Node> {
+    let template = args.first().map(|s| unquote(s)).unwrap_or_default();
+    let mut with_vars = Vec::new();
+    let mut only = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "only" {
+            only = true;
+        } else if args[i] == "with" {
+            // consumed implicitly; assignments follow as key=value tokens
+        } else if let Some((key, value)) = args[i].split_once('=') {
+            with_vars.push((key.trim().to_string(), value.trim().to_string()));
+        }
+        i += 1;
+    }
+
+    Ok(Node::Include {
+        template,
+        with_vars,
+        only,
+    })
+}
+
+fn parse_macro(cursor: &mut Cursor, args: &[String]) -> Result<Node> {
+    // Args were split on whitespace by the lexer, so `card(title, body)` may
+    // arrive as multiple pieces ("card(title," "body)") — rejoin before
+    // picking the signature apart.
+    let joined = args.join(" ");
+    let open = joined.find('(').ok_or_else(|| {
+        DjangoRustError::TemplateError(
+            "{% macro %} requires a signature: {% macro name(params) %}".to_string(),
+        )
+    })?;
+    let name = joined[..open].trim().to_string();
+    let close = joined.rfind(')').ok_or_else(|| {
+        DjangoRustError::TemplateError(format!(
+            "{{% macro {name} %}} is missing the closing ')' in its parameter list"
+        ))
+    })?;
+
+    let params = joined[open + 1..close]
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.split_once('=') {
+            Some((pname, default)) => (pname.trim().to_string(), Some(default.trim().to_string())),
+            None => (p.to_string(), None),
+        })
+        .collect();
+
+    let (body, terminator) = parse_until(cursor, &["endmacro"])?;
+    if terminator.is_none() {
+        return Err(DjangoRustError::TemplateError(format!(
+            "Unclosed {{% macro {name} %}} tag: expected {{% endmacro %}}"
+        )));
+    }
+    cursor.next(); // consume endmacro
+
+    Ok(Node::Macro { name, params, body })
+}
+
+fn parse_import(args: &[String]) -> Result<Node> {
+    let template = args.first().map(|s| unquote(s)).unwrap_or_default();
+    let alias = if args.get(1).map(|s| s.as_str()) == Some("as") {
+        args.get(2).cloned().unwrap_or_default()
+    } else {
+        String::new()
+    };
+    if template.is_empty() || alias.is_empty() {
+        return Err(DjangoRustError::TemplateError(
+            "{% import %} requires an alias: {% import \"file.html\" as name %}".to_string(),
+        ));
+    }
+    Ok(Node::Import { template, alias })
+}
+
+fn parse_from_import(args: &[String]) -> Result<Node> {
+    let joined = args.join(" ");
+    let import_pos = joined.find(" import ").ok_or_else(|| {
+        DjangoRustError::TemplateError(
+            "{% from %} requires 'import': {% from \"file.html\" import name %}".to_string(),
+        )
+    })?;
+    let template = unquote(joined[..import_pos].trim());
+
+    let mut rest = joined[import_pos + " import ".len()..].trim();
+    let with_context = if let Some(stripped) = rest.strip_suffix("with context") {
+        rest = stripped.trim();
+        true
+    } else {
+        false
+    };
+
+    let names = rest
+        .split(',')
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.split_once(" as ") {
+            Some((orig, alias)) => (orig.trim().to_string(), alias.trim().to_string()),
+            None => (part.to_string(), part.to_string()),
+        })
+        .collect();
+
+    Ok(Node::FromImport {
+        template,
+        names,
+        with_context,
+    })
+}
+
+fn unquote(s: &str) -> String {
+    s.trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+/// Walk a parsed node tree and collect the distinct variable names it
+/// references, for tooling that needs to know a template's inputs ahead of
+/// render time (e.g. JIT auto-serialization of a Python context).
+pub fn extract_template_variables(nodes: &[Node]) -> Vec<String> {
+    let mut vars = Vec::new();
+    collect_variables(nodes, &mut vars);
+    vars.sort();
+    vars.dedup();
+    vars
+}
+
+fn collect_variables(nodes: &[Node], vars: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Variable(name, ..) => vars.push(base_ident(name)),
+            Node::InlineIf {
+                true_expr,
+                condition,
+                false_expr,
+                ..
+            } => {
+                vars.push(base_ident(true_expr));
+                vars.push(base_ident(condition));
+                vars.push(base_ident(false_expr));
+            }
+            Node::If {
+                condition,
+                true_nodes,
+                false_nodes,
+            } => {
+                vars.push(base_ident(condition));
+                collect_variables(true_nodes, vars);
+                collect_variables(false_nodes, vars);
+            }
+            Node::For {
+                iterable,
+                nodes,
+                empty_nodes,
+                ..
+            } => {
+                vars.push(base_ident(iterable));
+                collect_variables(nodes, vars);
+                collect_variables(empty_nodes, vars);
+            }
+            Node::Block { nodes, .. }
+            | Node::Spaceless { nodes }
+            | Node::Autoescape { nodes, .. } => collect_variables(nodes, vars),
+            Node::With { nodes, .. } => collect_variables(nodes, vars),
+            Node::ReactComponent { children, .. } => collect_variables(children, vars),
+            _ => {}
+        }
+    }
+    vars.retain(|v| !v.is_empty());
+}
+
+/// The leading dotted-path segment of an expression, ignoring string/number
+/// literals (so `"x"` and `42` don't show up as fake variable names).
+fn base_ident(expr: &str) -> String {
+    let expr = expr.trim();
+    if expr.is_empty()
+        || expr.starts_with('"')
+        || expr.starts_with('\'')
+        || expr.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false)
+    {
+        return String::new();
+    }
+    expr.split(|c: char| c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .split('.')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    fn parse_src(src: &str) -> Vec<Node> {
+        let tokens = tokenize(src).unwrap();
+        parse(&tokens).unwrap()
+    }
+
+    #[test]
+    fn test_parse_plain_text() {
+        let nodes = parse_src("hello world");
+        assert_eq!(nodes, vec![Node::Text("hello world".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_variable_with_filters() {
+        let nodes = parse_src("{{ name|upper|default:\"x\" }}");
+        assert_eq!(
+            nodes,
+            vec![Node::Variable(
+                "name".to_string(),
+                vec![
+                    ("upper".to_string(), None),
+                    ("default".to_string(), Some("\"x\"".to_string()))
+                ],
+                EscapeContext::Text,
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_if_else() {
+        let nodes = parse_src("{% if cond %}yes{% else %}no{% endif %}");
+        match &nodes[0] {
+            Node::If {
+                true_nodes,
+                false_nodes,
+                ..
+            } => {
+                assert_eq!(true_nodes, &vec![Node::Text("yes".to_string())]);
+                assert_eq!(false_nodes, &vec![Node::Text("no".to_string())]);
+            }
+            other => panic!("expected If node, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_for_with_empty() {
+        let nodes = parse_src("{% for x in items %}{{ x }}{% empty %}none{% endfor %}");
+        match &nodes[0] {
+            Node::For {
+                var_names,
+                iterable,
+                empty_nodes,
+                ..
+            } => {
+                assert_eq!(var_names, &vec!["x".to_string()]);
+                assert_eq!(iterable, "items");
+                assert_eq!(empty_nodes, &vec![Node::Text("none".to_string())]);
+            }
+            other => panic!("expected For node, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_for_with_key() {
+        let nodes = parse_src("{% for item in items key item.id %}{{ item }}{% endfor %}");
+        match &nodes[0] {
+            Node::For {
+                var_names,
+                iterable,
+                key,
+                ..
+            } => {
+                assert_eq!(var_names, &vec!["item".to_string()]);
+                assert_eq!(iterable, "items");
+                assert_eq!(key.as_deref(), Some("item.id"));
+            }
+            other => panic!("expected For node, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_for_with_key_and_reversed() {
+        let nodes = parse_src("{% for item in items key item.id reversed %}{{ item }}{% endfor %}");
+        match &nodes[0] {
+            Node::For { reversed, key, .. } => {
+                assert!(*reversed);
+                assert_eq!(key.as_deref(), Some("item.id"));
+            }
+            other => panic!("expected For node, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let nodes = parse_src("{% block content %}hi{% endblock %}");
+        assert_eq!(
+            nodes[0],
+            Node::Block {
+                name: "content".to_string(),
+                nodes: vec![Node::Text("hi".to_string())],
+                scoped: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_scoped_block() {
+        let nodes = parse_src("{% block content scoped %}hi{% endblock %}");
+        match &nodes[0] {
+            Node::Block { name, scoped, .. } => {
+                assert_eq!(name, "content");
+                assert!(*scoped);
+            }
+            other => panic!("expected Block node, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_inline_if() {
+        let nodes = parse_src("{{ a if show else b }}");
+        assert_eq!(
+            nodes[0],
+            Node::InlineIf {
+                true_expr: "a".to_string(),
+                condition: "show".to_string(),
+                false_expr: "b".to_string(),
+                filters: vec![],
+                escape_context: EscapeContext::Text,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_propagates_attr_escape_context() {
+        let nodes = parse_src(r#"<a href="{{ url }}">link</a>"#);
+        let found = nodes.iter().find_map(|n| match n {
+            Node::Variable(name, _, ctx) if name == "url" => Some(*ctx),
+            _ => None,
+        });
+        assert_eq!(found, Some(EscapeContext::Url(crate::filters::UrlPart::BeforeQuery)));
+    }
+
+    #[test]
+    fn test_extract_template_variables() {
+        let nodes = parse_src("{{ user.name }} {% if active %}{{ count }}{% endif %}");
+        let vars = extract_template_variables(&nodes);
+        assert_eq!(vars, vec!["active".to_string(), "count".to_string(), "user".to_string()]);
+    }
+
+    #[test]
+    fn test_validate_template_rejects_duplicate_block_name_same_scope() {
+        let nodes = parse_src("{% block content %}a{% endblock %}{% block content %}b{% endblock %}");
+        let err = validate_template(&nodes).unwrap_err();
+        assert!(err.to_string().contains("content"));
+    }
+
+    #[test]
+    fn test_validate_template_allows_same_block_name_in_different_scopes() {
+        // One 'content' at top level, one nested inside an {% if %} — different
+        // scopes, so this is not a conflict.
+        let nodes = parse_src(
+            "{% block content %}a{% endblock %}{% if x %}{% block content %}b{% endblock %}{% endif %}",
+        );
+        assert!(validate_template(&nodes).is_ok());
+    }
+
+    #[test]
+    fn test_validate_template_rejects_double_extends() {
+        let nodes = parse_src("{% extends \"a.html\" %}{% extends \"b.html\" %}");
+        let err = validate_template(&nodes).unwrap_err();
+        assert!(err.to_string().contains("more than one"));
+    }
+
+    #[test]
+    fn test_validate_template_rejects_extends_after_content() {
+        let nodes = parse_src("hello {% extends \"a.html\" %}");
+        let err = validate_template(&nodes).unwrap_err();
+        assert!(err.to_string().contains("first tag"));
+    }
+
+    #[test]
+    fn test_validate_template_allows_extends_after_leading_whitespace_and_comment() {
+        let nodes = parse_src("  {# note #}{% extends \"a.html\" %}");
+        assert!(validate_template(&nodes).is_ok());
+    }
+
+    #[test]
+    fn test_parse_macro_signature() {
+        let nodes = parse_src("{% macro card(title, body=\"empty\") %}hi{% endmacro %}");
+        assert_eq!(
+            nodes[0],
+            Node::Macro {
+                name: "card".to_string(),
+                params: vec![
+                    ("title".to_string(), None),
+                    ("body".to_string(), Some("\"empty\"".to_string())),
+                ],
+                body: vec![Node::Text("hi".to_string())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_macro_call() {
+        let nodes = parse_src("{{ card(\"Hi\", body=\"x\") }}");
+        assert_eq!(
+            nodes[0],
+            Node::MacroCall {
+                name: "card".to_string(),
+                args: vec!["\"Hi\"".to_string()],
+                kwargs: vec![("body".to_string(), "\"x\"".to_string())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let nodes = parse_src("{% import \"helpers.html\" as h %}");
+        assert_eq!(
+            nodes[0],
+            Node::Import {
+                template: "helpers.html".to_string(),
+                alias: "h".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_from_import_with_alias_and_context() {
+        let nodes =
+            parse_src("{% from \"helpers.html\" import card, button as b with context %}");
+        assert_eq!(
+            nodes[0],
+            Node::FromImport {
+                template: "helpers.html".to_string(),
+                names: vec![
+                    ("card".to_string(), "card".to_string()),
+                    ("button".to_string(), "b".to_string()),
+                ],
+                with_context: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_macro_missing_signature_errors() {
+        let tokens = tokenize("{% macro card %}hi{% endmacro %}").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("signature"));
+    }
+
+    #[test]
+    fn test_parse_rejects_orphan_endif() {
+        let tokens = tokenize("hello {% endif %}").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("without matching"));
+        assert!(err.to_string().contains("{% if %}"));
+    }
+
+    #[test]
+    fn test_parse_rejects_else_outside_if() {
+        let tokens = tokenize("{% for x in y %}{% else %}{% endfor %}").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("expected {% endfor %}"));
+        assert!(err.to_string().contains("found {% else %}"));
+    }
+
+    #[test]
+    fn test_parse_rejects_mismatched_endfor_inside_if() {
+        let tokens = tokenize("{% if x %}{% endfor %}{% endif %}").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("expected {% endif %}"));
+        assert!(err.to_string().contains("found {% endfor %}"));
+    }
+
+    #[test]
+    fn test_parse_unclosed_with_tag_errors() {
+        let tokens = tokenize("{% with x=1 %}{{ x }}").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Unclosed {% with %}"));
+    }
+
+    #[test]
+    fn test_parse_unclosed_spaceless_tag_errors() {
+        let tokens = tokenize("{% spaceless %}<p>hi</p>").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Unclosed {% spaceless %}"));
+    }
+
+    #[test]
+    fn test_parse_unclosed_autoescape_tag_errors() {
+        let tokens = tokenize("{% autoescape off %}hi").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Unclosed {% autoescape %}"));
+    }
+
+    #[test]
+    fn test_parse_accepts_well_formed_nested_blocks() {
+        // Sanity check that the stray-keyword rejection above doesn't also
+        // reject ordinary, correctly nested block tags.
+        let nodes = parse_src(
+            "{% for x in y %}{% if x %}{{ x }}{% else %}none{% endif %}{% empty %}nothing{% endfor %}",
+        );
+        assert!(matches!(nodes[0], Node::For { .. }));
+    }
+
+    #[test]
+    fn test_parse_unclosed_markdown_tag_errors() {
+        let tokens = tokenize("{% markdown %}# hi").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Unclosed {% markdown %}"));
+    }
+
+    #[test]
+    fn test_parse_markdown_block_renders_prose_and_keeps_variables_live() {
+        let nodes = parse_src("{% markdown %}# Hi {{ name }}{% endmarkdown %}");
+        let Node::Markdown(rendered) = &nodes[0] else {
+            panic!("expected Node::Markdown, got {:?}", nodes[0]);
+        };
+        // The heading text (split from the embedded `{{ name }}` by the
+        // lexer before `parse_markdown_block` ever sees it) was rendered to
+        // HTML and re-tokenized into a real `Text` node...
+        assert!(matches!(&rendered[0], Node::Text(t) if t == "<h1 id=\"hi\">Hi</h1>\n"));
+        // ...while the embedded `{{ name }}` stayed a live `Variable` node
+        // rather than being baked into the rendered string.
+        assert!(rendered
+            .iter()
+            .any(|n| matches!(n, Node::Variable(path, _, _) if path == "name")));
+    }
+
+    #[test]
+    fn test_parse_markdown_block_honors_class_prefix_arg() {
+        let nodes = parse_src("{% markdown \"docs\" %}```\ncode\n```{% endmarkdown %}");
+        let Node::Markdown(rendered) = &nodes[0] else {
+            panic!("expected Node::Markdown, got {:?}", nodes[0]);
+        };
+        assert!(rendered
+            .iter()
+            .any(|n| matches!(n, Node::Text(t) if t.contains("class=\"docs-code-block\""))));
+    }
+}
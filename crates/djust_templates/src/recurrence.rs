@@ -0,0 +1,322 @@
+//! RFC 5545 `RRULE` expansion for the `recurrence` filter (see
+//! `filters::apply_filter`'s `"recurrence"` arm).
+//!
+//! There's no `{% recurrence %}` tag because `{% for %}` already has a
+//! perfectly good iteration protocol for a `Value::List` - this just needs
+//! to produce one. `{% for occ in start|recurrence:"FREQ=WEEKLY;COUNT=5" %}`
+//! resolves `start|recurrence:"..."` through the normal filter pipeline
+//! (`renderer::resolve_for_iteration` goes through `get_value`, which
+//! already applies filters), so each `occ` is a datetime `Value` the loop
+//! body can hand straight to `django_date_format`.
+//!
+//! Only `FREQ`, `INTERVAL`, `COUNT`, `UNTIL`, `BYDAY`, `BYMONTHDAY`, and
+//! `BYMONTH` are understood; anything else (`BYSETPOS`, `WKST`, ...) is
+//! silently ignored rather than rejected, matching how unknown Django
+//! template tags/filters degrade in this codebase.
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Weekday};
+
+/// Upper bound on generated occurrences (a larger `COUNT` is clamped to
+/// it) and on the `FREQ` windows stepped through, whatever `COUNT`/`UNTIL`
+/// say - otherwise an open-ended rule would generate forever.
+const SAFETY_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Default)]
+struct Rule {
+    freq: Option<Freq>,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<DateTime<FixedOffset>>,
+    by_day: Vec<Weekday>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    match code {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses a `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE` style rule string, with or
+/// without the `RRULE:` prefix. Returns `None` if no recognized `FREQ` is
+/// present - the caller falls back to treating the rule as a no-op.
+fn parse_rule(rrule: &str) -> Option<Rule> {
+    let mut rule = Rule {
+        interval: 1,
+        ..Default::default()
+    };
+
+    for part in rrule.trim_start_matches("RRULE:").split(';') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        let (key, value) = part.split_once('=')?;
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                rule.freq = Some(match value.to_ascii_uppercase().as_str() {
+                    "DAILY" => Freq::Daily,
+                    "WEEKLY" => Freq::Weekly,
+                    "MONTHLY" => Freq::Monthly,
+                    "YEARLY" => Freq::Yearly,
+                    _ => return None,
+                });
+            }
+            "INTERVAL" => rule.interval = value.parse().ok().filter(|n| *n > 0)?,
+            "COUNT" => rule.count = value.parse().ok(),
+            "UNTIL" => {
+                // RFC 5545's compact form, e.g. "20241231T235959Z".
+                let naive = chrono::NaiveDateTime::parse_from_str(
+                    value.trim_end_matches('Z'),
+                    "%Y%m%dT%H%M%S",
+                )
+                .ok()?;
+                rule.until = Some(FixedOffset::east_opt(0)?.from_utc_datetime(&naive));
+            }
+            "BYDAY" => rule.by_day = value.split(',').filter_map(parse_weekday).collect(),
+            "BYMONTHDAY" => {
+                rule.by_month_day = value.split(',').filter_map(|s| s.parse().ok()).collect();
+            }
+            "BYMONTH" => rule.by_month = value.split(',').filter_map(|s| s.parse().ok()).collect(),
+            _ => {}
+        }
+    }
+
+    rule.freq?;
+    Some(rule)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        _ => 30,
+    }
+}
+
+/// All candidate dates within the period containing `window_start` - the
+/// full week/month/year, not just `window_start` itself - so `BY*` filters
+/// can pick out whichever of those dates actually qualify.
+fn window_dates(window_start: DateTime<FixedOffset>, freq: Freq) -> Vec<NaiveDate> {
+    let base = window_start.date_naive();
+    match freq {
+        Freq::Daily => vec![base],
+        Freq::Weekly => {
+            let monday = base - Duration::days(base.weekday().num_days_from_monday() as i64);
+            (0..7).map(|i| monday + Duration::days(i)).collect()
+        }
+        Freq::Monthly => {
+            let days = days_in_month(base.year(), base.month());
+            (1..=days)
+                .filter_map(|d| NaiveDate::from_ymd_opt(base.year(), base.month(), d))
+                .collect()
+        }
+        Freq::Yearly => (1..=12)
+            .flat_map(|month| {
+                let days = days_in_month(base.year(), month);
+                (1..=days).filter_map(move |d| NaiveDate::from_ymd_opt(base.year(), month, d))
+            })
+            .collect(),
+    }
+}
+
+/// Whether `date` satisfies every `BY*` constraint present on `rule` (rules
+/// with no `BY*` constraints accept anything).
+fn matches_by_filters(date: NaiveDate, rule: &Rule) -> bool {
+    if !rule.by_day.is_empty() && !rule.by_day.contains(&date.weekday()) {
+        return false;
+    }
+    if !rule.by_month.is_empty() && !rule.by_month.contains(&date.month()) {
+        return false;
+    }
+    if !rule.by_month_day.is_empty() {
+        let days = days_in_month(date.year(), date.month()) as i32;
+        let day = date.day() as i32;
+        // A negative BYMONTHDAY counts back from the end of the month
+        // (RFC 5545 §3.3.10), e.g. -1 is the last day of the month.
+        let hit = rule
+            .by_month_day
+            .iter()
+            .any(|&n| day == if n > 0 { n } else { days + n + 1 });
+        if !hit {
+            return false;
+        }
+    }
+    true
+}
+
+/// Steps `start` forward by `steps` whole `FREQ` units, clamping the day of
+/// month for `Monthly`/`Yearly` steps that would otherwise overflow (e.g.
+/// Jan 31 + 1 month lands on Feb 28/29, not an invalid Feb 31).
+fn step_window(start: DateTime<FixedOffset>, freq: Freq, steps: i64) -> DateTime<FixedOffset> {
+    match freq {
+        Freq::Daily => start + Duration::days(steps),
+        Freq::Weekly => start + Duration::weeks(steps),
+        Freq::Monthly => add_months(start, steps as i32),
+        Freq::Yearly => add_months(start, steps as i32 * 12),
+    }
+}
+
+fn add_months(dt: DateTime<FixedOffset>, months: i32) -> DateTime<FixedOffset> {
+    let total = dt.year() * 12 + dt.month0() as i32 + months;
+    let year = total.div_euclid(12);
+    let month = total.rem_euclid(12) as u32 + 1;
+    let day = dt.day().min(days_in_month(year, month));
+    let naive = NaiveDate::from_ymd_opt(year, month, day)
+        .expect("clamped day is always valid for its month")
+        .and_time(dt.time());
+    dt.offset().from_local_datetime(&naive).single().unwrap_or(dt)
+}
+
+/// Expands `rrule` into the occurrences it produces starting from `start`
+/// (always included first, provided it satisfies the rule's own `BY*`
+/// filters). Returns just `[start]` if `rrule` has no recognizable `FREQ`.
+pub(crate) fn expand_rrule(
+    start: DateTime<FixedOffset>,
+    rrule: &str,
+) -> Vec<DateTime<FixedOffset>> {
+    let Some(rule) = parse_rule(rrule) else {
+        return vec![start];
+    };
+    let freq = rule.freq.expect("parse_rule only returns Some with a FREQ set");
+    let has_by_filters = !rule.by_day.is_empty() || !rule.by_month_day.is_empty() || !rule.by_month.is_empty();
+    let limit = rule.count.unwrap_or(SAFETY_LIMIT).min(SAFETY_LIMIT);
+
+    let mut occurrences = Vec::new();
+    let mut step = 0i64;
+
+    'windows: while step <= SAFETY_LIMIT as i64 {
+        let window_start = step_window(start, freq, rule.interval * step);
+        let mut dates = if has_by_filters {
+            window_dates(window_start, freq)
+                .into_iter()
+                .filter(|d| matches_by_filters(*d, &rule))
+                .collect::<Vec<_>>()
+        } else {
+            vec![window_start.date_naive()]
+        };
+        dates.sort();
+
+        for date in dates.drain(..) {
+            let Some(candidate) = start
+                .offset()
+                .from_local_datetime(&date.and_time(start.time()))
+                .single()
+            else {
+                continue;
+            };
+            if candidate < start {
+                continue;
+            }
+            if let Some(until) = rule.until {
+                if candidate > until {
+                    break 'windows;
+                }
+            }
+            occurrences.push(candidate);
+            if occurrences.len() >= limit {
+                break 'windows;
+            }
+        }
+
+        step += 1;
+    }
+
+    occurrences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(s: &str) -> DateTime<FixedOffset> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_daily_interval_with_count() {
+        let occurrences = expand_rrule(dt("2024-01-01T09:00:00+00:00"), "FREQ=DAILY;INTERVAL=2;COUNT=3");
+        let formatted: Vec<String> = occurrences.iter().map(|d| d.to_rfc3339()).collect();
+        assert_eq!(
+            formatted,
+            vec![
+                "2024-01-01T09:00:00+00:00",
+                "2024-01-03T09:00:00+00:00",
+                "2024-01-05T09:00:00+00:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weekly_byday_expands_within_each_week() {
+        // Monday start, weekly on Mon/Wed/Fri for two weeks = 6 occurrences.
+        let occurrences = expand_rrule(
+            dt("2024-01-01T09:00:00+00:00"),
+            "FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=6",
+        );
+        let days: Vec<String> = occurrences
+            .iter()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                "2024-01-01",
+                "2024-01-03",
+                "2024-01-05",
+                "2024-01-08",
+                "2024-01-10",
+                "2024-01-12",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_monthly_bymonthday_negative_counts_from_month_end() {
+        let occurrences = expand_rrule(dt("2024-01-31T09:00:00+00:00"), "FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=3");
+        let days: Vec<String> = occurrences
+            .iter()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .collect();
+        assert_eq!(days, vec!["2024-01-31", "2024-02-29", "2024-03-31"]);
+    }
+
+    #[test]
+    fn test_until_stops_before_exceeding_bound() {
+        let occurrences = expand_rrule(
+            dt("2024-01-01T09:00:00+00:00"),
+            "FREQ=DAILY;UNTIL=20240103T120000Z",
+        );
+        assert_eq!(occurrences.len(), 3);
+    }
+
+    #[test]
+    fn test_unrecognized_freq_falls_back_to_start_only() {
+        let occurrences = expand_rrule(dt("2024-01-01T09:00:00+00:00"), "FREQ=HOURLY");
+        assert_eq!(occurrences, vec![dt("2024-01-01T09:00:00+00:00")]);
+    }
+
+    #[test]
+    fn test_open_ended_rule_is_capped_by_safety_limit() {
+        let occurrences = expand_rrule(dt("2024-01-01T00:00:00+00:00"), "FREQ=DAILY");
+        assert_eq!(occurrences.len(), SAFETY_LIMIT);
+    }
+}
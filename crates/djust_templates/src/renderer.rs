@@ -2,12 +2,14 @@
 
 use crate::filters;
 use crate::inheritance::TemplateLoader;
-use crate::parser::Node;
+use crate::parser::{self, Node};
+use chrono::{Datelike, Offset};
 use djust_components::Component;
 use djust_core::{Context, DjangoRustError, Result, Value};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::sync::Mutex;
 
 /// Regex for {% spaceless %}: matches whitespace between > and <
@@ -34,19 +36,257 @@ pub fn render_nodes(nodes: &[Node], context: &Context) -> Result<String> {
     render_nodes_with_loader(nodes, context, None::<&NoOpLoader>)
 }
 
+/// Resolve `{% extends %}`/`{% block %}` inheritance on a raw node list and
+/// render the result - the free-function counterpart to
+/// [`crate::Template::render_with_loader`] for callers that already have a
+/// parsed `nodes` list instead of a [`crate::Template`]. When `nodes`
+/// contains no `{% extends %}`, this is equivalent to
+/// `render_nodes_with_loader`.
+pub fn render_template_with_loader<L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: &L,
+) -> Result<String> {
+    let uses_extends = nodes.iter().any(|node| matches!(node, Node::Extends(_)));
+
+    if uses_extends {
+        let chain = crate::inheritance::build_inheritance_chain(nodes.to_vec(), loader, 10)?;
+        let root_nodes = chain.get_root_nodes();
+        let final_nodes = chain.apply_block_overrides(root_nodes);
+        render_nodes_with_inheritance(&final_nodes, context, Some(loader), &chain.block_stacks)
+    } else {
+        render_nodes_with_loader(nodes, context, Some(loader))
+    }
+}
+
 /// Render nodes with an optional template loader for {% include %} support
 pub fn render_nodes_with_loader<L: TemplateLoader>(
     nodes: &[Node],
     context: &Context,
     loader: Option<&L>,
 ) -> Result<String> {
-    let mut output = String::new();
+    let mut buf = Vec::new();
+    render_nodes_to_writer(nodes, context, loader, &mut buf)?;
+    bytes_to_string(buf)
+}
 
+/// Render nodes straight into an arbitrary sink instead of building one
+/// `String` up front. Mirrors the Handlebars `Output`/`StringOutput` split:
+/// the tree walk appends directly to `out`, so a large inheritance/include
+/// tree can stream to a socket or file with bounded buffering instead of
+/// materializing the whole page in memory first.
+///
+/// Internally this compiles `nodes` to a flat `compiler::Program` and
+/// replays that, so existing callers keep working unchanged while paying
+/// the tree-walk cost only once per call. A caller rendering the same
+/// `nodes` repeatedly should call `compiler::compile` once and reuse the
+/// `Program` directly with `compiler::render_program` instead.
+pub fn render_nodes_to_writer<W: Write, L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: Option<&L>,
+    out: &mut W,
+) -> Result<()> {
+    let program = crate::compiler::compile(nodes);
+    crate::compiler::render_program(&program, context, loader, out)
+}
+
+/// Render nodes that came out of an inheritance chain, so `{{ block.super }}`
+/// can resolve against the chain's per-block ancestor stacks.
+pub(crate) fn render_nodes_with_inheritance<L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: Option<&L>,
+    block_stacks: &HashMap<String, Vec<Vec<Node>>>,
+) -> Result<String> {
+    let mut buf = Vec::new();
+    render_nodes_with_inheritance_to(nodes, context, loader, block_stacks, &mut buf)?;
+    bytes_to_string(buf)
+}
+
+/// Writer-sink counterpart to `render_nodes_with_inheritance`, for
+/// `Template::render_to` on a template that uses `{% extends %}`.
+pub(crate) fn render_nodes_with_inheritance_to<W: Write, L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: Option<&L>,
+    block_stacks: &HashMap<String, Vec<Vec<Node>>>,
+    out: &mut W,
+) -> Result<()> {
+    let mut macros = HashMap::new();
+    collect_macro_scope(nodes, loader, &mut macros)?;
+    write_nodes(
+        nodes,
+        context,
+        loader,
+        RenderScope {
+            stacks: Some(block_stacks),
+            active: &[],
+            macros: Some(&macros),
+        },
+        out,
+    )
+}
+
+/// Render nodes straight into any `std::fmt::Write` sink (e.g. a `String`,
+/// or another formatter-backed buffer) rather than an `io::Write` one,
+/// without detouring through a `Vec<u8>` and a UTF-8 validation pass.
+/// Reuses the same `render_nodes_to_writer` tree walk via [`FmtAsIoWriter`].
+pub fn render_nodes_to_fmt_writer<W: std::fmt::Write, L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: Option<&L>,
+    out: &mut W,
+) -> Result<()> {
+    render_nodes_to_writer(nodes, context, loader, &mut FmtAsIoWriter(out))
+}
+
+/// Writer-sink counterpart to `render_nodes_to_fmt_writer`, for
+/// `Template::render_to_fmt` on a template that uses `{% extends %}`.
+pub(crate) fn render_nodes_with_inheritance_to_fmt<W: std::fmt::Write, L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: Option<&L>,
+    block_stacks: &HashMap<String, Vec<Vec<Node>>>,
+    out: &mut W,
+) -> Result<()> {
+    render_nodes_with_inheritance_to(nodes, context, loader, block_stacks, &mut FmtAsIoWriter(out))
+}
+
+/// Adapts a `std::fmt::Write` sink into `std::io::Write` so the renderer's
+/// writer-based code path can target either kind of sink. Every write the
+/// renderer makes is a single complete UTF-8 `&str` turned into bytes (it
+/// never splits a string across two `write` calls), so `buf` is always
+/// valid UTF-8 on its own.
+struct FmtAsIoWriter<'a, W: std::fmt::Write>(&'a mut W);
+
+impl<W: std::fmt::Write> Write for FmtAsIoWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let s = std::str::from_utf8(buf)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.0
+            .write_str(s)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `render_nodes_to_writer`/`render_nodes_with_inheritance_to` build into a
+/// plain `Vec<u8>` sink for the `String`-returning entry points; template
+/// output is always UTF-8 since every source node is, so this only fails if
+/// a filter or custom tag somehow produced invalid bytes.
+fn bytes_to_string(buf: Vec<u8>) -> Result<String> {
+    String::from_utf8(buf).map_err(|e| {
+        DjangoRustError::TemplateError(format!("Rendered output was not valid UTF-8: {e}"))
+    })
+}
+
+/// Ancestor bodies still available to `{{ block.super }}` for whichever
+/// block is nearest on the call stack. `stacks` is the full per-block-name
+/// stack built from the inheritance chain (unchanged for the whole render);
+/// `active` is what's left for the block currently rendering — root-most
+/// ancestor first, immediate parent last — and is recomputed whenever a
+/// `Node::Block` is entered, then shrunk by one each time `block.super`
+/// resolves, so nesting drills through arbitrarily many inheritance levels.
+/// `macros` is the template's full macro scope (own `{% macro %}` defs plus
+/// anything pulled in via `{% import %}`/`{% from ... import ... %}`),
+/// collected once up front and carried unchanged through the whole render —
+/// macro calls aren't scoped to where they textually appear.
+#[derive(Clone, Copy, Default)]
+struct RenderScope<'a> {
+    stacks: Option<&'a HashMap<String, Vec<Vec<Node>>>>,
+    active: &'a [Vec<Node>],
+    macros: Option<&'a HashMap<String, MacroDef>>,
+}
+
+/// A `{% macro %}` definition, resolved once into `RenderScope::macros` so a
+/// call site never has to care whether it's in the same template, textually
+/// before or after the definition, or reached the macro via `{% import %}`.
+#[derive(Clone)]
+pub(crate) struct MacroDef {
+    params: Vec<(String, Option<String>)>,
+    body: Vec<Node>,
+}
+
+/// Walk `nodes` (recursing into every container scope via `child_scopes`)
+/// and collect its `{% macro %}` definitions, `{% import ... as ns %}`
+/// namespaces (bound as `ns.name`), and `{% from ... import ... %}`
+/// bindings into one flat scope. Imports are resolved eagerly through
+/// `loader` so a macro call never has to touch the loader at render time.
+pub(crate) fn collect_macro_scope<L: TemplateLoader>(
+    nodes: &[Node],
+    loader: Option<&L>,
+    macros: &mut HashMap<String, MacroDef>,
+) -> Result<()> {
     for node in nodes {
-        output.push_str(&render_node_with_loader(node, context, loader)?);
+        match node {
+            Node::Macro { name, params, body } => {
+                macros.insert(
+                    name.clone(),
+                    MacroDef {
+                        params: params.clone(),
+                        body: body.clone(),
+                    },
+                );
+            }
+            Node::Import { template, alias } => {
+                let imported = load_for_import(template, loader)?;
+                let mut imported_macros = HashMap::new();
+                collect_macro_scope(&imported, loader, &mut imported_macros)?;
+                for (macro_name, def) in imported_macros {
+                    macros.insert(format!("{alias}.{macro_name}"), def);
+                }
+            }
+            Node::FromImport {
+                template, names, ..
+            } => {
+                let imported = load_for_import(template, loader)?;
+                let mut imported_macros = HashMap::new();
+                collect_macro_scope(&imported, loader, &mut imported_macros)?;
+                for (orig, alias) in names {
+                    if let Some(def) = imported_macros.get(orig) {
+                        macros.insert(alias.clone(), def.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for scope in parser::child_scopes(node) {
+            collect_macro_scope(scope, loader, macros)?;
+        }
     }
 
-    Ok(output)
+    Ok(())
+}
+
+/// Load the template named by an `{% import %}`/`{% from %}` tag, requiring
+/// a loader the same way `{% include %}` does.
+fn load_for_import<L: TemplateLoader>(template: &str, loader: Option<&L>) -> Result<Vec<Node>> {
+    let loader = loader.ok_or_else(|| {
+        DjangoRustError::TemplateError(format!(
+            "\"{template}\" requires a template loader to import macros from"
+        ))
+    })?;
+    let name = template.trim_matches(|c| c == '"' || c == '\'');
+    loader.load_template(name)
+}
+
+fn write_nodes<W: Write, L: TemplateLoader>(
+    nodes: &[Node],
+    context: &Context,
+    loader: Option<&L>,
+    supers: RenderScope<'_>,
+    out: &mut W,
+) -> Result<()> {
+    for node in nodes {
+        write_node(node, context, loader, supers, out)?;
+    }
+    Ok(())
 }
 
 /// No-op loader for when no loader is provided
@@ -60,53 +300,269 @@ impl TemplateLoader for NoOpLoader {
     }
 }
 
-fn render_node_with_loader<L: TemplateLoader>(
-    node: &Node,
+/// Resolve and write a `{{ var|filter:arg }}` expression: the shared body
+/// of `Node::Variable`'s render arm, pulled out so the flat instruction
+/// compiler (see `compiler::Instruction::PushVar`) can replay it without
+/// going through a `Node` at all.
+pub(crate) fn render_variable<W: Write>(
+    var_name: &str,
+    filter_specs: &[(String, Option<String>)],
+    escape_context: filters::EscapeContext,
     context: &Context,
-    loader: Option<&L>,
-) -> Result<String> {
-    match node {
-        Node::Text(text) => Ok(text.clone()),
+    out: &mut W,
+) -> Result<()> {
+    // Goes through the `condition` module's arithmetic-aware evaluator
+    // rather than a raw `context.get` so `{{ price * qty }}` resolves the
+    // `+ - * / %` subsystem shared with `{% if %}`; a bare dotted path with
+    // no operators behaves exactly as before (falls back to a plain lookup).
+    let mut value = crate::condition::evaluate_value(var_name, context)?;
+
+    // Apply filters. `|noescape` is handled here rather than inside
+    // `apply_filter` since it needs `escape_context`, which filters don't
+    // otherwise see - it wraps the value in the `Safe*` variant matching
+    // the destination context so the check below treats it as verbatim.
+    for (filter_name, arg) in filter_specs {
+        if filter_name == "noescape" {
+            value = filters::wrap_safe_for_context(value.to_string(), escape_context);
+            continue;
+        }
+        let resolved_arg = eval_arg(arg.as_deref(), context)?;
+        value = filters::apply_filter(filter_name, &value, resolved_arg.as_deref(), context)?;
+    }
+
+    let text = value.to_string();
+
+    // Auto-escape unless:
+    // 1. |safe is the last filter (matches Django behavior)
+    // 2. The variable is marked safe in the context (like Django's SafeData)
+    // 3. A filter that produces already-escaped/safe output is in the chain
+    // 4. `{{{ }}}` (Handlebars-style triple-brace) was used, as a terser
+    //    spelling of `|safe`
+    // 5. We're inside `{% autoescape off %}`
+    // 6. `value` carries a typed `Safe*` variant matching `escape_context`
+    //    (e.g. `|escapejs`/`|urlencode`/`|json_script`, or `|noescape`) - a
+    //    `SafeHtml` landing in a JS context still gets JS-escaped below.
+    let safe_output_filters = [
+        "safe",
+        "safeseq",
+        "force_escape",
+        "urlize",
+        "urlizetrunc",
+        "unordered_list",
+        "markdown",
+    ];
+    let is_safe = filter_specs
+        .iter()
+        .any(|(name, _)| safe_output_filters.contains(&name.as_str()))
+        || context.is_safe(var_name)
+        || escape_context == filters::EscapeContext::Raw
+        || !context.autoescape()
+        || filters::value_matches_context(&value, escape_context);
+    if is_safe {
+        Ok(out.write_all(text.as_bytes())?)
+    } else {
+        Ok(out.write_all(filters::escape_for_context(&text, escape_context).as_bytes())?)
+    }
+}
 
-        Node::Variable(var_name, filter_specs) => {
-            let mut value = context.get(var_name).cloned().unwrap_or(Value::Null);
+/// Resolves a `{% for %}`'s iterable into the ordered `(original_index,
+/// item)` pairs to loop over, honoring `reversed`. `None` means the
+/// iterable isn't a supported collection (or `var_names` doesn't match an
+/// object's 2-tuple shape), which Django treats the same as an empty loop -
+/// the `{% empty %}` block should render instead. Shared by `Node::For`'s
+/// render arm and the flat instruction compiler (see
+/// `compiler::Instruction::ForBegin`) so both stay in lockstep.
+pub(crate) fn resolve_for_iteration(
+    context: &Context,
+    var_names: &[String],
+    iterable: &str,
+    reversed: bool,
+) -> Option<Vec<(usize, Value)>> {
+    // Goes through `get_value` rather than a raw `context.get` so a
+    // filtered iterable like `{% for occ in start|recurrence:"..." %}`
+    // resolves the same way a `{{ }}` expression would.
+    let iterable_value = get_value(iterable, context).unwrap_or(Value::Null);
+
+    // `{% for key, value in mydict %}` exposes each entry's key as the
+    // first loop variable and its value as the second. `HashMap` has no
+    // inherent order, so iterate keys sorted for deterministic output
+    // instead of HashMap's arbitrary order.
+    let items = match iterable_value {
+        Value::List(items) => items,
+        Value::Object(map) if var_names.len() == 2 => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+                .into_iter()
+                .map(|(key, value)| Value::List(vec![Value::String(key), value]))
+                .collect()
+        }
+        _ => return None,
+    };
+
+    Some(if reversed {
+        items.into_iter().enumerate().rev().collect()
+    } else {
+        items.into_iter().enumerate().collect()
+    })
+}
 
-            // Apply filters
-            for (filter_name, arg) in filter_specs {
-                value = filters::apply_filter(filter_name, &value, arg.as_deref())?;
+/// Binds one `{% for %}` iteration's `forloop` context object and loop
+/// variable(s) into `ctx`. Shared the same way as `resolve_for_iteration`.
+pub(crate) fn bind_for_iteration(
+    ctx: &mut Context,
+    var_names: &[String],
+    iterable: &str,
+    counter: usize,
+    total: usize,
+    index: usize,
+    item: Value,
+    saved_forloop: &Option<Value>,
+) {
+    // Set __djust_cycle_counter for {% cycle %} tag support
+    ctx.set(
+        "__djust_cycle_counter".to_string(),
+        Value::Integer(counter as i64),
+    );
+
+    // Django's `forloop` context object: https://docs.djangoproject.com/en/stable/ref/templates/builtins/#for
+    let mut forloop = HashMap::new();
+    forloop.insert("counter".to_string(), Value::Integer(counter as i64 + 1));
+    forloop.insert("counter0".to_string(), Value::Integer(counter as i64));
+    forloop.insert(
+        "revcounter".to_string(),
+        Value::Integer((total - counter) as i64),
+    );
+    forloop.insert(
+        "revcounter0".to_string(),
+        Value::Integer((total - counter - 1) as i64),
+    );
+    forloop.insert("first".to_string(), Value::Bool(counter == 0));
+    forloop.insert("last".to_string(), Value::Bool(counter == total - 1));
+    forloop.insert(
+        "parentloop".to_string(),
+        saved_forloop.clone().unwrap_or(Value::Null),
+    );
+    ctx.set("forloop".to_string(), Value::Object(forloop));
+
+    // Handle tuple unpacking: {% for a, b in items %}
+    if var_names.len() == 1 {
+        // Single variable: {% for item in items %}
+        ctx.set(var_names[0].clone(), item);
+        // Track loop mapping for safe key resolution
+        ctx.set_loop_mapping(var_names[0].clone(), iterable.to_string(), index);
+    } else {
+        // Multiple variables: {% for key, value in items %}
+        // Expect item to be a list/tuple
+        match &item {
+            Value::List(tuple_items) => {
+                // Unpack tuple items into separate variables
+                for (i, var_name) in var_names.iter().enumerate() {
+                    if i < tuple_items.len() {
+                        ctx.set(var_name.clone(), tuple_items[i].clone());
+                    } else {
+                        // If tuple has fewer items than var names, set to Null
+                        ctx.set(var_name.clone(), Value::Null);
+                    }
+                }
+            }
+            _ => {
+                // If item is not a list, set all vars to Null except first
+                ctx.set(var_names[0].clone(), item.clone());
+                for var_name in &var_names[1..] {
+                    ctx.set(var_name.clone(), Value::Null);
+                }
             }
+        }
+    }
+}
 
-            let text = value.to_string();
+/// Writes a `{% for %}` loop iteration's `<!--dj-for:KEY-->` anchor, if the
+/// loop declared a `key` expression (`{% for item in items key item.id %}`).
+/// Shared by the tree-walker (`write_node`'s `Node::For` arm) and the flat
+/// instruction compiler (`compiler::render_program`'s `ForBegin`/`ForEnd`
+/// handling) so both execution paths emit identical anchors.
+pub(crate) fn write_for_key_marker<W: Write>(
+    key: Option<&str>,
+    ctx: &Context,
+    out: &mut W,
+) -> Result<()> {
+    if let Some(key_expr) = key {
+        // Per-iteration anchor so a keyed diff can match this item across
+        // re-renders (reorders/deletes) instead of re-rendering the whole
+        // list - mirrors Handlebars' `helper_each` keyed moves.
+        let key_value = crate::condition::evaluate_value(key_expr, ctx).unwrap_or(Value::Null);
+        write!(out, "<!--dj-for:{key_value}-->")?;
+    }
+    Ok(())
+}
 
-            // Auto-escape unless:
-            // 1. |safe is the last filter (matches Django behavior)
-            // 2. The variable is marked safe in the context (like Django's SafeData)
-            // 3. A filter that produces already-escaped/safe output is in the chain
-            let safe_output_filters = [
-                "safe",
-                "safeseq",
-                "force_escape",
-                "json_script",
-                "urlize",
-                "urlizetrunc",
-                "unordered_list",
-            ];
-            let is_safe = filter_specs
-                .iter()
-                .any(|(name, _)| safe_output_filters.contains(&name.as_str()))
-                || context.is_safe(var_name);
-            if is_safe {
-                Ok(text)
-            } else {
-                Ok(filters::html_escape(&text))
+/// Renders a single node through the full recursive tree-walker, given a
+/// macro scope already collected for the whole template. Used by the flat
+/// instruction compiler's `RenderNode` fallback for constructs it doesn't
+/// flatten (inheritance, includes, macros, components, ...) - see
+/// `compiler::Instruction::RenderNode`.
+pub(crate) fn render_node_standalone<W: Write, L: TemplateLoader>(
+    node: &Node,
+    context: &Context,
+    loader: Option<&L>,
+    macros: &HashMap<String, MacroDef>,
+    out: &mut W,
+) -> Result<()> {
+    write_node(
+        node,
+        context,
+        loader,
+        RenderScope {
+            stacks: None,
+            active: &[],
+            macros: Some(macros),
+        },
+        out,
+    )
+}
+
+fn write_node<W: Write, L: TemplateLoader>(
+    node: &Node,
+    context: &Context,
+    loader: Option<&L>,
+    supers: RenderScope<'_>,
+    out: &mut W,
+) -> Result<()> {
+    match node {
+        Node::Text(text) => Ok(out.write_all(text.as_bytes())?),
+
+        Node::Variable(var_name, _filter_specs, _escape_context) if var_name == "block.super" => {
+            // Already-rendered trusted template content (the overridden
+            // parent block body), not user data — skip filters/escaping and
+            // just render it, shrinking `active` so further `block.super`
+            // calls inside it keep drilling toward the root.
+            match supers.active.split_last() {
+                Some((body, rest)) => write_nodes(
+                    body,
+                    context,
+                    loader,
+                    RenderScope {
+                        stacks: supers.stacks,
+                        active: rest,
+                        macros: supers.macros,
+                    },
+                    out,
+                ),
+                None => Ok(()),
             }
         }
 
+        Node::Variable(var_name, filter_specs, escape_context) => {
+            render_variable(var_name, filter_specs, *escape_context, context, out)
+        }
+
         Node::InlineIf {
             true_expr,
             condition,
             false_expr,
             filters,
+            escape_context,
         } => {
             let expr = if evaluate_condition(condition, context)? {
                 true_expr.as_str()
@@ -117,22 +573,28 @@ fn render_node_with_loader<L: TemplateLoader>(
             let mut value = get_value(expr, context)?;
 
             for (filter_name, arg) in filters {
-                value = filters::apply_filter(filter_name, &value, arg.as_deref())?;
+                if filter_name == "noescape" {
+                    value = filters::wrap_safe_for_context(value.to_string(), *escape_context);
+                    continue;
+                }
+                let resolved_arg = eval_arg(arg.as_deref(), context)?;
+                value = filters::apply_filter(filter_name, &value, resolved_arg.as_deref(), context)?;
             }
 
             let text = value.to_string();
             let safe_output_filters = [
-                "safe", "safeseq", "force_escape", "json_script", "urlize", "urlizetrunc",
-                "unordered_list",
+                "safe", "safeseq", "force_escape", "urlize", "urlizetrunc", "unordered_list",
+                "markdown",
             ];
             let is_safe = filters
                 .iter()
                 .any(|(name, _)| safe_output_filters.contains(&name.as_str()))
-                || context.is_safe(expr);
+                || context.is_safe(expr)
+                || filters::value_matches_context(&value, *escape_context);
             if is_safe {
-                Ok(text)
+                Ok(out.write_all(text.as_bytes())?)
             } else {
-                Ok(filters::html_escape(&text))
+                Ok(out.write_all(filters::escape_for_context(&text, *escape_context).as_bytes())?)
             }
         }
 
@@ -144,19 +606,13 @@ fn render_node_with_loader<L: TemplateLoader>(
             let condition_result = evaluate_condition(condition, context)?;
 
             if condition_result {
-                render_nodes_with_loader(true_nodes, context, loader)
+                write_nodes(true_nodes, context, loader, supers, out)
             } else if false_nodes.is_empty() {
                 // Fix for DJE-053: emit a placeholder comment so VDOM diffing has a stable
                 // DOM node to target when the condition later becomes true.
-                Ok("<!--dj-if-->".to_string())
+                Ok(out.write_all(b"<!--dj-if-->")?)
             } else {
-                // If false branch is empty, emit placeholder comment to maintain DOM structure
-                // This prevents VDOM diff from matching wrong siblings (issue #295)
-                if false_nodes.is_empty() {
-                    Ok("<!--dj-if-->".to_string())
-                } else {
-                    render_nodes_with_loader(false_nodes, context, loader)
-                }
+                write_nodes(false_nodes, context, loader, supers, out)
             }
         }
 
@@ -164,95 +620,85 @@ fn render_node_with_loader<L: TemplateLoader>(
             var_names,
             iterable,
             reversed,
+            key,
             nodes,
             empty_nodes,
         } => {
-            let iterable_value = context.get(iterable).cloned().unwrap_or(Value::Null);
+            let indices_and_items =
+                match resolve_for_iteration(context, var_names, iterable, *reversed) {
+                    Some(items) if !items.is_empty() => items,
+                    // Not a supported iterable, or an empty one - if there's
+                    // no {% empty %} block either, fall back to the same
+                    // DJE-053 placeholder comment `{% if %}` uses, so a
+                    // keyed diff still has a stable anchor to insert before.
+                    _ if empty_nodes.is_empty() => return Ok(out.write_all(b"<!--dj-for-->")?),
+                    _ => return write_nodes(empty_nodes, context, loader, supers, out),
+                };
 
-            match iterable_value {
-                Value::List(items) => {
-                    // If list is empty, render the {% empty %} block
-                    if items.is_empty() {
-                        return render_nodes_with_loader(empty_nodes, context, loader);
-                    }
+            let mut ctx = context.clone();
+            let total = indices_and_items.len();
 
-                    let mut output = String::new();
-                    let mut ctx = context.clone();
+            // Save outer cycle counter and forloop for nested loop support
+            let saved_cycle_counter = ctx.get("__djust_cycle_counter").cloned();
+            let saved_forloop = ctx.get("forloop").cloned();
 
-                    // Create an iterator with indices, reversing if needed
-                    let items_vec = items;
-                    let indices_and_items: Vec<(usize, Value)> = if *reversed {
-                        items_vec.into_iter().enumerate().rev().collect()
-                    } else {
-                        items_vec.into_iter().enumerate().collect()
-                    };
-
-                    // Save outer cycle counter for nested loop support
-                    let saved_cycle_counter = ctx.get("__djust_cycle_counter").cloned();
-
-                    for (counter, (index, item)) in indices_and_items.into_iter().enumerate() {
-                        // Set __djust_cycle_counter for {% cycle %} tag support
-                        ctx.set(
-                            "__djust_cycle_counter".to_string(),
-                            Value::Integer(counter as i64),
-                        );
-
-                        // Handle tuple unpacking: {% for a, b in items %}
-                        if var_names.len() == 1 {
-                            // Single variable: {% for item in items %}
-                            ctx.set(var_names[0].clone(), item);
-                            // Track loop mapping for safe key resolution
-                            ctx.set_loop_mapping(var_names[0].clone(), iterable.clone(), index);
-                        } else {
-                            // Multiple variables: {% for key, value in items %}
-                            // Expect item to be a list/tuple
-                            match &item {
-                                Value::List(tuple_items) => {
-                                    // Unpack tuple items into separate variables
-                                    for (i, var_name) in var_names.iter().enumerate() {
-                                        if i < tuple_items.len() {
-                                            ctx.set(var_name.clone(), tuple_items[i].clone());
-                                        } else {
-                                            // If tuple has fewer items than var names, set to Null
-                                            ctx.set(var_name.clone(), Value::Null);
-                                        }
-                                    }
-                                }
-                                _ => {
-                                    // If item is not a list, set all vars to Null except first
-                                    ctx.set(var_names[0].clone(), item.clone());
-                                    for var_name in &var_names[1..] {
-                                        ctx.set(var_name.clone(), Value::Null);
-                                    }
-                                }
-                            }
-                        }
-                        output.push_str(&render_nodes_with_loader(nodes, &ctx, loader)?);
-                    }
-
-                    // Restore outer cycle counter (for nested loops)
-                    if let Some(saved) = saved_cycle_counter {
-                        ctx.set("__djust_cycle_counter".to_string(), saved);
-                    }
+            for (counter, (index, item)) in indices_and_items.into_iter().enumerate() {
+                bind_for_iteration(
+                    &mut ctx,
+                    var_names,
+                    iterable,
+                    counter,
+                    total,
+                    index,
+                    item,
+                    &saved_forloop,
+                );
+                write_for_key_marker(key.as_deref(), &ctx, out)?;
+                write_nodes(nodes, &ctx, loader, supers, out)?;
+            }
 
-                    // Clear loop mappings after the loop
-                    for var_name in var_names {
-                        ctx.clear_loop_mapping(var_name);
-                    }
+            // Restore outer cycle counter and forloop (for nested loops)
+            if let Some(saved) = saved_cycle_counter {
+                ctx.set("__djust_cycle_counter".to_string(), saved);
+            }
+            if let Some(saved) = saved_forloop {
+                ctx.set("forloop".to_string(), saved);
+            }
 
-                    Ok(output)
-                }
-                _ => {
-                    // If not a list (null, etc.), render the empty block
-                    render_nodes_with_loader(empty_nodes, context, loader)
-                }
+            // Clear loop mappings after the loop
+            for var_name in var_names {
+                ctx.clear_loop_mapping(var_name);
             }
+
+            Ok(())
         }
 
-        Node::Block { name: _, nodes } => {
-            // For now, just render the block content
-            // In a full implementation, this would handle template inheritance
-            render_nodes_with_loader(nodes, context, loader)
+        Node::Block {
+            name,
+            nodes,
+            scoped: _,
+        } => {
+            // Block overrides are resolved structurally (see
+            // `inheritance::apply_override_to_node`), so by the time we get
+            // here `nodes` is already the most-derived body. Rendering it
+            // against `context` — whatever the enclosing tree handed us —
+            // means a block nested inside a `{% for %}` always sees that
+            // loop's variables, `scoped` or not; this interpreter has no
+            // separate per-block scope to opt into.
+            //
+            // `{{ block.super }}` inside this body resolves to everything
+            // but the most-derived entry in this name's stack (that's the
+            // body we're rendering right now).
+            let active: &[Vec<Node>] = match supers.stacks.and_then(|s| s.get(name)) {
+                Some(stack) if stack.len() > 1 => &stack[..stack.len() - 1],
+                _ => &[],
+            };
+            let block_supers = RenderScope {
+                stacks: supers.stacks,
+                active,
+                macros: supers.macros,
+            };
+            write_nodes(nodes, context, loader, block_supers, out)
         }
 
         Node::Include {
@@ -291,29 +737,84 @@ fn render_node_with_loader<L: TemplateLoader>(
                     include_context.set(key.clone(), value);
                 }
 
-                render_nodes_with_loader(&nodes, &include_context, Some(loader))
+                // An included template is an unrelated root — it has no
+                // ancestor blocks of its own, so `block.super` inside it
+                // (if any) resolves to nothing rather than leaking ours, and
+                // its macros are its own (collected fresh) rather than
+                // inherited from whoever included it.
+                let mut include_macros = HashMap::new();
+                collect_macro_scope(&nodes, Some(loader), &mut include_macros)?;
+                write_nodes(
+                    &nodes,
+                    &include_context,
+                    Some(loader),
+                    RenderScope {
+                        stacks: None,
+                        active: &[],
+                        macros: Some(&include_macros),
+                    },
+                    out,
+                )
             } else {
                 // No loader available - warn developers (once per template)
                 let tag_sig = format!("{{% include \"{template}\" %}} (no loader)");
                 warn_unsupported_tag(&tag_sig);
-                Ok(format!(
-                    "<!-- djust: include '{template}' ignored - no template loader -->"
-                ))
+                Ok(out.write_all(
+                    format!("<!-- djust: include '{template}' ignored - no template loader -->")
+                        .as_bytes(),
+                )?)
             }
         }
 
+        // Definitions and bindings resolve once up front (see
+        // `collect_macro_scope`); encountering one while rendering means it
+        // already did its job, so it renders as nothing.
+        Node::Macro { .. } | Node::Import { .. } | Node::FromImport { .. } => Ok(()),
+
+        Node::MacroCall { name, args, kwargs } => {
+            let def = supers
+                .macros
+                .and_then(|macros| macros.get(name))
+                .ok_or_else(|| {
+                    DjangoRustError::TemplateError(format!("Call to undefined macro '{name}'"))
+                })?;
+
+            // Bind positional args first, then matching kwargs, then the
+            // parameter's own default — same precedence as a Python call.
+            let mut call_context = context.clone();
+            for (i, (param_name, default)) in def.params.iter().enumerate() {
+                let value_expr = args
+                    .get(i)
+                    .map(String::as_str)
+                    .or_else(|| {
+                        kwargs
+                            .iter()
+                            .find(|(k, _)| k == param_name)
+                            .map(|(_, v)| v.as_str())
+                    })
+                    .or(default.as_deref());
+
+                let value = match value_expr {
+                    Some(expr) => get_value(expr.trim(), context)?,
+                    None => Value::Null,
+                };
+                call_context.set(param_name.clone(), value);
+            }
+
+            write_nodes(&def.body, &call_context, loader, supers, out)
+        }
+
         Node::ReactComponent {
             name,
             props,
             children,
         } => {
             // Render React component as data attributes for client-side hydration
-            let mut output = String::new();
-            output.push_str(&format!("<div data-react-component=\"{name}\""));
+            out.write_all(format!("<div data-react-component=\"{name}\"").as_bytes())?;
 
             // Add props as data attributes
             if !props.is_empty() {
-                output.push_str(" data-react-props='");
+                out.write_all(b" data-react-props='")?;
                 let props_json: Vec<String> = props
                     .iter()
                     .map(|(k, v)| {
@@ -338,24 +839,24 @@ fn render_node_with_loader<L: TemplateLoader>(
                         format!("\"{}\":\"{}\"", k, resolved_value.replace('"', "\\\""))
                     })
                     .collect();
-                output.push_str(&format!("{{{}}}", props_json.join(",")));
-                output.push('\'');
+                out.write_all(format!("{{{}}}", props_json.join(",")).as_bytes())?;
+                out.write_all(b"'")?;
             }
 
-            output.push('>');
+            out.write_all(b">")?;
 
             // Render children
             for child in children {
-                output.push_str(&render_node_with_loader(child, context, loader)?);
+                write_node(child, context, loader, supers, out)?;
             }
 
-            output.push_str("</div>");
-            Ok(output)
+            Ok(out.write_all(b"</div>")?)
         }
 
         Node::RustComponent { name, props } => {
             // Render Rust component server-side
-            render_rust_component(name, props, context)
+            let rendered = render_rust_component(name, props, context)?;
+            Ok(out.write_all(rendered.as_bytes())?)
         }
 
         Node::CsrfToken => {
@@ -366,9 +867,10 @@ fn render_node_with_loader<L: TemplateLoader>(
                 .map(|v| v.to_string())
                 .unwrap_or_else(|| "CSRF_TOKEN_NOT_PROVIDED".to_string());
 
-            Ok(format!(
-                "<input type=\"hidden\" name=\"csrfmiddlewaretoken\" value=\"{token}\">"
-            ))
+            Ok(out.write_all(
+                format!("<input type=\"hidden\" name=\"csrfmiddlewaretoken\" value=\"{token}\">")
+                    .as_bytes(),
+            )?)
         }
 
         Node::Static(path) => {
@@ -379,7 +881,7 @@ fn render_node_with_loader<L: TemplateLoader>(
                 .map(|v| v.to_string())
                 .unwrap_or_else(|| "/static/".to_string());
 
-            Ok(format!("{static_url}{path}"))
+            Ok(out.write_all(format!("{static_url}{path}").as_bytes())?)
         }
 
         Node::With { assignments, nodes } => {
@@ -399,7 +901,7 @@ fn render_node_with_loader<L: TemplateLoader>(
             }
 
             // Render children with new context
-            render_nodes_with_loader(nodes, &new_context, loader)
+            write_nodes(nodes, &new_context, loader, supers, out)
         }
 
         Node::Extends(_) => {
@@ -411,7 +913,7 @@ fn render_node_with_loader<L: TemplateLoader>(
             ))
         }
 
-        Node::Comment => Ok(String::new()),
+        Node::Comment => Ok(()),
 
         Node::WidthRatio {
             value,
@@ -424,10 +926,10 @@ fn render_node_with_loader<L: TemplateLoader>(
             let max_w = get_value(max_width, context)?.to_f64().unwrap_or(0.0);
 
             if max_val == 0.0 {
-                Ok("0".to_string())
+                Ok(out.write_all(b"0")?)
             } else {
                 let result = (val / max_val * max_w).round() as i64;
-                Ok(result.to_string())
+                Ok(out.write_all(result.to_string().as_bytes())?)
             }
         }
 
@@ -437,10 +939,10 @@ fn render_node_with_loader<L: TemplateLoader>(
             for arg in args {
                 let val = get_value(arg.trim(), context)?;
                 if val.is_truthy() {
-                    return Ok(filters::html_escape(&val.to_string()));
+                    return Ok(out.write_all(filters::html_escape(&val.to_string()).as_bytes())?);
                 }
             }
-            Ok(String::new())
+            Ok(())
         }
 
         Node::TemplateTag(name) => {
@@ -460,14 +962,37 @@ fn render_node_with_loader<L: TemplateLoader>(
                     )));
                 }
             };
-            Ok(output.to_string())
+            Ok(out.write_all(output.as_bytes())?)
         }
 
         Node::Spaceless { nodes } => {
             // {% spaceless %}...{% endspaceless %} → remove whitespace between HTML tags
-            let content = render_nodes_with_loader(nodes, context, loader)?;
+            // The regex needs the whole body at once, so this is the one
+            // place a subtree still has to be buffered before it reaches
+            // `out` rather than streaming straight through.
+            let mut buf = Vec::new();
+            write_nodes(nodes, context, loader, supers, &mut buf)?;
+            let content = bytes_to_string(buf)?;
             // Remove whitespace between > and <
-            Ok(SPACELESS_RE.replace_all(&content, "><").to_string())
+            Ok(out.write_all(SPACELESS_RE.replace_all(&content, "><").as_bytes())?)
+        }
+
+        Node::Autoescape { enabled, nodes } => {
+            // Renders against a cloned `Context` with `autoescape` flipped,
+            // so the flag reverts to whatever it was the moment this call
+            // returns - nesting (`{% autoescape off %}...{% autoescape on
+            // %}...{% endautoescape %}...{% endautoescape %}`) just works
+            // since each level clones from its enclosing one.
+            let mut inner_context = context.clone();
+            inner_context.set_autoescape(*enabled);
+            write_nodes(nodes, &inner_context, loader, supers, out)
+        }
+
+        Node::Markdown(nodes) => {
+            // Already real `Text`/`Variable`/... nodes by the time the
+            // renderer sees them (see `parser::parse_markdown_block`), so
+            // this is just a pass-through container like `With`/`Spaceless`.
+            write_nodes(nodes, context, loader, supers, out)
         }
 
         Node::Cycle { values, name: _ } => {
@@ -476,7 +1001,7 @@ fn render_node_with_loader<L: TemplateLoader>(
             // (renderer receives &Context, can't store cycle state).
             // Note: cycle outside a for loop always returns the first value (no counter).
             if values.is_empty() {
-                return Ok(String::new());
+                return Ok(());
             }
             let counter = context
                 .get("__djust_cycle_counter")
@@ -499,16 +1024,34 @@ fn render_node_with_loader<L: TemplateLoader>(
             // stored in context — the renderer receives &Context (immutable). The cycle
             // value is still computed correctly each iteration; only the "silent reference"
             // form ({% cycle name %} outside the cycle definition) is unsupported.
-            Ok(output)
+            Ok(out.write_all(output.as_bytes())?)
         }
 
         Node::Now(format) => {
             // {% now "Y-m-d" %} → current date/time
             let now = chrono::Local::now();
-            Ok(django_date_format(&now, format))
+            let locale = context.locale().and_then(crate::locale::Locale::from_code);
+            Ok(out.write_all(django_date_format(&now, format, locale.as_ref()).as_bytes())?)
         }
 
         Node::UnsupportedTag { name, args } => {
+            // A Rust handler registered after this template was parsed can
+            // still serve the tag - check before giving up on it.
+            if let Some(result) = crate::rust_tags::render_tag(name, args, context) {
+                let rendered = result?;
+                return Ok(out.write_all(rendered.as_bytes())?);
+            }
+
+            // Give the embedder's missing-tag hook a chance before falling
+            // back to the default warn-and-comment behavior.
+            if let Some(output) = crate::fallback::run(name, args, context)? {
+                return Ok(out.write_all(output.as_bytes())?);
+            }
+
+            if crate::fallback::is_strict() {
+                return Err(crate::fallback::strict_error("tag", name));
+            }
+
             // Build tag signature for warning (only warn once per unique tag)
             let args_str = if args.is_empty() {
                 String::new()
@@ -520,73 +1063,128 @@ fn render_node_with_loader<L: TemplateLoader>(
             // Warn once per tag signature (avoids log spam)
             warn_unsupported_tag(&tag_sig);
 
-            // Return HTML comment so it's visible in page source during development
-            Ok(format!("<!-- djust: unsupported tag '{tag_sig}' -->"))
+            // Write an HTML comment so it's visible in page source during development
+            Ok(out.write_all(format!("<!-- djust: unsupported tag '{tag_sig}' -->").as_bytes())?)
         }
 
         Node::CustomTag { name, args } => {
-            // Call Python handler for custom tags (e.g., {% url %}, {% static %})
+            // Call a custom tag handler for tags like {% url %}, {% static %}.
             //
-            // The handler is looked up in the registry and called with:
-            // - args: The raw arguments from the template tag
-            // - context: The current template context (converted to Python dict)
+            // The Rust-side registry (`rust_tags`) is tried first so
+            // pure-Rust embedders can implement tags without a Python
+            // bridge; only when no Rust handler is registered do we fall
+            // back to the Python handler looked up via `crate::registry`.
             //
-            // The handler must return a string to be inserted in the output.
+            // Either way, the handler is called with:
+            // - args: The tag's arguments, resolved against the context by
+            //   `resolve_custom_tag_args`
+            // - context: The current template context
+            let resolved_args = resolve_custom_tag_args(args, context)?;
 
-            // First, resolve any variable references in args
-            let resolved_args: Vec<String> = args
-                .iter()
-                .map(|arg| {
-                    // Check if arg is a variable reference (not a string literal)
-                    let arg_trimmed = arg.trim();
-                    if (arg_trimmed.starts_with('"') && arg_trimmed.ends_with('"'))
-                        || (arg_trimmed.starts_with('\'') && arg_trimmed.ends_with('\''))
-                    {
-                        // String literal - keep as-is
-                        arg.clone()
-                    } else if let Some(eq_pos) = arg.find('=') {
-                        // Named parameter: key=value
-                        let key = &arg[..eq_pos];
-                        let value = arg[eq_pos + 1..].trim();
-                        if (value.starts_with('"') && value.ends_with('"'))
-                            || (value.starts_with('\'') && value.ends_with('\''))
-                        {
-                            // Value is a string literal
-                            arg.clone()
-                        } else {
-                            // Value is a variable - try to resolve
-                            match context.get(value) {
-                                Some(resolved) => format!("{}={}", key, resolved),
-                                None => arg.clone(),
-                            }
-                        }
-                    } else {
-                        // Might be a variable - try to resolve
-                        match context.get(arg_trimmed) {
-                            Some(resolved) => resolved.to_string(),
-                            None => arg.clone(),
-                        }
-                    }
-                })
-                .collect();
+            if let Some(result) = crate::rust_tags::render_tag(name, &resolved_args, context) {
+                let rendered = result?;
+                return Ok(out.write_all(rendered.as_bytes())?);
+            }
 
             // Convert context to HashMap for the handler
             let context_map = context.to_hashmap();
 
             // Call the Python handler
-            crate::registry::call_handler(name, &resolved_args, &context_map).map_err(|e| {
-                DjangoRustError::TemplateError(format!("Custom tag '{}' error: {}", name, e))
-            })
+            let rendered = crate::registry::call_handler(name, &resolved_args, &context_map)
+                .map_err(|e| {
+                    DjangoRustError::TemplateError(format!("Custom tag '{}' error: {}", name, e))
+                })?;
+            Ok(out.write_all(rendered.as_bytes())?)
+        }
+
+        Node::CustomBlockTag { name, args, nodes } => {
+            // A block tag registered through `environment::register_tag`
+            // (only the parser produces this variant, and only for a name
+            // the environment has already declared - see
+            // `parser::parse_tag`), so the handler is guaranteed present.
+            let resolved_args = resolve_custom_tag_args(args, context)?;
+            let mut ctx = context.clone();
+            let rendered = crate::environment::call_tag(name, &mut ctx, &resolved_args, nodes)
+                .ok_or_else(|| {
+                    DjangoRustError::TemplateError(format!(
+                        "No handler registered for custom tag '{name}'"
+                    ))
+                })??;
+            Ok(out.write_all(rendered.as_bytes())?)
         }
     }
 }
 
+/// Resolves a `{% customtag arg1 key=arg2 %}` tag's raw argument strings
+/// against `context` - string literals pass through unchanged, `(now "Y")`
+/// style subexpressions are evaluated, and anything else is tried as a
+/// variable lookup (falling back to the raw text if it doesn't resolve).
+/// Shared by `Node::CustomTag` and `Node::CustomBlockTag`.
+fn resolve_custom_tag_args(args: &[String], context: &Context) -> Result<Vec<String>> {
+    args.iter()
+        .map(|arg| -> Result<String> {
+            let arg_trimmed = arg.trim();
+            if let Some(inner) = subexpression_inner(arg_trimmed) {
+                // Parenthesized subexpression, e.g. `(now "Y")` - evaluate
+                // it through the full pipeline first.
+                return Ok(eval_subexpression(inner, context)?.to_string());
+            }
+            if (arg_trimmed.starts_with('"') && arg_trimmed.ends_with('"'))
+                || (arg_trimmed.starts_with('\'') && arg_trimmed.ends_with('\''))
+            {
+                // String literal - keep as-is
+                Ok(arg.clone())
+            } else if let Some(eq_pos) = arg.find('=') {
+                // Named parameter: key=value
+                let key = &arg[..eq_pos];
+                let value = arg[eq_pos + 1..].trim();
+                if (value.starts_with('"') && value.ends_with('"'))
+                    || (value.starts_with('\'') && value.ends_with('\''))
+                {
+                    // Value is a string literal
+                    Ok(arg.clone())
+                } else if let Some(inner) = subexpression_inner(value) {
+                    Ok(format!("{}={}", key, eval_subexpression(inner, context)?))
+                } else {
+                    // Value is a variable - try to resolve
+                    match context.get(value) {
+                        Some(resolved) => Ok(format!("{}={}", key, resolved)),
+                        None => Ok(arg.clone()),
+                    }
+                }
+            } else {
+                // Might be a variable - try to resolve
+                match context.get(arg_trimmed) {
+                    Some(resolved) => Ok(resolved.to_string()),
+                    None => Ok(arg.clone()),
+                }
+            }
+        })
+        .collect::<Result<Vec<String>>>()
+}
+
 /// Render a Rust component by instantiating it and calling its render method
 fn render_rust_component(
     name: &str,
     props: &[(String, String)],
     context: &Context,
 ) -> Result<String> {
+    // `hiddenIf`/`disabledIf` gate every component generically, evaluated
+    // against the context through the same `evaluate_condition` that
+    // backs `{% if %}`, so template authors get expressive guards like
+    // `disabledIf="user.credits < 1 or locked"` instead of wrapping the
+    // component in `{% if %}`.
+    if let Ok(hidden_if) = get_prop("hiddenIf", props, context) {
+        if evaluate_condition(&hidden_if, context)? {
+            return Ok(String::new());
+        }
+    }
+
+    let disabled_by_condition = match get_prop("disabledIf", props, context) {
+        Ok(condition) => evaluate_condition(&condition, context)?,
+        Err(_) => false,
+    };
+
     // Get framework from context or default to Bootstrap5
     let framework = context
         .get("_framework")
@@ -601,8 +1199,15 @@ fn render_rust_component(
 
     let fw = framework.parse().unwrap();
 
+    // Resolve the active theme's color/sizing slots, overridden by a
+    // `theme` prop (JSON of slot overrides) if the component was given one.
+    let theme = match get_prop("theme", props, context) {
+        Ok(theme_json) => crate::theme::Theme::from_json(&theme_json)?,
+        Err(_) => crate::theme::Theme::default(),
+    };
+
     // Match component name and instantiate
-    match name {
+    let rendered = match name {
         "RustButton" => {
             // Extract required props
             let id = get_prop("id", props, context)?;
@@ -996,6 +1601,17 @@ fn render_rust_component(
                 dropdown.placeholder = Some(placeholder);
             }
 
+            if let Ok(search) = get_prop("search", props, context) {
+                dropdown.search = search == "true" || search == "True";
+            }
+
+            if dropdown.search {
+                dropdown.no_results_message = Some(
+                    get_prop("noResultsMessage", props, context)
+                        .unwrap_or_else(|_| "No results found".to_string()),
+                );
+            }
+
             // Render the component
             dropdown.render(fw).map_err(|e| {
                 DjangoRustError::TemplateError(format!("Failed to render RustDropdown: {e}"))
@@ -1062,19 +1678,102 @@ fn render_rust_component(
         _ => Err(DjangoRustError::TemplateError(format!(
             "Unknown Rust component: {name}"
         ))),
+    }?;
+
+    let rendered = if disabled_by_condition {
+        let reason = get_prop("disabledReason", props, context).ok();
+        inject_disabled_attrs(&rendered, reason.as_deref())
+    } else {
+        rendered
+    };
+
+    Ok(inject_theme_style(&rendered, &theme))
+}
+
+/// Stamp a theme's CSS custom-property declarations (`--dj-primary: ...;`)
+/// onto a rendered component's root tag as a `style` attribute, so every
+/// `Rust*` component gets runtime-overridable colors/sizing without each
+/// arm above repeating the same wiring.
+fn inject_theme_style(html: &str, theme: &crate::theme::Theme) -> String {
+    let declarations = theme.style_declarations();
+    if declarations.is_empty() {
+        return html.to_string();
+    }
+
+    match root_tag_insertion_point(html) {
+        Some(insert_at) => format!(
+            "{} style=\"{declarations}\"{}",
+            &html[..insert_at],
+            &html[insert_at..]
+        ),
+        None => html.to_string(),
     }
 }
 
-/// Get a prop value, resolving template variables if needed
+/// Stamp `disabled` (and, if `reason` is given, a `title` tooltip explaining
+/// why) onto a rendered component's root tag. Used when `disabledIf`
+/// evaluates true, so gating works the same way regardless of whether the
+/// component arm itself exposes a `disabled` prop.
+fn inject_disabled_attrs(html: &str, reason: Option<&str>) -> String {
+    let Some(insert_at) = root_tag_insertion_point(html) else {
+        return html.to_string();
+    };
+
+    let title_attr = match reason {
+        Some(reason) => format!(" title=\"{}\"", filters::escape_double_quoted_attr(reason)),
+        None => String::new(),
+    };
+
+    format!(
+        "{} disabled{title_attr}{}",
+        &html[..insert_at],
+        &html[insert_at..]
+    )
+}
+
+/// Find where to insert a new attribute into a rendered component's root
+/// tag - right after the tag name, before its first existing attribute (or
+/// its closing `>` if it has none). Shared by `inject_theme_style` and
+/// `inject_disabled_attrs`.
+fn root_tag_insertion_point(html: &str) -> Option<usize> {
+    let tag_start = html.find('<')?;
+    html[tag_start + 1..]
+        .find(|c: char| c.is_whitespace() || c == '>')
+        .map(|i| tag_start + 1 + i)
+}
+
+/// Get a prop value, resolving template variables if needed. `{{ var }}`
+/// props also support a Django-style filter chain (`{{ name|upper }}`,
+/// `{{ count|default:"0" }}`, `{{ created_at|date:"Y-m-d" }}`), applied
+/// left to right through `filters::apply_filter` - the same registry
+/// ordinary `{{ }}` template text uses - so every component prop can be
+/// formatted inline instead of pre-formatted in the view.
 fn get_prop(key: &str, props: &[(String, String)], context: &Context) -> Result<String> {
     for (k, v) in props {
         if k == key {
-            // Resolve Django template variable syntax: {{ var.path }}
+            // Resolve Django template variable syntax: {{ var.path|filter:arg }}
             if v.starts_with("{{") && v.ends_with("}}") {
-                let var_name = v.trim_start_matches("{{").trim_end_matches("}}").trim();
-
-                if let Some(ctx_value) = context.get(var_name) {
-                    return Ok(ctx_value.to_string());
+                let expr = v.trim_start_matches("{{").trim_end_matches("}}").trim();
+                let mut segments = expr.split('|');
+                let var_name = segments.next().unwrap_or("").trim();
+                let filter_specs: Vec<&str> = segments.collect();
+
+                let ctx_value = context.get(var_name).cloned();
+                if filter_specs.is_empty() {
+                    if let Some(ctx_value) = ctx_value {
+                        return Ok(ctx_value.to_string());
+                    }
+                } else {
+                    let mut value = ctx_value.unwrap_or(Value::Null);
+                    for filter_spec in filter_specs {
+                        let filter_spec = filter_spec.trim();
+                        let (filter_name, filter_arg) = match filter_spec.split_once(':') {
+                            Some((name, arg)) => (name.trim(), Some(arg.trim())),
+                            None => (filter_spec, None),
+                        };
+                        value = filters::apply_filter(filter_name, &value, filter_arg, context)?;
+                    }
+                    return Ok(value.to_string());
                 }
             } else if let Some(ctx_value) = context.get(v) {
                 // Direct variable reference (no {{ }})
@@ -1091,131 +1790,61 @@ fn get_prop(key: &str, props: &[(String, String)], context: &Context) -> Result<
     )))
 }
 
-fn evaluate_condition(condition: &str, context: &Context) -> Result<bool> {
-    let condition = condition.trim();
-
-    // Handle simple boolean values
-    if condition == "true" || condition == "True" {
-        return Ok(true);
-    }
-    if condition == "false" || condition == "False" {
-        return Ok(false);
-    }
-
-    // Handle "or" (lowest precedence - split first)
-    // Use " or " with spaces to avoid matching variable names containing "or"
-    if let Some(pos) = condition.find(" or ") {
-        let left = &condition[..pos];
-        let right = &condition[pos + 4..];
-        return Ok(evaluate_condition(left, context)? || evaluate_condition(right, context)?);
-    }
-
-    // Handle "and" (higher precedence than "or")
-    if let Some(pos) = condition.find(" and ") {
-        let left = &condition[..pos];
-        let right = &condition[pos + 5..];
-        return Ok(evaluate_condition(left, context)? && evaluate_condition(right, context)?);
-    }
-
-    // Handle variable lookups
-    if let Some(value) = context.get(condition) {
-        return Ok(value.is_truthy());
-    }
-
-    // Handle negation
-    if let Some(rest) = condition.strip_prefix("not ") {
-        return Ok(!evaluate_condition(rest, context)?);
-    }
-
-    // Handle comparisons
-    if condition.contains("==") {
-        let parts: Vec<&str> = condition.split("==").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let left = get_value(parts[0], context)?;
-            let right = get_value(parts[1], context)?;
-            return Ok(values_equal(&left, &right));
-        }
-    }
-
-    if condition.contains("!=") {
-        let parts: Vec<&str> = condition.split("!=").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let left = get_value(parts[0], context)?;
-            let right = get_value(parts[1], context)?;
-            return Ok(!values_equal(&left, &right));
-        }
-    }
+pub(crate) fn evaluate_condition(condition: &str, context: &Context) -> Result<bool> {
+    crate::condition::evaluate(condition.trim(), context)
+}
 
-    // Handle >= (must be before > to avoid false match)
-    if condition.contains(">=") {
-        let parts: Vec<&str> = condition.split(">=").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let left = get_value(parts[0], context)?;
-            let right = get_value(parts[1], context)?;
-            return Ok(compare_values(&left, &right) >= 0);
-        }
+/// Returns the inside of `s` if it's a parenthesized subexpression like
+/// `(other|upper)`, or `None` for a plain string-literal/dotted-path arg.
+fn subexpression_inner(s: &str) -> Option<&str> {
+    let trimmed = s.trim();
+    if trimmed.len() >= 2 && trimmed.starts_with('(') && trimmed.ends_with(')') {
+        Some(trimmed[1..trimmed.len() - 1].trim())
+    } else {
+        None
     }
+}
 
-    // Handle <= (must be before < to avoid false match)
-    if condition.contains("<=") {
-        let parts: Vec<&str> = condition.split("<=").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let left = get_value(parts[0], context)?;
-            let right = get_value(parts[1], context)?;
-            return Ok(compare_values(&left, &right) <= 0);
-        }
+/// Resolve a filter argument that may be a parenthesized subexpression,
+/// e.g. `{{ value|default:(other|upper) }}`, by evaluating it through the
+/// full variable/filter pipeline first. Plain arguments pass through
+/// unchanged so existing string-literal/dotted-path handling is unaffected.
+fn eval_arg(arg: Option<&str>, context: &Context) -> Result<Option<String>> {
+    match arg {
+        Some(raw) => match subexpression_inner(raw) {
+            Some(inner) => Ok(Some(eval_subexpression(inner, context)?.to_string())),
+            None => Ok(Some(raw.to_string())),
+        },
+        None => Ok(None),
     }
+}
 
-    // Handle "in" operator: {% if item in list %}
-    if condition.contains(" in ") {
-        let parts: Vec<&str> = condition.splitn(2, " in ").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let needle = get_value(parts[0], context)?;
-            let haystack = get_value(parts[1], context)?;
-            return match haystack {
-                Value::List(items) => Ok(items.iter().any(|item| values_equal(&needle, item))),
-                Value::String(s) => {
-                    if let Value::String(n) = &needle {
-                        Ok(s.contains(n.as_str()))
-                    } else {
-                        Ok(false)
-                    }
-                }
-                Value::Object(map) => {
-                    // Django: "x in dict" checks dict keys
-                    let key = needle.to_string();
-                    Ok(map.contains_key(&key))
-                }
-                _ => Ok(false),
+/// Evaluate the inside of a `(...)` subexpression - either a `now "..."`
+/// tag call or a `value|filter:arg` pipeline - reusing the same machinery a
+/// top-level `{{ }}` expression goes through.
+fn eval_subexpression(inner: &str, context: &Context) -> Result<Value> {
+    if let Some(rest) = inner.strip_prefix("now") {
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            let format_arg = rest.trim();
+            let format = if (format_arg.starts_with('"') && format_arg.ends_with('"'))
+                || (format_arg.starts_with('\'') && format_arg.ends_with('\''))
+            {
+                format_arg[1..format_arg.len() - 1].to_string()
+            } else {
+                format_arg.to_string()
             };
+            let locale = context.locale().and_then(crate::locale::Locale::from_code);
+            return Ok(Value::String(django_date_format(
+                &chrono::Local::now(),
+                &format,
+                locale.as_ref(),
+            )));
         }
     }
-
-    // Handle > (greater than)
-    if condition.contains(" > ") {
-        let parts: Vec<&str> = condition.split(" > ").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let left = get_value(parts[0], context)?;
-            let right = get_value(parts[1], context)?;
-            return Ok(compare_values(&left, &right) > 0);
-        }
-    }
-
-    // Handle < (less than)
-    if condition.contains(" < ") {
-        let parts: Vec<&str> = condition.split(" < ").map(|s| s.trim()).collect();
-        if parts.len() == 2 {
-            let left = get_value(parts[0], context)?;
-            let right = get_value(parts[1], context)?;
-            return Ok(compare_values(&left, &right) < 0);
-        }
-    }
-
-    // Default to false for unknown conditions
-    Ok(false)
+    get_value(inner, context)
 }
 
-fn get_value(expr: &str, context: &Context) -> Result<Value> {
+pub(crate) fn get_value(expr: &str, context: &Context) -> Result<Value> {
     // Handle pipe filters in expressions (e.g., "project.id|stringformat:\"s\"")
     if expr.contains('|') {
         let parts: Vec<&str> = expr.splitn(2, '|').collect();
@@ -1242,7 +1871,8 @@ fn get_value(expr: &str, context: &Context) -> Result<Value> {
                 (filter_part, None)
             };
 
-            value = filters::apply_filter(filter_name, &value, arg.as_deref())?;
+            let resolved_arg = eval_arg(arg.as_deref(), context)?;
+            value = filters::apply_filter(filter_name, &value, resolved_arg.as_deref(), context)?;
         }
 
         return Ok(value);
@@ -1269,23 +1899,60 @@ fn get_value(expr: &str, context: &Context) -> Result<Value> {
         return Ok(Value::String(expr[1..expr.len() - 1].to_string()));
     }
 
+    // ISO-8601 datetime literal (date/time separated by 'T' or a space, with
+    // an optional trailing 'Z'/±HH:MM offset - defaults to UTC if omitted).
+    if let Some(dt) = parse_iso8601_literal(expr) {
+        return Ok(Value::DateTime(dt));
+    }
+
     Ok(Value::Null)
 }
 
-fn values_equal(a: &Value, b: &Value) -> bool {
+/// Recognize an ISO-8601 `YYYY-MM-DD{T, }HH:MM:SS` literal, optionally
+/// followed by `Z` or a `±HH:MM` offset. Unlike `DateTime::parse_from_rfc3339`,
+/// the offset is optional (assumed UTC) and the separator may be a plain
+/// space, since both show up in stringified context values.
+pub(crate) fn parse_iso8601_literal(expr: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    use chrono::{FixedOffset, NaiveDateTime, TimeZone};
+
+    let bytes = expr.as_bytes();
+    if bytes.len() < 19 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    if !bytes[0..4].iter().all(u8::is_ascii_digit) || !(bytes[10] == b'T' || bytes[10] == b' ') {
+        return None;
+    }
+
+    let mut normalized = expr.to_string();
+    normalized.replace_range(10..11, "T");
+
+    if let Some(rest) = normalized.strip_suffix('Z') {
+        normalized = format!("{rest}+00:00");
+    }
+
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&normalized) {
+        return Some(dt);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(&normalized, "%Y-%m-%dT%H:%M:%S").ok()?;
+    FixedOffset::east_opt(0)?.from_local_datetime(&naive).single()
+}
+
+pub(crate) fn values_equal(a: &Value, b: &Value) -> bool {
     match (a, b) {
         (Value::Null, Value::Null) => true,
         (Value::Bool(a), Value::Bool(b)) => a == b,
         (Value::Integer(a), Value::Integer(b)) => a == b,
         (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
         (Value::String(a), Value::String(b)) => a == b,
+        (Value::DateTime(a), Value::DateTime(b)) => a == b,
         _ => false,
     }
 }
 
 /// Compare two values and return -1 (less), 0 (equal), or 1 (greater).
 /// Returns 0 for incomparable types.
-fn compare_values(a: &Value, b: &Value) -> i32 {
+pub(crate) fn compare_values(a: &Value, b: &Value) -> i32 {
     match (a, b) {
         (Value::Integer(a), Value::Integer(b)) => a.cmp(b) as i32,
         (Value::Float(a), Value::Float(b)) => {
@@ -1319,6 +1986,7 @@ fn compare_values(a: &Value, b: &Value) -> i32 {
             }
         }
         (Value::String(a), Value::String(b)) => a.cmp(b) as i32,
+        (Value::DateTime(a), Value::DateTime(b)) => a.cmp(b) as i32,
         // Null comparisons
         (Value::Null, Value::Null) => 0,
         // Incomparable types return 0 (treated as equal, so < and > fail)
@@ -1326,8 +1994,9 @@ fn compare_values(a: &Value, b: &Value) -> i32 {
     }
 }
 
-/// Convert a Value to f64 for arithmetic operations (widthratio)
-trait ToF64 {
+/// Convert a Value to f64 for arithmetic operations (widthratio, and the
+/// `condition::ValueExpr` `+ - * / %` subsystem).
+pub(crate) trait ToF64 {
     fn to_f64(&self) -> Option<f64>;
 }
 
@@ -1347,7 +2016,22 @@ impl ToF64 for Value {
 ///
 /// Django uses PHP-style single-character format codes (e.g., "Y" for 4-digit year).
 /// This converts the most common ones to chrono's strftime equivalents.
-fn django_date_format(dt: &chrono::DateTime<chrono::Local>, django_fmt: &str) -> String {
+pub(crate) fn django_date_format<Tz: chrono::TimeZone>(
+    dt: &chrono::DateTime<Tz>,
+    django_fmt: &str,
+    locale: Option<&crate::locale::Locale>,
+) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let default_locale;
+    let locale: &crate::locale::Locale = match locale {
+        Some(l) => l,
+        None => {
+            default_locale = crate::locale::Locale::english();
+            &default_locale
+        }
+    };
     let mut result = String::new();
     let chars = django_fmt.chars();
     let mut escaped = false;
@@ -1366,13 +2050,13 @@ fn django_date_format(dt: &chrono::DateTime<chrono::Local>, django_fmt: &str) ->
             // Day
             'd' => result.push_str(&dt.format("%d").to_string()), // 01-31
             'j' => result.push_str(&dt.format("%-d").to_string()), // 1-31
-            'D' => result.push_str(&dt.format("%a").to_string()), // Mon
-            'l' => result.push_str(&dt.format("%A").to_string()), // Monday
+            'D' => result.push_str(locale.weekday_abbr(dt.weekday())), // Mon
+            'l' => result.push_str(locale.weekday_name(dt.weekday())), // Monday
             // Month
             'm' => result.push_str(&dt.format("%m").to_string()), // 01-12
             'n' => result.push_str(&dt.format("%-m").to_string()), // 1-12
-            'M' => result.push_str(&dt.format("%b").to_string()), // Jan
-            'F' => result.push_str(&dt.format("%B").to_string()), // January
+            'M' => result.push_str(locale.month_abbr(dt.month())), // Jan
+            'F' => result.push_str(locale.month_name(dt.month())), // January
             // Year
             'Y' => result.push_str(&dt.format("%Y").to_string()), // 2024
             'y' => result.push_str(&dt.format("%y").to_string()), // 24
@@ -1382,41 +2066,35 @@ fn django_date_format(dt: &chrono::DateTime<chrono::Local>, django_fmt: &str) ->
             's' => result.push_str(&dt.format("%S").to_string()), // 00-59
             'G' => result.push_str(&dt.format("%-H").to_string()), // 0-23
             'g' => result.push_str(&dt.format("%-I").to_string()), // 1-12
-            'A' => result.push_str(&dt.format("%p").to_string()), // AM/PM
+            'A' => {
+                let is_am = dt.format("%P").to_string() == "am";
+                result.push_str(if is_am { locale.am } else { locale.pm });
+            }
             'P' => {
                 // Django's P format: "1 a.m.", "noon", "midnight"
                 let hour = dt.format("%-I").to_string().parse::<u32>().unwrap_or(0);
                 let minute = dt.format("%M").to_string();
-                let ampm = if dt.format("%P").to_string() == "am" {
-                    "a.m."
-                } else {
-                    "p.m."
-                };
+                let is_am = dt.format("%P").to_string() == "am";
+                let ampm = if is_am { locale.am } else { locale.pm };
                 if minute == "00" {
-                    if hour == 12 && ampm == "p.m." {
-                        result.push_str("noon");
-                    } else if hour == 12 && ampm == "a.m." {
-                        result.push_str("midnight");
+                    if hour == 12 && !is_am {
+                        result.push_str(locale.noon);
+                    } else if hour == 12 && is_am {
+                        result.push_str(locale.midnight);
                     } else {
-                        result.push_str(&format!("{} {}", hour, ampm));
+                        result.push_str(&format!("{hour} {ampm}"));
                     }
                 } else {
-                    result.push_str(&format!("{}:{} {}", hour, minute, ampm));
+                    result.push_str(&format!("{hour}:{minute} {ampm}"));
                 }
             }
             // Week/day-of-week
             'w' => result.push_str(&dt.format("%w").to_string()), // 0 (Sun) - 6 (Sat)
             'W' => result.push_str(&dt.format("%V").to_string()), // ISO week number
             'S' => {
-                // English ordinal suffix: st, nd, rd, th
+                // Ordinal suffix (e.g. "st", "nd"; empty for locales without one)
                 let day = dt.format("%-d").to_string().parse::<u32>().unwrap_or(0);
-                let suffix = match day {
-                    1 | 21 | 31 => "st",
-                    2 | 22 => "nd",
-                    3 | 23 => "rd",
-                    _ => "th",
-                };
-                result.push_str(suffix);
+                result.push_str(locale.ordinal_suffix(day));
             }
             't' => {
                 // Days in the month (28-31)
@@ -1444,6 +2122,15 @@ fn django_date_format(dt: &chrono::DateTime<chrono::Local>, django_fmt: &str) ->
             }
             // Timezone
             'e' => result.push_str(&dt.format("%Z").to_string()),
+            'O' => {
+                // Difference to UTC, e.g. "+0300"
+                let total_minutes = dt.offset().fix().local_minus_utc() / 60;
+                let sign = if total_minutes < 0 { '-' } else { '+' };
+                let abs = total_minutes.abs();
+                result.push_str(&format!("{sign}{:02}{:02}", abs / 60, abs % 60));
+            }
+            'T' => result.push_str(&dt.format("%Z").to_string()), // Abbreviation (no zone database; offset-formatted)
+            'Z' => result.push_str(&dt.offset().fix().local_minus_utc().to_string()), // Offset in seconds
             // ISO 8601
             'c' => result.push_str(&dt.format("%Y-%m-%dT%H:%M:%S%:z").to_string()),
             // RFC 2822
@@ -1461,8 +2148,10 @@ fn django_date_format(dt: &chrono::DateTime<chrono::Local>, django_fmt: &str) ->
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::filters::EscapeContext;
     use crate::lexer::tokenize;
     use crate::parser::parse;
+    use crate::rust_tags::register_tag;
 
     #[test]
     fn test_render_text() {
@@ -1474,7 +2163,7 @@ mod tests {
 
     #[test]
     fn test_render_variable() {
-        let nodes = vec![Node::Variable("name".to_string(), vec![])];
+        let nodes = vec![Node::Variable("name".to_string(), vec![], EscapeContext::Text)];
         let mut context = Context::new();
         context.set("name".to_string(), Value::String("World".to_string()));
         let result = render_nodes(&nodes, &context).unwrap();
@@ -1560,6 +2249,115 @@ mod tests {
         assert_eq!(result, "xyz");
     }
 
+    #[test]
+    fn test_forloop_counter_first_last() {
+        let tokens = tokenize(
+            "{% for item in items %}{{ forloop.counter }}:{{ forloop.counter0 }}:{{ forloop.first }}:{{ forloop.last }},{% endfor %}",
+        )
+        .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+                Value::String("c".to_string()),
+            ]),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "1:0:true:false,2:1:false:false,3:2:false:true,");
+    }
+
+    #[test]
+    fn test_forloop_revcounter() {
+        let tokens =
+            tokenize("{% for item in items %}{{ forloop.revcounter }}:{{ forloop.revcounter0 }},{% endfor %}")
+                .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+            ]),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "2:1,1:0,");
+    }
+
+    #[test]
+    fn test_forloop_parentloop() {
+        let tokens = tokenize(
+            "{% for outer in outers %}{% for inner in inners %}{{ forloop.counter }}/{{ forloop.parentloop.counter }} {% endfor %}{% endfor %}",
+        )
+        .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "outers".to_string(),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+        );
+        context.set(
+            "inners".to_string(),
+            Value::List(vec![Value::String("x".to_string()), Value::String("y".to_string())]),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "1/1 2/1 1/2 2/2 ");
+    }
+
+    #[test]
+    fn test_for_over_dict_exposes_key_and_value() {
+        let tokens =
+            tokenize("{% for key, value in mydict %}{{ key }}={{ value }},{% endfor %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), Value::Integer(1));
+        map.insert("b".to_string(), Value::Integer(2));
+        context.set("mydict".to_string(), Value::Object(map));
+        let result = render_nodes(&nodes, &context).unwrap();
+        // HashMap has no inherent order; iteration is sorted by key.
+        assert_eq!(result, "a=1,b=2,");
+    }
+
+    #[test]
+    fn test_filter_arg_subexpression_is_evaluated() {
+        let tokens = tokenize("{{ missing|default:(other|upper) }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("other".to_string(), Value::String("fallback".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "FALLBACK");
+    }
+
+    #[test]
+    fn test_filter_arg_without_parens_is_unaffected() {
+        let tokens = tokenize("{{ missing|default:\"plain\" }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let context = Context::new();
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "plain");
+    }
+
+    #[test]
+    fn test_custom_tag_arg_subexpression_is_evaluated() {
+        register_tag(
+            "shout_handler",
+            |args: &[String], _ctx: &Context| -> Result<String> {
+                Ok(args.first().cloned().unwrap_or_default())
+            },
+        );
+        let tokens = tokenize("{% shout_handler (name|upper) %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("name".to_string(), Value::String("ada".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "ADA");
+        crate::rust_tags::unregister_tag("shout_handler");
+    }
+
     #[test]
     fn test_render_for_empty_with_items() {
         // Test that empty block is NOT rendered when list has items
@@ -1915,6 +2713,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_autoescape_off_block_emits_raw_html() {
+        let tokens =
+            tokenize("{% autoescape off %}{{ content }}{% endautoescape %} and {{ content }}")
+                .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("content".to_string(), Value::String("<b>hi</b>".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        // Inside the block: raw. Outside (after endautoescape): still escaped.
+        assert_eq!(result, "<b>hi</b> and &lt;b&gt;hi&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_triple_brace_is_unescaped() {
+        let tokens = tokenize("{{{ content }}}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("content".to_string(), Value::String("<b>hi</b>".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "<b>hi</b>");
+    }
+
     #[test]
     fn test_safe_filter_skips_escape() {
         // {{ var|safe }} should NOT auto-escape
@@ -1943,6 +2764,53 @@ mod tests {
         assert_eq!(result, "&lt;b&gt;&quot;hi&quot;&lt;/b&gt;");
     }
 
+    #[test]
+    fn test_escapejs_in_script_context_is_not_double_escaped() {
+        // `|escapejs` already turns a double quote into a JS unicode escape
+        // (backslash, `u0022`); re-running
+        // `html_escape` over that (the pre-chunk13-2 behavior) would mangle
+        // it into `&quot;`-style garbage. The typed `SafeJs` value
+        // should be emitted verbatim since the destination context is also
+        // JS.
+        let tokens = tokenize("<script>var x = \"{{ content|escapejs }}\";</script>").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "content".to_string(),
+            Value::String("say \"hi\"".to_string()),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "<script>var x = \"say \\u0022hi\\u0022\";</script>");
+    }
+
+    #[test]
+    fn test_escapejs_outside_script_still_gets_html_escaped() {
+        // A `SafeJs` value landing in a plain HTML text position doesn't
+        // match that context, so it still goes through `html_escape`.
+        let tokens = tokenize("{{ content|escapejs }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("content".to_string(), Value::String("<b>".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        // escapejs turns '<' into a JS unicode escape (backslash, `u003C`);
+        // html_escape then leaves the
+        // backslash/digits alone since none of them are HTML-special.
+        assert_eq!(result, "\\u003Cb\\u003E");
+    }
+
+    #[test]
+    fn test_noescape_skips_escaping_for_current_context() {
+        let tokens = tokenize("{{ content|noescape }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "content".to_string(),
+            Value::String("<b>bold</b>".to_string()),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "<b>bold</b>");
+    }
+
     #[test]
     fn test_auto_escape_preserves_plain_text() {
         // Plain text without HTML chars should be unchanged
@@ -2016,6 +2884,64 @@ mod tests {
         assert_eq!(result, "<!--dj-if-->B<!--dj-if-->");
     }
 
+    // Tests for keyed {% for %} placeholders (mirrors the {% if %}
+    // placeholder mechanism above, extended to list reconciliation)
+
+    #[test]
+    fn test_empty_for_with_no_empty_block_emits_placeholder() {
+        let tokens = tokenize("{% for item in items %}{{ item }}{% endfor %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("items".to_string(), Value::List(vec![]));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "<!--dj-for-->");
+    }
+
+    #[test]
+    fn test_empty_for_with_empty_block_renders_it_not_placeholder() {
+        let tokens =
+            tokenize("{% for item in items %}{{ item }}{% empty %}none{% endfor %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("items".to_string(), Value::List(vec![]));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "none");
+        assert!(!result.contains("<!--dj-for-->"));
+    }
+
+    #[test]
+    fn test_for_with_key_emits_keyed_anchor_per_iteration() {
+        let tokens =
+            tokenize("{% for item in items key item.id %}{{ item.name }}{% endfor %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut obj_a = std::collections::HashMap::new();
+        obj_a.insert("id".to_string(), Value::Integer(1));
+        obj_a.insert("name".to_string(), Value::String("a".to_string()));
+        let mut obj_b = std::collections::HashMap::new();
+        obj_b.insert("id".to_string(), Value::Integer(2));
+        obj_b.insert("name".to_string(), Value::String("b".to_string()));
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![Value::Object(obj_a), Value::Object(obj_b)]),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "<!--dj-for:1-->a<!--dj-for:2-->b");
+    }
+
+    #[test]
+    fn test_for_without_key_emits_no_anchor() {
+        let tokens = tokenize("{% for item in items %}{{ item }}{% endfor %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "items".to_string(),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "12");
+    }
+
     // Tests for newly implemented Django template tags
 
     #[test]
@@ -2219,4 +3145,165 @@ mod tests {
         assert_eq!(result.len(), 4);
         assert!(result.chars().all(|c| c.is_numeric()));
     }
+
+    #[test]
+    fn test_macro_call_with_positional_arg() {
+        let tokens = tokenize(
+            "{% macro greet(name) %}Hello, {{ name }}!{% endmacro %}{{ greet(\"World\") }}",
+        )
+        .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let context = Context::new();
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "Hello, World!");
+    }
+
+    #[test]
+    fn test_macro_call_with_kwarg_and_default() {
+        let tokens = tokenize(
+            "{% macro card(title, body=\"empty\") %}[{{ title }}: {{ body }}]{% endmacro %}\
+             {{ card(title=\"Hi\") }}",
+        )
+        .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let context = Context::new();
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "[Hi: empty]");
+    }
+
+    #[test]
+    fn test_macro_call_before_definition_still_resolves() {
+        // Macros are collected over the whole template up front, so a call
+        // site textually above its definition still works.
+        let tokens =
+            tokenize("{{ shout(msg) }}{% macro shout(msg) %}{{ msg }}!!!{% endmacro %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("msg".to_string(), Value::String("hi".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "hi!!!");
+    }
+
+    #[test]
+    fn test_undefined_macro_call_errors() {
+        let tokens = tokenize("{{ nope(\"x\") }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let context = Context::new();
+        let err = render_nodes(&nodes, &context).unwrap_err();
+        assert!(err.to_string().contains("nope"));
+    }
+
+    #[test]
+    fn test_macro_sees_caller_context() {
+        // Macro bodies can read variables from the calling context, not just
+        // their own parameters.
+        let tokens =
+            tokenize("{% macro label() %}{{ prefix }}!{% endmacro %}{{ label() }}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("prefix".to_string(), Value::String("Note".to_string()));
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "Note!");
+    }
+
+    #[test]
+    fn test_render_nodes_to_writer_matches_render_nodes() {
+        let tokens = tokenize("Hello {{ name }}!").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set("name".to_string(), Value::String("World".to_string()));
+
+        let mut buf = Vec::new();
+        render_nodes_to_writer(&nodes, &context, None::<&crate::NoOpTemplateLoader>, &mut buf)
+            .unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), "Hello World!");
+    }
+
+    #[test]
+    fn test_render_nodes_to_writer_buffers_spaceless_internally() {
+        let tokens =
+            tokenize("{% spaceless %}<p>\n  <b>text</b>\n</p>{% endspaceless %}").unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let context = Context::new();
+
+        let mut buf = Vec::new();
+        render_nodes_to_writer(&nodes, &context, None::<&crate::NoOpTemplateLoader>, &mut buf)
+            .unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), "<p><b>text</b></p>");
+    }
+
+    #[test]
+    fn test_get_value_recognizes_iso8601_with_offset() {
+        let context = Context::new();
+        let value = get_value("2024-01-15T10:30:00+03:00", &context).unwrap();
+        assert_eq!(
+            value,
+            Value::DateTime("2024-01-15T10:30:00+03:00".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_get_value_recognizes_iso8601_with_space_and_z() {
+        let context = Context::new();
+        let value = get_value("2024-01-15 10:30:00Z", &context).unwrap();
+        assert_eq!(
+            value,
+            Value::DateTime("2024-01-15T10:30:00+00:00".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_get_value_recognizes_iso8601_with_no_offset_as_utc() {
+        let context = Context::new();
+        let value = get_value("2024-01-15T10:30:00", &context).unwrap();
+        assert_eq!(
+            value,
+            Value::DateTime("2024-01-15T10:30:00+00:00".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_django_date_format_emits_offset_codes() {
+        let dt: chrono::DateTime<chrono::FixedOffset> =
+            "2024-01-15T10:30:00+03:00".parse().unwrap();
+        assert_eq!(django_date_format(&dt, "O", None), "+0300");
+        assert_eq!(django_date_format(&dt, "Z", None), "10800");
+    }
+
+    #[test]
+    fn test_django_date_format_c_round_trips_through_get_value() {
+        let context = Context::new();
+        let original: chrono::DateTime<chrono::FixedOffset> =
+            "2024-01-15T10:30:00+03:00".parse().unwrap();
+        let formatted = django_date_format(&original, "c", None);
+        let reparsed = get_value(&formatted, &context).unwrap();
+        assert_eq!(reparsed, Value::DateTime(original));
+    }
+
+    #[test]
+    fn test_for_loop_iterates_a_filtered_recurrence() {
+        let tokens = tokenize(
+            "{% for occ in start|recurrence:\"FREQ=DAILY;COUNT=3\" %}{{ occ|date:\"Y-m-d\" }} {% endfor %}",
+        )
+        .unwrap();
+        let nodes = parse(&tokens).unwrap();
+        let mut context = Context::new();
+        context.set(
+            "start".to_string(),
+            Value::DateTime("2024-01-01T09:00:00+00:00".parse().unwrap()),
+        );
+        let result = render_nodes(&nodes, &context).unwrap();
+        assert_eq!(result, "2024-01-01 2024-01-02 2024-01-03 ");
+    }
+
+    #[test]
+    fn test_django_date_format_r_round_trips_via_rfc2822() {
+        let original: chrono::DateTime<chrono::FixedOffset> =
+            "2024-01-15T10:30:00+03:00".parse().unwrap();
+        let formatted = django_date_format(&original, "r", None);
+        let reparsed = chrono::DateTime::parse_from_rfc2822(&formatted).unwrap();
+        assert_eq!(reparsed, original);
+    }
 }
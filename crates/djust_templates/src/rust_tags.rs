@@ -0,0 +1,102 @@
+//! Native Rust tag registry, parallel to the Python `CustomTag` handler.
+//!
+//! `Node::CustomTag` normally dispatches to `crate::registry::call_handler`,
+//! which calls into Python - fine for the pyo3 bridge, but it leaves
+//! pure-Rust embedders with no way to implement tags like `{% url %}` or
+//! `{% trans %}` without a Python interpreter. This module adds a
+//! process-global registry of [`TagHandler`] implementations, keyed by tag
+//! name, that the renderer consults before falling back to Python.
+
+use djust_core::{Context, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A native Rust implementation of a custom template tag.
+///
+/// Mirrors the shape of the Python handler: raw (already variable-resolved)
+/// arguments in, a rendered string out.
+pub trait TagHandler: Send + Sync {
+    fn render(&self, args: &[String], ctx: &Context) -> Result<String>;
+}
+
+impl<F> TagHandler for F
+where
+    F: Fn(&[String], &Context) -> Result<String> + Send + Sync,
+{
+    fn render(&self, args: &[String], ctx: &Context) -> Result<String> {
+        self(args, ctx)
+    }
+}
+
+static REGISTRY: Lazy<RwLock<HashMap<String, Box<dyn TagHandler>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Registers `handler` as the Rust-side implementation of `{% <name> ... %}`.
+/// Overwrites any handler previously registered under the same name.
+pub fn register_tag<H: TagHandler + 'static>(name: &str, handler: H) {
+    REGISTRY
+        .write()
+        .unwrap()
+        .insert(name.to_string(), Box::new(handler));
+}
+
+/// Removes the Rust-side handler registered under `name`, if any. Mostly
+/// useful for tests that need a clean registry between cases.
+pub fn unregister_tag(name: &str) {
+    REGISTRY.write().unwrap().remove(name);
+}
+
+/// Whether a Rust handler is registered for `name`.
+pub fn has_handler(name: &str) -> bool {
+    REGISTRY.read().unwrap().contains_key(name)
+}
+
+/// Renders `name` through its registered Rust handler, if one exists.
+/// Returns `None` when no handler is registered, so callers can fall back to
+/// the Python bridge.
+pub fn render_tag(name: &str, args: &[String], ctx: &Context) -> Option<Result<String>> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .get(name)
+        .map(|handler| handler.render(args, ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct UppercaseTag;
+
+    impl TagHandler for UppercaseTag {
+        fn render(&self, args: &[String], _ctx: &Context) -> Result<String> {
+            Ok(args.join(" ").to_uppercase())
+        }
+    }
+
+    #[test]
+    fn test_register_and_render_tag() {
+        register_tag("shout", UppercaseTag);
+        let ctx = Context::new();
+        let result = render_tag("shout", &["hi".to_string()], &ctx)
+            .expect("handler should be registered")
+            .unwrap();
+        assert_eq!(result, "HI");
+        unregister_tag("shout");
+    }
+
+    #[test]
+    fn test_render_tag_returns_none_when_unregistered() {
+        let ctx = Context::new();
+        assert!(render_tag("no_such_tag", &[], &ctx).is_none());
+    }
+
+    #[test]
+    fn test_has_handler() {
+        register_tag("probe", UppercaseTag);
+        assert!(has_handler("probe"));
+        unregister_tag("probe");
+        assert!(!has_handler("probe"));
+    }
+}
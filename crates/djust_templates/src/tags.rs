@@ -0,0 +1,31 @@
+//! Built-in template tag metadata.
+//!
+//! Tag *parsing* lives in `parser.rs` (it needs the token cursor) and tag
+//! *rendering* lives in `renderer.rs`. This module tracks which tag names
+//! are recognized as built-ins, so callers can tell a supported tag from
+//! one that will fall through to `Node::CustomTag`.
+
+/// Names of tags with dedicated parsing/rendering support.
+pub const BUILTIN_TAGS: &[&str] = &[
+    "if", "elif", "else", "endif", "for", "empty", "endfor", "block", "endblock", "with",
+    "endwith", "spaceless", "endspaceless", "extends", "include", "csrf_token", "static",
+    "widthratio", "firstof", "templatetag", "cycle", "now", "autoescape", "endautoescape",
+    "markdown", "endmarkdown", "macro", "endmacro", "import", "from",
+];
+
+/// Whether `name` is handled by the built-in parser/renderer rather than
+/// falling through to the custom-tag registry.
+pub fn is_builtin_tag(name: &str) -> bool {
+    BUILTIN_TAGS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_builtin_tag() {
+        assert!(is_builtin_tag("if"));
+        assert!(!is_builtin_tag("url"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Theme / variable-slot subsystem for `djust_components` rendering.
+//!
+//! A [`Theme`] is a flat map of named slots (`primary`, `secondary`,
+//! `surface`, `text`, plus the sizing slots `roundness`, `uiScale`,
+//! `fontSize`) resolved into CSS custom-property values. `renderer::
+//! render_rust_component` parses a component's `theme` prop (JSON of slot
+//! overrides) into one of these and stamps its `style_declarations()` onto
+//! the component's root tag, so a page can restyle a single widget without
+//! swapping the whole CSS framework.
+//!
+//! A slot's value can also be a `--slot,mod` reference (e.g.
+//! `"hover": "--primary,-10"`), which lightens (positive `mod`) or darkens
+//! (negative `mod`) the referenced slot's color by converting it to HSL,
+//! nudging lightness, and converting back - so a theme can declare relative
+//! variants without restating colors.
+
+use djust_core::{DjangoRustError, Result};
+use std::collections::HashMap;
+
+/// Lightness percentage points adjusted per unit of a `--slot,mod` modifier.
+const LIGHTNESS_STEP: f64 = 2.0;
+
+/// A resolved set of theme slots, built from defaults overridden by a
+/// `theme` prop's JSON object.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    slots: HashMap<String, String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let mut slots = HashMap::new();
+        slots.insert("primary".to_string(), "#0d6efd".to_string());
+        slots.insert("secondary".to_string(), "#6c757d".to_string());
+        slots.insert("surface".to_string(), "#ffffff".to_string());
+        slots.insert("text".to_string(), "#212529".to_string());
+        slots.insert("roundness".to_string(), "0.375rem".to_string());
+        slots.insert("uiScale".to_string(), "1".to_string());
+        slots.insert("fontSize".to_string(), "1rem".to_string());
+        Theme { slots }
+    }
+}
+
+impl Theme {
+    /// Parse a `theme` prop's JSON object of slot overrides (e.g.
+    /// `{"primary": "#ff0000", "hover": "--primary,-10"}`) on top of the
+    /// defaults.
+    pub fn from_json(json: &str) -> Result<Theme> {
+        let mut theme = Theme::default();
+        let overrides: HashMap<String, String> = serde_json::from_str(json)
+            .map_err(|e| DjangoRustError::TemplateError(format!("Invalid theme prop: {e}")))?;
+        for (slot, value) in overrides {
+            theme.slots.insert(slot, value);
+        }
+        Ok(theme)
+    }
+
+    /// Resolve a slot's final value, following a `--slot,mod` reference if
+    /// present.
+    pub fn resolve(&self, slot: &str) -> Option<String> {
+        let raw = self.slots.get(slot)?;
+        Some(resolve_reference(raw, &self.slots))
+    }
+
+    /// Render every slot as a `--dj-<slot>: <value>;` CSS custom-property
+    /// declaration, suitable for a `style="..."` attribute. Slots are
+    /// emitted in sorted order so the same theme always produces identical
+    /// output.
+    ///
+    /// Both the slot name and its resolved value can come straight from a
+    /// `theme` prop bound to user-controlled data, so each is escaped with
+    /// [`crate::filters::escape_double_quoted_attr`] the same way
+    /// `renderer::inject_disabled_attrs` escapes its `reason` text before
+    /// it lands in an attribute.
+    pub fn style_declarations(&self) -> String {
+        let mut names: Vec<&String> = self.slots.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                format!(
+                    "--dj-{}: {};",
+                    crate::filters::escape_double_quoted_attr(name),
+                    crate::filters::escape_double_quoted_attr(&self.resolve(name).unwrap_or_default())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Resolve a single slot value, following a `--slot,mod` reference if
+/// present (`mod` is a signed number of lightness steps applied to the
+/// referenced slot's color). Anything else passes through unchanged.
+fn resolve_reference(raw: &str, slots: &HashMap<String, String>) -> String {
+    let Some(rest) = raw.strip_prefix("--") else {
+        return raw.to_string();
+    };
+    let Some((slot_name, mod_str)) = rest.split_once(',') else {
+        return raw.to_string();
+    };
+    let Some(base) = slots.get(slot_name) else {
+        return raw.to_string();
+    };
+    let Ok(step) = mod_str.trim().parse::<f64>() else {
+        return raw.to_string();
+    };
+
+    match hex_to_hsl(base) {
+        Some((h, s, l)) => {
+            let new_l = (l + step * LIGHTNESS_STEP).clamp(0.0, 100.0);
+            hsl_to_hex(h, s, new_l)
+        }
+        None => raw.to_string(),
+    }
+}
+
+/// Convert a `#rrggbb` hex color to HSL (hue in degrees, saturation/
+/// lightness as percentages).
+fn hex_to_hsl(hex: &str) -> Option<(f64, f64, f64)> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()? as f64 / 255.0;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()? as f64 / 255.0;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()? as f64 / 255.0;
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+
+    if d.abs() < f64::EPSILON {
+        return Some((0.0, 0.0, l * 100.0));
+    }
+
+    let s = if l > 0.5 {
+        d / (2.0 - max - min)
+    } else {
+        d / (max + min)
+    };
+    let h = if max == r {
+        ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+
+    Some((h * 60.0, s * 100.0, l * 100.0))
+}
+
+/// Convert HSL back to a `#rrggbb` hex color.
+fn hsl_to_hex(h: f64, s: f64, l: f64) -> String {
+    let h = h.rem_euclid(360.0) / 360.0;
+    let s = s / 100.0;
+    let l = l / 100.0;
+
+    let (r, g, b) = if s == 0.0 {
+        (l, l, l)
+    } else {
+        let q = if l < 0.5 {
+            l * (1.0 + s)
+        } else {
+            l + s - l * s
+        };
+        let p = 2.0 * l - q;
+        (
+            hue_to_rgb(p, q, h + 1.0 / 3.0),
+            hue_to_rgb(p, q, h),
+            hue_to_rgb(p, q, h - 1.0 / 3.0),
+        )
+    };
+
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        (r * 255.0).round() as u8,
+        (g * 255.0).round() as u8,
+        (b * 255.0).round() as u8,
+    )
+}
+
+fn hue_to_rgb(p: f64, q: f64, t: f64) -> f64 {
+    let t = t.rem_euclid(1.0);
+    if t < 1.0 / 6.0 {
+        p + (q - p) * 6.0 * t
+    } else if t < 1.0 / 2.0 {
+        q
+    } else if t < 2.0 / 3.0 {
+        p + (q - p) * (2.0 / 3.0 - t) * 6.0
+    } else {
+        p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_theme_resolves_known_slots() {
+        let theme = Theme::default();
+        assert_eq!(theme.resolve("primary").unwrap(), "#0d6efd");
+        assert_eq!(theme.resolve("roundness").unwrap(), "0.375rem");
+    }
+
+    #[test]
+    fn test_from_json_overrides_defaults() {
+        let theme = Theme::from_json("{\"primary\": \"#ff0000\"}").unwrap();
+        assert_eq!(theme.resolve("primary").unwrap(), "#ff0000");
+        assert_eq!(theme.resolve("secondary").unwrap(), "#6c757d");
+    }
+
+    #[test]
+    fn test_from_json_rejects_invalid_json() {
+        assert!(Theme::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_slot_modifier_darkens_referenced_color() {
+        let theme = Theme::from_json("{\"hover\": \"--primary,-10\"}").unwrap();
+        let primary = theme.resolve("primary").unwrap();
+        let hover = theme.resolve("hover").unwrap();
+        assert_ne!(primary, hover);
+    }
+
+    #[test]
+    fn test_hex_to_hsl_round_trips_through_hsl_to_hex() {
+        let (h, s, l) = hex_to_hsl("#336699").unwrap();
+        assert_eq!(hsl_to_hex(h, s, l), "#336699");
+    }
+
+    #[test]
+    fn test_style_declarations_includes_every_slot() {
+        let theme = Theme::default();
+        let css = theme.style_declarations();
+        assert!(css.contains("--dj-primary: #0d6efd;"));
+        assert!(css.contains("--dj-uiScale: 1;"));
+    }
+
+    #[test]
+    fn test_style_declarations_escapes_attacker_controlled_slot_value() {
+        let theme =
+            Theme::from_json("{\"primary\": \"red\\\" onmouseover=\\\"alert(1)\"}").unwrap();
+        let css = theme.style_declarations();
+        assert!(!css.contains('"'));
+        assert!(!css.contains("onmouseover="));
+        assert!(css.contains("&quot;"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Length-budgeted HTML writer for safe previews/excerpts.
+//!
+//! Cutting rendered HTML with a raw `&str[..n]` can split a tag in half or
+//! leave elements unclosed. `TruncatedHtmlWriter` instead tracks a budget of
+//! *visible* text characters — tag markup is free — and closes any tags
+//! still open when the budget runs out, so the result is always
+//! well-formed and tag-balanced.
+
+/// Streaming budget writer: counts visible text against `max_chars` and
+/// keeps a LIFO stack of currently-open tag names so the fragment can always
+/// be closed cleanly.
+pub struct TruncatedHtmlWriter {
+    buf: String,
+    unclosed: Vec<String>,
+    length: usize,
+    max_chars: usize,
+    ellipsis: String,
+    ellipsis_emitted: bool,
+}
+
+impl TruncatedHtmlWriter {
+    pub fn new(max_chars: usize) -> Self {
+        Self::with_ellipsis(max_chars, "…")
+    }
+
+    pub fn with_ellipsis(max_chars: usize, ellipsis: impl Into<String>) -> Self {
+        Self {
+            buf: String::new(),
+            unclosed: Vec::new(),
+            length: 0,
+            max_chars,
+            ellipsis: ellipsis.into(),
+            ellipsis_emitted: false,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.length >= self.max_chars
+    }
+
+    /// Push `<name>` and remember it so a matching `close_tag` call can emit
+    /// `</name>` later, or so `finish` can close it if the budget trips first.
+    pub fn open_tag(&mut self, name: &str) {
+        if self.is_full() {
+            return;
+        }
+        self.unclosed.push(name.to_string());
+        self.buf.push('<');
+        self.buf.push_str(name);
+        self.buf.push('>');
+    }
+
+    /// Pop the most recently opened tag and emit its closing form.
+    pub fn close_tag(&mut self) {
+        if let Some(name) = self.unclosed.pop() {
+            self.buf.push_str("</");
+            self.buf.push_str(&name);
+            self.buf.push('>');
+        }
+    }
+
+    /// Append already-escaped visible text, counting an escaped character
+    /// entity like `&amp;` as a single unit so truncation never splits one.
+    /// A no-op once the budget is spent, aside from emitting the ellipsis
+    /// marker exactly once for the push that would have added content.
+    pub fn push(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.is_full() {
+            self.emit_ellipsis();
+            return;
+        }
+
+        for unit in entity_units(text) {
+            if self.is_full() {
+                self.emit_ellipsis();
+                break;
+            }
+            self.buf.push_str(unit);
+            self.length += 1;
+        }
+    }
+
+    fn emit_ellipsis(&mut self) {
+        if !self.ellipsis_emitted {
+            self.buf.push_str(&self.ellipsis);
+            self.ellipsis_emitted = true;
+        }
+    }
+
+    /// Finish the fragment, closing any still-open tags in LIFO order.
+    pub fn finish(mut self) -> String {
+        while !self.unclosed.is_empty() {
+            self.close_tag();
+        }
+        self.buf
+    }
+}
+
+/// Split `text` into units that each count as one visible character: an
+/// HTML character entity (`&...;`) counts as a single unit, everything else
+/// is one unit per `char`.
+fn entity_units(text: &str) -> Vec<&str> {
+    let mut units = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < text.len() {
+        if bytes[i] == b'&' {
+            if let Some(rel) = text[i..].find(';') {
+                let end = i + rel + 1;
+                let candidate = &text[i..end];
+                let body = &candidate[1..candidate.len() - 1];
+                if !body.is_empty() && body.len() <= 10 && body.chars().all(|c| c.is_alphanumeric() || c == '#') {
+                    units.push(candidate);
+                    i = end;
+                    continue;
+                }
+            }
+        }
+        let ch_len = text[i..].chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+        units.push(&text[i..i + ch_len]);
+        i += ch_len;
+    }
+    units
+}
+
+/// Re-scan already-rendered HTML and replay it through a
+/// [`TruncatedHtmlWriter`], producing a tag-balanced excerpt of at most
+/// `max_chars` visible characters.
+pub fn truncate_html(html: &str, max_chars: usize) -> String {
+    let mut writer = TruncatedHtmlWriter::new(max_chars);
+    let mut i = 0;
+
+    while i < html.len() {
+        if html.as_bytes()[i] == b'<' {
+            if let Some(rel_end) = html[i..].find('>') {
+                let end = i + rel_end + 1;
+                let tag = &html[i..end];
+                if let Some(name) = tag_name_of_closing(tag) {
+                    let _ = name;
+                    writer.close_tag();
+                } else if tag.ends_with("/>") {
+                    if !writer.is_full() {
+                        writer.buf.push_str(tag);
+                    }
+                } else if let Some(name) = tag_name_of_opening(tag) {
+                    writer.open_tag(&name);
+                }
+                i = end;
+                continue;
+            }
+        }
+
+        let next_lt = html[i..].find('<').map(|p| i + p).unwrap_or(html.len());
+        writer.push(&html[i..next_lt]);
+        i = next_lt;
+    }
+
+    writer.finish()
+}
+
+fn tag_name_of_closing(tag: &str) -> Option<String> {
+    let inner = tag.strip_prefix("</")?.strip_suffix('>')?;
+    Some(inner.trim().to_string())
+}
+
+fn tag_name_of_opening(tag: &str) -> Option<String> {
+    let inner = tag.strip_prefix('<')?.strip_suffix('>')?;
+    inner.split_whitespace().next().map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writer_closes_unclosed_tags_on_finish() {
+        let mut w = TruncatedHtmlWriter::new(100);
+        w.open_tag("span");
+        w.push("hello");
+        assert_eq!(w.finish(), "<span>hello</span>");
+    }
+
+    #[test]
+    fn writer_stops_pushing_once_budget_spent() {
+        let mut w = TruncatedHtmlWriter::new(3);
+        w.push("hello world");
+        assert_eq!(w.finish(), "hel…");
+    }
+
+    #[test]
+    fn writer_counts_entities_as_one_char() {
+        let mut w = TruncatedHtmlWriter::new(2);
+        w.push("&amp;b");
+        assert_eq!(w.finish(), "&amp;b");
+    }
+
+    #[test]
+    fn writer_no_ellipsis_when_exactly_at_budget() {
+        let mut w = TruncatedHtmlWriter::new(5);
+        w.push("hello");
+        assert_eq!(w.finish(), "hello");
+    }
+
+    #[test]
+    fn truncate_html_balances_nested_tags() {
+        let html = "<div><span>hello world this is long</span></div>";
+        let result = truncate_html(html, 5);
+        assert_eq!(result, "<div><span>hello…</span></div>");
+    }
+
+    #[test]
+    fn truncate_html_leaves_short_fragments_untouched() {
+        let html = "<p>short</p>";
+        assert_eq!(truncate_html(html, 100), html);
+    }
+
+    #[test]
+    fn truncate_html_handles_self_closing_tags() {
+        let html = "<p>a<br/>bcdef</p>";
+        let result = truncate_html(html, 2);
+        assert_eq!(result, "<p>a<br/>b…</p>");
+    }
+}
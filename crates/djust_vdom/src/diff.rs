@@ -14,6 +14,162 @@
 
 use crate::{vdom_trace, Patch, VNode};
 use std::collections::HashMap;
+use std::fmt;
+
+/// Size limits for [`try_diff_nodes`].
+///
+/// `diff_nodes` recurses once per VNode and allocates a `Vec<usize>` path
+/// per node visited, so an untrusted or accidentally huge server-rendered
+/// tree can blow the stack or exhaust memory before a single patch comes
+/// out. `try_diff_nodes` walks both trees against these limits first and
+/// bails with a [`DiffError`] instead of ever starting the real diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffLimits {
+    /// Maximum nesting depth allowed in either tree.
+    pub max_depth: usize,
+    /// Maximum number of nodes allowed in either tree.
+    pub max_nodes: usize,
+}
+
+impl Default for DiffLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 256,
+            max_nodes: 1_000_000,
+        }
+    }
+}
+
+/// Why [`try_diff_nodes`] refused to diff a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffError {
+    /// A tree nests deeper than `max_depth`.
+    DepthExceeded { max_depth: usize },
+    /// A tree has more nodes than `max_nodes`.
+    NodeBudgetExceeded { max_nodes: usize },
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::DepthExceeded { max_depth } => {
+                write!(f, "tree nests deeper than the limit of {max_depth}")
+            }
+            DiffError::NodeBudgetExceeded { max_nodes } => {
+                write!(f, "tree has more than the limit of {max_nodes} nodes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+/// Above this many moves in a single keyed-children reorder,
+/// [`diff_keyed_children`] stops emitting one `Patch::MoveChild` per
+/// relocated element and instead emits a single `Patch::ReorderChildren`
+/// carrying the whole final order. A handful of moves is cheapest applied
+/// directly; a big shuffle (e.g. reversing a long list) is cheaper for the
+/// client to realize in one `DocumentFragment`/`insertBefore` pass than by
+/// replaying dozens of individual index-shifting moves.
+pub const REORDER_THRESHOLD: usize = 4;
+
+/// Above this fraction of the new text's length, a changed-text-node diff
+/// falls back to a whole-string `Patch::SetText` instead of a
+/// `Patch::SpliceText`: once the edit touches most of the string, splicing
+/// saves nothing over retransmitting it and the prefix/suffix scan is just
+/// extra work for the client to undo.
+pub const SPLICE_TEXT_MAX_RATIO: f64 = 0.6;
+
+/// Diff two text-node contents, preferring a localized `Patch::SpliceText`
+/// (a `CharacterData.replaceData`-shaped edit) over a whole-string
+/// `Patch::SetText` when the changed region is small relative to `new` -
+/// the common case for streamed/appended tokens. Falls back to `SetText`
+/// when `new` is empty (nothing to take a ratio of) or the edit covers
+/// more than [`SPLICE_TEXT_MAX_RATIO`] of it, since at that point splicing
+/// saves nothing over just resending the string.
+fn splice_or_set_text(old: &str, new: &str, path: &[usize]) -> Patch {
+    let old_bytes = old.as_bytes();
+    let new_bytes = new.as_bytes();
+    let max_common = old_bytes.len().min(new_bytes.len());
+
+    let mut prefix = 0;
+    while prefix < max_common && old_bytes[prefix] == new_bytes[prefix] {
+        prefix += 1;
+    }
+    while prefix > 0 && !old.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let mut suffix = 0;
+    while suffix < max_common - prefix
+        && old_bytes[old_bytes.len() - 1 - suffix] == new_bytes[new_bytes.len() - 1 - suffix]
+    {
+        suffix += 1;
+    }
+    while suffix > 0 && (!old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix))
+    {
+        suffix -= 1;
+    }
+
+    let delete_count = old.len() - prefix - suffix;
+    let insert = &new[prefix..new.len() - suffix];
+
+    let splice_is_small = !new.is_empty()
+        && (delete_count + insert.len()) as f64 <= new.len() as f64 * SPLICE_TEXT_MAX_RATIO;
+
+    if splice_is_small {
+        Patch::SpliceText {
+            path: path.to_vec(),
+            d: None, // Text nodes don't have IDs
+            start: prefix,
+            delete_count,
+            insert: insert.to_string(),
+        }
+    } else {
+        Patch::SetText {
+            path: path.to_vec(),
+            d: None,
+            text: new.to_string(),
+        }
+    }
+}
+
+/// Check `node` (and its subtree) against `limits`, short-circuiting as soon
+/// as either bound is crossed rather than walking the whole tree first.
+fn check_limits(node: &VNode, depth: usize, limits: &DiffLimits, visited: &mut usize) -> Result<(), DiffError> {
+    if depth > limits.max_depth {
+        return Err(DiffError::DepthExceeded {
+            max_depth: limits.max_depth,
+        });
+    }
+
+    *visited += 1;
+    if *visited > limits.max_nodes {
+        return Err(DiffError::NodeBudgetExceeded {
+            max_nodes: limits.max_nodes,
+        });
+    }
+
+    for child in &node.children {
+        check_limits(child, depth + 1, limits, visited)?;
+    }
+
+    Ok(())
+}
+
+/// Fallible counterpart to [`diff_nodes`] for untrusted or potentially huge
+/// server-rendered trees: checks both trees against `limits` up front and
+/// returns a [`DiffError`] instead of running (and risking OOM/stack
+/// overflow in) the ordinary recursive diff.
+pub fn try_diff_nodes(old: &VNode, new: &VNode, limits: &DiffLimits) -> Result<Vec<Patch>, DiffError> {
+    let mut visited = 0;
+    check_limits(old, 0, limits, &mut visited)?;
+
+    let mut visited = 0;
+    check_limits(new, 0, limits, &mut visited)?;
+
+    Ok(diff_nodes(old, new, &[]))
+}
 
 /// Diff two VNodes and generate patches.
 ///
@@ -25,6 +181,27 @@ use std::collections::HashMap;
 /// exists in the client DOM. The new node may have different IDs if the server
 /// re-parsed the HTML with a reset ID counter.
 pub fn diff_nodes(old: &VNode, new: &VNode, path: &[usize]) -> Vec<Patch> {
+    // Static-hash short-circuit: both sides carry an `static_hash` cached at
+    // parse time (see `VNode::compute_static_hashes`), so an equal pair is an
+    // O(1) field comparison rather than the subtree walk `subtree_hash` below
+    // does. This is what lets the unchanged scaffolding around a template's
+    // dynamic slots skip diffing entirely instead of just short-circuiting
+    // faster - the hash was never recomputed at all this render.
+    if let (Some(old_hash), Some(new_hash)) = (old.static_hash, new.static_hash) {
+        if old_hash == new_hash {
+            vdom_trace!("diff_nodes: path={:?} - static hash match, skipping", path);
+            return Vec::new();
+        }
+    }
+
+    // Merkle-style short-circuit: if the two subtrees hash equal, every node
+    // in them is equal too, so skip the tag/attr/children walk (and all the
+    // patch-building allocations it would do) entirely.
+    if old.subtree_hash() == new.subtree_hash() {
+        vdom_trace!("diff_nodes: path={:?} - subtree hash match, skipping", path);
+        return Vec::new();
+    }
+
     let mut patches = Vec::new();
 
     // Use OLD node's djust_id for targeting - that's what's in the client DOM
@@ -70,10 +247,13 @@ pub fn diff_nodes(old: &VNode, new: &VNode, path: &[usize]) -> Vec<Patch> {
                     .map(|t| t.chars().take(50).collect::<String>())
             );
             if let Some(text) = &new.text {
-                patches.push(Patch::SetText {
-                    path: path.to_vec(),
-                    d: None, // Text nodes don't have IDs
-                    text: text.clone(),
+                patches.push(match &old.text {
+                    Some(old_text) => splice_or_set_text(old_text, text, path),
+                    None => Patch::SetText {
+                        path: path.to_vec(),
+                        d: None, // Text nodes don't have IDs
+                        text: text.clone(),
+                    },
                 });
             }
         }
@@ -83,19 +263,80 @@ pub fn diff_nodes(old: &VNode, new: &VNode, path: &[usize]) -> Vec<Patch> {
     // Diff attributes
     patches.extend(diff_attrs(old, new, path, &target_id));
 
+    // Diff listeners (same shape as attrs, but emits NewListener/RemoveListener
+    // so the client can wire/unwire DOM event delegation instead of treating
+    // a handler change like any other attribute mutation).
+    patches.extend(diff_listeners(old, new, path, &target_id));
+
     // Diff children (parent's djust_id is used for child operations)
     patches.extend(diff_children(old, new, path, &target_id));
 
     patches
 }
 
+fn diff_listeners(old: &VNode, new: &VNode, path: &[usize], target_id: &Option<String>) -> Vec<Patch> {
+    let mut patches = Vec::new();
+
+    // Removed or changed listeners
+    for (event, old_handler) in &old.listeners {
+        match new.listeners.get(event) {
+            None => {
+                patches.push(Patch::RemoveListener {
+                    path: path.to_vec(),
+                    d: target_id.clone(),
+                    event: event.clone(),
+                });
+            }
+            Some(new_handler) if new_handler != old_handler => {
+                patches.push(Patch::NewListener {
+                    path: path.to_vec(),
+                    d: target_id.clone(),
+                    event: event.clone(),
+                    handler: new_handler.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Newly added listeners
+    for (event, handler) in &new.listeners {
+        if !old.listeners.contains_key(event) {
+            patches.push(Patch::NewListener {
+                path: path.to_vec(),
+                d: target_id.clone(),
+                event: event.clone(),
+                handler: handler.clone(),
+            });
+        }
+    }
+
+    patches
+}
+
 fn diff_attrs(old: &VNode, new: &VNode, path: &[usize], target_id: &Option<String>) -> Vec<Patch> {
     let mut patches = Vec::new();
 
+    // An element can opt out of token-level class diffing (e.g. one whose
+    // class string is itself a single interpolated value the caller wants
+    // replaced atomically) by carrying this reserved attribute, the same
+    // way `data-dj-id` is a parser-managed marker rather than real element
+    // state. When present, `class` falls back to an ordinary whole-string
+    // SetAttr/RemoveAttr like any other attribute.
+    let raw_class = new.attrs.contains_key("data-dj-raw-class");
+
     // Find removed and changed attributes
     for (key, old_value) in &old.attrs {
         // Skip data-dj-id attribute - it's managed by the parser and shouldn't generate patches
-        if key == "data-dj-id" {
+        if key == "data-dj-id" || key == "data-dj-raw-class" {
+            continue;
+        }
+
+        // `class` is diffed token-by-token below instead of as one opaque
+        // string - most class changes only toggle a single Bootstrap-style
+        // utility class (`is-invalid`, `d-none`), and a whole-string SetAttr
+        // would both bloat the patch and clobber classes added client-side.
+        if key == "class" && !raw_class {
             continue;
         }
 
@@ -122,7 +363,11 @@ fn diff_attrs(old: &VNode, new: &VNode, path: &[usize], target_id: &Option<Strin
     // Find added attributes
     for (key, new_value) in &new.attrs {
         // Skip data-dj-id attribute
-        if key == "data-dj-id" {
+        if key == "data-dj-id" || key == "data-dj-raw-class" {
+            continue;
+        }
+
+        if key == "class" && !raw_class {
             continue;
         }
 
@@ -136,6 +381,55 @@ fn diff_attrs(old: &VNode, new: &VNode, path: &[usize], target_id: &Option<Strin
         }
     }
 
+    if !raw_class {
+        patches.extend(diff_class(old, new, path, target_id));
+    }
+
+    patches
+}
+
+/// Tokenize the `class` attribute on whitespace and diff the two token
+/// lists as ordered sets, emitting one `RemoveClass` per dropped token (in
+/// old order) and one `AddClass` per added token (in new order) instead of
+/// a single `SetAttr` for the whole string. Only called by [`diff_attrs`]
+/// when `new` hasn't opted out via `data-dj-raw-class`.
+fn diff_class(old: &VNode, new: &VNode, path: &[usize], target_id: &Option<String>) -> Vec<Patch> {
+    let mut patches = Vec::new();
+
+    let old_classes: Vec<&str> = old
+        .attrs
+        .get("class")
+        .map(|v| v.split_whitespace().collect())
+        .unwrap_or_default();
+    let new_classes: Vec<&str> = new
+        .attrs
+        .get("class")
+        .map(|v| v.split_whitespace().collect())
+        .unwrap_or_default();
+
+    let new_set: std::collections::HashSet<&str> = new_classes.iter().copied().collect();
+    let old_set: std::collections::HashSet<&str> = old_classes.iter().copied().collect();
+
+    for class in &old_classes {
+        if !new_set.contains(class) {
+            patches.push(Patch::RemoveClass {
+                path: path.to_vec(),
+                d: target_id.clone(),
+                class: class.to_string(),
+            });
+        }
+    }
+
+    for class in &new_classes {
+        if !old_set.contains(class) {
+            patches.push(Patch::AddClass {
+                path: path.to_vec(),
+                d: target_id.clone(),
+                class: class.to_string(),
+            });
+        }
+    }
+
     patches
 }
 
@@ -163,31 +457,92 @@ fn diff_children(
         return replace_all_children(old, new, path, parent_id);
     }
 
-    // Check if we can use keyed diffing
-    let has_keys = new.children.iter().any(|n| n.key.is_some());
+    // `children_fully_keyed` is cached on both lists (see VNode), so a
+    // fully keyed pair never pays the O(n) "does any child have a key?"
+    // scan in `diff_child_list`; any other pair, fully unkeyed included,
+    // still runs that scan over the new children.
+    let fully_keyed = old.children_fully_keyed && new.children_fully_keyed;
+    patches.extend(diff_child_list(
+        &old.children,
+        &new.children,
+        path,
+        parent_id,
+        fully_keyed,
+    ));
+
+    patches
+}
+
+/// Diff two fragments - sibling lists with no wrapping element, the shape a
+/// component renders when it returns multiple root nodes (Dioxus calls this
+/// a `VFragment`). `parent_path`/`parent_id` name the *real* DOM container
+/// the fragment's roots live under (there's no synthetic element to carry
+/// that addressing the way a normal node's patches do), so every emitted
+/// `InsertChild`/`RemoveChild`/`MoveChild` resolves against it exactly like
+/// a regular child list would.
+pub fn diff_fragments(
+    old: &[VNode],
+    new: &[VNode],
+    parent_path: &[usize],
+    parent_id: &Option<String>,
+) -> Vec<Patch> {
+    let fully_keyed = old.iter().all(|n| n.key.is_some()) && new.iter().all(|n| n.key.is_some());
+    diff_child_list(old, new, parent_path, parent_id, fully_keyed)
+}
+
+/// The body of [`diff_children`], minus the `data-djust-replace`
+/// whole-list-replace check: that check needs the parent `VNode`'s attrs,
+/// which a raw child slice doesn't carry. Exposed so
+/// [`crate::template::diff_templated`] can diff a template's "child-list
+/// hole" (e.g. a `{% for %}` body), and so [`diff_fragments`] can diff a
+/// fragment's root list, without either needing a synthetic parent element
+/// to hang it off of.
+pub(crate) fn diff_child_list(
+    old_children: &[VNode],
+    new_children: &[VNode],
+    path: &[usize],
+    parent_id: &Option<String>,
+    fully_keyed: bool,
+) -> Vec<Patch> {
+    let mut patches = Vec::new();
+    let has_keys = fully_keyed || new_children.iter().any(|n| n.key.is_some());
 
     vdom_trace!(
-        "diff_children: path={:?} parent_id={:?} old_children={} new_children={} has_keys={}",
+        "diff_child_list: path={:?} parent_id={:?} old_children={} new_children={} has_keys={} fully_keyed={}",
         path,
         parent_id,
-        old.children.len(),
-        new.children.len(),
-        has_keys
+        old_children.len(),
+        new_children.len(),
+        has_keys,
+        fully_keyed
     );
 
     if has_keys {
-        vdom_trace!("  Using KEYED diffing");
+        // A duplicate key on either side corrupts the key->index maps that
+        // `diff_keyed_children` builds (the later duplicate silently wins,
+        // so moves/removals can target the wrong child). Rather than risk
+        // a mismatched patch, fall back to replacing the whole list.
+        if has_duplicate_keys(old_children) || has_duplicate_keys(new_children) {
+            vdom_trace!(
+                "diff_child_list: parent_id={:?} - duplicate keys detected, falling back to REPLACE",
+                parent_id
+            );
+            return replace_all_children_slice(old_children, new_children, path, parent_id);
+        }
+
+        vdom_trace!("  Using KEYED diffing (fully_keyed={})", fully_keyed);
         patches.extend(diff_keyed_children(
-            &old.children,
-            &new.children,
+            old_children,
+            new_children,
             path,
             parent_id,
+            fully_keyed,
         ));
     } else {
         vdom_trace!("  Using INDEXED diffing");
         patches.extend(diff_indexed_children(
-            &old.children,
-            &new.children,
+            old_children,
+            new_children,
             path,
             parent_id,
         ));
@@ -196,11 +551,202 @@ fn diff_children(
     patches
 }
 
+/// True if two or more children in `nodes` share the same key; children
+/// without a key are not compared.
+fn has_duplicate_keys(nodes: &[VNode]) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    nodes
+        .iter()
+        .filter_map(|n| n.key.as_ref())
+        .any(|k| !seen.insert(k))
+}
+
+/// A node's identity signature when it has no explicit `key`: its tag plus
+/// attribute set, excluding the parser-assigned `data-dj`/`data-dj-id`
+/// instance id (same exclusion [`VNode::compute_static_hashes`] applies,
+/// since that id is per-render and carries no semantic meaning of its
+/// own). Two unkeyed nodes with equal fingerprints are treated as
+/// plausibly "the same element" by [`rescue_tag_mismatched_siblings`].
+fn fingerprint(node: &VNode) -> (&str, Vec<(&str, &str)>) {
+    let mut attrs: Vec<(&str, &str)> = node
+        .attrs
+        .iter()
+        .filter(|(k, _)| k.as_str() != "data-dj" && k.as_str() != "data-dj-id")
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    attrs.sort_unstable();
+    (node.tag.as_str(), attrs)
+}
+
+/// Rescue unkeyed siblings whose tag differs at a shared index - the one
+/// case where diffing them in place would force a full [`Patch::Replace`]
+/// and discard the entire old subtree. `indices` names every such
+/// position; before giving up on any of them, look for a match among the
+/// *other* mismatched positions: an exact match (the node reappears
+/// verbatim elsewhere, so it just moved) first, then a `(tag, attrs)`
+/// [`fingerprint`] match (not identical, but plausibly the same element
+/// having both moved and changed) - either becomes a `MoveChild` plus an
+/// in-place diff instead of a destroy-and-recreate. A slot that still
+/// can't be matched but hasn't moved - i.e. it's the only mismatch left
+/// at that index on both sides - falls back to the plain `Replace`
+/// `diff_nodes` would have produced; only a slot whose partner *did* move
+/// away falls back further, to a `RemoveChild`/`InsertChild` pair.
+fn rescue_tag_mismatched_siblings(
+    indices: &[usize],
+    old: &[VNode],
+    new: &[VNode],
+    path: &[usize],
+    parent_id: &Option<String>,
+) -> Vec<Patch> {
+    let mut patches = Vec::new();
+    let mut moves = Vec::new();
+    let mut old_remaining: Vec<usize> = indices.to_vec();
+    let mut new_remaining: Vec<usize> = indices.to_vec();
+
+    // Tier 1: the exact same node (content and all) reappears at another
+    // mismatched slot - it didn't change, it moved.
+    new_remaining.retain(|&new_idx| {
+        let claim = old_remaining
+            .iter()
+            .position(|&old_idx| old[old_idx] == new[new_idx]);
+        match claim {
+            Some(pos) => {
+                let old_idx = old_remaining.remove(pos);
+                if old_idx != new_idx {
+                    vdom_trace!(
+                        "  MOVE (exact match, rescued from Replace) from {} to {}",
+                        old_idx,
+                        new_idx
+                    );
+                    moves.push(Patch::MoveChild {
+                        path: path.to_vec(),
+                        d: parent_id.clone(),
+                        from: old_idx,
+                        to: new_idx,
+                    });
+                }
+                false
+            }
+            None => true,
+        }
+    });
+
+    // Tier 2: not identical, but the same `(tag, attrs)` fingerprint -
+    // move-and-diff rather than replace.
+    new_remaining.retain(|&new_idx| {
+        let new_fp = fingerprint(&new[new_idx]);
+        let claim = old_remaining
+            .iter()
+            .position(|&old_idx| fingerprint(&old[old_idx]) == new_fp);
+        match claim {
+            Some(pos) => {
+                let old_idx = old_remaining.remove(pos);
+                if old_idx != new_idx {
+                    vdom_trace!(
+                        "  MOVE (fingerprint match, rescued from Replace) from {} to {}",
+                        old_idx,
+                        new_idx
+                    );
+                    moves.push(Patch::MoveChild {
+                        path: path.to_vec(),
+                        d: parent_id.clone(),
+                        from: old_idx,
+                        to: new_idx,
+                    });
+                }
+                let mut child_path = path.to_vec();
+                child_path.push(new_idx);
+                patches.extend(diff_nodes(&old[old_idx], &new[new_idx], &child_path));
+                false
+            }
+            None => true,
+        }
+    });
+
+    // Nothing left to rescue at that index specifically. If it's still
+    // sitting at the same slot on both sides, nobody else in the mismatch
+    // pool claimed it either way - that's exactly the plain tag-change
+    // `diff_nodes` would have turned into a `Replace`, so emit the same
+    // patch rather than a gratuitous remove/insert pair. Only a slot whose
+    // partner moved elsewhere falls back to remove+insert.
+    let new_set: std::collections::HashSet<usize> = new_remaining.iter().copied().collect();
+    let mut replaced = std::collections::HashSet::new();
+    for &idx in &old_remaining {
+        if new_set.contains(&idx) {
+            replaced.insert(idx);
+            let mut child_path = path.to_vec();
+            child_path.push(idx);
+            patches.push(Patch::Replace {
+                path: child_path,
+                d: old[idx].djust_id.clone(),
+                node: new[idx].clone(),
+            });
+        }
+    }
+    old_remaining.retain(|i| !replaced.contains(i));
+    new_remaining.retain(|i| !replaced.contains(i));
+
+    old_remaining.sort_unstable_by(|a, b| b.cmp(a));
+    for old_idx in old_remaining {
+        patches.push(Patch::RemoveChild {
+            path: path.to_vec(),
+            d: parent_id.clone(),
+            index: old_idx,
+        });
+    }
+    for new_idx in new_remaining {
+        patches.push(Patch::InsertChild {
+            path: path.to_vec(),
+            d: parent_id.clone(),
+            index: new_idx,
+            node: new[new_idx].clone(),
+        });
+    }
+
+    patches.extend(moves);
+    patches
+}
+
+/// Indices (into `seq`) belonging to the longest strictly increasing
+/// subsequence of `seq`, found in O(n log n) via the patience-sorting
+/// `tails` method.
+///
+/// `diff_keyed_children` uses this to decide which surviving keyed children
+/// are already in relative order (and so need no `MoveChild`) and which
+/// aren't (and need exactly one move each) - the provably minimal move set.
+fn longest_increasing_subsequence(seq: &[usize]) -> std::collections::HashSet<usize> {
+    // `tails[k]` is the index into `seq` of the smallest tail value among all
+    // increasing subsequences of length k + 1 found so far.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; seq.len()];
+
+    for i in 0..seq.len() {
+        let pos = tails.partition_point(|&t| seq[t] < seq[i]);
+        if pos > 0 {
+            prev[i] = Some(tails[pos - 1]);
+        }
+        if pos == tails.len() {
+            tails.push(i);
+        } else {
+            tails[pos] = i;
+        }
+    }
+
+    let mut lis_indices = std::collections::HashSet::new();
+    let mut cur = tails.last().copied();
+    while let Some(i) = cur {
+        lis_indices.insert(i);
+        cur = prev[i];
+    }
+    lis_indices
+}
+
 fn diff_keyed_children(
     old: &[VNode],
     new: &[VNode],
     path: &[usize],
     parent_id: &Option<String>,
+    fully_keyed: bool,
 ) -> Vec<Patch> {
     let mut patches = Vec::new();
 
@@ -241,7 +787,17 @@ fn diff_keyed_children(
     let mut processed_new_indices: std::collections::HashSet<usize> =
         std::collections::HashSet::new();
 
-    // Find keyed nodes to add, move, or diff
+    // Find new keyed nodes to insert, and collect the keyed nodes that
+    // survive into `matches` (in new-child order) instead of emitting their
+    // MoveChild right away - we need the full sequence first to compute the
+    // minimal move set below.
+    struct KeyedMatch<'a> {
+        new_idx: usize,
+        old_idx: usize,
+        key: &'a str,
+    }
+    let mut matches: Vec<KeyedMatch> = Vec::new();
+
     for (new_idx, new_node) in new.iter().enumerate() {
         if let Some(key) = &new_node.key {
             processed_new_indices.insert(new_idx);
@@ -258,74 +814,143 @@ fn diff_keyed_children(
                 }
                 Some(&old_idx) => {
                     processed_old_indices.insert(old_idx);
+                    matches.push(KeyedMatch {
+                        new_idx,
+                        old_idx,
+                        key,
+                    });
+                }
+            }
+        }
+    }
 
-                    // Existing keyed node - check if it moved
-                    if old_idx != new_idx {
-                        vdom_trace!("  MOVE key={} from {} to {}", key, old_idx, new_idx);
-                        patches.push(Patch::MoveChild {
-                            path: path.to_vec(),
-                            d: parent_id.clone(),
-                            from: old_idx,
-                            to: new_idx,
-                        });
-                    }
+    // `seq[j]` is the old index of the j-th surviving keyed child in new
+    // order. Children whose position is in its LIS are already in relative
+    // order and don't need to move; everything else gets exactly one
+    // MoveChild - the minimal possible number of moves.
+    let seq: Vec<usize> = matches.iter().map(|m| m.old_idx).collect();
+    let lis = longest_increasing_subsequence(&seq);
+    let move_count = matches.len() - lis.len();
+
+    // Past REORDER_THRESHOLD moves, skip the per-element MoveChild stream
+    // entirely and hand the client the whole final order in one patch - see
+    // REORDER_THRESHOLD's doc comment for why that's a win above the cutoff
+    // but not below it.
+    let batch_reorder = move_count > REORDER_THRESHOLD;
+    if batch_reorder {
+        let order: Vec<String> = matches.iter().map(|m| m.key.to_string()).collect();
+        vdom_trace!(
+            "  REORDER {} children in one batch ({} moves > threshold {})",
+            order.len(),
+            move_count,
+            REORDER_THRESHOLD
+        );
+        patches.push(Patch::ReorderChildren {
+            path: path.to_vec(),
+            d: parent_id.clone(),
+            order,
+        });
+    }
+
+    for (i, m) in matches.iter().enumerate() {
+        if !batch_reorder && !lis.contains(&i) {
+            vdom_trace!("  MOVE key={} from {} to {}", m.key, m.old_idx, m.new_idx);
+            patches.push(Patch::MoveChild {
+                path: path.to_vec(),
+                d: parent_id.clone(),
+                from: m.old_idx,
+                to: m.new_idx,
+            });
+        }
+
+        // Diff the keyed node itself
+        vdom_trace!(
+            "  DIFF key={} old_idx={} new_idx={}",
+            m.key,
+            m.old_idx,
+            m.new_idx
+        );
+        let mut child_path = path.to_vec();
+        child_path.push(m.new_idx);
+        patches.extend(diff_nodes(&old[m.old_idx], &new[m.new_idx], &child_path));
+    }
 
-                    // Diff the keyed node itself
-                    vdom_trace!("  DIFF key={} old_idx={} new_idx={}", key, old_idx, new_idx);
-                    let mut child_path = path.to_vec();
-                    child_path.push(new_idx);
-                    patches.extend(diff_nodes(&old[old_idx], new_node, &child_path));
+    // When both lists are known fully keyed (`fully_keyed`), there are no
+    // unkeyed children to scan for - skip straight past this bookkeeping,
+    // which is the whole point of the fast path.
+    if !fully_keyed {
+        // IMPORTANT: Also diff unkeyed children by index position
+        // This fixes the bug where unkeyed children were being skipped entirely
+        let mut mismatched_indices = Vec::new();
+        for (new_idx, new_node) in new.iter().enumerate() {
+            if new_node.key.is_none() && !processed_new_indices.contains(&new_idx) {
+                // This is an unkeyed child in new
+                if new_idx < old.len()
+                    && old[new_idx].key.is_none()
+                    && !processed_old_indices.contains(&new_idx)
+                {
+                    // There's a corresponding unkeyed child in old at the same index
+                    processed_old_indices.insert(new_idx);
+                    if old[new_idx].tag != new_node.tag {
+                        // A tag change here would force a full Replace -
+                        // stage it for rescue_tag_mismatched_siblings
+                        // instead, which gets one more chance to spot it
+                        // as a sibling that moved rather than one that was
+                        // destroyed and replaced.
+                        mismatched_indices.push(new_idx);
+                    } else {
+                        vdom_trace!("  DIFF unkeyed at index {}", new_idx);
+                        let mut child_path = path.to_vec();
+                        child_path.push(new_idx);
+                        patches.extend(diff_nodes(&old[new_idx], new_node, &child_path));
+                    }
+                } else {
+                    // No corresponding unkeyed child in old - insert it
+                    vdom_trace!("  INSERT unkeyed at index {}", new_idx);
+                    patches.push(Patch::InsertChild {
+                        path: path.to_vec(),
+                        d: parent_id.clone(),
+                        index: new_idx,
+                        node: new_node.clone(),
+                    });
                 }
             }
         }
-    }
 
-    // IMPORTANT: Also diff unkeyed children by index position
-    // This fixes the bug where unkeyed children were being skipped entirely
-    for (new_idx, new_node) in new.iter().enumerate() {
-        if new_node.key.is_none() && !processed_new_indices.contains(&new_idx) {
-            // This is an unkeyed child in new
-            if new_idx < old.len()
-                && old[new_idx].key.is_none()
-                && !processed_old_indices.contains(&new_idx)
+        if !mismatched_indices.is_empty() {
+            patches.extend(rescue_tag_mismatched_siblings(
+                &mismatched_indices,
+                old,
+                new,
+                path,
+                parent_id,
+            ));
+        }
+
+        // Remove unkeyed children from old that don't have corresponding children in new
+        for (old_idx, old_node) in old.iter().enumerate() {
+            if old_node.key.is_none()
+                && !processed_old_indices.contains(&old_idx)
+                && (old_idx >= new.len() || new[old_idx].key.is_some())
             {
-                // There's a corresponding unkeyed child in old at the same index
-                processed_old_indices.insert(new_idx);
-                vdom_trace!("  DIFF unkeyed at index {}", new_idx);
-                let mut child_path = path.to_vec();
-                child_path.push(new_idx);
-                patches.extend(diff_nodes(&old[new_idx], new_node, &child_path));
-            } else {
-                // No corresponding unkeyed child in old - insert it
-                vdom_trace!("  INSERT unkeyed at index {}", new_idx);
-                patches.push(Patch::InsertChild {
+                vdom_trace!("  REMOVE unkeyed at index {}", old_idx);
+                patches.push(Patch::RemoveChild {
                     path: path.to_vec(),
                     d: parent_id.clone(),
-                    index: new_idx,
-                    node: new_node.clone(),
+                    index: old_idx,
                 });
             }
         }
     }
 
-    // Remove unkeyed children from old that don't have corresponding children in new
-    for (old_idx, old_node) in old.iter().enumerate() {
-        if old_node.key.is_none()
-            && !processed_old_indices.contains(&old_idx)
-            && (old_idx >= new.len() || new[old_idx].key.is_some())
-        {
-            vdom_trace!("  REMOVE unkeyed at index {}", old_idx);
-            patches.push(Patch::RemoveChild {
-                path: path.to_vec(),
-                d: parent_id.clone(),
-                index: old_idx,
-            });
-        }
-    }
-
     patches
 }
 
+/// Diff two unkeyed child lists by position. Common-range children are
+/// diffed in place; children beyond the shorter list's length are removed
+/// or inserted, except that a removed subtree whose content hash matches an
+/// inserted one becomes a single `MoveChild` (see the staging below) rather
+/// than a delete + full insert.
 fn diff_indexed_children(
     old: &[VNode],
     new: &[VNode],
@@ -343,10 +968,13 @@ fn diff_indexed_children(
         old_len.min(new_len)
     );
 
-    // Diff common children
+    // Diff common children in place - except a tag mismatch, which on its
+    // own would force `diff_nodes` to emit a full Replace and discard the
+    // entire old subtree. Stage those indices for
+    // rescue_tag_mismatched_siblings instead, which gets one more chance
+    // to recognize the node as having moved rather than been destroyed.
+    let mut mismatched_indices = Vec::new();
     for i in 0..old_len.min(new_len) {
-        let mut child_path = path.to_vec();
-        child_path.push(i);
         vdom_trace!(
             "  Comparing child[{}]: old=<{}> (id={:?}) vs new=<{}> (id={:?})",
             i,
@@ -355,52 +983,97 @@ fn diff_indexed_children(
             new[i].tag,
             new[i].djust_id
         );
+        if old[i].tag != new[i].tag {
+            mismatched_indices.push(i);
+            continue;
+        }
+        let mut child_path = path.to_vec();
+        child_path.push(i);
         patches.extend(diff_nodes(&old[i], &new[i], &child_path));
     }
 
-    // Remove extra old children
-    if old_len > new_len {
-        vdom_trace!(
-            "  Removing {} extra children (indices {}-{})",
-            old_len - new_len,
-            new_len,
-            old_len - 1
-        );
-        for i in (new_len..old_len).rev() {
-            vdom_trace!("    RemoveChild index={} parent_id={:?}", i, parent_id);
-            patches.push(Patch::RemoveChild {
-                path: path.to_vec(),
-                d: parent_id.clone(),
-                index: i,
-            });
-        }
+    if !mismatched_indices.is_empty() {
+        patches.extend(rescue_tag_mismatched_siblings(
+            &mismatched_indices,
+            old,
+            new,
+            path,
+            parent_id,
+        ));
     }
 
-    // Add new children
+    // The tail of old beyond new's length would simply be removed, and the
+    // tail of new beyond old's length would simply be inserted - but before
+    // committing to that, check whether a removed subtree's content (by
+    // `subtree_hash`) reappears among the inserted ones. If so, it was cut
+    // from one spot and dropped back in elsewhere rather than actually
+    // changed, so emit a single MoveChild instead of a delete + full insert.
+    // `unconsumed_removed` tracks which removal candidates haven't already
+    // been claimed by an earlier insertion slot, so each old subtree is
+    // matched at most once.
+    let mut unconsumed_removed: Vec<usize> = if old_len > new_len {
+        (new_len..old_len).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut moves = Vec::new();
+    let mut inserts = Vec::new();
+
     if new_len > old_len {
-        vdom_trace!(
-            "  Adding {} new children (indices {}-{})",
-            new_len - old_len,
-            old_len,
-            new_len - 1
-        );
         #[allow(clippy::needless_range_loop)]
         for i in old_len..new_len {
-            vdom_trace!(
-                "    InsertChild index={} tag=<{}> parent_id={:?}",
-                i,
-                new[i].tag,
-                parent_id
-            );
-            patches.push(Patch::InsertChild {
-                path: path.to_vec(),
-                d: parent_id.clone(),
-                index: i,
-                node: new[i].clone(),
-            });
-        }
+            let new_node = &new[i];
+            let new_hash = new_node.subtree_hash();
+            let claim = unconsumed_removed
+                .iter()
+                .position(|&old_idx| old[old_idx].subtree_hash() == new_hash && old[old_idx] == *new_node);
+
+            if let Some(pos) = claim {
+                let old_idx = unconsumed_removed.remove(pos);
+                vdom_trace!(
+                    "    MOVE (content hash match) from old_idx={} to new_idx={} parent_id={:?}",
+                    old_idx,
+                    i,
+                    parent_id
+                );
+                moves.push(Patch::MoveChild {
+                    path: path.to_vec(),
+                    d: parent_id.clone(),
+                    from: old_idx,
+                    to: i,
+                });
+            } else {
+                vdom_trace!(
+                    "    InsertChild index={} tag=<{}> parent_id={:?}",
+                    i,
+                    new_node.tag,
+                    parent_id
+                );
+                inserts.push(Patch::InsertChild {
+                    path: path.to_vec(),
+                    d: parent_id.clone(),
+                    index: i,
+                    node: new_node.clone(),
+                });
+            }
+        }
     }
 
+    // Whatever wasn't claimed as a move target is a genuine removal -
+    // highest index first so earlier removals don't shift the indices the
+    // later ones still need.
+    for old_idx in unconsumed_removed.into_iter().rev() {
+        vdom_trace!("    RemoveChild index={} parent_id={:?}", old_idx, parent_id);
+        patches.push(Patch::RemoveChild {
+            path: path.to_vec(),
+            d: parent_id.clone(),
+            index: old_idx,
+        });
+    }
+    patches.extend(moves);
+    patches.extend(inserts);
+
     patches
 }
 
@@ -415,10 +1088,22 @@ fn replace_all_children(
     new: &VNode,
     path: &[usize],
     parent_id: &Option<String>,
+) -> Vec<Patch> {
+    replace_all_children_slice(&old.children, &new.children, path, parent_id)
+}
+
+/// The slice-taking body of [`replace_all_children`] - see
+/// [`diff_child_list`] for why this needs to exist independent of a parent
+/// `VNode`.
+fn replace_all_children_slice(
+    old_children: &[VNode],
+    new_children: &[VNode],
+    path: &[usize],
+    parent_id: &Option<String>,
 ) -> Vec<Patch> {
     let mut patches = Vec::new();
-    let old_len = old.children.len();
-    let new_len = new.children.len();
+    let old_len = old_children.len();
+    let new_len = new_children.len();
 
     vdom_trace!(
         "replace_all_children: removing {} old, inserting {} new",
@@ -438,22 +1123,279 @@ fn replace_all_children(
 
     // Insert all new children
     for i in 0..new_len {
-        vdom_trace!("  InsertChild index={} tag=<{}>", i, new.children[i].tag);
+        vdom_trace!(
+            "  InsertChild index={} tag=<{}>",
+            i,
+            new_children[i].tag
+        );
         patches.push(Patch::InsertChild {
             path: path.to_vec(),
             d: parent_id.clone(),
             index: i,
-            node: new.children[i].clone(),
+            node: new_children[i].clone(),
         });
     }
 
     patches
 }
 
+/// One pending node-pair comparison on a [`DiffSession`]'s work stack - the
+/// same `(old, new, path)` triple `diff_nodes` would otherwise pass down
+/// through a recursive call.
+struct Frame<'a> {
+    old: &'a VNode,
+    new: &'a VNode,
+    path: Vec<usize>,
+}
+
+/// Result of a single [`DiffSession::step`] call. Both variants carry only
+/// the patches produced *during this call* - concatenating every `step`
+/// call's patches, in order, reproduces what a one-shot
+/// `diff_nodes(old, new, &[])` call would have returned. Returning a slice
+/// instead of the whole accumulated history keeps a budgeted loop's total
+/// clone cost linear in the number of patches rather than quadratic in the
+/// number of steps.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffProgress {
+    /// `budget` ran out with frames still on the stack. Call `step` again
+    /// (with a fresh budget) to keep going.
+    Yielded(Vec<Patch>),
+    /// The stack emptied inside this call; there is no more work to do.
+    Done(Vec<Patch>),
+}
+
+/// A resumable counterpart to [`diff_nodes`]: rather than recursing to
+/// completion, pending work is held as an explicit stack of node-pair
+/// frames - the same call-stack-escaping trick Dioxus's diffing engine
+/// uses - so a caller can process a huge tree in budgeted slices,
+/// interleaving a large diff with other requests on a server or yielding
+/// to a WASM event loop between frames, instead of blocking until the
+/// whole tree is walked.
+///
+/// Sibling comparisons in a plain child list - same length, no keys, same
+/// tag at every index - are pushed back onto the stack one frame per
+/// child rather than diffed immediately, so a wide, shallow tree (the
+/// common shape for a large page: many rows, cells, list items) actually
+/// pauses and resumes between them. A child list that needs real
+/// reconciliation - a keyed diff, a length or tag-shape change, a
+/// `data-djust-replace` reset - is still resolved by [`diff_children`] in
+/// one go: its key-matching and fingerprint-rescue passes aren't
+/// meaningfully resumable mid-pass, so that slice of work isn't split any
+/// further (`step`'s `budget` counts it as a single unit, however large
+/// the list behind it is).
+pub struct DiffSession<'a> {
+    stack: Vec<Frame<'a>>,
+    patches: Vec<Patch>,
+}
+
+impl<'a> DiffSession<'a> {
+    /// Start a session diffing `old` into `new`, with `root_path` as the
+    /// path prefix every emitted patch is relative to (usually `&[]`, the
+    /// same default one-shot `diff_nodes` callers pass).
+    pub fn new(old: &'a VNode, new: &'a VNode, root_path: &[usize]) -> Self {
+        Self {
+            stack: vec![Frame {
+                old,
+                new,
+                path: root_path.to_vec(),
+            }],
+            patches: Vec::new(),
+        }
+    }
+
+    /// Process at most `budget` node-pair comparisons. Returns
+    /// [`DiffProgress::Done`] once the stack empties, or
+    /// [`DiffProgress::Yielded`] once the budget is spent with frames still
+    /// pending - either way, carrying only the patches this call produced;
+    /// the caller accumulates them across calls.
+    pub fn step(&mut self, budget: usize) -> DiffProgress {
+        let start = self.patches.len();
+        let mut remaining = budget;
+        while remaining > 0 {
+            let Some(frame) = self.stack.pop() else {
+                break;
+            };
+            remaining -= 1;
+            self.compare(frame);
+        }
+
+        let produced = self.patches.split_off(start);
+        if self.stack.is_empty() {
+            DiffProgress::Done(produced)
+        } else {
+            DiffProgress::Yielded(produced)
+        }
+    }
+
+    /// Compare one frame's node pair, the same logic `diff_nodes` runs for
+    /// a single call - except children that can be walked without
+    /// reconciliation are pushed back onto the stack instead of recursed
+    /// into directly.
+    fn compare(&mut self, frame: Frame<'a>) {
+        let Frame { old, new, path } = frame;
+
+        if let (Some(old_hash), Some(new_hash)) = (old.static_hash, new.static_hash) {
+            if old_hash == new_hash {
+                return;
+            }
+        }
+        if old.subtree_hash() == new.subtree_hash() {
+            return;
+        }
+
+        let target_id = old.djust_id.clone();
+
+        if old.tag != new.tag {
+            self.patches.push(Patch::Replace {
+                path: path.clone(),
+                d: target_id,
+                node: new.clone(),
+            });
+            return;
+        }
+
+        if old.is_text() {
+            if old.text != new.text {
+                if let Some(text) = &new.text {
+                    self.patches.push(match &old.text {
+                        Some(old_text) => splice_or_set_text(old_text, text, &path),
+                        None => Patch::SetText {
+                            path: path.clone(),
+                            d: None,
+                            text: text.clone(),
+                        },
+                    });
+                }
+            }
+            return;
+        }
+
+        self.patches.extend(diff_attrs(old, new, &path, &target_id));
+        self.patches.extend(diff_listeners(old, new, &path, &target_id));
+
+        let should_replace = old.attrs.contains_key("data-djust-replace")
+            || new.attrs.contains_key("data-djust-replace");
+        let fully_keyed = old.children_fully_keyed && new.children_fully_keyed;
+        let has_keys = fully_keyed || new.children.iter().any(|n| n.key.is_some());
+
+        let simple_same_shape = !should_replace
+            && !has_keys
+            && old.children.len() == new.children.len()
+            && old
+                .children
+                .iter()
+                .zip(new.children.iter())
+                .all(|(o, n)| o.is_text() == n.is_text() && (o.is_text() || o.tag == n.tag));
+
+        if simple_same_shape {
+            for (i, (o, n)) in old
+                .children
+                .iter()
+                .zip(new.children.iter())
+                .enumerate()
+                .rev()
+            {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                self.stack.push(Frame {
+                    old: o,
+                    new: n,
+                    path: child_path,
+                });
+            }
+        } else {
+            self.patches
+                .extend(diff_children(old, new, &path, &target_id));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_try_diff_nodes_within_limits_succeeds() {
+        let old = VNode::element("div").with_child(VNode::text("Hello"));
+        let new = VNode::element("div").with_child(VNode::text("World"));
+
+        let limits = DiffLimits {
+            max_depth: 10,
+            max_nodes: 10,
+        };
+        let patches = try_diff_nodes(&old, &new, &limits).unwrap();
+        assert!(!patches.is_empty());
+    }
+
+    #[test]
+    fn test_try_diff_nodes_rejects_too_many_nodes() {
+        let mut old = VNode::element("div");
+        old.children = (0..50).map(|i| VNode::text(i.to_string())).collect();
+        let new = old.clone();
+
+        let limits = DiffLimits {
+            max_depth: 10,
+            max_nodes: 10,
+        };
+        assert_eq!(
+            try_diff_nodes(&old, &new, &limits),
+            Err(DiffError::NodeBudgetExceeded { max_nodes: 10 })
+        );
+    }
+
+    #[test]
+    fn test_try_diff_nodes_rejects_too_deep() {
+        let mut old = VNode::text("leaf");
+        for _ in 0..20 {
+            old = VNode::element("div").with_child(old);
+        }
+        let new = old.clone();
+
+        let limits = DiffLimits {
+            max_depth: 5,
+            max_nodes: 1_000,
+        };
+        assert_eq!(
+            try_diff_nodes(&old, &new, &limits),
+            Err(DiffError::DepthExceeded { max_depth: 5 })
+        );
+    }
+
+    #[test]
+    fn test_diff_identical_subtree_short_circuits() {
+        let old = VNode::element("div")
+            .with_attr("class", "card")
+            .with_child(VNode::element("span").with_child(VNode::text("Hello")));
+        let new = old.clone();
+
+        assert!(diff_nodes(&old, &new, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_skips_subtree_with_matching_static_hash() {
+        let mut old = VNode::element("div")
+            .with_attr("class", "card")
+            .with_child(VNode::element("span").with_child(VNode::text("Hello")));
+        let mut new = old.clone();
+        old.compute_static_hashes();
+        new.compute_static_hashes();
+
+        assert!(diff_nodes(&old, &new, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_still_runs_when_static_hash_differs() {
+        let mut old = VNode::element("div").with_child(VNode::text("Hello"));
+        let mut new = VNode::element("div").with_child(VNode::text("World"));
+        old.compute_static_hashes();
+        new.compute_static_hashes();
+
+        let patches = diff_nodes(&old, &new, &[]);
+        assert!(patches
+            .iter()
+            .any(|p| matches!(p, Patch::SetText { text, .. } if text == "World")));
+    }
+
     #[test]
     fn test_diff_text_change() {
         let old = VNode::text("Hello");
@@ -482,6 +1424,106 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_diff_listener_added_and_removed() {
+        let old = VNode::element("button")
+            .with_djust_id("0")
+            .with_listener("click", "old_handler");
+        let new = VNode::element("button")
+            .with_djust_id("0")
+            .with_listener("submit", "submit_handler");
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(patches.iter().any(
+            |p| matches!(p, Patch::RemoveListener { event, d, .. } if event == "click" && d == &Some("0".to_string()))
+        ));
+        assert!(patches.iter().any(
+            |p| matches!(p, Patch::NewListener { event, handler, d, .. } if event == "submit" && handler == "submit_handler" && d == &Some("0".to_string()))
+        ));
+    }
+
+    #[test]
+    fn test_diff_listener_handler_change() {
+        let old = VNode::element("button")
+            .with_djust_id("0")
+            .with_listener("click", "old_handler");
+        let new = VNode::element("button")
+            .with_djust_id("0")
+            .with_listener("click", "new_handler");
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::NewListener { event, handler, .. } if event == "click" && handler == "new_handler"
+        ));
+    }
+
+    #[test]
+    fn test_diff_class_toggle_emits_add_and_remove_class() {
+        let old = VNode::element("input")
+            .with_djust_id("0")
+            .with_attr("class", "form-control is-invalid");
+        let new = VNode::element("input")
+            .with_djust_id("0")
+            .with_attr("class", "form-control is-valid");
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(!patches
+            .iter()
+            .any(|p| matches!(p, Patch::SetAttr { key, .. } if key == "class")));
+        assert!(patches.iter().any(
+            |p| matches!(p, Patch::RemoveClass { class, .. } if class == "is-invalid")
+        ));
+        assert!(
+            patches
+                .iter()
+                .any(|p| matches!(p, Patch::AddClass { class, .. } if class == "is-valid"))
+        );
+        assert!(!patches
+            .iter()
+            .any(|p| matches!(p, Patch::RemoveClass { class, .. } if class == "form-control")));
+    }
+
+    #[test]
+    fn test_diff_class_unchanged_emits_no_patches() {
+        let old = VNode::element("div")
+            .with_djust_id("0")
+            .with_attr("class", "card shadow-sm");
+        let new = VNode::element("div")
+            .with_djust_id("0")
+            .with_attr("class", "shadow-sm card");
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(patches.is_empty());
+    }
+
+    #[test]
+    fn test_diff_class_opts_out_to_whole_attribute_setattr() {
+        // An element carrying data-dj-raw-class wants `class` treated like
+        // any other attribute - e.g. it's a single interpolated value, not a
+        // token list the client should diff piecemeal.
+        let old = VNode::element("div")
+            .with_djust_id("0")
+            .with_attr("class", "a b");
+        let new = VNode::element("div")
+            .with_djust_id("0")
+            .with_attr("class", "c")
+            .with_attr("data-dj-raw-class", "true");
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(!patches
+            .iter()
+            .any(|p| matches!(p, Patch::AddClass { .. } | Patch::RemoveClass { .. })));
+        assert!(patches
+            .iter()
+            .any(|p| matches!(p, Patch::SetAttr { key, value, .. } if key == "class" && value == "c")));
+        // The marker attribute itself is never surfaced as a patch.
+        assert!(!patches
+            .iter()
+            .any(|p| matches!(p, Patch::SetAttr { key, .. } if key == "data-dj-raw-class")));
+    }
+
     #[test]
     fn test_diff_children_insert() {
         let old = VNode::element("div").with_djust_id("0");
@@ -1073,6 +2115,209 @@ mod tests {
         assert_eq!(remove_count, 2, "Should remove both old children");
     }
 
+    #[test]
+    fn test_longest_increasing_subsequence_trivial_cases() {
+        assert_eq!(longest_increasing_subsequence(&[]).len(), 0);
+        assert_eq!(longest_increasing_subsequence(&[5]), [0].into());
+    }
+
+    #[test]
+    fn test_longest_increasing_subsequence_picks_minimal_complement() {
+        // seq = old indices in new order. The LIS is {1, 3, 4} -> 2,3,6;
+        // everything else (0, 2) is the minimal set that needs to move.
+        let seq = vec![5, 2, 8, 3, 6];
+        let lis = longest_increasing_subsequence(&seq);
+        assert_eq!(lis, [1, 3, 4].into());
+    }
+
+    #[test]
+    fn test_keyed_all_new_list_inserts_everything_without_moves() {
+        // No key in `new` exists in `old`, so `seq` (the surviving matches)
+        // is empty - the LIS pass has nothing to do and every child is an
+        // InsertChild, not a MoveChild.
+        let old = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("b"),
+        ]);
+        let new = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("x"),
+            VNode::element("li").with_key("y"),
+            VNode::element("li").with_key("z"),
+        ]);
+
+        let patches = diff_nodes(&old, &new, &[]);
+        let move_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { .. }))
+            .count();
+        let insert_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::InsertChild { .. }))
+            .count();
+        assert_eq!(move_count, 0);
+        assert_eq!(insert_count, 3);
+    }
+
+    #[test]
+    fn test_keyed_reorder_emits_minimal_moves() {
+        // Appending a new first child shifts every other key's old index up
+        // by one in a naive index comparison, but none of them actually
+        // changed relative order - so zero moves should be emitted.
+        let old = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("b"),
+            VNode::element("li").with_key("c"),
+        ]);
+        let new = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("z"),
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("b"),
+            VNode::element("li").with_key("c"),
+        ]);
+
+        let patches = diff_nodes(&old, &new, &[]);
+        let move_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { .. }))
+            .count();
+        assert_eq!(
+            move_count, 0,
+            "Inserting a new leading key shouldn't move any survivors: {patches:?}"
+        );
+    }
+
+    #[test]
+    fn test_keyed_full_reverse_uses_fewer_moves_than_naive() {
+        // Reversing n keyed children: a naive "old_idx != new_idx" approach
+        // moves all n of them. The LIS of a reversed sequence has length 1,
+        // so the minimal move set is n - 1.
+        let keys = ["a", "b", "c", "d", "e"];
+        let old = VNode::element("ul").with_children(
+            keys.iter()
+                .map(|k| VNode::element("li").with_key(*k))
+                .collect(),
+        );
+        let new = VNode::element("ul").with_children(
+            keys.iter()
+                .rev()
+                .map(|k| VNode::element("li").with_key(*k))
+                .collect(),
+        );
+
+        let patches = diff_nodes(&old, &new, &[]);
+        let move_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { .. }))
+            .count();
+        assert_eq!(move_count, keys.len() - 1);
+    }
+
+    #[test]
+    fn test_keyed_large_reorder_batches_into_reorder_children() {
+        // A big reverse needs far more than REORDER_THRESHOLD moves - past
+        // that cutoff the individual MoveChild stream should collapse into
+        // one ReorderChildren carrying the final id order, not one patch
+        // per relocated element.
+        let keys: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let old = VNode::element("ul").with_djust_id("list").with_children(
+            keys.iter()
+                .map(|k| VNode::element("li").with_key(k.as_str()).with_djust_id(k.as_str()))
+                .collect(),
+        );
+        let new = VNode::element("ul").with_djust_id("list").with_children(
+            keys.iter()
+                .rev()
+                .map(|k| VNode::element("li").with_key(k.as_str()).with_djust_id(k.as_str()))
+                .collect(),
+        );
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(
+            !patches.iter().any(|p| matches!(p, Patch::MoveChild { .. })),
+            "Large reorder should not emit individual moves: {patches:?}"
+        );
+
+        let reorder = patches
+            .iter()
+            .find(|p| matches!(p, Patch::ReorderChildren { .. }));
+        assert!(
+            reorder.is_some(),
+            "Large reorder should emit a single ReorderChildren patch: {patches:?}"
+        );
+        if let Some(Patch::ReorderChildren { d, order, .. }) = reorder {
+            assert_eq!(d, &Some("list".to_string()));
+            let expected: Vec<String> = keys.iter().rev().cloned().collect();
+            assert_eq!(order, &expected);
+        }
+    }
+
+    #[test]
+    fn test_keyed_small_reorder_stays_below_threshold() {
+        // Below REORDER_THRESHOLD, the existing per-element MoveChild
+        // stream is still cheaper than a full-order patch, so it should be
+        // left alone.
+        let keys = ["a", "b", "c"];
+        let old = VNode::element("ul").with_children(
+            keys.iter()
+                .map(|k| VNode::element("li").with_key(*k))
+                .collect(),
+        );
+        let new = VNode::element("ul").with_children(
+            keys.iter()
+                .rev()
+                .map(|k| VNode::element("li").with_key(*k))
+                .collect(),
+        );
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(
+            !patches.iter().any(|p| matches!(p, Patch::ReorderChildren { .. })),
+            "Small reorder shouldn't batch: {patches:?}"
+        );
+        assert!(patches.iter().any(|p| matches!(p, Patch::MoveChild { .. })));
+    }
+
+    #[test]
+    fn test_keyed_reorder_with_new_keys_uses_minimal_moves_and_inserts() {
+        // Survivors reorder *and* a brand-new key is introduced in the same
+        // diff: the new key should become a plain InsertChild (never part of
+        // the LIS sequence, per the "sentinel for new keys" framing this
+        // reconciler is modeled on), while the surviving keys still only
+        // move the minimum the LIS of their old-index sequence requires.
+        let old = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("b"),
+            VNode::element("li").with_key("c"),
+            VNode::element("li").with_key("d"),
+        ]);
+        let new = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("d"),
+            VNode::element("li").with_key("new"),
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("c"),
+            VNode::element("li").with_key("b"),
+        ]);
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        let insert_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::InsertChild { .. }))
+            .count();
+        assert_eq!(insert_count, 1, "Only \"new\" should be inserted: {patches:?}");
+
+        // Surviving old-index sequence in new order is [d, a, c, b] = [3, 0, 2, 1].
+        // Its longest increasing subsequence is length 2 (e.g. [0, 2]), so the
+        // minimal move count is 4 - 2 = 2.
+        let move_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { .. }))
+            .count();
+        assert_eq!(move_count, 2, "Patches: {patches:?}");
+    }
+
     #[test]
     fn test_interleaved_keyed_and_unkeyed_children() {
         // Keyed children reorder while unkeyed children change content
@@ -1139,4 +2384,471 @@ mod tests {
             patches
         );
     }
+
+    #[test]
+    fn test_duplicate_keys_fall_back_to_replace() {
+        // Two children sharing the key "dup" would corrupt the key->index
+        // map diff_keyed_children builds, so the whole list should be
+        // replaced wholesale instead of risking a mismatched move/remove.
+        let old = VNode::element("ul").with_djust_id("list").with_children(vec![
+            VNode::element("li").with_key("dup").with_djust_id("a"),
+            VNode::element("li").with_key("dup").with_djust_id("b"),
+        ]);
+        let new = VNode::element("ul").with_djust_id("list").with_children(vec![
+            VNode::element("li").with_key("dup").with_djust_id("c"),
+        ]);
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(
+            patches
+                .iter()
+                .all(|p| matches!(p, Patch::RemoveChild { .. } | Patch::InsertChild { .. })),
+            "Expected a remove-all/insert-all replace, got: {:?}",
+            patches
+        );
+        let remove_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::RemoveChild { .. }))
+            .count();
+        let insert_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::InsertChild { .. }))
+            .count();
+        assert_eq!(remove_count, 2);
+        assert_eq!(insert_count, 1);
+    }
+
+    #[test]
+    fn test_indexed_diff_emits_move_for_relocated_unkeyed_subtree() {
+        // old: [A, B, C] -> new: [A, C] with a brand new D appended.
+        // C is unchanged content cut from the middle and dropped at the
+        // tail, so it should come out as a single Move, not a delete of B
+        // and C followed by a full re-insert of C and D.
+        let old = VNode::element("ul").with_djust_id("list").with_children(vec![
+            VNode::element("li")
+                .with_djust_id("a")
+                .with_child(VNode::text("A")),
+            VNode::element("li")
+                .with_djust_id("b")
+                .with_child(VNode::text("B")),
+            VNode::element("li")
+                .with_djust_id("c")
+                .with_child(VNode::text("C")),
+        ]);
+
+        let new = VNode::element("ul").with_djust_id("list").with_children(vec![
+            VNode::element("li")
+                .with_djust_id("a")
+                .with_child(VNode::text("A")),
+            VNode::element("li")
+                .with_djust_id("c")
+                .with_child(VNode::text("C")),
+            VNode::element("li")
+                .with_djust_id("d")
+                .with_child(VNode::text("D")),
+        ]);
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        let move_patches: Vec<_> = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { .. }))
+            .collect();
+        assert_eq!(
+            move_patches.len(),
+            1,
+            "Expected exactly one Move for the relocated <li>C</li>. Patches: {:?}",
+            patches
+        );
+        assert!(matches!(
+            move_patches[0],
+            Patch::MoveChild { from: 2, to: 1, .. }
+        ));
+
+        let insert_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::InsertChild { .. }))
+            .count();
+        assert_eq!(insert_count, 1, "Only the new D should be inserted");
+
+        let remove_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::RemoveChild { .. }))
+            .count();
+        assert_eq!(remove_count, 1, "Only B should be removed");
+    }
+
+    #[test]
+    fn test_indexed_diff_falls_back_to_delete_insert_without_match() {
+        // old: [A, B] -> new: [A] with a new C appended: B is removed and
+        // C is a genuinely new subtree, so no Move should be emitted.
+        let old = VNode::element("ul").with_djust_id("list").with_children(vec![
+            VNode::element("li")
+                .with_djust_id("a")
+                .with_child(VNode::text("A")),
+            VNode::element("li")
+                .with_djust_id("b")
+                .with_child(VNode::text("B")),
+        ]);
+
+        let new = VNode::element("ul").with_djust_id("list").with_children(vec![
+            VNode::element("li")
+                .with_djust_id("a")
+                .with_child(VNode::text("A")),
+            VNode::element("li")
+                .with_djust_id("c")
+                .with_child(VNode::text("C")),
+        ]);
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert!(
+            !patches.iter().any(|p| matches!(p, Patch::MoveChild { .. })),
+            "No matching content, so no Move should be emitted. Patches: {:?}",
+            patches
+        );
+        assert_eq!(
+            patches
+                .iter()
+                .filter(|p| matches!(p, Patch::RemoveChild { .. }))
+                .count(),
+            1
+        );
+        assert_eq!(
+            patches
+                .iter()
+                .filter(|p| matches!(p, Patch::InsertChild { .. }))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_diff_fragments_targets_patches_at_the_real_container() {
+        // No wrapping element on either side - the container is whatever
+        // the caller says it is, named by `parent_id`.
+        let old = vec![
+            VNode::element("li").with_key("a").with_djust_id("a"),
+            VNode::element("li").with_key("b").with_djust_id("b"),
+        ];
+        let new = vec![
+            VNode::element("li").with_key("b").with_djust_id("b"),
+            VNode::element("li").with_key("a").with_djust_id("a"),
+        ];
+
+        let container_id = Some("list".to_string());
+        let patches = diff_fragments(&old, &new, &[], &container_id);
+
+        assert_eq!(
+            patches
+                .iter()
+                .filter(|p| matches!(p, Patch::MoveChild { .. }))
+                .count(),
+            1
+        );
+        assert!(patches
+            .iter()
+            .all(|p| matches!(p, Patch::MoveChild { d, .. } if d == &container_id)));
+    }
+
+    #[test]
+    fn test_diff_fragments_inserts_and_removes_unkeyed_roots() {
+        let old = vec![VNode::text("A")];
+        let new = vec![VNode::text("A"), VNode::text("B")];
+
+        let patches = diff_fragments(&old, &new, &[], &Some("frag".to_string()));
+
+        assert_eq!(
+            patches
+                .iter()
+                .filter(|p| matches!(p, Patch::InsertChild { .. }))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_tag_mismatch_rescued_as_move_when_exact_match_moved() {
+        // Two unkeyed siblings swap tags *and* positions: old[0] is the div
+        // that now lives at new[1], old[1] is the span that now lives at
+        // new[0]. Diffing in place would see a tag mismatch at both indices
+        // and replace both subtrees; rescue should instead recognize each
+        // one verbatim at its new slot and emit a plain move.
+        let old = vec![
+            VNode::element("span").with_djust_id("s").with_attr("class", "a"),
+            VNode::element("div").with_djust_id("d").with_attr("class", "b"),
+        ];
+        let new = vec![
+            VNode::element("div").with_djust_id("d").with_attr("class", "b"),
+            VNode::element("span").with_djust_id("s").with_attr("class", "a"),
+        ];
+
+        let patches = diff_indexed_children(&old, &new, &[], &Some("parent".to_string()));
+
+        assert!(
+            !patches.iter().any(|p| matches!(p, Patch::Replace { .. })),
+            "Should rescue the swap as moves, not replace. Patches: {:?}",
+            patches
+        );
+        assert_eq!(
+            patches
+                .iter()
+                .filter(|p| matches!(p, Patch::MoveChild { .. }))
+                .count(),
+            2,
+            "Both swapped siblings should move. Patches: {:?}",
+            patches
+        );
+    }
+
+    #[test]
+    fn test_tag_mismatch_rescued_as_move_and_diff_on_fingerprint_match() {
+        // Same shape, but the moved node also picked up a changed text
+        // child - not identical, but the same (tag, attrs) fingerprint, so
+        // it should still be rescued (move + diff) rather than replaced.
+        let old = vec![
+            VNode::element("span")
+                .with_djust_id("s")
+                .with_attr("class", "a")
+                .with_child(VNode::text("old text")),
+            VNode::element("div").with_djust_id("d").with_attr("class", "b"),
+        ];
+        let new = vec![
+            VNode::element("div").with_djust_id("d").with_attr("class", "b"),
+            VNode::element("span")
+                .with_djust_id("s")
+                .with_attr("class", "a")
+                .with_child(VNode::text("new text")),
+        ];
+
+        let patches = diff_indexed_children(&old, &new, &[], &Some("parent".to_string()));
+
+        assert!(
+            !patches.iter().any(|p| matches!(p, Patch::Replace { .. })),
+            "Fingerprint match should rescue the swap, not replace. Patches: {:?}",
+            patches
+        );
+        assert!(
+            patches.iter().any(|p| matches!(p, Patch::MoveChild { .. })),
+            "Should move the rescued span. Patches: {:?}",
+            patches
+        );
+        assert!(
+            patches.iter().any(|p| matches!(p, Patch::SetText { .. })),
+            "Should diff the rescued span's changed text. Patches: {:?}",
+            patches
+        );
+    }
+
+    #[test]
+    fn test_tag_mismatch_with_no_rescue_candidate_falls_back_to_replace() {
+        // A single tag change at a shared index, with nothing else in the
+        // list to match it against, should behave exactly like an ordinary
+        // `diff_nodes` Replace - not a gratuitous remove/insert pair.
+        let old = vec![VNode::element("h2").with_djust_id("h2")];
+        let new = vec![VNode::element("div").with_djust_id("content")];
+
+        let patches = diff_indexed_children(&old, &new, &[], &Some("parent".to_string()));
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::Replace { d, .. } if d == &Some("h2".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_text_append_emits_splice_text() {
+        // The common streaming case: new tokens tacked onto the end. The
+        // whole old string is a shared prefix, so this should splice in
+        // just the appended tail rather than resending everything.
+        let old = VNode::text("Hello, wor");
+        let new = VNode::text("Hello, world");
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::SpliceText { start, delete_count, insert, .. }
+                if *start == 10 && *delete_count == 0 && insert == "ld"
+        ));
+    }
+
+    #[test]
+    fn test_text_middle_edit_emits_splice_text_with_shared_suffix() {
+        // A small edit in the middle keeps both a shared prefix and a
+        // shared suffix; only the differing middle span should splice.
+        let old = VNode::text("The cat sat on the mat");
+        let new = VNode::text("The dog sat on the mat");
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::SpliceText { start, delete_count, insert, .. }
+                if *start == 4 && *delete_count == 3 && insert == "dog"
+        ));
+    }
+
+    #[test]
+    fn test_text_near_total_rewrite_falls_back_to_set_text() {
+        // When the changed region dominates the new string, splicing buys
+        // nothing over just resending it - falls back to SetText.
+        let old = VNode::text("abc");
+        let new = VNode::text("xyz");
+
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::SetText { text, .. } if text == "xyz"
+        ));
+    }
+
+    #[test]
+    fn test_text_cleared_to_empty_falls_back_to_set_text() {
+        // new.is_empty() would divide by zero in the ratio check - make
+        // sure it takes the SetText path instead of panicking.
+        let old = VNode::text("some content");
+        let new = VNode::text("");
+
+        // diff_nodes only pushes a patch when `new.text` is `Some`, and an
+        // empty string is still `Some("")`, so this should still emit one.
+        let patches = diff_nodes(&old, &new, &[]);
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::SetText { text, .. } if text.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_splice_or_set_text_clamps_to_utf8_char_boundaries() {
+        // "caf\u{e9}" (\u{e9}) and "caf\u{e8}" (\u{e8}) both encode their
+        // final character as two bytes sharing the same lead byte (0xC3),
+        // so a naive byte-wise scan would stop one byte INTO that
+        // character rather than before it. The prefix/suffix must clamp
+        // back out to the nearest char boundary instead of splitting it.
+        let old = "a very long shared prefix caf\u{e9} and a long shared suffix too";
+        let new = "a very long shared prefix caf\u{e8} and a long shared suffix too";
+
+        let patch = splice_or_set_text(old, new, &[0]);
+
+        match patch {
+            Patch::SpliceText { start, delete_count, insert, .. } => {
+                assert!(old.is_char_boundary(start));
+                assert!(old.is_char_boundary(start + delete_count));
+                assert_eq!(start, 29);
+                assert_eq!(insert, "\u{e8}");
+            }
+            other => panic!("Expected SpliceText, got {other:?}"),
+        }
+    }
+
+    fn deep_uniform_tree(depth: usize, text: &str) -> VNode {
+        let mut node = VNode::text(text);
+        for _ in 0..depth {
+            node = VNode::element("div").with_child(node);
+        }
+        node
+    }
+
+    #[test]
+    fn test_diff_session_with_huge_budget_matches_one_shot_diff_nodes() {
+        let old = deep_uniform_tree(20, "before");
+        let new = deep_uniform_tree(20, "after");
+
+        let one_shot = diff_nodes(&old, &new, &[]);
+
+        let mut session = DiffSession::new(&old, &new, &[]);
+        let progress = session.step(1000);
+
+        match progress {
+            DiffProgress::Done(patches) => assert_eq!(patches, one_shot),
+            DiffProgress::Yielded(_) => panic!("budget should have been more than enough"),
+        }
+    }
+
+    #[test]
+    fn test_diff_session_step_only_returns_this_calls_patches() {
+        // Each `step` call must hand back only the patches it produced,
+        // not the whole history re-cloned - otherwise a budgeted loop's
+        // total clone cost grows quadratically with the number of steps.
+        let old = deep_uniform_tree(10, "before");
+        let new = deep_uniform_tree(10, "after");
+
+        let mut session = DiffSession::new(&old, &new, &[]);
+        let mut accumulated = Vec::new();
+        loop {
+            match session.step(1) {
+                DiffProgress::Yielded(patches) => {
+                    assert!(
+                        patches.len() <= 1,
+                        "a budget of 1 should produce at most one patch per step"
+                    );
+                    accumulated.extend(patches);
+                }
+                DiffProgress::Done(patches) => {
+                    accumulated.extend(patches);
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(accumulated, diff_nodes(&old, &new, &[]));
+    }
+
+    #[test]
+    fn test_diff_session_small_budget_yields_then_finishes() {
+        let old = deep_uniform_tree(10, "before");
+        let new = deep_uniform_tree(10, "after");
+
+        let one_shot = diff_nodes(&old, &new, &[]);
+
+        let mut session = DiffSession::new(&old, &new, &[]);
+        let mut steps = 0;
+        let mut final_patches = Vec::new();
+        loop {
+            match session.step(1) {
+                DiffProgress::Yielded(patches) => {
+                    final_patches.extend(patches);
+                    steps += 1;
+                    assert!(steps < 1000, "session never finished");
+                }
+                DiffProgress::Done(patches) => {
+                    final_patches.extend(patches);
+                    break;
+                }
+            }
+        }
+
+        assert!(steps > 0, "a budget of 1 per step should need more than one step");
+        assert_eq!(final_patches, one_shot);
+    }
+
+    #[test]
+    fn test_diff_session_falls_back_to_diff_children_for_keyed_list() {
+        // A keyed reorder isn't decomposed into per-child frames - it's
+        // resolved by `diff_children` in a single step, same as diff_nodes.
+        let old = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("b"),
+        ]);
+        let new = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("b"),
+            VNode::element("li").with_key("a"),
+        ]);
+
+        let one_shot = diff_nodes(&old, &new, &[]);
+
+        let mut session = DiffSession::new(&old, &new, &[]);
+        let progress = session.step(1);
+
+        assert_eq!(progress, DiffProgress::Done(one_shot));
+    }
 }
@@ -0,0 +1,143 @@
+//! Cheap, structurally-shared snapshots of a VNode tree for undo/redo.
+//!
+//! Cloning a `VNode` is O(n) in the size of its subtree. `SnapshotHistory`
+//! instead keeps each snapshot behind an `Rc<VNode>`, so recording a new
+//! snapshot or stepping through undo/redo history is O(1) - every snapshot
+//! shares its tree with whichever caller still holds a reference to it
+//! rather than being deep-copied.
+
+use crate::VNode;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// An O(1)-clonable handle to a point-in-time VNode tree.
+pub type Snapshot = Rc<VNode>;
+
+/// A bounded undo/redo stack of VNode snapshots.
+///
+/// Pushing past `capacity` drops the oldest snapshot rather than growing
+/// unbounded, so callers that snapshot on every render don't have to think
+/// about memory.
+pub struct SnapshotHistory {
+    past: VecDeque<Snapshot>,
+    present: Snapshot,
+    future: Vec<Snapshot>,
+    capacity: usize,
+}
+
+impl SnapshotHistory {
+    /// Start a new history at `initial`, keeping at most `capacity` past
+    /// snapshots.
+    pub fn new(initial: VNode, capacity: usize) -> Self {
+        Self {
+            past: VecDeque::new(),
+            present: Rc::new(initial),
+            future: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Record `next` as the current snapshot, pushing the previous one onto
+    /// the undo stack and clearing redo history - a fresh change invalidates
+    /// whatever was undone before it.
+    pub fn push(&mut self, next: VNode) {
+        let previous = std::mem::replace(&mut self.present, Rc::new(next));
+        self.past.push_back(previous);
+        if self.past.len() > self.capacity {
+            self.past.pop_front();
+        }
+        self.future.clear();
+    }
+
+    /// The current snapshot.
+    pub fn current(&self) -> &Snapshot {
+        &self.present
+    }
+
+    /// Step back to the previous snapshot, if any.
+    pub fn undo(&mut self) -> Option<&Snapshot> {
+        let previous = self.past.pop_back()?;
+        let current = std::mem::replace(&mut self.present, previous);
+        self.future.push(current);
+        Some(&self.present)
+    }
+
+    /// Step forward to the snapshot that was last undone, if any.
+    pub fn redo(&mut self) -> Option<&Snapshot> {
+        let next = self.future.pop()?;
+        let current = std::mem::replace(&mut self.present, next);
+        self.past.push_back(current);
+        Some(&self.present)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_then_undo_restores_previous() {
+        let mut history = SnapshotHistory::new(VNode::text("v1"), 10);
+        history.push(VNode::text("v2"));
+        assert_eq!(history.current().text, Some("v2".to_string()));
+
+        let restored = history.undo().unwrap();
+        assert_eq!(restored.text, Some("v1".to_string()));
+    }
+
+    #[test]
+    fn test_undo_then_redo_restores_next() {
+        let mut history = SnapshotHistory::new(VNode::text("v1"), 10);
+        history.push(VNode::text("v2"));
+        history.undo();
+
+        let redone = history.redo().unwrap();
+        assert_eq!(redone.text, Some("v2".to_string()));
+    }
+
+    #[test]
+    fn test_push_clears_redo_history() {
+        let mut history = SnapshotHistory::new(VNode::text("v1"), 10);
+        history.push(VNode::text("v2"));
+        history.undo();
+        history.push(VNode::text("v3"));
+
+        assert!(history.redo().is_none());
+        assert_eq!(history.current().text, Some("v3".to_string()));
+    }
+
+    #[test]
+    fn test_undo_past_the_start_returns_none() {
+        let mut history = SnapshotHistory::new(VNode::text("v1"), 10);
+        assert!(history.undo().is_none());
+        assert_eq!(history.current().text, Some("v1".to_string()));
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest_snapshot() {
+        let mut history = SnapshotHistory::new(VNode::text("v0"), 2);
+        history.push(VNode::text("v1"));
+        history.push(VNode::text("v2"));
+        history.push(VNode::text("v3"));
+
+        // Capacity is 2, so "v0" should have been dropped - only two undos
+        // are possible before hitting the end of recorded history.
+        assert!(history.undo().is_some()); // back to v2
+        assert!(history.undo().is_some()); // back to v1
+        assert!(history.undo().is_none()); // v0 was evicted
+    }
+
+    #[test]
+    fn test_snapshots_share_structure_cheaply() {
+        let big = VNode::element("div").with_children(
+            (0..1000).map(|i| VNode::text(i.to_string())).collect(),
+        );
+        let mut history = SnapshotHistory::new(big, 10);
+        let first = Rc::clone(history.current());
+
+        // Pushing an unrelated small snapshot doesn't touch `first`'s tree -
+        // it's still the same Rc-backed allocation.
+        history.push(VNode::text("small"));
+        assert!(Rc::ptr_eq(&first, &history.past[0]));
+    }
+}
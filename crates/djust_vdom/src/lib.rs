@@ -7,42 +7,85 @@
 #![allow(clippy::useless_conversion)]
 
 use djust_core::Result;
+use indexmap::IndexMap;
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 pub mod diff;
+pub mod history;
 pub mod parser;
 pub mod patch;
+pub mod query;
+pub mod template;
 
 /// A virtual DOM node
+///
+/// `attrs` is an [`IndexMap`] rather than a `HashMap`: it preserves
+/// insertion order (so re-parsing the same HTML always yields the same
+/// attribute order, and `diff`/patch output is deterministic) and supports
+/// index-based access (`get_index`, `get_index_of`) for callers that want to
+/// address an attribute by position instead of by key.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VNode {
     pub tag: String,
-    pub attrs: HashMap<String, String>,
+    pub attrs: IndexMap<String, String>,
+    /// Event listeners bound to this element, keyed by event name (e.g.
+    /// `"click"`) and mapping to a server-side handler id. Diffed
+    /// separately from `attrs` so the client can wire/unwire DOM event
+    /// delegation from explicit [`Patch::NewListener`]/[`Patch::RemoveListener`]
+    /// patches instead of re-parsing HTML to discover handler changes.
+    pub listeners: HashMap<String, String>,
     pub children: Vec<VNode>,
     pub text: Option<String>,
     pub key: Option<String>,
+    /// Whether every entry in `children` carries a key, cached so
+    /// `diff::diff_children` can skip the O(n) "is this list keyed?" probe
+    /// on the common case. Kept up to date by the builder methods below;
+    /// callers who mutate `children` directly (e.g. the HTML parser, patch
+    /// application) must call [`recheck_fully_keyed`](VNode::recheck_fully_keyed)
+    /// afterward to restore the invariant.
+    pub children_fully_keyed: bool,
+    /// An FNV-1a hash over this subtree's tag/sorted attrs (excluding the
+    /// parser-assigned `data-dj`/`data-dj-id` instance id)/sorted
+    /// listeners/key/text plus
+    /// every child's own `static_hash`, cached once at parse time by
+    /// [`compute_static_hashes`](VNode::compute_static_hashes) rather than
+    /// recomputed on every diff. Two subtrees with equal, non-`None` hashes
+    /// are identical, so `diff::diff_nodes` can skip straight past them -
+    /// the scaffolding around a server-rendered template's dynamic slots
+    /// rarely changes between renders, so this turns most of a render's
+    /// diff into a handful of integer comparisons. `None` until computed;
+    /// stale after a direct (non-builder) mutation until recomputed.
+    pub static_hash: Option<u64>,
 }
 
 impl VNode {
     pub fn element(tag: impl Into<String>) -> Self {
         Self {
             tag: tag.into(),
-            attrs: HashMap::new(),
+            attrs: IndexMap::new(),
+            listeners: HashMap::new(),
             children: Vec::new(),
             text: None,
             key: None,
+            // Vacuously true: an empty list has no unkeyed child.
+            children_fully_keyed: true,
+            static_hash: None,
         }
     }
 
     pub fn text(content: impl Into<String>) -> Self {
         Self {
             tag: "#text".to_string(),
-            attrs: HashMap::new(),
+            attrs: IndexMap::new(),
+            listeners: HashMap::new(),
             children: Vec::new(),
             text: Some(content.into()),
             key: None,
+            children_fully_keyed: true,
+            static_hash: None,
         }
     }
 
@@ -51,56 +94,250 @@ impl VNode {
         self
     }
 
+    /// Bind `handler` as the server-side handler id for `event` (e.g.
+    /// `with_listener("click", "handle_click")`).
+    pub fn with_listener(mut self, event: impl Into<String>, handler: impl Into<String>) -> Self {
+        self.listeners.insert(event.into(), handler.into());
+        self
+    }
+
     pub fn with_key(mut self, key: impl Into<String>) -> Self {
         self.key = Some(key.into());
         self
     }
 
     pub fn with_child(mut self, child: VNode) -> Self {
+        self.children_fully_keyed = self.children_fully_keyed && child.key.is_some();
         self.children.push(child);
         self
     }
 
     pub fn with_children(mut self, children: Vec<VNode>) -> Self {
+        self.children_fully_keyed = children.iter().all(|c| c.key.is_some());
         self.children = children;
         self
     }
 
+    /// Recompute `children_fully_keyed` from the current contents of
+    /// `children`. Call this after mutating `children` directly instead of
+    /// through [`with_child`](VNode::with_child)/[`with_children`](VNode::with_children).
+    pub fn recheck_fully_keyed(&mut self) {
+        self.children_fully_keyed = self.children.iter().all(|c| c.key.is_some());
+    }
+
     pub fn is_text(&self) -> bool {
         self.tag == "#text"
     }
+
+    /// Compute a Merkle-style content hash over this node's entire subtree:
+    /// it folds in this node's own tag/attrs/text/key plus every child's
+    /// subtree hash, so two subtrees only hash equal when every node in
+    /// them is equal. `diff::diff_nodes` uses this to short-circuit the
+    /// diff entirely for subtrees that haven't changed at all.
+    pub fn subtree_hash(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash_subtree(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_subtree<H: Hasher>(&self, hasher: &mut H) {
+        self.tag.hash(hasher);
+        self.text.hash(hasher);
+        self.key.hash(hasher);
+
+        // `attrs` preserves insertion order, but two nodes with the same
+        // attrs inserted in a different order should still hash equal, so
+        // sort by key before folding them in.
+        let mut attrs: Vec<_> = self.attrs.iter().collect();
+        attrs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in attrs {
+            key.hash(hasher);
+            value.hash(hasher);
+        }
+
+        // `listeners` is a HashMap, so sort by event name before folding it
+        // in for the same reason `attrs` does: insertion order must not
+        // affect the hash.
+        let mut listeners: Vec<_> = self.listeners.iter().collect();
+        listeners.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for (event, handler) in listeners {
+            event.hash(hasher);
+            handler.hash(hasher);
+        }
+
+        for child in &self.children {
+            child.subtree_hash().hash(hasher);
+        }
+    }
+
+    /// Recompute and cache [`static_hash`](VNode::static_hash) for this
+    /// subtree and every descendant, bottom-up so each child's hash is
+    /// already cached by the time its parent folds it in. Call this once
+    /// after a tree is fully built (the HTML parser does this at the end of
+    /// every `parse_html`) rather than after each individual edit - unlike
+    /// `children_fully_keyed`, this isn't meant to be kept live incrementally.
+    pub fn compute_static_hashes(&mut self) {
+        for child in &mut self.children {
+            child.compute_static_hashes();
+        }
+
+        let mut hash = fnv1a(self.tag.as_bytes(), FNV_OFFSET_BASIS);
+
+        // `data-dj`/`data-dj-id` is the parser-assigned per-instance id, not
+        // part of the node's actual content - two structurally identical
+        // subtrees get different ids whenever something ahead of them
+        // shifts their position (e.g. a row prepended to a list), which
+        // would otherwise make their hashes differ for no real reason and
+        // defeat the diff short-circuit in exactly the case it matters most.
+        let mut attrs: Vec<_> = self
+            .attrs
+            .iter()
+            .filter(|(key, _)| key.as_str() != "data-dj" && key.as_str() != "data-dj-id")
+            .collect();
+        attrs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in attrs {
+            hash = fnv1a(key.as_bytes(), hash);
+            hash = fnv1a(value.as_bytes(), hash);
+        }
+
+        let mut listeners: Vec<_> = self.listeners.iter().collect();
+        listeners.sort_unstable();
+        for (event, handler) in listeners {
+            hash = fnv1a(event.as_bytes(), hash);
+            hash = fnv1a(handler.as_bytes(), hash);
+        }
+
+        if let Some(key) = &self.key {
+            hash = fnv1a(key.as_bytes(), hash);
+        }
+
+        if let Some(text) = &self.text {
+            hash = fnv1a(text.as_bytes(), hash);
+        }
+
+        for child in &self.children {
+            // `unwrap_or(0)` only matters if a child's own hash somehow
+            // failed to compute, which can't happen for a freshly built
+            // subtree - every child was just visited above.
+            hash = fnv1a(&child.static_hash.unwrap_or(0).to_le_bytes(), hash);
+        }
+
+        self.static_hash = Some(hash);
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a over `bytes`, continuing from `hash` so callers can fold several
+/// fields into one running hash (see [`VNode::compute_static_hashes`]).
+fn fnv1a(bytes: &[u8], hash: u64) -> u64 {
+    let mut h = hash;
+    for &byte in bytes {
+        h ^= byte as u64;
+        h = h.wrapping_mul(FNV_PRIME);
+    }
+    h
 }
 
 /// A patch operation to apply to the DOM
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Patch {
     /// Replace a node at path
-    Replace { path: Vec<usize>, node: VNode },
+    Replace {
+        path: Vec<usize>,
+        d: Option<String>,
+        node: VNode,
+    },
     /// Update text content
-    SetText { path: Vec<usize>, text: String },
+    SetText {
+        path: Vec<usize>,
+        d: Option<String>,
+        text: String,
+    },
     /// Set an attribute
     SetAttr {
         path: Vec<usize>,
+        d: Option<String>,
         key: String,
         value: String,
     },
     /// Remove an attribute
-    RemoveAttr { path: Vec<usize>, key: String },
+    RemoveAttr {
+        path: Vec<usize>,
+        d: Option<String>,
+        key: String,
+    },
     /// Insert a child at index
     InsertChild {
         path: Vec<usize>,
+        d: Option<String>,
         index: usize,
         node: VNode,
     },
     /// Remove a child at index
-    RemoveChild { path: Vec<usize>, index: usize },
+    RemoveChild {
+        path: Vec<usize>,
+        d: Option<String>,
+        index: usize,
+    },
     /// Move a child from one index to another
     MoveChild {
         path: Vec<usize>,
+        d: Option<String>,
         from: usize,
         to: usize,
     },
+    /// Bind a new (or changed) event listener
+    NewListener {
+        path: Vec<usize>,
+        d: Option<String>,
+        event: String,
+        handler: String,
+    },
+    /// Unbind an event listener
+    RemoveListener {
+        path: Vec<usize>,
+        d: Option<String>,
+        event: String,
+    },
+    /// Add a single token to the `class` attribute, leaving the rest of the
+    /// class list (and any classes another script added client-side) alone.
+    AddClass {
+        path: Vec<usize>,
+        d: Option<String>,
+        class: String,
+    },
+    /// Remove a single token from the `class` attribute.
+    RemoveClass {
+        path: Vec<usize>,
+        d: Option<String>,
+        class: String,
+    },
+    /// Collapse a large keyed reorder into a single instruction: the final
+    /// ordered list of surviving children's `key`s, for the client to
+    /// realize with one `DocumentFragment`/`insertBefore` pass instead of
+    /// replaying each move individually. Emitted by `diff_keyed_children` in
+    /// place of its usual `MoveChild` patches once the move count crosses
+    /// [`diff::REORDER_THRESHOLD`](crate::diff::REORDER_THRESHOLD).
+    ReorderChildren {
+        path: Vec<usize>,
+        d: Option<String>,
+        order: Vec<String>,
+    },
+    /// Replace `old[start..start + delete_count]` with `insert`, the same
+    /// shape as `CharacterData.replaceData`. Emitted instead of `SetText`
+    /// for a changed text node when the edit is a small, localized region
+    /// (e.g. streamed/appended tokens) rather than a near-total rewrite -
+    /// see [`diff::SPLICE_TEXT_MAX_RATIO`](crate::diff::SPLICE_TEXT_MAX_RATIO).
+    SpliceText {
+        path: Vec<usize>,
+        d: Option<String>,
+        start: usize,
+        delete_count: usize,
+        insert: String,
+    },
 }
 
 /// Compute the difference between two virtual DOM trees
@@ -108,11 +345,72 @@ pub fn diff(old: &VNode, new: &VNode) -> Vec<Patch> {
     diff::diff_nodes(old, new, &[])
 }
 
+/// Like [`diff`], but for untrusted or potentially huge server-rendered
+/// trees: rejects trees that exceed `limits` instead of risking OOM/stack
+/// overflow in the diff itself.
+pub fn try_diff(
+    old: &VNode,
+    new: &VNode,
+    limits: &diff::DiffLimits,
+) -> Result<Vec<Patch>, diff::DiffError> {
+    diff::try_diff_nodes(old, new, limits)
+}
+
 /// Parse HTML into a virtual DOM
 pub fn parse_html(html: &str) -> Result<VNode> {
     parser::parse_html(html)
 }
 
+/// Like [`parse_html`], but allocates `data-dj` ids from a parse-local
+/// sequence seeded by `options` instead of always starting at `0`. Use this
+/// to give independently-parsed fragments non-overlapping id namespaces
+/// before composing them onto the same page - see
+/// [`parser::ParseOptions`].
+pub fn parse_html_with_options(html: &str, options: &parser::ParseOptions) -> Result<VNode> {
+    parser::parse_html_with_options(html, options)
+}
+
+/// Parse HTML into a virtual DOM, scrubbing it against `policy`. Use this
+/// instead of [`parse_html`] for user-authored content, since it drops
+/// disallowed tags and strips event handlers and dangerous URLs.
+pub fn parse_html_sanitized(html: &str, policy: &parser::SanitizePolicy) -> Result<VNode> {
+    parser::parse_html_sanitized(html, policy)
+}
+
+/// Like [`parse_html_sanitized`], but also takes [`parser::ParseOptions`]
+/// (see [`parse_html_with_options`]).
+pub fn parse_html_sanitized_with_options(
+    html: &str,
+    policy: &parser::SanitizePolicy,
+    options: &parser::ParseOptions,
+) -> Result<VNode> {
+    parser::parse_html_sanitized_with_options(html, policy, options)
+}
+
+/// Parse an HTML fragment into every one of its top-level nodes, in order,
+/// instead of just the first one. Use this for multi-root partials (e.g.
+/// `<li>...</li><li>...</li>`) that [`parse_html`] would otherwise truncate.
+pub fn parse_fragment(html: &str) -> Result<Vec<VNode>> {
+    parser::parse_fragment(html)
+}
+
+/// Like [`parse_fragment`], but also takes [`parser::ParseOptions`] (see
+/// [`parse_html_with_options`]).
+pub fn parse_fragment_with_options(
+    html: &str,
+    options: &parser::ParseOptions,
+) -> Result<Vec<VNode>> {
+    parser::parse_fragment_with_options(html, options)
+}
+
+/// Like [`parse_html`], but never hard-fails on malformed-but-recoverable
+/// markup - instead it returns the best tree html5ever could build plus
+/// the parse errors and quirks mode it recorded along the way. See
+/// [`parser::ParsedDocument`].
+pub fn parse_html_verbose(html: &str) -> Result<parser::ParsedDocument> {
+    parser::parse_html_verbose(html)
+}
+
 /// Python bindings
 #[pyclass]
 #[derive(Clone)]
@@ -181,4 +479,151 @@ mod tests {
         assert!(node.is_text());
         assert_eq!(node.text, Some("Hello World".to_string()));
     }
+
+    #[test]
+    fn test_element_is_vacuously_fully_keyed() {
+        assert!(VNode::element("div").children_fully_keyed);
+    }
+
+    #[test]
+    fn test_with_child_tracks_fully_keyed_incrementally() {
+        let node = VNode::element("ul")
+            .with_child(VNode::element("li").with_key("a"))
+            .with_child(VNode::element("li").with_key("b"));
+        assert!(node.children_fully_keyed);
+
+        let mixed = VNode::element("ul")
+            .with_child(VNode::element("li").with_key("a"))
+            .with_child(VNode::element("li"));
+        assert!(!mixed.children_fully_keyed);
+    }
+
+    #[test]
+    fn test_with_children_recomputes_fully_keyed() {
+        let all_keyed = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_key("a"),
+            VNode::element("li").with_key("b"),
+        ]);
+        assert!(all_keyed.children_fully_keyed);
+
+        let not_all_keyed = VNode::element("ul")
+            .with_children(vec![VNode::element("li").with_key("a"), VNode::element("li")]);
+        assert!(!not_all_keyed.children_fully_keyed);
+    }
+
+    #[test]
+    fn test_recheck_fully_keyed_after_direct_mutation() {
+        let mut node = VNode::element("ul").with_child(VNode::element("li").with_key("a"));
+        assert!(node.children_fully_keyed);
+
+        node.children.push(VNode::element("li")); // unkeyed, bypasses with_child
+        node.recheck_fully_keyed();
+        assert!(!node.children_fully_keyed);
+    }
+
+    #[test]
+    fn test_subtree_hash_equal_for_identical_trees() {
+        let a = VNode::element("div")
+            .with_attr("class", "card")
+            .with_child(VNode::text("Hello"));
+        let b = VNode::element("div")
+            .with_attr("class", "card")
+            .with_child(VNode::text("Hello"));
+
+        assert_eq!(a.subtree_hash(), b.subtree_hash());
+    }
+
+    #[test]
+    fn test_subtree_hash_ignores_attr_insertion_order() {
+        let a = VNode::element("div")
+            .with_attr("class", "card")
+            .with_attr("id", "x");
+        let b = VNode::element("div")
+            .with_attr("id", "x")
+            .with_attr("class", "card");
+
+        assert_eq!(a.subtree_hash(), b.subtree_hash());
+    }
+
+    #[test]
+    fn test_subtree_hash_differs_for_changed_child() {
+        let a = VNode::element("div").with_child(VNode::text("Hello"));
+        let b = VNode::element("div").with_child(VNode::text("World"));
+
+        assert_ne!(a.subtree_hash(), b.subtree_hash());
+    }
+
+    #[test]
+    fn test_compute_static_hashes_equal_for_identical_trees() {
+        let mut a = VNode::element("div")
+            .with_attr("class", "card")
+            .with_child(VNode::text("Hello"));
+        let mut b = VNode::element("div")
+            .with_attr("class", "card")
+            .with_child(VNode::text("Hello"));
+
+        a.compute_static_hashes();
+        b.compute_static_hashes();
+
+        assert!(a.static_hash.is_some());
+        assert_eq!(a.static_hash, b.static_hash);
+    }
+
+    #[test]
+    fn test_compute_static_hashes_differs_for_changed_descendant() {
+        let mut a = VNode::element("div").with_child(
+            VNode::element("span").with_child(VNode::text("Hello")),
+        );
+        let mut b = VNode::element("div").with_child(
+            VNode::element("span").with_child(VNode::text("World")),
+        );
+
+        a.compute_static_hashes();
+        b.compute_static_hashes();
+
+        assert_ne!(a.static_hash, b.static_hash);
+        // The change is buried two levels deep, but the root's cached hash
+        // still reflects it.
+        assert_ne!(
+            a.children[0].static_hash,
+            b.children[0].static_hash
+        );
+    }
+
+    #[test]
+    fn test_compute_static_hashes_ignores_data_dj_id() {
+        // Same content, different parser-assigned instance ids - e.g. this
+        // node shifted one slot because a sibling was prepended ahead of
+        // it. The hash must still match, or the diff short-circuit would
+        // never fire for the exact "list grew a row" case it exists for.
+        let mut a = VNode::element("li")
+            .with_attr("data-dj", "3")
+            .with_child(VNode::text("same"));
+        let mut b = VNode::element("li")
+            .with_attr("data-dj", "4")
+            .with_child(VNode::text("same"));
+
+        a.compute_static_hashes();
+        b.compute_static_hashes();
+
+        assert_eq!(a.static_hash, b.static_hash);
+    }
+
+    #[test]
+    fn test_compute_static_hashes_differs_for_different_key() {
+        let mut a = VNode::element("li").with_key("a");
+        let mut b = VNode::element("li").with_key("b");
+
+        a.compute_static_hashes();
+        b.compute_static_hashes();
+
+        assert_ne!(a.static_hash, b.static_hash);
+    }
+
+    #[test]
+    fn test_uncomputed_static_hash_is_none() {
+        let node = VNode::element("div").with_child(VNode::text("Hello"));
+        assert_eq!(node.static_hash, None);
+        assert_eq!(node.children[0].static_hash, None);
+    }
 }
@@ -2,12 +2,79 @@
 //!
 //! Generates compact `data-dj` IDs on each element for reliable patch targeting.
 
-use crate::{next_djust_id, reset_id_counter, VNode};
+use crate::VNode;
 use djust_core::{DjangoRustError, Result};
-use html5ever::parse_document;
 use html5ever::tendril::TendrilSink;
+use html5ever::tokenizer::TokenizerOpts;
+use html5ever::{parse_document, ParseOpts, QuirksMode};
+use indexmap::IndexMap;
 use markup5ever_rcdom::{Handle, NodeData, RcDom};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Alphabet for [`IdAllocator`]'s base62-encoded `data-dj` ids.
+const BASE62_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn to_base62(mut n: u64) -> String {
+    if n == 0 {
+        return "0".to_string();
+    }
+    let mut digits = Vec::new();
+    while n > 0 {
+        digits.push(BASE62_ALPHABET[(n % 62) as usize]);
+        n /= 62;
+    }
+    digits.reverse();
+    String::from_utf8(digits).expect("base62 alphabet is ASCII")
+}
+
+/// Options for [`parse_html_with_options`]/[`parse_fragment_with_options`].
+///
+/// `data-dj` ids used to come from crate-global mutable state
+/// (`reset_id_counter`/`next_djust_id`), which meant concurrent parses on
+/// a multi-threaded server interleaved and handed out colliding ids. Each
+/// `parse_*` call now builds its own [`IdAllocator`] instead, seeded from
+/// these options, so ids are deterministic and collision-free regardless
+/// of what else is parsing concurrently.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Prepended to every id generated during this parse, e.g. `"modal-"`
+    /// so a dialog fragment's ids never collide with the base page's when
+    /// both are composed into the same document.
+    pub id_prefix: Option<String>,
+    /// The first id value this parse allocates, before base62 encoding and
+    /// prefixing. Defaults to `0`; set this to give independently-parsed
+    /// fragments non-overlapping id namespaces without a shared prefix.
+    pub start_id: u64,
+}
+
+/// A parse-local, sequential `data-dj` id allocator. Threaded through
+/// [`convert_children`]/[`handle_to_vnode`] for the duration of a single
+/// `parse_*` call rather than mutating shared state, so two parses running
+/// on different threads can never interleave their id sequences.
+struct IdAllocator {
+    next: u64,
+    prefix: Option<String>,
+}
+
+impl IdAllocator {
+    fn new(options: &ParseOptions) -> Self {
+        Self {
+            next: options.start_id,
+            prefix: options.id_prefix.clone(),
+        }
+    }
+
+    /// Allocates the next id in this parse's sequence.
+    fn next_id(&mut self) -> String {
+        let id = to_base62(self.next);
+        self.next += 1;
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}{id}"),
+            None => id,
+        }
+    }
+}
 
 /// Parse HTML into a virtual DOM with compact IDs for patch targeting.
 ///
@@ -23,54 +90,553 @@ use std::collections::HashMap;
 /// </div>
 /// ```
 pub fn parse_html(html: &str) -> Result<VNode> {
-    // Reset ID counter for this parse session
-    reset_id_counter();
+    parse_html_with_options(html, &ParseOptions::default())
+}
+
+/// Like [`parse_html`], but allocates `data-dj` ids per `options` instead of
+/// always starting a fresh base62 sequence at `0` - see [`ParseOptions`].
+pub fn parse_html_with_options(html: &str, options: &ParseOptions) -> Result<VNode> {
+    let mut roots = parse_fragment_with_options(html, options)?;
+
+    if roots.len() > 1 {
+        return Err(DjangoRustError::VdomError(format!(
+            "Expected a single root element but found {} top-level nodes; use parse_fragment for multi-root content",
+            roots.len()
+        )));
+    }
+
+    Ok(roots.remove(0))
+}
+
+/// Like [`parse_html`], but scrubs the tree against `policy` while it's
+/// being built (see [`SanitizePolicy`]) so that untrusted, user-authored
+/// markup can't smuggle scripts, event handlers, or dangerous URLs into a
+/// rendered patch stream.
+///
+/// Filtering happens during construction rather than as a pass over the
+/// finished tree: a disallowed tag's subtree is never visited and never
+/// consumes `data-dj` IDs, so the IDs assigned to the surviving nodes stay
+/// dense and deterministic regardless of how much untrusted markup was
+/// dropped.
+pub fn parse_html_sanitized(html: &str, policy: &SanitizePolicy) -> Result<VNode> {
+    parse_html_sanitized_with_options(html, policy, &ParseOptions::default())
+}
+
+/// Like [`parse_html_sanitized`], but also takes [`ParseOptions`] (see
+/// [`parse_html_with_options`]).
+pub fn parse_html_sanitized_with_options(
+    html: &str,
+    policy: &SanitizePolicy,
+    options: &ParseOptions,
+) -> Result<VNode> {
+    let mut roots = parse_fragment_impl(html, Some(policy), options)?;
+
+    if roots.len() > 1 {
+        return Err(DjangoRustError::VdomError(format!(
+            "Expected a single root element but found {} top-level nodes; use parse_fragment for multi-root content",
+            roots.len()
+        )));
+    }
+
+    Ok(roots.remove(0))
+}
+
+/// Parse an HTML fragment into every one of its top-level nodes, in order,
+/// instead of just the first one.
+///
+/// `parse_html` discards any sibling that follows the first root element,
+/// which silently drops content for fragments like `<li>A</li><li>B</li>`
+/// (a common shape for template partials). This walks the same
+/// `<html><body>` wrapper html5ever produces but collects all of the
+/// body's children - elements and meaningful text alike, comments and
+/// whitespace-only text filtered out exactly as they are for any other
+/// element's children.
+///
+/// Returns an error (rather than silently substituting an empty `<div>`)
+/// when the fragment has no root element at all, e.g. an empty string or a
+/// fragment consisting only of comments/whitespace.
+pub fn parse_fragment(html: &str) -> Result<Vec<VNode>> {
+    parse_fragment_with_options(html, &ParseOptions::default())
+}
+
+/// Like [`parse_fragment`], but also takes [`ParseOptions`] (see
+/// [`parse_html_with_options`]).
+pub fn parse_fragment_with_options(html: &str, options: &ParseOptions) -> Result<Vec<VNode>> {
+    parse_fragment_impl(html, None, options)
+}
+
+/// Shared implementation behind [`parse_fragment_with_options`] and
+/// [`parse_html_sanitized_with_options`]: `policy` is threaded down into
+/// [`convert_children`]/[`handle_to_vnode`] so that, when present, tag and
+/// attribute filtering happens while each node is constructed rather than
+/// as a pass over the finished tree, and `options` seeds this call's own
+/// [`IdAllocator`].
+fn parse_fragment_impl(
+    html: &str,
+    policy: Option<&SanitizePolicy>,
+    options: &ParseOptions,
+) -> Result<Vec<VNode>> {
+    let dom = parse_dom(html, ParseOpts::default())?;
+    let mut ids = IdAllocator::new(options);
+    roots_from_dom(&dom, policy, &mut ids)
+}
 
-    let dom = parse_document(RcDom::default(), Default::default())
+/// Runs html5ever's `parse_document` over `html`.
+fn parse_dom(html: &str, opts: ParseOpts) -> Result<RcDom> {
+    parse_document(RcDom::default(), opts)
         .from_utf8()
         .read_from(&mut html.as_bytes())
-        .map_err(|e| DjangoRustError::VdomError(format!("Failed to parse HTML: {e}")))?;
+        .map_err(|e| DjangoRustError::VdomError(format!("Failed to parse HTML: {e}")))
+}
+
+/// Finds `dom`'s `<body>`, converts its children to `VNode`s (applying
+/// `policy` if given and allocating `data-dj` ids from `ids`), and caches
+/// each root's static hash. Shared by every `parse_*` entry point once it
+/// has a parsed `RcDom` in hand.
+fn roots_from_dom(
+    dom: &RcDom,
+    policy: Option<&SanitizePolicy>,
+    ids: &mut IdAllocator,
+) -> Result<Vec<VNode>> {
+    let body = find_body(&dom.document).ok_or_else(|| {
+        DjangoRustError::VdomError(
+            "Failed to parse HTML fragment: no root element found".to_string(),
+        )
+    })?;
+
+    let mut roots = convert_children(&body, false, policy, ids)?;
+
+    if roots.is_empty() {
+        return Err(DjangoRustError::VdomError(
+            "Failed to parse HTML fragment: no root element found".to_string(),
+        ));
+    }
+
+    // Cache each subtree's static hash once, here at parse time, so
+    // `diff::diff_nodes` can short-circuit an unchanged subtree with an
+    // O(1) field comparison instead of re-walking it every render.
+    for root in &mut roots {
+        root.compute_static_hashes();
+    }
+
+    Ok(roots)
+}
+
+/// The result of [`parse_html_verbose`]: the parsed tree plus html5ever's
+/// own diagnostics, mirroring the `scraper` crate's `Html` design.
+///
+/// Unlike [`parse_html`], a parse that hits recoverable markup errors
+/// (unclosed tags, misnested elements) doesn't hard-fail here - html5ever's
+/// tree builder already recovers from these the same way a browser does,
+/// so `root` is still the best tree it could produce. `errors` lets a
+/// template author be warned about the malformed markup instead of it
+/// silently diffing into a surprising tree.
+#[derive(Debug)]
+pub struct ParsedDocument {
+    pub root: VNode,
+    /// Parse errors html5ever's tree builder recovered from, in document
+    /// order.
+    pub errors: Vec<String>,
+    /// The document's detected quirks mode, set once html5ever sees (or
+    /// fails to see) a standards `<!DOCTYPE html>`.
+    pub quirks_mode: QuirksMode,
+}
+
+/// Like [`parse_html`], but never hard-fails on malformed-but-recoverable
+/// markup: instead of only ever returning `Ok(VNode)` or an `Err`, it
+/// returns the best tree html5ever could build plus the parse errors and
+/// quirks mode it recorded along the way. See [`ParsedDocument`].
+///
+/// Still returns `Err` for the cases `parse_html` already treats as fatal:
+/// no root element at all, or more than one top-level node.
+pub fn parse_html_verbose(html: &str) -> Result<ParsedDocument> {
+    let opts = ParseOpts {
+        tokenizer: TokenizerOpts {
+            exact_errors: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let dom = parse_dom(html, opts)?;
+    let errors = dom.errors.borrow().iter().map(|e| e.to_string()).collect();
+    let quirks_mode = dom.quirks_mode.get();
+
+    let mut ids = IdAllocator::new(&ParseOptions::default());
+    let mut roots = roots_from_dom(&dom, None, &mut ids)?;
+
+    if roots.len() > 1 {
+        return Err(DjangoRustError::VdomError(format!(
+            "Expected a single root element but found {} top-level nodes; use parse_fragment for multi-root content",
+            roots.len()
+        )));
+    }
+
+    Ok(ParsedDocument {
+        root: roots.remove(0),
+        errors,
+        quirks_mode,
+    })
+}
+
+/// Rewrites a URL-bearing attribute on a surviving element, e.g. turning
+/// `src="https://example.com/x.png"` into
+/// `data-source="https://example.com/x.png"` so remote images never load
+/// until a caller explicitly promotes them back to `src` client-side.
+/// Applied to every attribute in [`URL_ATTRS`] that survives the rest of
+/// [`SanitizePolicy`]'s checks, not just ones carrying a disallowed scheme.
+pub trait UrlAttrRewriter: Send + Sync {
+    /// Returns the replacement `(attribute_name, value)` pair.
+    fn rewrite(&self, attr: &str, value: &str) -> (String, String);
+}
+
+impl<F> UrlAttrRewriter for F
+where
+    F: Fn(&str, &str) -> (String, String) + Send + Sync,
+{
+    fn rewrite(&self, attr: &str, value: &str) -> (String, String) {
+        self(attr, value)
+    }
+}
 
-    // Find the body or first child
-    let root = find_root(&dom.document);
-    handle_to_vnode(&root)
+/// Allowlist-driven policy for [`parse_html_sanitized`].
+///
+/// Tags and attributes not present in their respective allowlists are
+/// dropped. `on*` event attributes and `javascript:`/`data:` URLs are
+/// always stripped regardless of the allowlists, since they are never
+/// safe to forward to the client as-is.
+pub struct SanitizePolicy {
+    /// Tags permitted to appear in the sanitized tree. A disallowed tag
+    /// not listed in `unwrap_tags` drops its entire subtree, since tags
+    /// like `<script>` and `<style>` carry content that should never be
+    /// rendered as plain text either.
+    pub allowed_tags: HashSet<String>,
+    /// Disallowed tags in this set are unwrapped - the tag itself is
+    /// dropped but its children are promoted into its parent's place -
+    /// instead of being removed along with their whole subtree. Leave
+    /// empty to always remove disallowed tags outright.
+    pub unwrap_tags: HashSet<String>,
+    /// Attribute names permitted on any element, in addition to the
+    /// internal `data-dj`/`data-key` bookkeeping attributes which are
+    /// always preserved.
+    pub allowed_attrs: HashSet<String>,
+    /// Additional attribute names permitted on a specific tag only, on top
+    /// of `allowed_attrs`.
+    pub allowed_attrs_per_tag: HashMap<String, HashSet<String>>,
+    /// URL schemes permitted in the attributes listed in [`URL_ATTRS`],
+    /// checked case-insensitively; a scheme-less (relative or fragment)
+    /// URL is always allowed. Leave empty to allow any scheme except the
+    /// always-blocked `javascript:`/`data:`.
+    pub allowed_url_schemes: HashSet<String>,
+    /// When set, a `src` attribute carrying a `javascript:`/`data:` URL is
+    /// rewritten to this placeholder value instead of being dropped.
+    pub rewrite_src_placeholder: Option<String>,
+    /// When set, called for every surviving [`URL_ATTRS`] attribute to
+    /// rewrite it - see [`UrlAttrRewriter`].
+    pub url_rewriter: Option<Box<dyn UrlAttrRewriter>>,
+}
+
+impl fmt::Debug for SanitizePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SanitizePolicy")
+            .field("allowed_tags", &self.allowed_tags)
+            .field("unwrap_tags", &self.unwrap_tags)
+            .field("allowed_attrs", &self.allowed_attrs)
+            .field("allowed_attrs_per_tag", &self.allowed_attrs_per_tag)
+            .field("allowed_url_schemes", &self.allowed_url_schemes)
+            .field("rewrite_src_placeholder", &self.rewrite_src_placeholder)
+            .field(
+                "url_rewriter",
+                &self.url_rewriter.as_ref().map(|_| "Box<dyn UrlAttrRewriter>"),
+            )
+            .finish()
+    }
 }
 
-fn find_root(handle: &Handle) -> Handle {
-    // html5ever wraps fragments in <html><head/><body>content</body></html>
-    // We want to find the actual content element, not the html wrapper
+impl Default for SanitizePolicy {
+    /// A conservative default: common formatting/structural tags, no
+    /// scripting or embedding tags, and only the attributes needed for
+    /// links, images, and basic styling hooks. No unwrap tags, no per-tag
+    /// or per-scheme extensions, and no URL rewriting.
+    fn default() -> Self {
+        let allowed_tags = [
+            "div", "span", "p", "a", "strong", "em", "b", "i", "u", "br", "hr", "ul", "ol", "li",
+            "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "pre", "code", "table", "thead",
+            "tbody", "tr", "td", "th", "img", "form", "label", "input", "button", "select",
+            "option", "textarea",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
 
-    // First, find the <html> element
+        let allowed_attrs = [
+            "class", "id", "href", "src", "alt", "title", "type", "name", "value", "placeholder",
+            "for", "rel", "target",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        Self {
+            allowed_tags,
+            unwrap_tags: HashSet::new(),
+            allowed_attrs,
+            allowed_attrs_per_tag: HashMap::new(),
+            allowed_url_schemes: HashSet::new(),
+            rewrite_src_placeholder: None,
+            url_rewriter: None,
+        }
+    }
+}
+
+/// URL-bearing attributes that get checked for dangerous schemes.
+const URL_ATTRS: &[&str] = &["src", "href", "action", "formaction", "background", "poster"];
+
+/// Whether `value` carries a scheme that should never be forwarded: always
+/// `javascript:`/`data:`, plus anything outside `policy.allowed_url_schemes`
+/// when that allowlist is non-empty.
+fn url_is_disallowed(value: &str, policy: &SanitizePolicy) -> bool {
+    if matches!(url_scheme(value).as_deref(), Some("javascript") | Some("data")) {
+        return true;
+    }
+
+    if policy.allowed_url_schemes.is_empty() {
+        return false;
+    }
+
+    match url_scheme(value) {
+        Some(scheme) => !policy
+            .allowed_url_schemes
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(&scheme)),
+        None => false,
+    }
+}
+
+/// Extracts a URL's scheme (e.g. `"https"` from `"https://example.com"`),
+/// or `None` for a scheme-less (relative or fragment) URL like `"/path"`
+/// or `"#frag"`.
+///
+/// Skips whitespace/control characters while scanning rather than just
+/// trimming the start, so an embedded tab or newline inside the scheme
+/// (e.g. `"java\tscript:alert(1)"`) can't be used to slip a dangerous
+/// scheme past a naive `starts_with` check - browsers strip those bytes
+/// before parsing the scheme too, so rejecting them here matches what
+/// actually executes. Mirrors `djust_templates::filters::detect_url_scheme`.
+fn url_scheme(value: &str) -> Option<String> {
+    let mut scheme = String::new();
+    for c in value.chars() {
+        if c.is_whitespace() || c.is_control() {
+            continue;
+        }
+        if c == ':' {
+            return if scheme.is_empty() { None } else { Some(scheme.to_ascii_lowercase()) };
+        }
+        if scheme.is_empty() {
+            if !c.is_ascii_alphabetic() {
+                return None;
+            }
+        } else if !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')) {
+            return None;
+        }
+        scheme.push(c);
+    }
+    None
+}
+
+/// Strips disallowed attributes and event handlers from a single node, in
+/// place, applying `policy`'s URL-scheme and rewriter rules to whatever
+/// [`URL_ATTRS`] survive.
+fn sanitize_attrs(node: &mut VNode, policy: &SanitizePolicy) {
+    let keys: Vec<String> = node.attrs.keys().cloned().collect();
+    for key in keys {
+        if key == "data-dj" || key == "data-key" {
+            continue;
+        }
+
+        if key.to_ascii_lowercase().starts_with("on") {
+            node.attrs.shift_remove(&key);
+            continue;
+        }
+
+        let tag_allowed = policy
+            .allowed_attrs_per_tag
+            .get(&node.tag)
+            .is_some_and(|attrs| attrs.contains(&key));
+        if !policy.allowed_attrs.contains(&key) && !tag_allowed {
+            node.attrs.shift_remove(&key);
+            continue;
+        }
+
+        if !URL_ATTRS.contains(&key.as_str()) {
+            continue;
+        }
+
+        let dangerous = node
+            .attrs
+            .get(&key)
+            .map(|v| url_is_disallowed(v, policy))
+            .unwrap_or(false);
+
+        if dangerous {
+            if key == "src" {
+                if let Some(placeholder) = &policy.rewrite_src_placeholder {
+                    node.attrs.insert(key.clone(), placeholder.clone());
+                } else {
+                    node.attrs.shift_remove(&key);
+                    continue;
+                }
+            } else {
+                node.attrs.shift_remove(&key);
+                continue;
+            }
+        }
+
+        if let Some(rewriter) = &policy.url_rewriter {
+            if let Some(value) = node.attrs.shift_remove(&key) {
+                let (new_key, new_value) = rewriter.rewrite(&key, &value);
+                node.attrs.insert(new_key, new_value);
+            }
+        }
+    }
+
+    // Inline event listeners captured during diffing (e.g. `@click`
+    // handlers) are never safe to forward for untrusted content either.
+    node.listeners.clear();
+}
+
+/// Finds the `<body>` element that html5ever wraps fragment content in
+/// (`<html><head/><body>content</body></html>`), which `parse_fragment`
+/// needs to see through to reach the actual content.
+fn find_body(handle: &Handle) -> Option<Handle> {
     for child in handle.children.borrow().iter() {
         if let NodeData::Element { ref name, .. } = child.data {
             if name.local.as_ref() == "html" {
-                // Found <html>, now look for <body>
                 for html_child in child.children.borrow().iter() {
                     if let NodeData::Element { ref name, .. } = html_child.data {
                         if name.local.as_ref() == "body" {
-                            // Found <body>, return its first element child
-                            for body_child in html_child.children.borrow().iter() {
-                                if let NodeData::Element { .. } = body_child.data {
-                                    return body_child.clone();
-                                }
-                            }
+                            return Some(html_child.clone());
                         }
                     }
                 }
             }
         }
     }
+    None
+}
 
-    // Fallback: return first element found
-    for child in handle.children.borrow().iter() {
-        if let NodeData::Element { .. } = child.data {
-            return child.clone();
+/// Tags whose text content is rendered verbatim by the browser - runs of
+/// whitespace are significant and must never be collapsed or dropped.
+const PRESERVE_WHITESPACE_TAGS: &[&str] = &["pre", "textarea"];
+
+/// Block-level tags, used to decide when a whitespace-only text node
+/// between two siblings is safe to drop entirely: block boxes impose
+/// their own line breaks, so whitespace between two of them carries no
+/// visual meaning. Modeled on the usual HTML block-level tag set (see
+/// e.g. pulldown-cmark's `BLOCK_TAGS`).
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "dd", "details", "dialog", "div", "dl",
+    "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5",
+    "h6", "header", "hr", "html", "li", "main", "nav", "ol", "p", "pre", "section", "table",
+    "tbody", "td", "tfoot", "th", "thead", "tr", "ul",
+];
+
+fn is_preserve_whitespace_tag(tag: &str) -> bool {
+    PRESERVE_WHITESPACE_TAGS.contains(&tag)
+}
+
+fn is_block_tag(tag: &str) -> bool {
+    BLOCK_TAGS.contains(&tag)
+}
+
+/// Whether a whitespace-only text node next to `neighbor` is safe to drop
+/// entirely rather than collapse to a single space: true when there's no
+/// neighbor at all (the edge of the parent) or the neighbor is a
+/// block-level element. A neighbor that's an inline element or a text
+/// node means the whitespace is visually significant and must be kept.
+fn neighbor_allows_drop(neighbor: Option<&Handle>) -> bool {
+    match neighbor {
+        None => true,
+        Some(handle) => match &handle.data {
+            NodeData::Element { name, .. } => is_block_tag(name.local.as_ref()),
+            _ => false,
+        },
+    }
+}
+
+/// Converts each of `handle`'s children to a `VNode`, skipping HTML
+/// comments. Shared by `handle_to_vnode` (an element's own children) and
+/// `parse_fragment` (a fragment's top-level nodes), so both apply the
+/// identical rules.
+///
+/// `preserve` is true inside a `<pre>`/`<textarea>` subtree (see
+/// [`PRESERVE_WHITESPACE_TAGS`]), in which case every text node is kept
+/// verbatim. Otherwise, a whitespace-only text node is dropped when both
+/// neighboring siblings are block-level elements (or absent - the edge of
+/// the parent), and collapsed to a single space otherwise, so e.g.
+/// `<span>a</span> <span>b</span>` keeps the space between two inline
+/// elements instead of losing it.
+///
+/// `policy` is `Some` for [`parse_html_sanitized`]: a child tag absent from
+/// `policy.allowed_tags` is skipped (or, if listed in `policy.unwrap_tags`,
+/// replaced by its own converted children) before it's ever turned into a
+/// `VNode` - so a dropped subtree never consumes a `data-dj` id from `ids`.
+fn convert_children(
+    handle: &Handle,
+    preserve: bool,
+    policy: Option<&SanitizePolicy>,
+    ids: &mut IdAllocator,
+) -> Result<Vec<VNode>> {
+    let raw: Vec<Handle> = handle
+        .children
+        .borrow()
+        .iter()
+        .filter(|child| !matches!(child.data, NodeData::Comment { .. }))
+        .cloned()
+        .collect();
+
+    let mut children = Vec::new();
+    for (i, child) in raw.iter().enumerate() {
+        if let (NodeData::Element { ref name, .. }, Some(policy)) = (&child.data, policy) {
+            let tag = name.local.as_ref();
+            if !policy.allowed_tags.contains(tag) {
+                if policy.unwrap_tags.contains(tag) {
+                    children.extend(convert_children(child, preserve, Some(policy), ids)?);
+                }
+                continue;
+            }
         }
+
+        let child_vnode = handle_to_vnode(child, preserve, policy, ids)?;
+
+        if !preserve && child_vnode.is_text() {
+            let is_whitespace_only = child_vnode
+                .text
+                .as_deref()
+                .is_some_and(|text| text.chars().all(|c| c.is_whitespace()));
+
+            if is_whitespace_only {
+                let prev = i.checked_sub(1).and_then(|j| raw.get(j));
+                let next = raw.get(i + 1);
+                if neighbor_allows_drop(prev) && neighbor_allows_drop(next) {
+                    continue;
+                }
+                children.push(VNode::text(" "));
+                continue;
+            }
+        }
+
+        children.push(child_vnode);
     }
-    handle.clone()
+    Ok(children)
 }
 
-fn handle_to_vnode(handle: &Handle) -> Result<VNode> {
+fn handle_to_vnode(
+    handle: &Handle,
+    preserve: bool,
+    policy: Option<&SanitizePolicy>,
+    ids: &mut IdAllocator,
+) -> Result<VNode> {
     match &handle.data {
         NodeData::Text { contents } => {
             let text = contents.borrow().to_string();
@@ -82,11 +648,11 @@ fn handle_to_vnode(handle: &Handle) -> Result<VNode> {
             let mut vnode = VNode::element(tag.clone());
 
             // Generate compact unique ID for this element
-            let djust_id = next_djust_id();
+            let djust_id = ids.next_id();
             vnode.djust_id = Some(djust_id.clone());
 
             // Convert attributes and extract data-key for keyed diffing
-            let mut attributes = HashMap::new();
+            let mut attributes = IndexMap::new();
             let mut key: Option<String> = None;
 
             // Add data-dj attribute for client-side querySelector lookup
@@ -111,34 +677,14 @@ fn handle_to_vnode(handle: &Handle) -> Result<VNode> {
             vnode.attrs = attributes;
             vnode.key = key;
 
-            // Convert children
-            let mut children = Vec::new();
-            for child in handle.children.borrow().iter() {
-                // Skip comment nodes - they are not part of the DOM that JavaScript sees
-                if matches!(child.data, NodeData::Comment { .. }) {
-                    // Debug logging disabled - too verbose
-                    // eprintln!("[Parser] Filtered comment node");
-                    continue;
-                }
-
-                let child_vnode = handle_to_vnode(child)?;
-                // Skip empty text nodes - use more robust whitespace detection
-                if child_vnode.is_text() {
-                    if let Some(text) = &child_vnode.text {
-                        // Use chars().all() for more reliable whitespace detection
-                        // This catches all Unicode whitespace characters
-                        if !text.chars().all(|c| c.is_whitespace()) {
-                            children.push(child_vnode);
-                        } else {
-                            // Debug logging disabled - too verbose
-                            // eprintln!("[Parser] Filtered whitespace text node: {:?}", text);
-                        }
-                    }
-                } else {
-                    children.push(child_vnode);
-                }
+            if let Some(policy) = policy {
+                sanitize_attrs(&mut vnode, policy);
             }
-            vnode.children = children;
+
+            // Convert children
+            let preserve = preserve || is_preserve_whitespace_tag(&tag);
+            vnode.children = convert_children(handle, preserve, policy, ids)?;
+            vnode.recheck_fully_keyed();
 
             // Debug: log final child count for form elements
             if tag == "form" {
@@ -168,7 +714,7 @@ fn handle_to_vnode(handle: &Handle) -> Result<VNode> {
         NodeData::Document => {
             // For document nodes, process children and return first element
             for child in handle.children.borrow().iter() {
-                if let Ok(vnode) = handle_to_vnode(child) {
+                if let Ok(vnode) = handle_to_vnode(child, false, policy, ids) {
                     if !vnode.is_text() {
                         return Ok(vnode);
                     }
@@ -358,4 +904,406 @@ mod tests {
         assert_eq!(vnode.children.len(), 1);
         assert_eq!(vnode.children[0].key, Some("child".to_string()));
     }
+
+    #[test]
+    fn test_sanitized_drops_disallowed_tags() {
+        let html = "<div><script>alert(1)</script><p>Safe</p></div>";
+        let vnode = parse_html_sanitized(html, &SanitizePolicy::default()).unwrap();
+
+        assert_eq!(vnode.children.len(), 1);
+        assert_eq!(vnode.children[0].tag, "p");
+    }
+
+    #[test]
+    fn test_sanitized_strips_event_handler_attrs() {
+        let html = r#"<div onclick="evil()" class="ok">Content</div>"#;
+        let vnode = parse_html_sanitized(html, &SanitizePolicy::default()).unwrap();
+
+        assert!(vnode.attrs.get("onclick").is_none());
+        assert_eq!(vnode.attrs.get("class"), Some(&"ok".to_string()));
+    }
+
+    #[test]
+    fn test_sanitized_strips_javascript_url() {
+        let html = r#"<a href="javascript:alert(1)">click</a>"#;
+        let vnode = parse_html_sanitized(html, &SanitizePolicy::default()).unwrap();
+
+        assert!(vnode.attrs.get("href").is_none());
+    }
+
+    #[test]
+    fn test_sanitized_strips_javascript_url_with_embedded_tab() {
+        // Browsers strip ASCII tabs/newlines from a URL before parsing its
+        // scheme, so "java\tscript:" still executes - a naive
+        // `starts_with("javascript:")` check must not be fooled by it.
+        let html = "<a href=\"java\tscript:alert(1)\">click</a>";
+        let vnode = parse_html_sanitized(html, &SanitizePolicy::default()).unwrap();
+
+        assert!(vnode.attrs.get("href").is_none());
+    }
+
+    #[test]
+    fn test_sanitized_rewrites_dangerous_src_to_placeholder() {
+        let html = r#"<img src="data:text/html,evil">"#;
+        let mut policy = SanitizePolicy::default();
+        policy.rewrite_src_placeholder = Some("about:blank".to_string());
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        assert_eq!(vnode.attrs.get("src"), Some(&"about:blank".to_string()));
+    }
+
+    #[test]
+    fn test_sanitized_drops_attrs_outside_allowlist() {
+        let html = r#"<div data-evil="x" class="ok">Content</div>"#;
+        let vnode = parse_html_sanitized(html, &SanitizePolicy::default()).unwrap();
+
+        assert!(vnode.attrs.get("data-evil").is_none());
+        assert_eq!(vnode.attrs.get("class"), Some(&"ok".to_string()));
+    }
+
+    #[test]
+    fn test_sanitized_unwraps_disallowed_tag_instead_of_dropping_children() {
+        let html = "<div><font color=\"red\">Keep me</font></div>";
+        let mut policy = SanitizePolicy::default();
+        policy.unwrap_tags.insert("font".to_string());
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        // <font> itself is gone, but its text child survived in its place.
+        assert_eq!(vnode.children.len(), 1);
+        assert!(vnode.children[0].is_text());
+        assert_eq!(vnode.children[0].text.as_deref(), Some("Keep me"));
+    }
+
+    #[test]
+    fn test_sanitized_dropped_tag_does_not_consume_djust_ids() {
+        // Ids come from this parse's own sequential allocator, so with <script> dropped
+        // before it's ever converted to a VNode, the surviving <p> should
+        // get the very next id rather than one bumped past the dropped tag.
+        let html = "<div><script>alert(1)</script><p>Safe</p></div>";
+        let vnode = parse_html_sanitized(html, &SanitizePolicy::default()).unwrap();
+
+        let root_id: u64 = vnode.attrs.get("data-dj").unwrap().parse().unwrap_or(0);
+        let child_id: u64 = vnode.children[0]
+            .attrs
+            .get("data-dj")
+            .unwrap()
+            .parse()
+            .unwrap_or(0);
+        assert_eq!(child_id, root_id + 1);
+    }
+
+    #[test]
+    fn test_sanitized_allows_attr_via_per_tag_allowlist() {
+        let html = r#"<img data-zoom="2x">"#;
+        let mut policy = SanitizePolicy::default();
+        policy
+            .allowed_attrs_per_tag
+            .entry("img".to_string())
+            .or_default()
+            .insert("data-zoom".to_string());
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        assert_eq!(vnode.attrs.get("data-zoom"), Some(&"2x".to_string()));
+    }
+
+    #[test]
+    fn test_sanitized_per_tag_attr_does_not_leak_to_other_tags() {
+        let html = r#"<div data-zoom="2x"></div>"#;
+        let mut policy = SanitizePolicy::default();
+        policy
+            .allowed_attrs_per_tag
+            .entry("img".to_string())
+            .or_default()
+            .insert("data-zoom".to_string());
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        assert!(vnode.attrs.get("data-zoom").is_none());
+    }
+
+    #[test]
+    fn test_sanitized_rejects_scheme_outside_allowlist() {
+        let html = r#"<a href="ftp://example.com/file">dl</a>"#;
+        let mut policy = SanitizePolicy::default();
+        policy.allowed_url_schemes.insert("https".to_string());
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        assert!(vnode.attrs.get("href").is_none());
+    }
+
+    #[test]
+    fn test_sanitized_allows_relative_url_even_with_scheme_allowlist() {
+        let html = r#"<a href="/local/path">dl</a>"#;
+        let mut policy = SanitizePolicy::default();
+        policy.allowed_url_schemes.insert("https".to_string());
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        assert_eq!(vnode.attrs.get("href"), Some(&"/local/path".to_string()));
+    }
+
+    #[test]
+    fn test_sanitized_url_rewriter_renames_attr_and_value() {
+        let html = r#"<img src="https://example.com/x.png">"#;
+        let mut policy = SanitizePolicy::default();
+        policy.url_rewriter = Some(Box::new(|attr: &str, value: &str| {
+            (format!("data-{attr}"), value.to_string())
+        }));
+        let vnode = parse_html_sanitized(html, &policy).unwrap();
+
+        assert!(vnode.attrs.get("src").is_none());
+        assert_eq!(
+            vnode.attrs.get("data-src"),
+            Some(&"https://example.com/x.png".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment_returns_all_top_level_siblings() {
+        let html = "<li>Item 1</li><li>Item 2</li><li>Item 3</li>";
+        let roots = parse_fragment(html).unwrap();
+
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[0].tag, "li");
+        assert_eq!(roots[1].tag, "li");
+        assert_eq!(roots[2].tag, "li");
+    }
+
+    #[test]
+    fn test_parse_fragment_keeps_leading_and_trailing_text_siblings() {
+        let html = "Before <span>middle</span> after";
+        let roots = parse_fragment(html).unwrap();
+
+        assert_eq!(roots.len(), 3);
+        assert!(roots[0].is_text());
+        assert_eq!(roots[1].tag, "span");
+        assert!(roots[2].is_text());
+    }
+
+    #[test]
+    fn test_parse_fragment_filters_comments_and_whitespace_like_element_children() {
+        let html = "<!-- comment --> <div>Hi</div> ";
+        let roots = parse_fragment(html).unwrap();
+
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].tag, "div");
+    }
+
+    #[test]
+    fn test_parse_fragment_errors_on_empty_input() {
+        let err = parse_fragment("").unwrap_err();
+        assert!(err.to_string().contains("no root element"));
+    }
+
+    #[test]
+    fn test_parse_fragment_errors_when_only_comments_present() {
+        let err = parse_fragment("<!-- just a comment -->").unwrap_err();
+        assert!(err.to_string().contains("no root element"));
+    }
+
+    #[test]
+    fn test_parse_html_still_returns_single_root() {
+        let vnode = parse_html("<div>Hello</div>").unwrap();
+        assert_eq!(vnode.tag, "div");
+    }
+
+    #[test]
+    fn test_parse_html_errors_on_multiple_roots() {
+        let err = parse_html("<li>A</li><li>B</li>").unwrap_err();
+        assert!(err.to_string().contains("top-level nodes"));
+    }
+
+    #[test]
+    fn test_pre_preserves_whitespace_verbatim() {
+        let html = "<pre>  line one\n    line two  </pre>";
+        let vnode = parse_html(html).unwrap();
+
+        assert_eq!(vnode.tag, "pre");
+        assert_eq!(vnode.children.len(), 1);
+        assert_eq!(
+            vnode.children[0].text.as_deref(),
+            Some("  line one\n    line two  ")
+        );
+    }
+
+    #[test]
+    fn test_textarea_preserves_whitespace_verbatim() {
+        let html = "<textarea>\n  indented\n</textarea>";
+        let vnode = parse_html(html).unwrap();
+
+        assert_eq!(vnode.tag, "textarea");
+        assert_eq!(vnode.children[0].text.as_deref(), Some("\n  indented\n"));
+    }
+
+    #[test]
+    fn test_pre_preserves_whitespace_in_nested_elements() {
+        let html = "<pre><code>  foo  </code></pre>";
+        let vnode = parse_html(html).unwrap();
+
+        let code = &vnode.children[0];
+        assert_eq!(code.tag, "code");
+        assert_eq!(code.children[0].text.as_deref(), Some("  foo  "));
+    }
+
+    #[test]
+    fn test_whitespace_between_inline_elements_collapses_to_single_space() {
+        let html = "<div><span>a</span> <span>b</span></div>";
+        let vnode = parse_html(html).unwrap();
+
+        assert_eq!(vnode.children.len(), 3);
+        assert_eq!(vnode.children[0].tag, "span");
+        assert!(vnode.children[1].is_text());
+        assert_eq!(vnode.children[1].text.as_deref(), Some(" "));
+        assert_eq!(vnode.children[2].tag, "span");
+    }
+
+    #[test]
+    fn test_whitespace_run_between_inline_elements_collapses_to_one_space() {
+        let html = "<div><span>a</span>\n    <span>b</span></div>";
+        let vnode = parse_html(html).unwrap();
+
+        assert_eq!(vnode.children.len(), 3);
+        assert_eq!(vnode.children[1].text.as_deref(), Some(" "));
+    }
+
+    #[test]
+    fn test_whitespace_between_block_elements_is_still_dropped() {
+        let html = "<div><p>a</p>\n<p>b</p></div>";
+        let vnode = parse_html(html).unwrap();
+
+        // No text node between the two paragraphs - block boxes impose
+        // their own line breaks, so the whitespace carries no meaning.
+        assert_eq!(vnode.children.len(), 2);
+        assert_eq!(vnode.children[0].tag, "p");
+        assert_eq!(vnode.children[1].tag, "p");
+    }
+
+    #[test]
+    fn test_leading_and_trailing_whitespace_at_block_edges_is_dropped() {
+        let html = "<div>\n  <p>Hi</p>\n</div>";
+        let vnode = parse_html(html).unwrap();
+
+        assert_eq!(vnode.children.len(), 1);
+        assert_eq!(vnode.children[0].tag, "p");
+    }
+
+    #[test]
+    fn test_parse_html_verbose_root_matches_parse_html_for_well_formed_input() {
+        let html = "<!DOCTYPE html><div class=\"ok\">Hi</div>";
+        let plain = parse_html("<div class=\"ok\">Hi</div>").unwrap();
+        let verbose = parse_html_verbose(html).unwrap();
+
+        assert_eq!(verbose.root.tag, plain.tag);
+        assert_eq!(verbose.root.attrs.get("class"), plain.attrs.get("class"));
+    }
+
+    #[test]
+    fn test_parse_html_verbose_detects_no_quirks_with_standard_doctype() {
+        let html = "<!DOCTYPE html><div>Hi</div>";
+        let doc = parse_html_verbose(html).unwrap();
+
+        assert_eq!(doc.quirks_mode, QuirksMode::NoQuirks);
+    }
+
+    #[test]
+    fn test_parse_html_verbose_detects_quirks_mode_without_doctype() {
+        // A full document with no doctype at all triggers quirks mode per
+        // the HTML5 parsing spec, same as it would in a browser.
+        let html = "<div>Hi</div>";
+        let doc = parse_html_verbose(html).unwrap();
+
+        assert_eq!(doc.quirks_mode, QuirksMode::Quirks);
+    }
+
+    #[test]
+    fn test_parse_html_verbose_collects_errors_for_stray_end_tag() {
+        // </span> with no matching open <span> in scope is a textbook
+        // HTML5 tree-construction parse error that html5ever recovers
+        // from (by ignoring the stray end tag) rather than hard-failing.
+        let html = "<div>Hi</span></div>";
+        let doc = parse_html_verbose(html).unwrap();
+
+        assert!(!doc.errors.is_empty());
+        assert_eq!(doc.root.tag, "div");
+    }
+
+    #[test]
+    fn test_parse_html_verbose_errors_on_empty_input_like_parse_html() {
+        let err = parse_html_verbose("").unwrap_err();
+        assert!(err.to_string().contains("no root element found"));
+    }
+
+    #[test]
+    fn test_parse_html_default_options_starts_ids_at_zero() {
+        let vnode = parse_html("<div><span>Hi</span></div>").unwrap();
+
+        assert_eq!(vnode.attrs.get("data-dj"), Some(&"0".to_string()));
+        assert_eq!(vnode.children[0].attrs.get("data-dj"), Some(&"1".to_string()));
+    }
+
+    #[test]
+    fn test_parse_html_with_options_honors_start_id() {
+        let options = ParseOptions {
+            id_prefix: None,
+            start_id: 10,
+        };
+        let vnode = parse_html_with_options("<div>Hi</div>", &options).unwrap();
+
+        assert_eq!(vnode.attrs.get("data-dj"), Some(&"A".to_string()));
+    }
+
+    #[test]
+    fn test_parse_html_with_options_honors_id_prefix() {
+        let options = ParseOptions {
+            id_prefix: Some("modal-".to_string()),
+            start_id: 0,
+        };
+        let vnode = parse_html_with_options("<div>Hi</div>", &options).unwrap();
+
+        assert_eq!(vnode.attrs.get("data-dj"), Some(&"modal-0".to_string()));
+    }
+
+    #[test]
+    fn test_two_independent_parses_never_collide_on_ids() {
+        // Two fragments given non-overlapping namespaces via ParseOptions
+        // can be composed onto the same page without id collisions, even
+        // though each parse resets its own sequence independently.
+        let page = parse_html_with_options(
+            "<div>Page</div>",
+            &ParseOptions {
+                id_prefix: Some("page-".to_string()),
+                start_id: 0,
+            },
+        )
+        .unwrap();
+        let modal = parse_html_with_options(
+            "<div>Modal</div>",
+            &ParseOptions {
+                id_prefix: Some("modal-".to_string()),
+                start_id: 0,
+            },
+        )
+        .unwrap();
+
+        assert_ne!(
+            page.attrs.get("data-dj"),
+            modal.attrs.get("data-dj")
+        );
+        assert_eq!(page.attrs.get("data-dj"), Some(&"page-0".to_string()));
+        assert_eq!(modal.attrs.get("data-dj"), Some(&"modal-0".to_string()));
+    }
+
+    #[test]
+    fn test_parse_html_sanitized_with_options_still_applies_policy() {
+        let html = "<div><script>alert(1)</script><p>Safe</p></div>";
+        let options = ParseOptions {
+            id_prefix: None,
+            start_id: 5,
+        };
+        let vnode =
+            parse_html_sanitized_with_options(html, &SanitizePolicy::default(), &options)
+                .unwrap();
+
+        assert_eq!(vnode.attrs.get("data-dj"), Some(&"5".to_string()));
+        assert_eq!(vnode.children.len(), 1);
+        assert_eq!(vnode.children[0].tag, "p");
+    }
 }
@@ -3,6 +3,7 @@
 //! Utilities for applying patches to virtual DOM trees.
 //! In the LiveView system, patches are serialized and sent to the client.
 
+use crate::diff::diff_nodes;
 use crate::{Patch, VNode};
 
 /// Apply a list of patches to a virtual DOM tree (for testing purposes).
@@ -51,6 +52,7 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
         removes: Vec<(&'a Vec<usize>, usize)>,
         inserts: Vec<(&'a Vec<usize>, usize, &'a VNode)>,
         moves: Vec<&'a Patch>,
+        reorders: Vec<&'a Patch>,
         others: Vec<&'a Patch>,
     }
 
@@ -64,6 +66,7 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
                     removes: Vec::new(),
                     inserts: Vec::new(),
                     moves: Vec::new(),
+                    reorders: Vec::new(),
                     others: Vec::new(),
                 });
                 level.removes.push((path, *index));
@@ -75,6 +78,7 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
                     removes: Vec::new(),
                     inserts: Vec::new(),
                     moves: Vec::new(),
+                    reorders: Vec::new(),
                     others: Vec::new(),
                 });
                 level.inserts.push((path, *index, node));
@@ -84,15 +88,31 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
                     removes: Vec::new(),
                     inserts: Vec::new(),
                     moves: Vec::new(),
+                    reorders: Vec::new(),
                     others: Vec::new(),
                 });
                 level.moves.push(patch);
             }
+            Patch::ReorderChildren { path, .. } => {
+                let level = levels.entry(path.len()).or_insert_with(|| LevelPatches {
+                    removes: Vec::new(),
+                    inserts: Vec::new(),
+                    moves: Vec::new(),
+                    reorders: Vec::new(),
+                    others: Vec::new(),
+                });
+                level.reorders.push(patch);
+            }
             _ => {
                 let depth = match patch {
                     Patch::SetText { path, .. }
+                    | Patch::SpliceText { path, .. }
                     | Patch::SetAttr { path, .. }
                     | Patch::RemoveAttr { path, .. }
+                    | Patch::NewListener { path, .. }
+                    | Patch::RemoveListener { path, .. }
+                    | Patch::AddClass { path, .. }
+                    | Patch::RemoveClass { path, .. }
                     | Patch::Replace { path, .. } => path.len(),
                     _ => 0,
                 };
@@ -100,6 +120,7 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
                     removes: Vec::new(),
                     inserts: Vec::new(),
                     moves: Vec::new(),
+                    reorders: Vec::new(),
                     others: Vec::new(),
                 });
                 level.others.push(patch);
@@ -117,6 +138,7 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
             if let Some(target) = get_node_mut(root, path) {
                 if *index < target.children.len() {
                     target.children.remove(*index);
+                    target.recheck_fully_keyed();
                 }
             }
         }
@@ -150,6 +172,18 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
             }
         }
 
+        // Apply batched reorders - each names the final key order for
+        // the children surviving at this level, so it runs after removes
+        // (which may have shrunk that set) and before inserts (which
+        // haven't landed yet, so aren't part of the order list).
+        for patch in &level.reorders {
+            if let Patch::ReorderChildren { path, order, .. } = patch {
+                if let Some(target) = get_node_mut(root, path) {
+                    reorder_children_by_id(&mut target.children, order);
+                }
+            }
+        }
+
         // Apply inserts in ascending index order.
         level
             .inserts
@@ -158,6 +192,7 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
             if let Some(target) = get_node_mut(root, path) {
                 let insert_at = (*index).min(target.children.len());
                 target.children.insert(insert_at, (*node).clone());
+                target.recheck_fully_keyed();
             }
         }
 
@@ -168,6 +203,21 @@ pub fn apply_patches(root: &mut VNode, patches: &[Patch]) {
     }
 }
 
+/// Apply `patches` to a clone of `root` and return the result, leaving
+/// `root` itself untouched.
+///
+/// This is the server-side counterpart to the client's patch application:
+/// a renderer that wants to keep an authoritative post-patch `VNode` (to
+/// diff the *next* render against, or to verify a diff round-trips
+/// correctly) can call this instead of threading a `&mut VNode` through
+/// its own state. Built on [`apply_patches`] rather than duplicating its
+/// level-by-level/djust_id-resolution logic.
+pub fn apply_patches_cloned(root: &VNode, patches: &[Patch]) -> VNode {
+    let mut result = root.clone();
+    apply_patches(&mut result, patches);
+    result
+}
+
 /// Apply a single patch to a virtual DOM tree (for testing purposes)
 ///
 /// Note: For correct `MoveChild` handling with multiple moves, prefer
@@ -188,6 +238,21 @@ pub fn apply_patch(root: &mut VNode, patch: &Patch) {
             }
         }
 
+        Patch::SpliceText {
+            path,
+            start,
+            delete_count,
+            insert,
+            ..
+        } => {
+            if let Some(target) = get_node_mut(root, path) {
+                if let Some(existing) = &mut target.text {
+                    let end = (*start + *delete_count).min(existing.len());
+                    existing.replace_range(*start..end, insert);
+                }
+            }
+        }
+
         Patch::SetAttr {
             path, key, value, ..
         } => {
@@ -198,7 +263,9 @@ pub fn apply_patch(root: &mut VNode, patch: &Patch) {
 
         Patch::RemoveAttr { path, key, .. } => {
             if let Some(target) = get_node_mut(root, path) {
-                target.attrs.remove(key);
+                // `shift_remove`, not `swap_remove`/`remove` - attrs is an
+                // IndexMap precisely so attribute order stays stable.
+                target.attrs.shift_remove(key);
             }
         }
 
@@ -208,6 +275,7 @@ pub fn apply_patch(root: &mut VNode, patch: &Patch) {
             if let Some(target) = get_node_mut(root, path) {
                 if *index <= target.children.len() {
                     target.children.insert(*index, node.clone());
+                    target.recheck_fully_keyed();
                 }
             }
         }
@@ -216,6 +284,7 @@ pub fn apply_patch(root: &mut VNode, patch: &Patch) {
             if let Some(target) = get_node_mut(root, path) {
                 if *index < target.children.len() {
                     target.children.remove(*index);
+                    target.recheck_fully_keyed();
                 }
             }
         }
@@ -228,9 +297,210 @@ pub fn apply_patch(root: &mut VNode, patch: &Patch) {
                 }
             }
         }
+
+        Patch::NewListener { path, event, handler, .. } => {
+            if let Some(target) = get_node_mut(root, path) {
+                target.listeners.insert(event.clone(), handler.clone());
+            }
+        }
+
+        Patch::RemoveListener { path, event, .. } => {
+            if let Some(target) = get_node_mut(root, path) {
+                target.listeners.remove(event);
+            }
+        }
+
+        Patch::AddClass { path, class, .. } => {
+            if let Some(target) = get_node_mut(root, path) {
+                add_class_token(target, class);
+            }
+        }
+
+        Patch::RemoveClass { path, class, .. } => {
+            if let Some(target) = get_node_mut(root, path) {
+                remove_class_token(target, class);
+            }
+        }
+
+        Patch::ReorderChildren { path, order, .. } => {
+            if let Some(target) = get_node_mut(root, path) {
+                reorder_children_by_id(&mut target.children, order);
+            }
+        }
     }
 }
 
+/// Add `class` to `node`'s `class` attribute if it isn't already present,
+/// preserving the existing token order.
+fn add_class_token(node: &mut VNode, class: &str) {
+    let mut tokens: Vec<&str> = node
+        .attrs
+        .get("class")
+        .map(|v| v.split_whitespace().collect())
+        .unwrap_or_default();
+    if !tokens.contains(&class) {
+        tokens.push(class);
+    }
+    node.attrs.insert("class".to_string(), tokens.join(" "));
+}
+
+/// Remove `class` from `node`'s `class` attribute, dropping the attribute
+/// entirely once its last token is removed.
+fn remove_class_token(node: &mut VNode, class: &str) {
+    let Some(existing) = node.attrs.get("class") else {
+        return;
+    };
+    let tokens: Vec<&str> = existing.split_whitespace().filter(|t| *t != class).collect();
+    if tokens.is_empty() {
+        node.attrs.shift_remove("class");
+    } else {
+        let joined = tokens.join(" ");
+        node.attrs.insert("class".to_string(), joined);
+    }
+}
+
+/// Reorder `children` to match `order`, a list of `key`s naming the
+/// desired final sequence for the children that have one. Children whose
+/// key isn't in `order` (no key at all, or one [`diff::diff_keyed_children`]
+/// didn't carry over - e.g. a text node, or one handled by a separate
+/// `InsertChild`/`RemoveChild` already applied at this level) are left out
+/// of the reshuffle and appended afterward in their original relative
+/// order, the same "leave what it doesn't recognize alone" stance
+/// `remove_class_token` takes with an unrecognized token.
+fn reorder_children_by_id(children: &mut Vec<VNode>, order: &[String]) {
+    let mut by_key: std::collections::HashMap<String, VNode> = std::collections::HashMap::new();
+    let mut leftovers: Vec<VNode> = Vec::new();
+    for child in std::mem::take(children) {
+        match child.key.clone() {
+            Some(key) if order.contains(&key) => {
+                by_key.insert(key, child);
+            }
+            _ => leftovers.push(child),
+        }
+    }
+
+    let mut reordered: Vec<VNode> = order.iter().filter_map(|key| by_key.remove(key)).collect();
+    reordered.extend(leftovers);
+    *children = reordered;
+}
+
+/// Collect the index-path of every node in `root`, in pre-order (the node
+/// itself, then each child subtree left to right). Position `i` in the
+/// returned vec is node `i`'s path - the same numbering
+/// [`apply_patches_indexed`] rebuilds when it walks the patched-against tree,
+/// so index `i` always names the same node on both ends.
+fn preorder_paths(root: &VNode) -> Vec<Vec<usize>> {
+    fn walk(node: &VNode, path: &[usize], out: &mut Vec<Vec<usize>>) {
+        out.push(path.to_vec());
+        for (i, child) in node.children.iter().enumerate() {
+            let mut child_path = path.to_vec();
+            child_path.push(i);
+            walk(child, &child_path, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(root, &[], &mut out);
+    out
+}
+
+/// Clone `patch` with its path replaced by `path` (see [`diff_indexed`] and
+/// [`apply_patches_indexed`], which use this to swap a single pre-order
+/// index for a real path and back again).
+fn with_path(patch: Patch, path: Vec<usize>) -> Patch {
+    match patch {
+        Patch::Replace { d, node, .. } => Patch::Replace { path, d, node },
+        Patch::SetText { d, text, .. } => Patch::SetText { path, d, text },
+        Patch::SpliceText {
+            d,
+            start,
+            delete_count,
+            insert,
+            ..
+        } => Patch::SpliceText {
+            path,
+            d,
+            start,
+            delete_count,
+            insert,
+        },
+        Patch::SetAttr { d, key, value, .. } => Patch::SetAttr {
+            path,
+            d,
+            key,
+            value,
+        },
+        Patch::RemoveAttr { d, key, .. } => Patch::RemoveAttr { path, d, key },
+        Patch::NewListener {
+            d, event, handler, ..
+        } => Patch::NewListener {
+            path,
+            d,
+            event,
+            handler,
+        },
+        Patch::RemoveListener { d, event, .. } => Patch::RemoveListener { path, d, event },
+        Patch::AddClass { d, class, .. } => Patch::AddClass { path, d, class },
+        Patch::RemoveClass { d, class, .. } => Patch::RemoveClass { path, d, class },
+        Patch::InsertChild { d, index, node, .. } => Patch::InsertChild {
+            path,
+            d,
+            index,
+            node,
+        },
+        Patch::RemoveChild { d, index, .. } => Patch::RemoveChild { path, d, index },
+        Patch::MoveChild { d, from, to, .. } => Patch::MoveChild { path, d, from, to },
+        Patch::ReorderChildren { d, order, .. } => Patch::ReorderChildren { path, d, order },
+    }
+}
+
+/// Like [`crate::diff`], but addresses each patch's target by a single
+/// `u32` pre-order index instead of a full `Vec<usize>` path - this is the
+/// node-index scheme Percy and early Dioxus use, and it shrinks the
+/// serialized patch payload for deep/wide trees where a path can run to a
+/// dozen-plus entries. The index is carried in `path` as a one-element vec
+/// so [`Patch`]'s shape doesn't need its own indexed variant; pair this with
+/// [`apply_patches_indexed`], which rebuilds the same numbering on the
+/// receiving tree to resolve it back to a path.
+pub fn diff_indexed(old: &VNode, new: &VNode) -> Vec<Patch> {
+    let patches = diff_nodes(old, new, &[]);
+
+    // All of `diff_nodes`'s paths reference `old`'s layout (see diff.rs),
+    // so numbering `old` in pre-order gives every patch's target a stable index.
+    let index_of: std::collections::HashMap<Vec<usize>, u32> = preorder_paths(old)
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| (path, i as u32))
+        .collect();
+
+    patches
+        .into_iter()
+        .filter_map(|patch| {
+            let idx = *index_of.get(patch_path(&patch))?;
+            Some(with_path(patch, vec![idx as usize]))
+        })
+        .collect()
+}
+
+/// Apply patches produced by [`diff_indexed`]: walks `root` in pre-order to
+/// rebuild the index->path numbering `diff_indexed` used, translates each
+/// patch's index back to a path, and applies the result with
+/// [`apply_patches`].
+pub fn apply_patches_indexed(root: &mut VNode, patches: &[Patch]) {
+    let paths = preorder_paths(root);
+
+    let translated: Vec<Patch> = patches
+        .iter()
+        .filter_map(|patch| {
+            let idx = *patch_path(patch).first()?;
+            let path = paths.get(idx)?.clone();
+            Some(with_path(patch.clone(), path))
+        })
+        .collect();
+
+    apply_patches(root, &translated);
+}
+
 fn get_node<'a>(root: &'a VNode, path: &[usize]) -> Option<&'a VNode> {
     let mut current = root;
 
@@ -259,6 +529,456 @@ fn get_node_mut<'a>(root: &'a mut VNode, path: &[usize]) -> Option<&'a mut VNode
     Some(current)
 }
 
+/// Apply patches in-place with a single tree walk instead of re-descending
+/// from `root` once per patch the way [`apply_patches`] does: patches are
+/// grouped by the child index at each level and handed to that child as an
+/// exclusive, disjoint `&mut VNode` borrow, so sibling subtrees never alias
+/// each other. That disjointness is also what would let a future
+/// `par_apply` variant recurse into siblings concurrently without
+/// restructuring this walk.
+///
+/// Patch paths are assumed to reference the FINAL tree layout at their
+/// level (as [`apply_patches`] documents) - structural edits
+/// (insert/remove/move) at a node are applied before recursing into its
+/// children, so the child indices in the remaining, path-shortened patches
+/// line up with the node's already-mutated `children`.
+pub fn apply_patches_in_place(root: &mut VNode, patches: &[Patch]) {
+    apply_patches_at(root, patches);
+}
+
+fn apply_patches_at(node: &mut VNode, patches: &[Patch]) {
+    if patches.is_empty() {
+        return;
+    }
+
+    // Patches targeting `node` itself vs. patches descending into one of
+    // `node`'s children, grouped by child index with that first path
+    // segment stripped off.
+    let mut here: Vec<&Patch> = Vec::new();
+    let mut by_child: std::collections::BTreeMap<usize, Vec<Patch>> =
+        std::collections::BTreeMap::new();
+
+    for patch in patches {
+        let path = patch_path(patch);
+        if path.is_empty() {
+            here.push(patch);
+        } else {
+            by_child
+                .entry(path[0])
+                .or_default()
+                .push(reparent(patch, &path[1..]));
+        }
+    }
+
+    apply_node_level(node, &here);
+
+    for (index, child_patches) in by_child {
+        if let Some(child) = node.children.get_mut(index) {
+            apply_patches_at(child, &child_patches);
+        }
+    }
+}
+
+/// Apply the patches whose path already points at `node` itself: structural
+/// edits to `node.children` (removes, then moves, then inserts - the same
+/// order [`apply_patches`] uses) followed by attribute/text/replace edits.
+fn apply_node_level(node: &mut VNode, patches: &[&Patch]) {
+    let mut removes: Vec<usize> = Vec::new();
+    let mut moves: Vec<(usize, usize)> = Vec::new();
+    let mut inserts: Vec<(usize, &VNode)> = Vec::new();
+    let mut others: Vec<&Patch> = Vec::new();
+
+    let mut reorders: Vec<&[String]> = Vec::new();
+
+    for patch in patches {
+        match patch {
+            Patch::RemoveChild { index, .. } => removes.push(*index),
+            Patch::MoveChild { from, to, .. } => moves.push((*from, *to)),
+            Patch::InsertChild { index, node: n, .. } => inserts.push((*index, n)),
+            Patch::ReorderChildren { order, .. } => reorders.push(order),
+            other => others.push(other),
+        }
+    }
+
+    let mutates_children =
+        !removes.is_empty() || !moves.is_empty() || !inserts.is_empty() || !reorders.is_empty();
+
+    removes.sort_unstable_by(|a, b| b.cmp(a));
+    for index in removes {
+        if index < node.children.len() {
+            node.children.remove(index);
+        }
+    }
+
+    for (from, to) in moves {
+        if from < node.children.len() && to <= node.children.len() {
+            let moved = node.children.remove(from);
+            node.children.insert(to, moved);
+        }
+    }
+
+    for order in reorders {
+        reorder_children_by_id(&mut node.children, order);
+    }
+
+    inserts.sort_by_key(|(index, _)| *index);
+    for (index, new_node) in inserts {
+        let at = index.min(node.children.len());
+        node.children.insert(at, new_node.clone());
+    }
+
+    if mutates_children {
+        node.recheck_fully_keyed();
+    }
+
+    for patch in others {
+        match patch {
+            Patch::Replace { node: new_node, .. } => *node = new_node.clone(),
+            Patch::SetText { text, .. } => node.text = Some(text.clone()),
+            Patch::SpliceText {
+                start,
+                delete_count,
+                insert,
+                ..
+            } => {
+                if let Some(existing) = &mut node.text {
+                    let end = (*start + *delete_count).min(existing.len());
+                    existing.replace_range(*start..end, insert);
+                }
+            }
+            Patch::SetAttr { key, value, .. } => {
+                node.attrs.insert(key.clone(), value.clone());
+            }
+            Patch::RemoveAttr { key, .. } => {
+                node.attrs.shift_remove(key);
+            }
+            Patch::NewListener { event, handler, .. } => {
+                node.listeners.insert(event.clone(), handler.clone());
+            }
+            Patch::RemoveListener { event, .. } => {
+                node.listeners.remove(event);
+            }
+            Patch::AddClass { class, .. } => add_class_token(node, class),
+            Patch::RemoveClass { class, .. } => remove_class_token(node, class),
+            Patch::InsertChild { .. }
+            | Patch::RemoveChild { .. }
+            | Patch::MoveChild { .. }
+            | Patch::ReorderChildren { .. } => {
+                unreachable!("structural patches are handled above")
+            }
+        }
+    }
+}
+
+fn patch_path(patch: &Patch) -> &[usize] {
+    match patch {
+        Patch::Replace { path, .. }
+        | Patch::SetText { path, .. }
+        | Patch::SpliceText { path, .. }
+        | Patch::SetAttr { path, .. }
+        | Patch::RemoveAttr { path, .. }
+        | Patch::NewListener { path, .. }
+        | Patch::RemoveListener { path, .. }
+        | Patch::AddClass { path, .. }
+        | Patch::RemoveClass { path, .. }
+        | Patch::InsertChild { path, .. }
+        | Patch::RemoveChild { path, .. }
+        | Patch::MoveChild { path, .. }
+        | Patch::ReorderChildren { path, .. } => path,
+    }
+}
+
+/// Clone `patch` with its path replaced by `rest` (the path with the first,
+/// already-consumed segment stripped off).
+fn reparent(patch: &Patch, rest: &[usize]) -> Patch {
+    match patch {
+        Patch::Replace { node, .. } => Patch::Replace {
+            path: rest.to_vec(),
+            d: None,
+            node: node.clone(),
+        },
+        Patch::SetText { text, .. } => Patch::SetText {
+            path: rest.to_vec(),
+            d: None,
+            text: text.clone(),
+        },
+        Patch::SpliceText {
+            start,
+            delete_count,
+            insert,
+            ..
+        } => Patch::SpliceText {
+            path: rest.to_vec(),
+            d: None,
+            start: *start,
+            delete_count: *delete_count,
+            insert: insert.clone(),
+        },
+        Patch::SetAttr { key, value, .. } => Patch::SetAttr {
+            path: rest.to_vec(),
+            d: None,
+            key: key.clone(),
+            value: value.clone(),
+        },
+        Patch::RemoveAttr { key, .. } => Patch::RemoveAttr {
+            path: rest.to_vec(),
+            d: None,
+            key: key.clone(),
+        },
+        Patch::NewListener { event, handler, .. } => Patch::NewListener {
+            path: rest.to_vec(),
+            d: None,
+            event: event.clone(),
+            handler: handler.clone(),
+        },
+        Patch::RemoveListener { event, .. } => Patch::RemoveListener {
+            path: rest.to_vec(),
+            d: None,
+            event: event.clone(),
+        },
+        Patch::AddClass { class, .. } => Patch::AddClass {
+            path: rest.to_vec(),
+            d: None,
+            class: class.clone(),
+        },
+        Patch::RemoveClass { class, .. } => Patch::RemoveClass {
+            path: rest.to_vec(),
+            d: None,
+            class: class.clone(),
+        },
+        Patch::InsertChild { index, node, .. } => Patch::InsertChild {
+            path: rest.to_vec(),
+            d: None,
+            index: *index,
+            node: node.clone(),
+        },
+        Patch::RemoveChild { index, .. } => Patch::RemoveChild {
+            path: rest.to_vec(),
+            d: None,
+            index: *index,
+        },
+        Patch::MoveChild { from, to, .. } => Patch::MoveChild {
+            path: rest.to_vec(),
+            d: None,
+            from: *from,
+            to: *to,
+        },
+        Patch::ReorderChildren { order, .. } => Patch::ReorderChildren {
+            path: rest.to_vec(),
+            d: None,
+            order: order.clone(),
+        },
+    }
+}
+
+/// Compose two sequential patch sets - `p1` taking tree A to B, `p2` taking
+/// B to C - into a single A -> C patch set, without ever materializing B.
+///
+/// This folds the common case a real render loop produces: repeated
+/// attribute/text edits and matching insert/remove pairs on the same path
+/// collapsing to their net effect, the same way `BTreeMap::append` walks two
+/// already-ordered streams and merges entries that land on the same key. An
+/// `InsertChild` from `p1` consumed by a `RemoveChild` from `p2` at the same
+/// path and index cancels out entirely; a `Replace`, `SetText`, or
+/// `SetAttr`/`RemoveAttr` at a given path keeps only the later one of the
+/// two streams.
+///
+/// Structural patches (`InsertChild`/`RemoveChild`/`MoveChild`) that don't
+/// cancel are passed through in stream order. This does NOT renumber `p2`'s
+/// indices to account for structural changes `p1` made first - doing that in
+/// general requires knowing B's actual shape, i.e. materializing it, which
+/// is exactly what this function avoids. It's correct for the common case
+/// callers want (batching several frames of attribute/text tweaks into one
+/// DOM mutation) and a reasonable approximation otherwise.
+pub fn compose_patches(p1: &[Patch], p2: &[Patch]) -> Vec<Patch> {
+    use std::collections::HashMap;
+
+    let mut replace_at: HashMap<Vec<usize>, VNode> = HashMap::new();
+    let mut replace_order: Vec<Vec<usize>> = Vec::new();
+    let mut text_at: HashMap<Vec<usize>, String> = HashMap::new();
+    let mut text_order: Vec<Vec<usize>> = Vec::new();
+    let mut attr_at: HashMap<(Vec<usize>, String), Option<String>> = HashMap::new();
+    let mut attr_order: Vec<(Vec<usize>, String)> = Vec::new();
+    let mut listener_at: HashMap<(Vec<usize>, String), Option<String>> = HashMap::new();
+    let mut listener_order: Vec<(Vec<usize>, String)> = Vec::new();
+    // `true` = AddClass, `false` = RemoveClass, keyed by (path, class token).
+    let mut class_at: HashMap<(Vec<usize>, String), bool> = HashMap::new();
+    let mut class_order: Vec<(Vec<usize>, String)> = Vec::new();
+    let mut child_patches: Vec<Patch> = Vec::new();
+
+    for patch in p1.iter().chain(p2.iter()) {
+        match patch {
+            Patch::Replace { path, node, .. } => {
+                if !replace_at.contains_key(path) {
+                    replace_order.push(path.clone());
+                }
+                replace_at.insert(path.clone(), node.clone());
+            }
+            Patch::SetText { path, text, .. } => {
+                if !text_at.contains_key(path) {
+                    text_order.push(path.clone());
+                }
+                text_at.insert(path.clone(), text.clone());
+            }
+            // `SpliceText` names an edit relative to whatever text is
+            // already there, so unlike `SetText` it can't collapse into a
+            // single net value without materializing B - the same
+            // limitation noted above for structural patches. Passed through
+            // in stream order instead.
+            Patch::SpliceText {
+                path,
+                start,
+                delete_count,
+                insert,
+                ..
+            } => {
+                child_patches.push(Patch::SpliceText {
+                    path: path.clone(),
+                    d: None,
+                    start: *start,
+                    delete_count: *delete_count,
+                    insert: insert.clone(),
+                });
+            }
+            Patch::SetAttr { path, key, value, .. } => {
+                let k = (path.clone(), key.clone());
+                if !attr_at.contains_key(&k) {
+                    attr_order.push(k.clone());
+                }
+                attr_at.insert(k, Some(value.clone()));
+            }
+            Patch::RemoveAttr { path, key, .. } => {
+                let k = (path.clone(), key.clone());
+                if !attr_at.contains_key(&k) {
+                    attr_order.push(k.clone());
+                }
+                attr_at.insert(k, None);
+            }
+            Patch::InsertChild { path, index, node, .. } => {
+                child_patches.push(Patch::InsertChild {
+                    path: path.clone(),
+                    d: None,
+                    index: *index,
+                    node: node.clone(),
+                });
+            }
+            Patch::RemoveChild { path, index, .. } => {
+                // Cancel against an as-yet-unconsumed InsertChild at the same
+                // path and index from earlier in the combined stream.
+                let cancels = child_patches.iter().position(|existing| {
+                    matches!(existing, Patch::InsertChild { path: ip, index: ii, .. } if ip == path && ii == index)
+                });
+                match cancels {
+                    Some(pos) => {
+                        child_patches.remove(pos);
+                    }
+                    None => child_patches.push(Patch::RemoveChild {
+                        path: path.clone(),
+                        d: None,
+                        index: *index,
+                    }),
+                }
+            }
+            Patch::MoveChild { path, from, to, .. } => {
+                child_patches.push(Patch::MoveChild {
+                    path: path.clone(),
+                    d: None,
+                    from: *from,
+                    to: *to,
+                });
+            }
+            Patch::ReorderChildren { path, order, .. } => {
+                child_patches.push(Patch::ReorderChildren {
+                    path: path.clone(),
+                    d: None,
+                    order: order.clone(),
+                });
+            }
+            Patch::NewListener { path, event, handler, .. } => {
+                let k = (path.clone(), event.clone());
+                if !listener_at.contains_key(&k) {
+                    listener_order.push(k.clone());
+                }
+                listener_at.insert(k, Some(handler.clone()));
+            }
+            Patch::RemoveListener { path, event, .. } => {
+                let k = (path.clone(), event.clone());
+                if !listener_at.contains_key(&k) {
+                    listener_order.push(k.clone());
+                }
+                listener_at.insert(k, None);
+            }
+            Patch::AddClass { path, class, .. } => {
+                let k = (path.clone(), class.clone());
+                if !class_at.contains_key(&k) {
+                    class_order.push(k.clone());
+                }
+                class_at.insert(k, true);
+            }
+            Patch::RemoveClass { path, class, .. } => {
+                let k = (path.clone(), class.clone());
+                if !class_at.contains_key(&k) {
+                    class_order.push(k.clone());
+                }
+                class_at.insert(k, false);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for path in replace_order {
+        out.push(Patch::Replace {
+            node: replace_at.remove(&path).unwrap(),
+            path,
+            d: None,
+        });
+    }
+    for path in text_order {
+        out.push(Patch::SetText {
+            text: text_at.remove(&path).unwrap(),
+            path,
+            d: None,
+        });
+    }
+    for (path, key) in attr_order {
+        match attr_at.remove(&(path.clone(), key.clone())).unwrap() {
+            Some(value) => out.push(Patch::SetAttr { path, d: None, key, value }),
+            None => out.push(Patch::RemoveAttr { path, d: None, key }),
+        }
+    }
+    for (path, event) in listener_order {
+        match listener_at.remove(&(path.clone(), event.clone())).unwrap() {
+            Some(handler) => out.push(Patch::NewListener {
+                path,
+                d: None,
+                event,
+                handler,
+            }),
+            None => out.push(Patch::RemoveListener {
+                path,
+                d: None,
+                event,
+            }),
+        }
+    }
+    for (path, class) in class_order {
+        match class_at.remove(&(path.clone(), class.clone())).unwrap() {
+            true => out.push(Patch::AddClass {
+                path,
+                d: None,
+                class,
+            }),
+            false => out.push(Patch::RemoveClass {
+                path,
+                d: None,
+                class,
+            }),
+        }
+    }
+    out.extend(child_patches);
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -303,4 +1023,260 @@ mod tests {
         apply_patch(&mut root, &patch);
         assert_eq!(root.children.len(), 1);
     }
+
+    #[test]
+    fn test_apply_new_and_remove_listener() {
+        let mut root = VNode::element("button");
+
+        apply_patch(
+            &mut root,
+            &Patch::NewListener {
+                path: vec![],
+                d: Some("0".to_string()),
+                event: "click".to_string(),
+                handler: "handle_click".to_string(),
+            },
+        );
+        assert_eq!(
+            root.listeners.get("click"),
+            Some(&"handle_click".to_string())
+        );
+
+        apply_patch(
+            &mut root,
+            &Patch::RemoveListener {
+                path: vec![],
+                d: Some("0".to_string()),
+                event: "click".to_string(),
+            },
+        );
+        assert!(root.listeners.get("click").is_none());
+    }
+
+    #[test]
+    fn test_apply_add_and_remove_class_token() {
+        let mut root = VNode::element("input").with_attr("class", "form-control");
+
+        apply_patch(
+            &mut root,
+            &Patch::AddClass {
+                path: vec![],
+                d: None,
+                class: "is-invalid".to_string(),
+            },
+        );
+        assert_eq!(
+            root.attrs.get("class"),
+            Some(&"form-control is-invalid".to_string())
+        );
+
+        apply_patch(
+            &mut root,
+            &Patch::RemoveClass {
+                path: vec![],
+                d: None,
+                class: "form-control".to_string(),
+            },
+        );
+        assert_eq!(root.attrs.get("class"), Some(&"is-invalid".to_string()));
+
+        apply_patch(
+            &mut root,
+            &Patch::RemoveClass {
+                path: vec![],
+                d: None,
+                class: "is-invalid".to_string(),
+            },
+        );
+        assert!(root.attrs.get("class").is_none());
+    }
+
+    #[test]
+    fn test_compose_patches_attr_set_then_set_keeps_later_value() {
+        let p1 = vec![Patch::SetAttr {
+            path: vec![0],
+            d: None,
+            key: "class".to_string(),
+            value: "old".to_string(),
+        }];
+        let p2 = vec![Patch::SetAttr {
+            path: vec![0],
+            d: None,
+            key: "class".to_string(),
+            value: "new".to_string(),
+        }];
+
+        let composed = compose_patches(&p1, &p2);
+        assert_eq!(composed.len(), 1);
+        assert!(matches!(
+            &composed[0],
+            Patch::SetAttr { value, .. } if value == "new"
+        ));
+    }
+
+    #[test]
+    fn test_compose_patches_text_set_then_set_keeps_later_value() {
+        let p1 = vec![Patch::SetText {
+            path: vec![1],
+            d: None,
+            text: "first".to_string(),
+        }];
+        let p2 = vec![Patch::SetText {
+            path: vec![1],
+            d: None,
+            text: "second".to_string(),
+        }];
+
+        let composed = compose_patches(&p1, &p2);
+        assert_eq!(composed.len(), 1);
+        assert!(matches!(
+            &composed[0],
+            Patch::SetText { text, .. } if text == "second"
+        ));
+    }
+
+    #[test]
+    fn test_compose_patches_insert_then_remove_cancels() {
+        let p1 = vec![Patch::InsertChild {
+            path: vec![],
+            d: None,
+            index: 2,
+            node: VNode::text("child"),
+        }];
+        let p2 = vec![Patch::RemoveChild {
+            path: vec![],
+            d: None,
+            index: 2,
+        }];
+
+        let composed = compose_patches(&p1, &p2);
+        assert!(
+            composed.is_empty(),
+            "insert immediately undone by remove should cancel: {composed:?}"
+        );
+    }
+
+    #[test]
+    fn test_compose_patches_passes_through_unrelated_patches() {
+        let p1 = vec![Patch::SetAttr {
+            path: vec![0],
+            d: None,
+            key: "class".to_string(),
+            value: "a".to_string(),
+        }];
+        let p2 = vec![Patch::SetText {
+            path: vec![1],
+            d: None,
+            text: "b".to_string(),
+        }];
+
+        let composed = compose_patches(&p1, &p2);
+        assert_eq!(composed.len(), 2);
+    }
+
+    #[test]
+    fn test_compose_patches_applying_composed_equals_applying_sequentially() {
+        let mut a = VNode::element("div").with_child(VNode::text("start"));
+
+        let p1 = vec![Patch::SetText {
+            path: vec![0],
+            d: None,
+            text: "middle".to_string(),
+        }];
+        let p2 = vec![Patch::SetText {
+            path: vec![0],
+            d: None,
+            text: "end".to_string(),
+        }];
+
+        let mut sequential = a.clone();
+        apply_patches(&mut sequential, &p1);
+        apply_patches(&mut sequential, &p2);
+
+        let composed = compose_patches(&p1, &p2);
+        apply_patches(&mut a, &composed);
+
+        assert_eq!(a, sequential);
+    }
+
+    #[test]
+    fn test_apply_patch_splice_text_edits_in_place() {
+        let mut root = VNode::element("div").with_child(VNode::text("Hello, wor"));
+
+        apply_patch(
+            &mut root,
+            &Patch::SpliceText {
+                path: vec![0],
+                d: None,
+                start: 10,
+                delete_count: 0,
+                insert: "ld".to_string(),
+            },
+        );
+
+        assert_eq!(root.children[0].text.as_deref(), Some("Hello, world"));
+    }
+
+    #[test]
+    fn test_diff_then_apply_splice_text_round_trips() {
+        let old = VNode::element("div").with_child(VNode::text("The cat sat on the mat"));
+        let new = VNode::element("div").with_child(VNode::text("The dog sat on the mat"));
+
+        let patches = diff_nodes(&old, &new, &[]);
+        assert!(patches.iter().any(|p| matches!(p, Patch::SpliceText { .. })));
+
+        let mut applied = old.clone();
+        apply_patches(&mut applied, &patches);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn test_apply_patches_cloned_leaves_original_untouched() {
+        let old = VNode::element("div").with_child(VNode::text("before"));
+        let patches = vec![Patch::SetText {
+            path: vec![0],
+            d: None,
+            text: "after".to_string(),
+        }];
+
+        let result = apply_patches_cloned(&old, &patches);
+
+        assert_eq!(old.children[0].text.as_deref(), Some("before"));
+        assert_eq!(result.children[0].text.as_deref(), Some("after"));
+    }
+
+    #[test]
+    fn test_diff_indexed_uses_single_element_paths() {
+        let old = VNode::element("div").with_child(
+            VNode::element("span").with_child(VNode::text("old")),
+        );
+        let new = VNode::element("div").with_child(
+            VNode::element("span").with_child(VNode::text("new")),
+        );
+
+        let patches = diff_indexed(&old, &new);
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(&patches[0], Patch::SetText { path, text, .. } if path.len() == 1 && text == "new"));
+    }
+
+    #[test]
+    fn test_apply_patches_indexed_matches_path_based_result() {
+        let old = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_child(VNode::text("a")),
+            VNode::element("li").with_child(VNode::text("b")),
+        ]);
+        let new = VNode::element("ul").with_children(vec![
+            VNode::element("li").with_child(VNode::text("a")),
+            VNode::element("li").with_child(VNode::text("changed")),
+        ]);
+
+        let mut via_path = old.clone();
+        apply_patches(&mut via_path, &diff_nodes(&old, &new, &[]));
+
+        let mut via_index = old.clone();
+        apply_patches_indexed(&mut via_index, &diff_indexed(&old, &new));
+
+        assert_eq!(via_path, new);
+        assert_eq!(via_index, new);
+    }
 }
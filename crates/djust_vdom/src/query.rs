@@ -0,0 +1,497 @@
+//! A small CSS-selector query layer over a parsed [`VNode`] tree, so
+//! callers can locate nodes after parsing without hand-writing recursion.
+//!
+//! Supports, at minimum: tag (`div`), class (`.foo`), id (`#bar`),
+//! attribute (`[data-key]`, `[type="email"]`), descendant (`div span`) and
+//! direct-child (`div > span`) combinators, and compound selectors that
+//! combine several of the above on one element (`div.card#main[data-key]`).
+
+use crate::VNode;
+use std::collections::HashMap;
+
+/// A single predicate against one element, e.g. the `div.card#main` part
+/// of `div.card#main > span`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CompoundSelector {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<AttrPredicate>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum AttrPredicate {
+    /// `[attr]` - the attribute must be present, any value.
+    Exists(String),
+    /// `[attr="value"]` - the attribute must be present with exactly this value.
+    Equals(String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    /// `a b` - `b` may be any descendant of `a`.
+    Descendant,
+    /// `a > b` - `b` must be an immediate child of `a`.
+    Child,
+}
+
+/// A parsed selector: a list of compound selectors joined by combinators,
+/// read left to right the way the selector text reads (`div > span.label`
+/// is `[div, span.label]` joined by `[Child]`).
+#[derive(Debug, Clone, PartialEq)]
+struct Selector {
+    compounds: Vec<CompoundSelector>,
+    /// One shorter than `compounds`: `combinators[i]` joins `compounds[i]`
+    /// to `compounds[i + 1]`.
+    combinators: Vec<Combinator>,
+}
+
+fn parse_selector(input: &str) -> Result<Selector, String> {
+    // Normalize so `>` is always its own whitespace-delimited token,
+    // whether the source wrote `div>span`, `div > span`, or `div >span`.
+    let normalized = input.replace('>', " > ");
+    let tokens: Vec<&str> = normalized.split_whitespace().collect();
+
+    if tokens.is_empty() {
+        return Err("empty selector".to_string());
+    }
+
+    let mut compounds = Vec::new();
+    let mut combinators = Vec::new();
+    let mut pending_combinator = None;
+
+    for token in tokens {
+        if token == ">" {
+            pending_combinator = Some(Combinator::Child);
+            continue;
+        }
+
+        if !compounds.is_empty() {
+            combinators.push(pending_combinator.take().unwrap_or(Combinator::Descendant));
+        }
+        compounds.push(parse_compound(token)?);
+    }
+
+    if pending_combinator.is_some() {
+        return Err(format!("selector '{input}' ends with a dangling combinator"));
+    }
+
+    Ok(Selector {
+        compounds,
+        combinators,
+    })
+}
+
+fn parse_compound(token: &str) -> Result<CompoundSelector, String> {
+    let mut chars = token.chars().peekable();
+    let mut compound = CompoundSelector::default();
+
+    if let Some(&c) = chars.peek() {
+        if c != '.' && c != '#' && c != '[' {
+            compound.tag = Some(take_name(&mut chars));
+        }
+    }
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '.' => {
+                chars.next();
+                let name = take_name(&mut chars);
+                if name.is_empty() {
+                    return Err(format!("empty class selector in '{token}'"));
+                }
+                compound.classes.push(name);
+            }
+            '#' => {
+                chars.next();
+                let name = take_name(&mut chars);
+                if name.is_empty() {
+                    return Err(format!("empty id selector in '{token}'"));
+                }
+                compound.id = Some(name);
+            }
+            '[' => {
+                chars.next();
+                let mut inner = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    inner.push(c);
+                }
+                if !closed {
+                    return Err(format!("unterminated attribute selector in '{token}'"));
+                }
+                compound.attrs.push(parse_attr_predicate(&inner)?);
+            }
+            _ => return Err(format!("unexpected character '{c}' in selector '{token}'")),
+        }
+    }
+
+    Ok(compound)
+}
+
+/// Consumes a bare identifier (everything up to the next `.`/`#`/`[`).
+fn take_name(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == '.' || c == '#' || c == '[' {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    name
+}
+
+fn parse_attr_predicate(inner: &str) -> Result<AttrPredicate, String> {
+    match inner.split_once('=') {
+        Some((name, value)) => {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            Ok(AttrPredicate::Equals(
+                name.trim().to_string(),
+                value.to_string(),
+            ))
+        }
+        None => Ok(AttrPredicate::Exists(inner.trim().to_string())),
+    }
+}
+
+fn matches_compound(compound: &CompoundSelector, node: &VNode) -> bool {
+    if let Some(tag) = &compound.tag {
+        if node.tag != *tag {
+            return false;
+        }
+    }
+
+    if let Some(id) = &compound.id {
+        if node.attrs.get("id") != Some(id) {
+            return false;
+        }
+    }
+
+    if !compound.classes.is_empty() {
+        let node_classes: Vec<&str> = node
+            .attrs
+            .get("class")
+            .map(|c| c.split_whitespace().collect())
+            .unwrap_or_default();
+        if !compound
+            .classes
+            .iter()
+            .all(|c| node_classes.contains(&c.as_str()))
+        {
+            return false;
+        }
+    }
+
+    for attr in &compound.attrs {
+        let matches = match attr {
+            AttrPredicate::Exists(name) => node.attrs.contains_key(name),
+            AttrPredicate::Equals(name, value) => node.attrs.get(name) == Some(value),
+        };
+        if !matches {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Whether `node`, at the end of an ancestor chain `ancestors` (root
+/// first, immediate parent last), satisfies the full selector.
+fn matches_full(selector: &Selector, ancestors: &[&VNode], node: &VNode) -> bool {
+    let last = selector.compounds.len() - 1;
+    if !matches_compound(&selector.compounds[last], node) {
+        return false;
+    }
+
+    // Walk the combinator chain right to left, consuming ancestors from
+    // the bottom (immediate parent) upward as each compound is satisfied.
+    let mut frontier = ancestors.len();
+    for i in (0..last).rev() {
+        match selector.combinators[i] {
+            Combinator::Child => {
+                if frontier == 0 {
+                    return false;
+                }
+                frontier -= 1;
+                if !matches_compound(&selector.compounds[i], ancestors[frontier]) {
+                    return false;
+                }
+            }
+            Combinator::Descendant => {
+                let mut matched = false;
+                while frontier > 0 {
+                    frontier -= 1;
+                    if matches_compound(&selector.compounds[i], ancestors[frontier]) {
+                        matched = true;
+                        break;
+                    }
+                }
+                if !matched {
+                    return false;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+/// Collects every descendant of `node` in document order, each paired
+/// with its ancestor chain (root first, immediate parent last) so
+/// `matches_full` can check combinators against it. `ancestors` must
+/// already contain `node` itself as its last entry (callers searching a
+/// whole tree start with `vec![node]`), so each direct child's chain
+/// correctly includes its parent.
+fn collect_descendants<'a>(
+    node: &'a VNode,
+    ancestors: &mut Vec<&'a VNode>,
+    out: &mut Vec<(Vec<&'a VNode>, &'a VNode)>,
+) {
+    for child in &node.children {
+        out.push((ancestors.clone(), child));
+        ancestors.push(child);
+        collect_descendants(child, ancestors, out);
+        ancestors.pop();
+    }
+}
+
+impl VNode {
+    /// Selects every descendant matching `selector`, in document order.
+    ///
+    /// An unparseable selector returns an empty result rather than an
+    /// error, matching `querySelectorAll`'s fail-closed behavior.
+    pub fn select(&self, selector: &str) -> Vec<&VNode> {
+        let Ok(selector) = parse_selector(selector) else {
+            return Vec::new();
+        };
+
+        let mut ancestors = vec![self];
+        let mut entries = Vec::new();
+        collect_descendants(self, &mut ancestors, &mut entries);
+
+        entries
+            .iter()
+            .filter(|(anc, node)| matches_full(&selector, anc, node))
+            .map(|(_, node)| *node)
+            .collect()
+    }
+
+    /// Like [`select`](VNode::select), but returns only the first match.
+    pub fn select_one(&self, selector: &str) -> Option<&VNode> {
+        self.select(selector).into_iter().next()
+    }
+
+    /// Finds the descendant (or self) carrying this `data-dj` id. Every
+    /// element the parser produces carries a unique one (see
+    /// `parser::handle_to_vnode`), so this is a quick way to resolve a
+    /// single patch target. Callers resolving many ids against the same
+    /// tree should build a [`DjustIdIndex`] once instead, which makes each
+    /// lookup O(1) rather than an O(n) walk.
+    pub fn by_djust_id(&self, id: &str) -> Option<&VNode> {
+        if self.attrs.get("data-dj").map(String::as_str) == Some(id) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.by_djust_id(id))
+    }
+}
+
+/// A precomputed `data-dj` id -> child-index path index over a `VNode`
+/// tree, so patch code resolving many ids against the same tree (one per
+/// patch, for every render) does so in O(1) per lookup instead of an O(n)
+/// walk each time.
+#[derive(Debug, Clone, Default)]
+pub struct DjustIdIndex {
+    paths: HashMap<String, Vec<usize>>,
+}
+
+impl DjustIdIndex {
+    /// Walks `root` once, recording each element's `data-dj` id and the
+    /// child-index path to reach it.
+    pub fn build(root: &VNode) -> Self {
+        let mut paths = HashMap::new();
+        let mut path = Vec::new();
+        Self::index_node(root, &mut path, &mut paths);
+        Self { paths }
+    }
+
+    fn index_node(node: &VNode, path: &mut Vec<usize>, paths: &mut HashMap<String, Vec<usize>>) {
+        if let Some(id) = node.attrs.get("data-dj") {
+            paths.insert(id.clone(), path.clone());
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            Self::index_node(child, path, paths);
+            path.pop();
+        }
+    }
+
+    /// Resolves `id` to its node within `root` - the same tree (or one
+    /// with identical structure, e.g. after an attribute-only patch) that
+    /// [`build`](DjustIdIndex::build) indexed.
+    pub fn resolve<'a>(&self, root: &'a VNode, id: &str) -> Option<&'a VNode> {
+        let path = self.paths.get(id)?;
+        let mut node = root;
+        for &i in path {
+            node = node.children.get(i)?;
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> VNode {
+        VNode::element("div")
+            .with_attr("id", "root")
+            .with_child(
+                VNode::element("span")
+                    .with_attr("class", "label primary")
+                    .with_child(VNode::text("Hello")),
+            )
+            .with_child(
+                VNode::element("ul").with_attr("id", "items").with_child(
+                    VNode::element("li")
+                        .with_attr("data-key", "1")
+                        .with_child(VNode::text("Item 1")),
+                ),
+            )
+    }
+
+    #[test]
+    fn test_select_by_tag() {
+        let root = tree();
+        let matches = root.select("span");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "span");
+    }
+
+    #[test]
+    fn test_select_by_class() {
+        let root = tree();
+        let matches = root.select(".primary");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "span");
+    }
+
+    #[test]
+    fn test_select_by_id() {
+        // `select` only searches descendants, matching `querySelectorAll` -
+        // it never returns the node it was called on, so the id under
+        // test has to live on a descendant rather than the root itself.
+        let root = tree();
+        let matches = root.select("#items");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "ul");
+    }
+
+    #[test]
+    fn test_select_by_attr_exists() {
+        let root = tree();
+        let matches = root.select("[data-key]");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "li");
+    }
+
+    #[test]
+    fn test_select_by_attr_equals() {
+        let root = tree();
+        let matches = root.select(r#"[data-key="1"]"#);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "li");
+    }
+
+    #[test]
+    fn test_select_attr_equals_rejects_other_values() {
+        let root = tree();
+        assert!(root.select(r#"[data-key="2"]"#).is_empty());
+    }
+
+    #[test]
+    fn test_select_descendant_combinator() {
+        let root = tree();
+        let matches = root.select("ul li");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "li");
+    }
+
+    #[test]
+    fn test_select_child_combinator_matches_immediate_child() {
+        let root = tree();
+        assert_eq!(root.select("div > span").len(), 1);
+    }
+
+    #[test]
+    fn test_select_child_combinator_rejects_grandchild() {
+        let root = tree();
+        // `li` is a grandchild of the root `div`, not an immediate child.
+        assert!(root.select("div > li").is_empty());
+    }
+
+    #[test]
+    fn test_select_compound_selector() {
+        let root = tree();
+        let matches = root.select("span.label");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tag, "span");
+        assert!(root.select("span.missing").is_empty());
+    }
+
+    #[test]
+    fn test_select_one_returns_first_match() {
+        let root = tree();
+        assert_eq!(root.select_one("li").unwrap().tag, "li");
+    }
+
+    #[test]
+    fn test_select_one_returns_none_when_no_match() {
+        let root = tree();
+        assert!(root.select_one("table").is_none());
+    }
+
+    #[test]
+    fn test_select_unparseable_selector_returns_empty() {
+        let root = tree();
+        assert!(root.select("div >").is_empty());
+    }
+
+    #[test]
+    fn test_by_djust_id_finds_self() {
+        let root = VNode::element("div").with_attr("data-dj", "0");
+        assert_eq!(root.by_djust_id("0").unwrap().tag, "div");
+    }
+
+    #[test]
+    fn test_by_djust_id_finds_descendant() {
+        let root = VNode::element("div")
+            .with_attr("data-dj", "0")
+            .with_child(VNode::element("span").with_attr("data-dj", "1"));
+        assert_eq!(root.by_djust_id("1").unwrap().tag, "span");
+    }
+
+    #[test]
+    fn test_by_djust_id_missing_returns_none() {
+        let root = VNode::element("div").with_attr("data-dj", "0");
+        assert!(root.by_djust_id("missing").is_none());
+    }
+
+    #[test]
+    fn test_djust_id_index_resolves_o1_lookup() {
+        let root = VNode::element("div").with_attr("data-dj", "0").with_child(
+            VNode::element("ul").with_attr("data-dj", "1").with_child(
+                VNode::element("li").with_attr("data-dj", "2"),
+            ),
+        );
+        let index = DjustIdIndex::build(&root);
+
+        assert_eq!(index.resolve(&root, "2").unwrap().tag, "li");
+        assert_eq!(index.resolve(&root, "0").unwrap().tag, "div");
+        assert!(index.resolve(&root, "missing").is_none());
+    }
+}
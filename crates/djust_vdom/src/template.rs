@@ -0,0 +1,304 @@
+//! Template/static-hole diffing, inspired by Dioxus's path-based template
+//! mutations: a server render is split into a static skeleton plus the
+//! list of "dynamic holes" (text spans, attribute values, child-list
+//! slots) an interpolated value filled in, each addressed by an index
+//! path into the skeleton. Two renders of the *same* template only need
+//! their holes compared - the skeleton between them is guaranteed
+//! identical, so [`diff_templated`] never re-walks it the way
+//! [`crate::diff::diff_nodes`] would.
+//!
+//! This is a separate, opt-in fast path: nothing in `parse_html`/`diff`
+//! constructs a [`TemplateInstance`] today. A renderer that already knows
+//! which spans of its output came from interpolation (rather than
+//! rediscovering that by diffing two parsed trees) is what would build
+//! one directly.
+
+use crate::diff::{diff_child_list, diff_nodes};
+use crate::{Patch, VNode};
+
+/// A single dynamic slot in a template: a place where an interpolated
+/// value was substituted into the otherwise-static skeleton, addressed by
+/// `path` - the same child-index path [`Patch`] variants use.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicHole {
+    /// A text node whose content came from an interpolated value.
+    Text { path: Vec<usize>, value: String },
+    /// An attribute whose value came from an interpolated value.
+    Attr {
+        path: Vec<usize>,
+        key: String,
+        value: String,
+    },
+    /// A child-list slot (e.g. a `{% for %}` body, or an `{% if %}`
+    /// branch) populated from interpolated data. Diffed as a full child
+    /// list via [`diff_child_list`] rather than hole-by-hole, since its
+    /// contents aren't themselves a flat list of further holes.
+    Children { path: Vec<usize>, nodes: Vec<VNode> },
+}
+
+/// One render of a compiled template: the static skeleton (used as the
+/// `diff_nodes` fallback when two instances don't share a `template_id`)
+/// plus the dynamic holes filled into it, in the template's declaration
+/// order.
+#[derive(Debug, Clone)]
+pub struct TemplateInstance {
+    /// Identifies the compiled template this instance came from. Two
+    /// instances only take the hole-diff fast path when their ids match -
+    /// a different id means the skeleton itself may differ in shape, so
+    /// the holes can't be compared position-by-position.
+    pub template_id: String,
+    /// The djust_id of this instance's root element, for targeting the
+    /// patches below the way `diff_nodes` targets with the old node's id.
+    pub djust_id: Option<String>,
+    pub skeleton: VNode,
+    pub holes: Vec<DynamicHole>,
+}
+
+impl TemplateInstance {
+    pub fn new(template_id: impl Into<String>, skeleton: VNode) -> Self {
+        Self {
+            template_id: template_id.into(),
+            djust_id: None,
+            skeleton,
+            holes: Vec::new(),
+        }
+    }
+
+    pub fn with_djust_id(mut self, djust_id: impl Into<String>) -> Self {
+        self.djust_id = Some(djust_id.into());
+        self
+    }
+
+    pub fn with_hole(mut self, hole: DynamicHole) -> Self {
+        self.holes.push(hole);
+        self
+    }
+}
+
+/// Diff two template renders. When `old`/`new` share a `template_id`,
+/// only the holes are compared - same-index holes of the same kind are
+/// compared by value, each difference becoming exactly one patch (or, for
+/// a `Children` hole, whatever [`diff_child_list`] emits for that slot).
+/// Falls back to [`diff_nodes`] over the full skeletons when the ids
+/// differ, since there's then no guarantee the holes still line up.
+pub fn diff_templated(old: &TemplateInstance, new: &TemplateInstance) -> Vec<Patch> {
+    if old.template_id != new.template_id {
+        return diff_nodes(&old.skeleton, &new.skeleton, &[]);
+    }
+
+    let mut patches = Vec::new();
+
+    // Same template id means the same hole list shape by construction, so
+    // positional zip is enough - there's no keying/reordering concern the
+    // way there is for a plain child list.
+    for (old_hole, new_hole) in old.holes.iter().zip(new.holes.iter()) {
+        match (old_hole, new_hole) {
+            (
+                DynamicHole::Text {
+                    path,
+                    value: old_value,
+                },
+                DynamicHole::Text {
+                    value: new_value, ..
+                },
+            ) => {
+                if old_value != new_value {
+                    patches.push(Patch::SetText {
+                        path: path.clone(),
+                        d: old.djust_id.clone(),
+                        text: new_value.clone(),
+                    });
+                }
+            }
+            (
+                DynamicHole::Attr {
+                    path,
+                    key,
+                    value: old_value,
+                },
+                DynamicHole::Attr {
+                    value: new_value, ..
+                },
+            ) => {
+                if old_value != new_value {
+                    patches.push(Patch::SetAttr {
+                        path: path.clone(),
+                        d: old.djust_id.clone(),
+                        key: key.clone(),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+            (
+                DynamicHole::Children {
+                    path,
+                    nodes: old_nodes,
+                },
+                DynamicHole::Children {
+                    nodes: new_nodes, ..
+                },
+            ) => {
+                let fully_keyed = old_nodes.iter().all(|n| n.key.is_some())
+                    && new_nodes.iter().all(|n| n.key.is_some());
+                patches.extend(diff_child_list(
+                    old_nodes,
+                    new_nodes,
+                    path,
+                    &old.djust_id,
+                    fully_keyed,
+                ));
+            }
+            // Two instances of the same template_id are expected to carry
+            // the same hole kind at the same position; a mismatch here
+            // means the template changed shape without a new id, which the
+            // fast path can't reason about. Fall back to a full diff of
+            // that hole's subtree rather than risk an ill-formed patch.
+            (old_mismatched, new_mismatched) => {
+                let path = hole_path(old_mismatched);
+                patches.extend(diff_nodes(
+                    &hole_as_node(old_mismatched),
+                    &hole_as_node(new_mismatched),
+                    &path[..path.len().saturating_sub(1)],
+                ));
+            }
+        }
+    }
+
+    patches
+}
+
+fn hole_path(hole: &DynamicHole) -> Vec<usize> {
+    match hole {
+        DynamicHole::Text { path, .. } => path.clone(),
+        DynamicHole::Attr { path, .. } => path.clone(),
+        DynamicHole::Children { path, .. } => path.clone(),
+    }
+}
+
+/// Render a hole as a standalone `VNode`, purely so the mismatched-
+/// kind fallback above can hand it to `diff_nodes`: a `Text` hole becomes a
+/// text node, an `Attr` hole a synthetic `#attr-hole` element carrying that
+/// one attribute, and a `Children` hole a synthetic `#children-hole`
+/// container around its nodes.
+fn hole_as_node(hole: &DynamicHole) -> VNode {
+    match hole {
+        DynamicHole::Text { value, .. } => VNode::text(value.clone()),
+        DynamicHole::Attr { key, value, .. } => VNode::element("#attr-hole").with_attr(key, value),
+        DynamicHole::Children { nodes, .. } => {
+            VNode::element("#children-hole").with_children(nodes.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_templated_same_id_diffs_only_changed_text_hole() {
+        let skeleton = VNode::element("div").with_child(VNode::text("placeholder"));
+        let old = TemplateInstance::new("greeting", skeleton.clone())
+            .with_djust_id("0")
+            .with_hole(DynamicHole::Text {
+                path: vec![0],
+                value: "Hello, Alice".to_string(),
+            });
+        let new = TemplateInstance::new("greeting", skeleton)
+            .with_djust_id("0")
+            .with_hole(DynamicHole::Text {
+                path: vec![0],
+                value: "Hello, Bob".to_string(),
+            });
+
+        let patches = diff_templated(&old, &new);
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::SetText { path, text, d }
+                if path == &vec![0] && text == "Hello, Bob" && d == &Some("0".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_diff_templated_same_id_no_holes_changed_emits_nothing() {
+        let skeleton = VNode::element("div");
+        let old = TemplateInstance::new("card", skeleton.clone()).with_hole(DynamicHole::Attr {
+            path: vec![0],
+            key: "class".to_string(),
+            value: "active".to_string(),
+        });
+        let new = TemplateInstance::new("card", skeleton).with_hole(DynamicHole::Attr {
+            path: vec![0],
+            key: "class".to_string(),
+            value: "active".to_string(),
+        });
+
+        assert!(diff_templated(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_templated_changed_attr_hole() {
+        let skeleton = VNode::element("div");
+        let old = TemplateInstance::new("card", skeleton.clone()).with_hole(DynamicHole::Attr {
+            path: vec![0],
+            key: "class".to_string(),
+            value: "inactive".to_string(),
+        });
+        let new = TemplateInstance::new("card", skeleton).with_hole(DynamicHole::Attr {
+            path: vec![0],
+            key: "class".to_string(),
+            value: "active".to_string(),
+        });
+
+        let patches = diff_templated(&old, &new);
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(
+            &patches[0],
+            Patch::SetAttr { key, value, .. }
+                if key == "class" && value == "active"
+        ));
+    }
+
+    #[test]
+    fn test_diff_templated_children_hole_reorders_with_minimal_moves() {
+        let skeleton = VNode::element("ul");
+        let old = TemplateInstance::new("list", skeleton.clone()).with_hole(DynamicHole::Children {
+            path: vec![0],
+            nodes: vec![
+                VNode::element("li").with_key("a"),
+                VNode::element("li").with_key("b"),
+            ],
+        });
+        let new = TemplateInstance::new("list", skeleton).with_hole(DynamicHole::Children {
+            path: vec![0],
+            nodes: vec![
+                VNode::element("li").with_key("b"),
+                VNode::element("li").with_key("a"),
+            ],
+        });
+
+        let patches = diff_templated(&old, &new);
+        let move_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { .. }))
+            .count();
+        assert_eq!(move_count, 1);
+    }
+
+    #[test]
+    fn test_diff_templated_different_template_id_falls_back_to_diff_nodes() {
+        let old = TemplateInstance::new(
+            "v1",
+            VNode::element("div").with_child(VNode::text("old")),
+        );
+        let new = TemplateInstance::new(
+            "v2",
+            VNode::element("section").with_child(VNode::text("new")),
+        );
+
+        let patches = diff_templated(&old, &new);
+        assert!(patches
+            .iter()
+            .any(|p| matches!(p, Patch::Replace { .. })));
+    }
+}
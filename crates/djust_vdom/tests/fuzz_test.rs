@@ -18,8 +18,8 @@
 //! See: https://github.com/djust-org/djust/issues/152
 
 use djust_vdom::diff::diff_nodes;
-use djust_vdom::patch::apply_patches;
-use djust_vdom::VNode;
+use djust_vdom::patch::{apply_patches, apply_patches_cloned, apply_patches_in_place};
+use djust_vdom::{Patch, VNode};
 use proptest::prelude::*;
 use std::collections::HashMap;
 
@@ -544,6 +544,49 @@ fn structurally_equal(a: &VNode, b: &VNode) -> bool {
         .all(|(ca, cb)| structurally_equal(ca, cb))
 }
 
+// ============================================================================
+// Minimal-move keyed reconciliation generator (chunk3-1)
+//
+// Generates two flat lists of keyed <li> children (old and new) sharing some
+// keys, used to check that diff_keyed_children's move count never exceeds
+// the provably-minimal `surviving - LIS_len` bound.
+// ============================================================================
+
+/// A list of unique, short, lowercase keys.
+fn arb_key_list() -> BoxedStrategy<Vec<String>> {
+    prop::collection::vec("[a-z]{1,3}", 1..=8)
+        .prop_map(|keys| {
+            let mut seen = std::collections::HashSet::new();
+            keys.into_iter().filter(|k| seen.insert(k.clone())).collect()
+        })
+        .boxed()
+}
+
+fn keyed_li_children(keys: &[String]) -> VNode {
+    let mut parent = VNode::element("ul");
+    parent.children = keys
+        .iter()
+        .map(|k| VNode::element("li").with_key(k.clone()))
+        .collect();
+    parent
+}
+
+/// Reference (test-only) longest increasing subsequence *length*, computed
+/// independently of the production `longest_increasing_subsequence` in
+/// `diff.rs` so this test actually checks the implementation rather than
+/// re-deriving its own answer.
+fn lis_len(seq: &[usize]) -> usize {
+    let mut tails: Vec<usize> = Vec::new();
+    for &x in seq {
+        match tails.binary_search(&x) {
+            Ok(_) => {}
+            Err(pos) if pos == tails.len() => tails.push(x),
+            Err(pos) => tails[pos] = x,
+        }
+    }
+    tails.len()
+}
+
 // ============================================================================
 // Property tests
 // ============================================================================
@@ -683,4 +726,97 @@ proptest! {
             a, b, patches, patched,
         );
     }
+
+    /// Property 7: keyed reordering emits the minimal number of moves
+    /// (chunk3-1). For a flat list of keyed children, the number of
+    /// MoveChild patches must never exceed `surviving - |LIS|`, where the
+    /// LIS is taken over the old indices of surviving keys in new order.
+    #[test]
+    fn minimal_move_count_bounded(
+        old_keys in arb_key_list(),
+        new_keys in arb_key_list(),
+    ) {
+        let old_parent = keyed_li_children(&old_keys);
+        let new_parent = keyed_li_children(&new_keys);
+
+        let seq: Vec<usize> = new_keys
+            .iter()
+            .filter_map(|k| old_keys.iter().position(|ok| ok == k))
+            .collect();
+        let surviving = seq.len();
+        let expected_max_moves = surviving.saturating_sub(lis_len(&seq));
+
+        let patches = diff_nodes(&old_parent, &new_parent, &[]);
+        let move_count = patches
+            .iter()
+            .filter(|p| matches!(p, Patch::MoveChild { path, .. } if path.is_empty()))
+            .count();
+
+        prop_assert!(
+            move_count <= expected_max_moves,
+            "Got {} moves, expected at most {} (surviving={}, seq={:?}). Patches: {:?}",
+            move_count, expected_max_moves, surviving, seq, patches,
+        );
+    }
+
+    /// Property 8: in-place patch application (chunk3-4) produces the same
+    /// tree as the existing clone-then-apply `apply_patches`, for both the
+    /// keyed-mutation and mixed-interleave generators (the two pair
+    /// generators that exercise structural InsertChild/RemoveChild/MoveChild
+    /// patches most heavily).
+    #[test]
+    fn in_place_apply_matches_clone_then_apply(
+        pair in prop_oneof![arb_keyed_mutation_pair(), arb_mixed_interleave_pair()],
+    ) {
+        let (mut a, mut b) = pair;
+
+        let mut counter = 0u64;
+        assign_ids(&mut a, &mut counter);
+        assign_ids(&mut b, &mut counter);
+
+        let patches = diff_nodes(&a, &b, &[]);
+
+        let mut cloned = a.clone();
+        apply_patches(&mut cloned, &patches);
+
+        let mut in_place = a.clone();
+        apply_patches_in_place(&mut in_place, &patches);
+
+        prop_assert!(
+            structurally_equal(&in_place, &cloned),
+            "In-place application diverged from clone-then-apply.\nA: {:?}\nPatches: {:?}\nClone-then-apply: {:?}\nIn-place: {:?}",
+            a, patches, cloned, in_place,
+        );
+    }
+
+    /// Property 9: `apply_patches_cloned(&old, &diff_nodes(&old, &new, &[]))`
+    /// structurally equals `new` without mutating `old` - the non-mutating,
+    /// server-side counterpart to property 2's `apply_patches` round-trip.
+    #[test]
+    fn apply_patches_cloned_round_trip(
+        tree_a in arb_keyed_tree(),
+        tree_b in arb_keyed_tree(),
+    ) {
+        let mut a = tree_a;
+        let mut b = tree_b;
+
+        let mut counter = 0u64;
+        assign_ids(&mut a, &mut counter);
+        assign_ids(&mut b, &mut counter);
+
+        let original = a.clone();
+        let patches = diff_nodes(&a, &b, &[]);
+        let patched = apply_patches_cloned(&a, &patches);
+
+        prop_assert!(
+            structurally_equal(&a, &original),
+            "apply_patches_cloned mutated its root argument.\nBefore: {:?}\nAfter: {:?}",
+            original, a,
+        );
+        prop_assert!(
+            structurally_equal(&patched, &b),
+            "Cloned round-trip failed.\nA: {:?}\nB: {:?}\nPatches: {:?}\nPatched: {:?}",
+            a, b, patches, patched,
+        );
+    }
 }
@@ -47,6 +47,30 @@ impl Badge {
         format!(r#"<span class="{}">{}</span>"#, classes, html_escape(&self.text))
     }
 
+    /// Render the badge, truncating its text to `max_chars` visible
+    /// characters and appending an ellipsis if it was cut short. Safe to use
+    /// for previews of user-supplied text without risking malformed markup.
+    pub fn render_preview(&self, max_chars: usize) -> String {
+        let mut classes = format!("badge bg-{}", self.variant);
+
+        match self.size.as_str() {
+            "sm" => {}, // Default badge size (0.75em)
+            "md" => classes.push_str(" fs-6"),  // 1rem
+            "lg" => classes.push_str(" fs-5"),  // 1.25rem
+            _ => {},
+        }
+
+        if self.pill {
+            classes.push_str(" rounded-pill");
+        }
+
+        format!(
+            r#"<span class="{}">{}</span>"#,
+            classes,
+            truncate_escaped(&self.text, max_chars)
+        )
+    }
+
     pub fn __str__(&self) -> String {
         self.render()
     }
@@ -68,6 +92,19 @@ fn html_escape(s: &str) -> String {
         .replace('\'', "&#x27;")
 }
 
+/// Truncate `text` to at most `max_chars` characters, then escape it,
+/// appending an ellipsis when truncation actually happens. Truncating
+/// before escaping means an entity like `&amp;` is never cut in half.
+fn truncate_escaped(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return html_escape(text);
+    }
+    let truncated: String = text.chars().take(max_chars).collect();
+    let mut escaped = html_escape(&truncated);
+    escaped.push('…');
+    escaped
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +151,19 @@ mod tests {
         assert!(html.contains("&lt;script&gt;"));
         assert!(!html.contains("<script>"));
     }
+
+    #[test]
+    fn test_render_preview_truncates_long_text() {
+        let badge = Badge::new("This is a very long badge label".to_string(), "primary", "md", false);
+        let html = badge.render_preview(10);
+        assert!(html.contains("…"));
+        assert!(html.contains("</span>"));
+    }
+
+    #[test]
+    fn test_render_preview_leaves_short_text_untouched() {
+        let badge = Badge::new("Short".to_string(), "primary", "md", false);
+        let html = badge.render_preview(50);
+        assert_eq!(html, badge.render());
+    }
 }
@@ -0,0 +1,280 @@
+//! Code block component - pure Rust implementation for maximum performance.
+//!
+//! Renders a syntax-highlighted `<pre><code>` listing, mirroring the
+//! classed-token approach rustdoc uses for its source listings: each token
+//! gets a `<span class="tok-*">` of a small fixed class set (keyword,
+//! string, number, comment, ident, punct), and every token's text is
+//! escaped through `html_escape` so user-submitted code can't break out of
+//! the listing.
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A single classified span of source text. An empty `class` (whitespace,
+/// mostly) is rendered unwrapped.
+struct Token {
+    class: &'static str,
+    text: String,
+}
+
+/// Per-language tokenizer. New languages are added by implementing this
+/// trait and registering them in `lexer_for`, without touching `render`.
+trait Lexer {
+    fn tokenize_line(&self, line: &str) -> Vec<Token>;
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "async", "await", "dyn",
+];
+
+/// Tokenizer shared by `RustLexer` and `PlainLexer`: splits a line into
+/// whitespace, string, number, ident, and punct runs. `classify_ident`
+/// decides whether an ident run is a keyword.
+fn tokenize_generic(line: &str, classify_ident: impl Fn(&str) -> &'static str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            let start = i;
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            tokens.push(Token {
+                class: "",
+                text: chars[start..i].iter().collect(),
+            });
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            tokens.push(Token {
+                class: "comment",
+                text: chars[i..].iter().collect(),
+            });
+            i = chars.len();
+        } else if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i < chars.len() {
+                i += 1; // closing quote
+            }
+            tokens.push(Token {
+                class: "string",
+                text: chars[start..i].iter().collect(),
+            });
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token {
+                class: "number",
+                text: chars[start..i].iter().collect(),
+            });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let class = classify_ident(&text);
+            tokens.push(Token { class, text });
+        } else {
+            let start = i;
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !chars[i].is_alphanumeric()
+                && chars[i] != '_'
+                && chars[i] != '"'
+            {
+                i += 1;
+            }
+            if i == start {
+                i += 1; // guarantee progress on an unexpected char
+            }
+            tokens.push(Token {
+                class: "punct",
+                text: chars[start..i].iter().collect(),
+            });
+        }
+    }
+
+    tokens
+}
+
+/// Rust lexer: recognizes the keyword set above, falling back to `ident`.
+struct RustLexer;
+
+impl Lexer for RustLexer {
+    fn tokenize_line(&self, line: &str) -> Vec<Token> {
+        tokenize_generic(line, |ident| {
+            if RUST_KEYWORDS.contains(&ident) {
+                "keyword"
+            } else {
+                "ident"
+            }
+        })
+    }
+}
+
+/// Generic fallback lexer for languages without a dedicated lexer: same
+/// string/number/comment/punct handling as `RustLexer`, but with no
+/// keyword set, so every identifier is classified `ident`.
+struct PlainLexer;
+
+impl Lexer for PlainLexer {
+    fn tokenize_line(&self, line: &str) -> Vec<Token> {
+        tokenize_generic(line, |_| "ident")
+    }
+}
+
+fn lexer_for(language: &str) -> Box<dyn Lexer> {
+    match language.to_lowercase().as_str() {
+        "rust" | "rs" => Box::new(RustLexer),
+        _ => Box::new(PlainLexer),
+    }
+}
+
+#[pyclass(name = "RustCodeBlock")]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CodeBlock {
+    source: String,
+    language: String,
+    line_numbers: bool,
+    highlight_lines: Vec<usize>,
+}
+
+#[pymethods]
+impl CodeBlock {
+    #[new]
+    #[pyo3(signature = (source, language="text", line_numbers=false, highlight_lines=vec![]))]
+    pub fn new(source: String, language: &str, line_numbers: bool, highlight_lines: Vec<usize>) -> Self {
+        Self {
+            source,
+            language: language.to_string(),
+            line_numbers,
+            highlight_lines,
+        }
+    }
+
+    /// Render the listing to HTML, one `<span class="code-line">` per
+    /// source line holding an optional line-number gutter span and the
+    /// tokenized, escaped source.
+    pub fn render(&self) -> String {
+        let lexer = lexer_for(&self.language);
+        let mut out = String::from("<pre><code>");
+
+        for (idx, line) in self.source.lines().enumerate() {
+            let line_no = idx + 1;
+            let mut classes = String::from("code-line");
+            if self.highlight_lines.contains(&line_no) {
+                classes.push_str(" highlighted");
+            }
+
+            out.push_str(&format!(r#"<span class="{classes}">"#));
+            if self.line_numbers {
+                out.push_str(&format!(
+                    r#"<span class="line-number">{line_no}</span>"#
+                ));
+            }
+            for tok in lexer.tokenize_line(line) {
+                if tok.class.is_empty() {
+                    out.push_str(&html_escape(&tok.text));
+                } else {
+                    out.push_str(&format!(
+                        r#"<span class="tok-{}">{}</span>"#,
+                        tok.class,
+                        html_escape(&tok.text)
+                    ));
+                }
+            }
+            out.push_str("</span>\n");
+        }
+
+        out.push_str("</code></pre>");
+        out
+    }
+
+    pub fn __str__(&self) -> String {
+        self.render()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "RustCodeBlock(language={:?}, line_numbers={}, highlight_lines={:?})",
+            self.language, self.line_numbers, self.highlight_lines
+        )
+    }
+}
+
+/// HTML escape function for safety
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_block_basic() {
+        let block = CodeBlock::new("let x = 1;".to_string(), "rust", false, vec![]);
+        let html = block.render();
+        assert!(html.contains(r#"<span class="tok-keyword">let</span>"#));
+        assert!(html.contains(r#"<span class="tok-ident">x</span>"#));
+        assert!(html.contains(r#"<span class="tok-number">1</span>"#));
+    }
+
+    #[test]
+    fn test_code_block_escapes_source() {
+        let block = CodeBlock::new("\"<script>\"".to_string(), "rust", false, vec![]);
+        let html = block.render();
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn test_code_block_line_numbers() {
+        let block = CodeBlock::new("a\nb".to_string(), "rust", true, vec![]);
+        let html = block.render();
+        assert!(html.contains(r#"<span class="line-number">1</span>"#));
+        assert!(html.contains(r#"<span class="line-number">2</span>"#));
+    }
+
+    #[test]
+    fn test_code_block_highlight_lines() {
+        let block = CodeBlock::new("a\nb\nc".to_string(), "rust", false, vec![2]);
+        let html = block.render();
+        assert!(html.contains(r#"<span class="code-line highlighted">"#));
+        assert_eq!(html.matches("highlighted").count(), 1);
+    }
+
+    #[test]
+    fn test_code_block_comment_class() {
+        let block = CodeBlock::new("// hello".to_string(), "rust", false, vec![]);
+        let html = block.render();
+        assert!(html.contains(r#"<span class="tok-comment">// hello</span>"#));
+    }
+
+    #[test]
+    fn test_code_block_fallback_lexer_has_no_keywords() {
+        let block = CodeBlock::new("let x = 1".to_string(), "unknown-lang", false, vec![]);
+        let html = block.render();
+        assert!(!html.contains("tok-keyword"));
+        assert!(html.contains(r#"<span class="tok-ident">let</span>"#));
+    }
+}
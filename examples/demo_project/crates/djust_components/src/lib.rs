@@ -8,13 +8,16 @@ use serde::{Deserialize, Serialize};
 
 pub mod badge;
 pub mod button;
+pub mod code_block;
 
 pub use badge::Badge;
 pub use button::Button;
+pub use code_block::CodeBlock;
 
 /// Re-export components for PyO3 module
 pub fn register_components(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Badge>()?;
     m.add_class::<Button>()?;
+    m.add_class::<CodeBlock>()?;
     Ok(())
 }